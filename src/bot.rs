@@ -0,0 +1,439 @@
+//! Chat commands of `rem bot`, which posts the agenda of each day to a Matrix room or Telegram
+//! chat and applies the commands sent there
+//!
+//! The chat services are reached with `curl`, so rem needs no HTTP client of its own. Tokens are
+//! passed to curl on stdin rather than as arguments, where other users could see them.
+
+use std::str::FromStr;
+
+use crate::sheet::{agenda_time, priority_line, Sheet};
+
+/// What a message sent to the bot asks for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// complete the task with the id
+    Done(u64),
+    /// add a task from a line of the quick-add grammar, see [`crate::quickadd`]
+    Add(String),
+    Agenda,
+    Help,
+}
+
+pub const HELP: &str = "Commands:
+done <id>: complete a task
+add <title> [today|tomorrow|due:DATE] [!priority] [#tag] [@context]: add a task
+agenda: post the agenda of today
+help: show this message";
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (word, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let rest = rest.trim();
+        match word.to_lowercase().as_str() {
+            "done" => rest
+                .trim_start_matches('#')
+                .parse()
+                .map(Self::Done)
+                .map_err(|_| format!("Expected the id of a task after 'done', got '{rest}'")),
+            "add" if rest.is_empty() => Err("Expected a title after 'add'".to_string()),
+            "add" => {
+                // a trailing day reads more naturally in a chat than the annotation
+                let mut words = rest.split_whitespace().collect::<Vec<_>>();
+                let due = match words.last() {
+                    Some(&"today") if words.len() > 1 => Some("due:today"),
+                    Some(&"tomorrow") if words.len() > 1 => Some("due:tomorrow"),
+                    _ => None,
+                };
+                if let Some(due) = due {
+                    if !words.iter().any(|word| word.starts_with("due:")) {
+                        *words.last_mut().expect("words are not empty") = due;
+                    }
+                }
+                Ok(Self::Add(words.join(" ")))
+            }
+            "agenda" | "today" => Ok(Self::Agenda),
+            "help" | "/help" | "/start" => Ok(Self::Help),
+            _ => Err(format!("Unknown command '{word}'. Send 'help' for a list.")),
+        }
+    }
+}
+
+/// Plain text of a day for the chat, with ids of the tasks to complete them by
+pub fn agenda(sheet: &Sheet) -> String {
+    let mut ret = format!("Agenda for {}\n", sheet.title());
+    if sheet.agenda.is_empty() {
+        ret.push_str("Nothing due.\n");
+    }
+    for task in sheet.agenda.iter() {
+        ret.push_str(&format!(
+            "[{}] {} {}\n",
+            task.id,
+            agenda_time(task),
+            task.title
+        ));
+    }
+    if !sheet.priorities.is_empty() {
+        ret.push_str("\nTop priorities:\n");
+        for task in sheet.priorities.iter() {
+            ret.push_str(&format!(
+                "[{}] {}\n",
+                task.id,
+                priority_line(task, sheet.iso)
+            ));
+        }
+    }
+    if !sheet.habits.is_empty() {
+        ret.push_str("\nHabits:\n");
+        for habit in sheet.habits.iter() {
+            ret.push_str(&format!("- {habit}\n"));
+        }
+    }
+    ret.trim_end().to_string()
+}
+
+/// A room or chat the bot reads messages from and posts to
+#[cfg(feature = "bot")]
+pub trait Chat {
+    /// Wait up to `timeout` for messages, returning the text of those sent by others
+    fn receive(&mut self, timeout: std::time::Duration) -> Result<Vec<String>, String>;
+    fn send(&mut self, text: &str) -> Result<(), String>;
+}
+
+/// Quote a value for a curl config file
+#[cfg(feature = "bot")]
+fn curl_quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{escaped}\"")
+}
+
+/// Run a request with curl, returning the JSON it replied with
+///
+/// * `options`: curl config options with their values, e.g. `("url", ...)`
+#[cfg(feature = "bot")]
+fn curl(options: &[(&str, &str)]) -> Result<serde_json::Value, String> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Could not run curl: {err}"))?;
+    let config = options
+        .iter()
+        .map(|(option, value)| format!("{option} = {}\n", curl_quote(value)))
+        .collect::<String>();
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(config.as_bytes())
+        .map_err(|err| format!("Could not pass request to curl: {err}"))?;
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("Could not run curl: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "curl failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|err| format!("Invalid reply: {err}"))
+}
+
+/// Escape `text` for a path segment or query value of a URL
+#[cfg(feature = "bot")]
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// A Telegram chat, talked to with the token of a bot created with the BotFather
+#[cfg(feature = "bot")]
+pub struct Telegram {
+    token: String,
+    /// messages from other chats are ignored, so strangers cannot use the bot
+    chat: i64,
+    /// id of the next update to receive
+    offset: i64,
+}
+
+#[cfg(feature = "bot")]
+impl Telegram {
+    pub fn new(token: String, chat: i64) -> Self {
+        Self {
+            token,
+            chat,
+            offset: 0,
+        }
+    }
+
+    fn call(&self, method: &str, body: Option<&str>) -> Result<serde_json::Value, String> {
+        let url = format!("https://api.telegram.org/bot{}/{method}", self.token);
+        let mut options = vec![("url", url.as_str())];
+        if let Some(body) = body {
+            options.extend([("header", "Content-Type: application/json"), ("data", body)]);
+        }
+        let reply = curl(&options)?;
+        if reply["ok"].as_bool() != Some(true) {
+            return Err(format!(
+                "Telegram refused {method}: {}",
+                reply["description"].as_str().unwrap_or("no reason given")
+            ));
+        }
+        Ok(reply["result"].clone())
+    }
+}
+
+#[cfg(feature = "bot")]
+impl Chat for Telegram {
+    fn receive(&mut self, timeout: std::time::Duration) -> Result<Vec<String>, String> {
+        let body = serde_json::json!({
+            "offset": self.offset,
+            "timeout": timeout.as_secs(),
+            "allowed_updates": ["message"],
+        });
+        let updates = self.call("getUpdates", Some(&body.to_string()))?;
+        let mut ret = Vec::new();
+        for update in updates.as_array().into_iter().flatten() {
+            if let Some(id) = update["update_id"].as_i64() {
+                self.offset = self.offset.max(id + 1);
+            }
+            let message = &update["message"];
+            if message["chat"]["id"].as_i64() != Some(self.chat) {
+                continue;
+            }
+            if let Some(text) = message["text"].as_str() {
+                ret.push(text.to_string());
+            }
+        }
+        Ok(ret)
+    }
+
+    fn send(&mut self, text: &str) -> Result<(), String> {
+        let body = serde_json::json!({ "chat_id": self.chat, "text": text });
+        self.call("sendMessage", Some(&body.to_string()))
+            .map(|_| ())
+    }
+}
+
+/// A Matrix room, talked to with the access token of an account that joined it
+#[cfg(feature = "bot")]
+pub struct Matrix {
+    homeserver: String,
+    token: String,
+    room: String,
+    /// the only account whose messages are read, so other members cannot use the bot
+    allowed: String,
+    /// the account of the bot, whose own messages are ignored
+    user: String,
+    /// position in the event stream to continue syncing from
+    since: String,
+    sent: u64,
+}
+
+#[cfg(feature = "bot")]
+impl Matrix {
+    /// Log in with `token`, skipping the messages sent before
+    ///
+    /// * `allowed`: account whose messages are read, another one than that of `token`
+    pub fn connect(
+        homeserver: &str,
+        token: String,
+        room: String,
+        allowed: String,
+    ) -> Result<Self, String> {
+        let mut ret = Self {
+            homeserver: homeserver.trim_end_matches('/').to_string(),
+            token,
+            room,
+            allowed,
+            user: String::new(),
+            since: String::new(),
+            sent: 0,
+        };
+        let whoami = ret.call("GET", "account/whoami", None)?;
+        ret.user = whoami["user_id"]
+            .as_str()
+            .ok_or("Matrix did not tell the account of the token")?
+            .to_string();
+        // the bot would read its own replies as commands
+        if ret.user == ret.allowed {
+            return Err(format!(
+                "matrix_user needs to be another account than {}, which the token belongs to",
+                ret.user
+            ));
+        }
+        let sync = ret.call("GET", "sync?timeout=0", None)?;
+        ret.since = sync["next_batch"]
+            .as_str()
+            .ok_or("Matrix did not return a sync token")?
+            .to_string();
+        Ok(ret)
+    }
+
+    fn call(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&str>,
+    ) -> Result<serde_json::Value, String> {
+        let url = format!("{}/_matrix/client/v3/{path}", self.homeserver);
+        let auth = format!("Authorization: Bearer {}", self.token);
+        let mut options = vec![
+            ("url", url.as_str()),
+            ("request", method),
+            ("header", auth.as_str()),
+        ];
+        if let Some(body) = body {
+            options.extend([("header", "Content-Type: application/json"), ("data", body)]);
+        }
+        let reply = curl(&options)?;
+        if let Some(err) = reply["error"].as_str() {
+            return Err(format!("Matrix refused {method} {path}: {err}"));
+        }
+        Ok(reply)
+    }
+}
+
+#[cfg(feature = "bot")]
+impl Chat for Matrix {
+    fn receive(&mut self, timeout: std::time::Duration) -> Result<Vec<String>, String> {
+        let sync = self.call(
+            "GET",
+            &format!(
+                "sync?timeout={}&since={}",
+                timeout.as_millis(),
+                percent_encode(&self.since)
+            ),
+            None,
+        )?;
+        if let Some(since) = sync["next_batch"].as_str() {
+            self.since = since.to_string();
+        }
+        Ok(matrix_messages(&sync, &self.room, &self.allowed))
+    }
+
+    fn send(&mut self, text: &str) -> Result<(), String> {
+        self.sent += 1;
+        let txn = format!("rem-{}-{}", std::process::id(), self.sent);
+        let body = serde_json::json!({ "msgtype": "m.text", "body": text });
+        self.call(
+            "PUT",
+            &format!(
+                "rooms/{}/send/m.room.message/{txn}",
+                percent_encode(&self.room)
+            ),
+            Some(&body.to_string()),
+        )
+        .map(|_| ())
+    }
+}
+
+/// Texts of the messages `sender` sent to `room` in the response to a sync
+#[cfg(feature = "bot")]
+fn matrix_messages(sync: &serde_json::Value, room: &str, sender: &str) -> Vec<String> {
+    sync["rooms"]["join"][room]["timeline"]["events"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|event| event["type"] == "m.room.message" && event["sender"] == sender)
+        .filter_map(|event| event["content"]["body"].as_str())
+        .map(|body| body.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{start_of_day, Task};
+    use chrono::{NaiveDate, TimeDelta};
+
+    #[test]
+    fn commands() {
+        assert_eq!("done 12".parse(), Ok(Command::Done(12)));
+        assert_eq!(" Done #12 ".parse(), Ok(Command::Done(12)));
+        assert!("done milk".parse::<Command>().is_err());
+        assert_eq!(
+            "add buy milk tomorrow".parse(),
+            Ok(Command::Add("buy milk due:tomorrow".to_string()))
+        );
+        assert_eq!(
+            "add call mom !high due:1.7 today".parse(),
+            Ok(Command::Add("call mom !high due:1.7 today".to_string()))
+        );
+        assert_eq!(
+            "add tomorrow".parse(),
+            Ok(Command::Add("tomorrow".to_string()))
+        );
+        assert!("add".parse::<Command>().is_err());
+        assert_eq!("agenda".parse(), Ok(Command::Agenda));
+        assert_eq!("/start".parse(), Ok(Command::Help));
+        assert!("hello".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn agendas() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let day = start_of_day(date);
+        let mut meeting = Task::builder()
+            .title("Standup")
+            .created(day)
+            .due(day + TimeDelta::hours(9))
+            .build();
+        meeting.id = 3;
+        let mut report = Task::builder().title("write report").created(day).build();
+        report.id = 7;
+        let text = agenda(&Sheet {
+            date,
+            agenda: vec![&meeting],
+            priorities: vec![&report],
+            habits: vec!["stretch".to_string()],
+            iso: false,
+        });
+        assert_eq!(
+            text,
+            "Agenda for Monday, 04.03.2024\n[3] 09:00 Standup\n\nTop priorities:\n[7] write report\n\nHabits:\n- stretch"
+        );
+    }
+
+    #[cfg(feature = "bot")]
+    #[test]
+    fn curl_config() {
+        assert_eq!(curl_quote("a \"b\"\\\n"), "\"a \\\"b\\\"\\\\\\n\"");
+        assert_eq!(percent_encode("!room:example.org"), "%21room%3Aexample.org");
+    }
+
+    #[cfg(feature = "bot")]
+    #[test]
+    fn matrix_senders() {
+        let event = |sender: &str, body: &str| serde_json::json!({"type": "m.room.message", "sender": sender, "content": {"body": body}});
+        let sync = serde_json::json!({"rooms": {"join": {"!room:x.org": {"timeline": {"events": [
+            event("@me:x.org", "today"),
+            event("@bot:x.org", "Agenda"),
+            event("@guest:x.org", "done 3"),
+            {"type": "m.reaction", "sender": "@me:x.org", "content": {}},
+        ]}}}}});
+        assert_eq!(
+            matrix_messages(&sync, "!room:x.org", "@me:x.org"),
+            ["today"]
+        );
+        assert!(matrix_messages(&sync, "!other:x.org", "@me:x.org").is_empty());
+    }
+}
@@ -0,0 +1,578 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate, NaiveTime, TimeDelta, Weekday};
+use serde::Deserialize;
+
+use crate::score::{UrgencyCoefficients, Weights};
+use crate::theme::{Icons, Theme};
+use crate::{start_of_day, LocalDT, Priority};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// time of day for due dates given without a time. If unset, such dates are all-day.
+    pub default_due_time: Option<NaiveTime>,
+    pub workday_start: Option<NaiveTime>,
+    pub workday_end: Option<NaiveTime>,
+    pub working_days: Vec<Weekday>,
+    /// weights for recommending the next task
+    pub next: Weights,
+    pub urgency: UrgencyCoefficients,
+    /// number of missed occurrences of a reminder that are generated without asking
+    pub backfill_limit: usize,
+    /// force colored output on or off. If unset, color is used on terminals only.
+    pub color: Option<bool>,
+    /// arguments to run when no subcommand is given, e.g. `tasks --sort due`
+    pub default_command: Option<String>,
+    /// user-defined subcommands, expanded to the arguments they stand for
+    pub aliases: BTreeMap<String, String>,
+    /// minutes without running rem or, as noted by the daemon, keyboard and mouse input after
+    /// which a running work session is considered idle. 0 disables the check.
+    pub session_idle_minutes: u64,
+    /// `rem nudge` reminds to track time when no work session ran for this many minutes during
+    /// working hours
+    pub tracking_nudge_minutes: Option<u64>,
+    /// hourly rates per client for timesheets
+    pub rates: BTreeMap<String, f64>,
+    /// tasks count as due soon this many hours before their deadline
+    pub due_soon_hours: u64,
+    /// colors of tasks per state
+    pub theme: Theme,
+    pub icons: Icons,
+    /// force clickable links on or off. If unset, they are used on terminals known to support
+    /// them.
+    pub hyperlinks: Option<bool>,
+    pub database: Database,
+    /// minutes between refreshes of `rem daemon`
+    pub daemon_interval_minutes: u64,
+    /// show desktop notifications when the daemon generates tasks or finds untracked time
+    pub notifications: bool,
+    /// also read dates in other common forms than DD.MM.YYYY, see [`crate::parse::parse_date`]
+    pub lenient_dates: bool,
+    /// show dates as ISO 8601 instead of DD.MM.YYYY
+    pub iso_dates: bool,
+    /// weeks without changes after which a project with open tasks gets a task to review it. 0
+    /// disables reviews.
+    pub project_review_weeks: u64,
+    /// weeks per project title, overriding `project_review_weeks`
+    pub project_reviews: BTreeMap<String, u64>,
+    pub budgets: Budgets,
+    pub bot: Bot,
+    pub serve: Serve,
+    /// reminders kept in the database as declared here, see [`DeclaredReminder`]
+    pub reminders: Vec<DeclaredReminder>,
+    /// when the declared reminders are applied to the database
+    pub reconcile: ReconcileMode,
+}
+
+/// Reminder defined in a `[[reminders]]` block of the config
+///
+/// rem creates it when it is missing from the database, updates it when the block changed and
+/// stops it when the block is removed. Attributes not declared here, like the checklist, are
+/// left alone.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeclaredReminder {
+    /// identifies the reminder across edits of the block, stored as its external id
+    pub name: String,
+    pub title: String,
+    pub description: Option<String>,
+    /// as given to `rem reminder`, e.g. `06.01.2025 09:00`. Relative dates like `today` would
+    /// move the reminder on every run.
+    pub first_due: String,
+    /// e.g. `1w`
+    pub period: String,
+    pub at: Option<NaiveTime>,
+    pub until: Option<String>,
+    pub priority: Option<Priority>,
+    pub context: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub require_completion: bool,
+}
+
+impl DeclaredReminder {
+    /// Prefix of the external ids of declared reminders
+    pub const PREFIX: &'static str = "config:";
+
+    pub fn external_id(&self) -> String {
+        format!("{}{}", Self::PREFIX, self.name)
+    }
+}
+
+/// When the `[[reminders]]` blocks of the config are applied to the database
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReconcileMode {
+    /// before generating tasks, e.g. with `rem tasks` or in the daemon
+    #[default]
+    Auto,
+    /// only by `rem reconcile`, so the plan of `rem reconcile --diff` can be reviewed first
+    Manual,
+}
+
+/// How connections to the database are set up, configured in the `[database]` section
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Database {
+    /// keep a write-ahead log, so status lines and prompts can read while another rem writes.
+    /// Writes are synced less often then, which is safe against crashes of rem but not of the OS.
+    pub wal: bool,
+    /// pages after which the write-ahead log is checkpointed into the database. 0 disables
+    /// automatic checkpoints.
+    pub wal_autocheckpoint: u32,
+    /// milliseconds to wait for another rem holding a lock on the database before failing
+    pub busy_timeout_ms: u64,
+}
+
+/// Chats of `rem bot`, configured in the `[bot]` section
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bot {
+    /// time of day at which the agenda is posted
+    pub agenda_time: NaiveTime,
+    pub telegram_token: Option<String>,
+    /// id of the chat with the Telegram bot. Messages from other chats are ignored.
+    pub telegram_chat: Option<i64>,
+    /// URL of the Matrix homeserver, e.g. `https://matrix.org`
+    pub matrix_homeserver: Option<String>,
+    pub matrix_token: Option<String>,
+    /// id of the room to post to, e.g. `!abc:matrix.org`
+    pub matrix_room: Option<String>,
+    /// account whose messages in the room are applied, e.g. `@me:matrix.org`. Messages of other
+    /// members are ignored.
+    pub matrix_user: Option<String>,
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        Self {
+            agenda_time: NaiveTime::from_hms_opt(7, 0, 0).expect("time is valid"),
+            telegram_token: None,
+            telegram_chat: None,
+            matrix_homeserver: None,
+            matrix_token: None,
+            matrix_room: None,
+            matrix_user: None,
+        }
+    }
+}
+
+/// Hours of work per week, configured in the `[budgets]` section and shown by `rem report` and
+/// `rem status`
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Budgets {
+    /// hours per tag
+    pub tags: BTreeMap<String, f64>,
+    /// hours per project title
+    pub projects: BTreeMap<String, f64>,
+}
+
+/// HTTP server of `rem serve`, configured in the `[serve]` section
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Serve {
+    /// address and port to listen at
+    pub listen: String,
+    /// token allowing to read tasks and reminders, e.g. for a dashboard
+    pub read_token: Option<String>,
+    /// token allowing to also change them
+    pub write_token: Option<String>,
+}
+
+impl Default for Serve {
+    fn default() -> Self {
+        Self {
+            listen: "127.0.0.1:8383".to_string(),
+            read_token: None,
+            write_token: None,
+        }
+    }
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self {
+            wal: true,
+            wal_autocheckpoint: 1000,
+            busy_timeout_ms: 5000,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            default_due_time: None,
+            workday_start: None,
+            workday_end: None,
+            working_days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            next: Weights::default(),
+            urgency: UrgencyCoefficients::default(),
+            backfill_limit: 30,
+            color: None,
+            default_command: None,
+            aliases: BTreeMap::new(),
+            session_idle_minutes: 120,
+            tracking_nudge_minutes: None,
+            rates: BTreeMap::new(),
+            due_soon_hours: 24,
+            theme: Theme::default(),
+            icons: Icons::default(),
+            hyperlinks: None,
+            database: Database::default(),
+            daemon_interval_minutes: 5,
+            notifications: true,
+            lenient_dates: false,
+            iso_dates: false,
+            project_review_weeks: 4,
+            project_reviews: BTreeMap::new(),
+            budgets: Budgets::default(),
+            bot: Bot::default(),
+            serve: Serve::default(),
+            reminders: Vec::new(),
+            reconcile: ReconcileMode::default(),
+        }
+    }
+}
+
+/// Check that `value` in the unit of `span`, e.g. [`TimeDelta::try_hours`], is at most a
+/// century, so times moved by it stay in range
+fn check_span(name: &str, value: u64, span: fn(i64) -> Option<TimeDelta>) -> Result<(), String> {
+    match i64::try_from(value).ok().and_then(span) {
+        Some(span) if span <= TimeDelta::days(36525) => Ok(()),
+        _ => Err(format!(
+            "{name} needs to be at most a hundred years, got {value}"
+        )),
+    }
+}
+
+impl Config {
+    pub fn from_toml(repr: &str) -> Result<Self, String> {
+        let mut config: Self = match toml::from_str::<toml::Table>(repr) {
+            // the top-level key of configs written before the `[database]` section
+            Ok(mut table) if table.contains_key("wal_autocheckpoint") => {
+                let pages = table.remove("wal_autocheckpoint").expect("key is present");
+                let database = table
+                    .entry("database")
+                    .or_insert_with(|| toml::Table::new().into());
+                if let Some(database) = database.as_table_mut() {
+                    database.entry("wal_autocheckpoint").or_insert(pages);
+                }
+                toml::Value::Table(table).try_into()
+            }
+            _ => toml::from_str(repr),
+        }
+        .map_err(|err| err.to_string())?;
+        if let (Some(start), Some(end)) = (config.workday_start, config.workday_end) {
+            if start >= end {
+                return Err(format!(
+                    "workday_start ({start}) needs to be before workday_end ({end})"
+                ));
+            }
+        }
+        if let Some((name, _)) = config
+            .aliases
+            .iter()
+            .find(|(_, expansion)| expansion.split_whitespace().next().is_none())
+        {
+            return Err(format!("alias '{name}' needs to expand to a command"));
+        }
+        if let Some((name, hours)) = config
+            .budgets
+            .tags
+            .iter()
+            .chain(config.budgets.projects.iter())
+            .find(|(_, hours)| !(**hours > 0.0 && hours.is_finite()))
+        {
+            return Err(format!(
+                "budget of '{name}' needs to be a positive number of hours, got {hours}"
+            ));
+        }
+        // tags of budgets may be written with their `#`, as in `"#admin" = 10`
+        let mut tags = BTreeMap::new();
+        for (tag, hours) in std::mem::take(&mut config.budgets.tags) {
+            let tag = crate::tags::validate_tag(&tag)
+                .map_err(|err| format!("budget: {err}"))?
+                .to_string();
+            if tags.insert(tag.clone(), hours).is_some() {
+                return Err(format!("budget of tag '{tag}' is given twice"));
+            }
+        }
+        config.budgets.tags = tags;
+        if config.daemon_interval_minutes == 0 {
+            return Err("daemon_interval_minutes needs to be at least 1".to_string());
+        }
+        for (name, value, span) in [
+            (
+                "due_soon_hours",
+                Some(config.due_soon_hours),
+                TimeDelta::try_hours as fn(i64) -> Option<TimeDelta>,
+            ),
+            (
+                "session_idle_minutes",
+                Some(config.session_idle_minutes),
+                TimeDelta::try_minutes,
+            ),
+            (
+                "tracking_nudge_minutes",
+                config.tracking_nudge_minutes,
+                TimeDelta::try_minutes,
+            ),
+            (
+                "daemon_interval_minutes",
+                Some(config.daemon_interval_minutes),
+                TimeDelta::try_minutes,
+            ),
+        ] {
+            if let Some(value) = value {
+                check_span(name, value, span)?;
+            }
+        }
+        check_span(
+            "project_review_weeks",
+            config.project_review_weeks,
+            TimeDelta::try_weeks,
+        )?;
+        for (title, weeks) in config.project_reviews.iter() {
+            check_span(
+                &format!("project_reviews of '{title}'"),
+                *weeks,
+                TimeDelta::try_weeks,
+            )?;
+        }
+        for (name, token) in [
+            ("read_token", &config.serve.read_token),
+            ("write_token", &config.serve.write_token),
+        ] {
+            if token.as_ref().is_some_and(|token| token.trim().is_empty()) {
+                return Err(format!(
+                    "serve.{name} needs to be a token, leave it out to disable it"
+                ));
+            }
+        }
+        for (idx, reminder) in config.reminders.iter().enumerate() {
+            if reminder.name.trim().is_empty() {
+                return Err(format!("reminder '{}' needs a name", reminder.title));
+            }
+            if config.reminders[..idx]
+                .iter()
+                .any(|other| other.name == reminder.name)
+            {
+                return Err(format!(
+                    "reminder name '{}' is declared twice",
+                    reminder.name
+                ));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Replace an alias in the subcommand position of `args` with its expansion, or add the
+    /// default command if no arguments are given
+    ///
+    /// Aliases are expanded once, so they cannot refer to other aliases. Expansions are split at
+    /// whitespace, quoting is not supported.
+    ///
+    /// * `valued`: options before the subcommand taking the next argument as their value, like
+    ///   `--tz`
+    pub fn expand_alias(&self, mut args: Vec<String>, valued: &[&str]) -> Vec<String> {
+        if args.len() == 1 {
+            if let Some(ref command) = self.default_command {
+                args.extend(command.split_whitespace().map(String::from));
+                return args;
+            }
+        }
+
+        // the subcommand is the first argument that is neither an option nor its value
+        let mut idx = 1;
+        while let Some(arg) = args
+            .get(idx)
+            .filter(|arg| arg.starts_with('-') && *arg != "--")
+        {
+            idx += if valued.contains(&arg.as_str()) { 2 } else { 1 };
+        }
+        if let Some(expansion) = args.get(idx).and_then(|cmd| self.aliases.get(cmd)) {
+            let expansion = expansion.split_whitespace().map(String::from);
+            args.splice(idx..idx + 1, expansion.collect::<Vec<_>>());
+        }
+        args
+    }
+
+    pub fn is_working_day(&self, date: NaiveDate) -> bool {
+        self.working_days.contains(&date.weekday())
+    }
+
+    /// Point in time after which something due on `date` as a whole is overdue.
+    ///
+    /// This is the end of the workday on working days if configured, and the end of the day
+    /// otherwise.
+    pub fn end_of_due_day(&self, date: NaiveDate) -> LocalDT {
+        match self.workday_end {
+            Some(end) if self.is_working_day(date) => date
+                .and_time(end)
+                .and_local_timezone(chrono::Local)
+                .unwrap(),
+            _ => start_of_day(date.succ_opt().expect("date is in range")),
+        }
+    }
+
+    /// Format of datetimes shown to the user, [`crate::ISO_DATETIME_FMT`] with `iso_dates` and
+    /// [`crate::DATETIME_FMT`] otherwise
+    pub fn datetime_fmt(&self) -> &'static str {
+        if self.iso_dates {
+            crate::ISO_DATETIME_FMT
+        } else {
+            crate::DATETIME_FMT
+        }
+    }
+
+    /// Format of dates shown to the user, [`crate::ISO_DATE_FMT`] with `iso_dates` and
+    /// [`crate::DATE_FMT`] otherwise
+    pub fn date_fmt(&self) -> &'static str {
+        if self.iso_dates {
+            crate::ISO_DATE_FMT
+        } else {
+            crate::DATE_FMT
+        }
+    }
+
+    /// Point in time from which something due on `date` as a whole is considered due today.
+    pub fn start_of_due_day(&self, date: NaiveDate) -> LocalDT {
+        match self.workday_start {
+            Some(start) if self.is_working_day(date) => date
+                .and_time(start)
+                .and_local_timezone(chrono::Local)
+                .unwrap(),
+            _ => start_of_day(date),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(repr: &str) -> Vec<String> {
+        repr.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn aliases() {
+        let config = Config::from_toml(
+            r#"
+            [aliases]
+            d = "tasks --sort due"
+            done = "complete"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.expand_alias(args("rem d --all"), &[]),
+            args("rem tasks --sort due --all")
+        );
+        assert_eq!(
+            config.expand_alias(args("rem done 3"), &[]),
+            args("rem complete 3")
+        );
+        assert_eq!(
+            config.expand_alias(args("rem tasks"), &[]),
+            args("rem tasks")
+        );
+        assert_eq!(
+            config.expand_alias(args("rem -q d"), &[]),
+            args("rem -q tasks --sort due")
+        );
+        assert_eq!(
+            config.expand_alias(args("rem --tz UTC d"), &["--tz"]),
+            args("rem --tz UTC tasks --sort due")
+        );
+        // the value of an option is not a subcommand
+        assert_eq!(
+            config.expand_alias(args("rem --tz d tasks"), &["--tz"]),
+            args("rem --tz d tasks")
+        );
+        assert_eq!(config.expand_alias(args("rem"), &[]), args("rem"));
+
+        let config = Config::from_toml(r#"default_command = "tasks --sort due""#).unwrap();
+        assert_eq!(
+            config.expand_alias(args("rem"), &[]),
+            args("rem tasks --sort due")
+        );
+        assert_eq!(config.expand_alias(args("rem tags"), &[]), args("rem tags"));
+
+        assert!(Config::from_toml("[aliases]\nx = \" \"").is_err());
+    }
+
+    #[test]
+    fn database() {
+        let config = Config::from_toml("[database]\nwal = false").unwrap();
+        assert!(!config.database.wal);
+        assert_eq!(config.database.busy_timeout_ms, 5000);
+        assert!(Config::default().database.wal);
+        assert!(Config::from_toml("[database]\nwal_autocheckpoint = -1").is_err());
+
+        let config = Config::from_toml("wal_autocheckpoint = 200").unwrap();
+        assert_eq!(config.database.wal_autocheckpoint, 200);
+        let config =
+            Config::from_toml("wal_autocheckpoint = 200\n[database]\nwal_autocheckpoint = 300")
+                .unwrap();
+        assert_eq!(config.database.wal_autocheckpoint, 300);
+    }
+
+    #[test]
+    fn budgets() {
+        let config = Config::from_toml(
+            r#"
+            [budgets.tags]
+            admin = 10
+            [budgets.projects]
+            thesis = 12.5
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.budgets.tags["admin"], 10.0);
+        assert_eq!(config.budgets.projects["thesis"], 12.5);
+        assert!(Config::from_toml("[budgets.tags]\nadmin = -1").is_err());
+        assert!(Config::from_toml("[budgets.tags]\nadmin = 0").is_err());
+        assert!(Config::from_toml("[budgets]\nadmin = 10").is_err());
+
+        let config = Config::from_toml("[budgets.tags]\n\"#admin\" = 10").unwrap();
+        assert_eq!(config.budgets.tags["admin"], 10.0);
+        assert!(Config::from_toml("[budgets.tags]\n\"#admin\" = 10\nadmin = 5").is_err());
+        assert!(Config::from_toml("[budgets.tags]\n\"#\" = 10").is_err());
+    }
+
+    #[test]
+    fn daemon_interval() {
+        assert_eq!(
+            Config::from_toml("daemon_interval_minutes = 10")
+                .unwrap()
+                .daemon_interval_minutes,
+            10
+        );
+        assert!(Config::from_toml("daemon_interval_minutes = 0").is_err());
+        assert!(Config::from_toml("due_soon_hours = 876000").is_ok());
+        assert!(Config::from_toml("due_soon_hours = 9223372036854775807").is_err());
+        assert!(Config::from_toml("tracking_nudge_minutes = 9223372036854775807").is_err());
+        assert!(Config::from_toml("project_review_weeks = 9223372036854775807").is_err());
+        assert!(Config::from_toml("[project_reviews]\nthesis = 5300").is_err());
+        assert!(Config::from_toml("[project_reviews]\nthesis = 52").is_ok());
+    }
+
+    #[test]
+    fn serve_tokens() {
+        let config = Config::from_toml("[serve]\nread_token = \"reader\"").unwrap();
+        assert_eq!(config.serve.read_token.as_deref(), Some("reader"));
+        assert!(Config::from_toml("[serve]\nwrite_token = \"\"").is_err());
+        assert!(Config::from_toml("[serve]\nread_token = \" \"").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! Background process refreshing periodically and when told to over a unix socket
+//!
+//! Clients send one request per connection as a line of text and read the reply until the
+//! daemon closes the connection.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::LocalDT;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Request {
+    /// refresh right away instead of at the end of the interval
+    Refresh,
+    Status,
+    Stop,
+}
+
+impl Request {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Refresh => "refresh",
+            Self::Status => "status",
+            Self::Stop => "stop",
+        }
+    }
+}
+
+impl FromStr for Request {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "refresh" => Ok(Self::Refresh),
+            "status" => Ok(Self::Status),
+            "stop" => Ok(Self::Stop),
+            other => Err(format!("Unknown request '{other}'")),
+        }
+    }
+}
+
+/// What the daemon did so far, reported on [`Request::Status`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Status {
+    pub pid: u32,
+    pub started: LocalDT,
+    pub refreshes: u64,
+    /// time and outcome of the last refresh
+    pub last: Option<(LocalDT, Result<String, String>)>,
+}
+
+impl Status {
+    /// One line telling the status, with times in `datetime_fmt`
+    pub fn describe(&self, datetime_fmt: &str) -> String {
+        let ret = format!(
+            "running as pid {} since {}, {} refresh{}",
+            self.pid,
+            self.started.format(datetime_fmt),
+            self.refreshes,
+            if self.refreshes == 1 { "" } else { "es" }
+        );
+        match &self.last {
+            Some((at, Ok(summary))) => {
+                format!("{ret}, last at {}: {summary}", at.format(datetime_fmt))
+            }
+            Some((at, Err(err))) => {
+                format!("{ret}, last at {} failed: {err}", at.format(datetime_fmt))
+            }
+            None => ret,
+        }
+    }
+}
+
+/// Send a request to the daemon listening at `path`, returning its reply
+pub fn send(path: &Path, request: Request) -> Result<String, String> {
+    let mut stream = UnixStream::connect(path)
+        .map_err(|err| format!("No daemon listening at {}: {err}", path.display()))?;
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .map_err(|err| format!("Could not configure socket: {err}"))?;
+    writeln!(stream, "{}", request.as_str())
+        .map_err(|err| format!("Could not send request: {err}"))?;
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .map_err(|err| format!("Could not read reply: {err}"))?;
+    Ok(reply.trim_end().to_string())
+}
+
+/// Send a request to the daemon listening at `path` without waiting for its reply, e.g. a
+/// refresh after a change that should not hold up the command making it
+pub fn notify(path: &Path, request: Request) -> Result<(), String> {
+    let mut stream = UnixStream::connect(path)
+        .map_err(|err| format!("No daemon listening at {}: {err}", path.display()))?;
+    writeln!(stream, "{}", request.as_str()).map_err(|err| format!("Could not send request: {err}"))
+}
+
+/// Listen at `path` and call `refresh` every `interval` and whenever a client asks for it, until
+/// a client sends [`Request::Stop`]
+///
+/// Fails if another daemon is listening at `path` already. A socket left behind by a daemon that
+/// did not stop cleanly is replaced.
+///
+/// * `refresh`: summary of what was done
+/// * `now`: current time, for the status
+/// * `datetime_fmt`: format of the times in the status
+pub fn serve(
+    path: &Path,
+    interval: Duration,
+    mut refresh: impl FnMut() -> Result<String, String>,
+    now: impl Fn() -> LocalDT,
+    datetime_fmt: &str,
+) -> Result<(), String> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!(
+                "A daemon is listening at {} already",
+                path.display()
+            ));
+        }
+        std::fs::remove_file(path)
+            .map_err(|err| format!("Could not remove stale socket {}: {err}", path.display()))?;
+    }
+    let listener = UnixListener::bind(path)
+        .map_err(|err| format!("Could not listen at {}: {err}", path.display()))?;
+
+    let (tx, rx) = mpsc::channel::<(Request, UnixStream)>();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            // a client sending nothing must not block the others
+            if stream
+                .set_read_timeout(Some(Duration::from_secs(1)))
+                .is_err()
+            {
+                continue;
+            }
+            let mut line = String::new();
+            if BufReader::new(&stream).read_line(&mut line).is_err() {
+                continue;
+            }
+            match line.parse() {
+                Ok(request) => {
+                    if tx.send((request, stream)).is_err() {
+                        return;
+                    }
+                }
+                Err(err) => {
+                    let _ = writeln!(stream, "ERROR: {err}");
+                }
+            }
+        }
+    });
+
+    let mut status = Status {
+        pid: std::process::id(),
+        started: now(),
+        refreshes: 0,
+        last: None,
+    };
+    let mut run = |status: &mut Status| {
+        let outcome = refresh();
+        status.refreshes += 1;
+        status.last = Some((now(), outcome));
+    };
+    run(&mut status);
+    // requests in between, like frequent status queries, must not postpone the next refresh
+    let mut next = Instant::now() + interval;
+    loop {
+        match rx.recv_timeout(next.saturating_duration_since(Instant::now())) {
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                run(&mut status);
+                next = Instant::now() + interval;
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                let _ = std::fs::remove_file(path);
+                return Err("Stopped accepting connections".to_string());
+            }
+            Ok((Request::Refresh, mut stream)) => {
+                run(&mut status);
+                next = Instant::now() + interval;
+                let _ = writeln!(stream, "refreshed");
+            }
+            Ok((Request::Status, mut stream)) => {
+                let _ = writeln!(stream, "{}", status.describe(datetime_fmt));
+            }
+            Ok((Request::Stop, mut stream)) => {
+                let _ = std::fs::remove_file(path);
+                let _ = writeln!(stream, "stopped");
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn requests() {
+        let path = std::env::temp_dir().join(format!("rem-daemon-{}.sock", std::process::id()));
+        let server = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                let mut count = 0;
+                serve(
+                    &path,
+                    Duration::from_secs(3600),
+                    || {
+                        count += 1;
+                        Ok(format!("refresh {count}"))
+                    },
+                    chrono::Local::now,
+                    crate::DATETIME_FMT,
+                )
+            })
+        };
+        while !path.exists() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(send(&path, Request::Refresh).unwrap(), "refreshed");
+        let status = send(&path, Request::Status).unwrap();
+        assert!(status.contains("2 refreshes"), "{status}");
+        assert!(status.ends_with("refresh 2"), "{status}");
+        // handled in order, so before the status request following it
+        notify(&path, Request::Refresh).unwrap();
+        let status = send(&path, Request::Status).unwrap();
+        assert!(status.ends_with("refresh 3"), "{status}");
+        assert_eq!(send(&path, Request::Stop).unwrap(), "stopped");
+        server.join().unwrap().unwrap();
+        assert!(!path.exists());
+        assert!(send(&path, Request::Status).is_err());
+    }
+
+    #[test]
+    fn polled() {
+        let path = std::env::temp_dir().join(format!("rem-polled-{}.sock", std::process::id()));
+        let server = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                serve(
+                    &path,
+                    Duration::from_millis(200),
+                    || Ok(String::new()),
+                    chrono::Local::now,
+                    crate::DATETIME_FMT,
+                )
+            })
+        };
+        while !path.exists() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // a client that never sends a request
+        let _silent = UnixStream::connect(&path).unwrap();
+        let started = Instant::now();
+        let mut status = String::new();
+        while started.elapsed() < Duration::from_millis(1500) {
+            status = send(&path, Request::Status).unwrap();
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        let refreshes = status
+            .split(", ")
+            .nth(1)
+            .and_then(|part| part.split(' ').next())
+            .and_then(|count| count.parse::<u64>().ok())
+            .unwrap();
+        assert!(refreshes >= 3, "{status}");
+        assert_eq!(send(&path, Request::Stop).unwrap(), "stopped");
+        server.join().unwrap().unwrap();
+    }
+}
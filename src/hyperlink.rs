@@ -0,0 +1,132 @@
+//! Clickable links in terminal output using OSC 8 escape sequences
+
+/// Make `label` a link to `url`
+pub fn wrap(url: &str, label: &str) -> String {
+    format!("\x1b]8;;{url}\x1b\\{label}\x1b]8;;\x1b\\")
+}
+
+/// Make every `http://` and `https://` URL in `text` a link to itself
+pub fn linkify(text: &str) -> String {
+    let mut ret = String::new();
+    let mut rest = text;
+    while let Some(start) = ["https://", "http://"]
+        .iter()
+        .filter_map(|scheme| rest.find(scheme))
+        .min()
+    {
+        ret.push_str(&rest[..start]);
+        let (url, after) = split_url(&rest[start..]).expect("text starts with a scheme");
+        ret.push_str(&wrap(url, url));
+        rest = after;
+    }
+    ret.push_str(rest);
+    ret
+}
+
+/// `file://` URL of an absolute path, `None` for relative ones
+///
+/// Windows paths like `C:\src` are recognized by their drive letter on every platform.
+pub fn file_url(path: &str) -> Option<String> {
+    let path = if path.as_bytes().get(1) == Some(&b':') {
+        format!("/{}", path.replace('\\', "/"))
+    } else if path.starts_with('/') {
+        path.to_string()
+    } else {
+        return None;
+    };
+    let mut ret = "file://".to_string();
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~:".contains(&byte) {
+            ret.push(byte as char);
+        } else {
+            ret.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    Some(ret)
+}
+
+/// Make an absolute `path` a link to itself, keeping relative ones as they are
+pub fn link_path(path: &str) -> String {
+    match file_url(path) {
+        Some(url) => wrap(&url, path),
+        None => path.to_string(),
+    }
+}
+
+/// Split off the `http://` or `https://` URL `text` starts with
+pub fn split_url(text: &str) -> Option<(&str, &str)> {
+    if !text.starts_with("https://") && !text.starts_with("http://") {
+        return None;
+    }
+    let end = text
+        .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | ')'))
+        .unwrap_or(text.len());
+    // trailing punctuation most likely ends the sentence rather than the URL
+    let url = text[..end].trim_end_matches(['.', ',', ';', ':', '!', '?']);
+    Some((url, &text[url.len()..]))
+}
+
+/// Whether the terminal is known to support OSC 8 links, judging by its environment variables
+pub fn supported(var: impl Fn(&str) -> Option<String>) -> bool {
+    if var("VTE_VERSION").is_some_and(|v| v.parse::<u32>().is_ok_and(|v| v >= 5000)) {
+        return true;
+    }
+    if [
+        "KITTY_WINDOW_ID",
+        "WT_SESSION",
+        "KONSOLE_VERSION",
+        "WEZTERM_EXECUTABLE",
+    ]
+    .iter()
+    .any(|name| var(name).is_some())
+    {
+        return true;
+    }
+    var("TERM_PROGRAM").is_some_and(|program| {
+        matches!(
+            program.as_str(),
+            "iTerm.app" | "WezTerm" | "vscode" | "ghostty" | "Hyper"
+        )
+    }) || var("TERM").is_some_and(|term| term.contains("kitty") || term.contains("alacritty"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn links() {
+        assert_eq!(
+            linkify("see https://example.com/1."),
+            format!(
+                "see {}.",
+                wrap("https://example.com/1", "https://example.com/1")
+            )
+        );
+        assert_eq!(linkify("no links"), "no links");
+
+        assert_eq!(
+            file_url("/home/me/my notes").as_deref(),
+            Some("file:///home/me/my%20notes")
+        );
+        assert_eq!(
+            file_url(r"C:\src\rem").as_deref(),
+            Some("file:///C:/src/rem")
+        );
+        assert_eq!(file_url("src/rem"), None);
+        assert_eq!(link_path("/srv/rem"), wrap("file:///srv/rem", "/srv/rem"));
+        assert_eq!(link_path("src"), "src");
+
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert!(supported(env(&[("TERM_PROGRAM", "WezTerm")])));
+        assert!(supported(env(&[("VTE_VERSION", "7600")])));
+        assert!(!supported(env(&[("VTE_VERSION", "4000")])));
+        assert!(!supported(env(&[("TERM", "xterm-256color")])));
+    }
+}
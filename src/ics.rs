@@ -0,0 +1,120 @@
+//! iCalendar export of `rem ics` for calendar applications
+//!
+//! Every task with a due date becomes an event. All-day tasks are whole-day events with `DATE`
+//! values, timed ones span their due window, or are a moment at the due time if it has no start.
+
+use chrono::{TimeDelta, Utc};
+
+use crate::{LocalDT, Task};
+
+/// Calendar of the tasks with a due date, skipping the others
+///
+/// * `now`: stamped on every event as the time it was exported
+pub fn calendar<'a>(tasks: impl IntoIterator<Item = &'a Task>, now: LocalDT) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//rem//rem//EN".to_string(),
+    ];
+    for task in tasks {
+        let Some(due) = task.due else {
+            continue;
+        };
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:task-{}@rem", task.id));
+        lines.push(format!("DTSTAMP:{}", utc(now)));
+        if task.due_all_day {
+            let first = task.due_start.unwrap_or(due).date_naive();
+            let end = due.date_naive() + TimeDelta::days(1);
+            lines.push(format!("DTSTART;VALUE=DATE:{}", first.format("%Y%m%d")));
+            lines.push(format!("DTEND;VALUE=DATE:{}", end.format("%Y%m%d")));
+        } else if let Some(start) = task.due_start {
+            lines.push(format!("DTSTART:{}", utc(start)));
+            lines.push(format!("DTEND:{}", utc(due)));
+        } else {
+            lines.push(format!("DTSTART:{}", utc(due)));
+        }
+        lines.push(format!("SUMMARY:{}", escape(&task.title)));
+        if let Some(description) = &task.description {
+            lines.push(format!("DESCRIPTION:{}", escape(description)));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+fn utc(datetime: LocalDT) -> String {
+    datetime
+        .with_timezone(&Utc)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Split a line into lines of at most 75 bytes, continued by a leading space
+fn fold(line: &str) -> String {
+    let mut ret = String::new();
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            ret.push_str("\r\n ");
+            len = 1;
+        }
+        ret.push(c);
+        len += c.len_utf8();
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn events() {
+        let day = crate::start_of_day(NaiveDate::from_ymd_opt(2025, 6, 2).unwrap());
+        let mut all_day = Task::builder()
+            .title("rent; due")
+            .created(day)
+            .due(day)
+            .all_day(true)
+            .build();
+        all_day.id = 1;
+        let mut timed = Task::builder()
+            .title("call")
+            .created(day)
+            .due(day + TimeDelta::hours(9))
+            .build();
+        timed.id = 2;
+        let undated = Task::builder().title("someday").created(day).build();
+
+        let ics = calendar([&all_day, &timed, &undated], day);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+        assert!(ics.contains("DTSTART;VALUE=DATE:20250602\r\nDTEND;VALUE=DATE:20250603\r\n"));
+        assert!(ics.contains("SUMMARY:rent\\; due\r\n"));
+        let start = utc(day + TimeDelta::hours(9));
+        assert!(ics.contains(&format!("DTSTART:{start}\r\nSUMMARY:call\r\n")));
+    }
+
+    #[test]
+    fn folding() {
+        let line = "x".repeat(80);
+        assert_eq!(
+            fold(&line),
+            format!("{}\r\n {}", "x".repeat(75), "x".repeat(5))
+        );
+        assert_eq!(fold("short"), "short");
+    }
+}
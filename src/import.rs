@@ -0,0 +1,526 @@
+//! Reading tasks exported by other todo applications
+//!
+//! Supported are [todo.txt](http://todotxt.org) files, the JSON array written by `task export`
+//! of Taskwarrior and iCalendar files. All are read into [`Entry`]s, one per line, exported task
+//! or calendar component, that the caller stores.
+
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta, TimeZone};
+use serde_json::Value;
+
+use crate::{start_of_day, LocalDT, Priority, Task};
+
+/// A task read from an export
+pub struct Entry {
+    /// line in a todo.txt file or position in a Taskwarrior export or calendar, counted from 1
+    pub line: usize,
+    pub task: Task,
+    /// key of the task in the application it was exported from, e.g. the uuid in Taskwarrior
+    pub external_id: Option<String>,
+}
+
+/// A task read from an export, `None` if it is not to be imported, or the line and error of a
+/// task that could not be read
+pub type Record = Result<Option<Entry>, (usize, String)>;
+
+/// Read a todo.txt file
+///
+/// Priorities `(A)` to `(C)` become high to low, `+project` becomes a tag, the first `@context`
+/// the context and `due:YYYY-MM-DD` an all-day due date. Blank lines are ignored.
+///
+/// * `now`: creation time of tasks without a creation date
+pub fn todo_txt(text: &str, now: LocalDT) -> Vec<Record> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            todo_txt_line(line, now)
+                .map(|task| {
+                    Some(Entry {
+                        line: i + 1,
+                        task,
+                        external_id: None,
+                    })
+                })
+                .map_err(|err| (i + 1, err))
+        })
+        .collect()
+}
+
+fn todo_txt_line(line: &str, now: LocalDT) -> Result<Task, String> {
+    let mut words = line.split_whitespace().peekable();
+    let date = |word: &str| NaiveDate::parse_from_str(word, "%Y-%m-%d").ok();
+
+    let done = words.next_if_eq(&"x").is_some();
+    let priority = match words.peek() {
+        Some(&"(A)") => Some(Priority::High),
+        Some(&"(B)") => Some(Priority::Medium),
+        Some(w) if w.len() == 3 && w.starts_with('(') && w.ends_with(')') => Some(Priority::Low),
+        _ => None,
+    };
+    if priority.is_some() {
+        words.next();
+    }
+    // completed tasks list the completion date before the creation date
+    let completed = if done {
+        words.next_if(|w| date(w).is_some()).and_then(date)
+    } else {
+        None
+    };
+    let created = words.next_if(|w| date(w).is_some()).and_then(date);
+
+    let mut title = Vec::new();
+    let mut tags = Vec::new();
+    let mut context = None;
+    let mut due = None;
+    for word in words {
+        if let Some(project) = word.strip_prefix('+').filter(|p| !p.is_empty()) {
+            tags.push(project.to_string());
+        } else if let Some(ctx) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+            context.get_or_insert(ctx.to_string());
+        } else if let Some(repr) = word.strip_prefix("due:") {
+            due =
+                Some(date(repr).ok_or(format!("Invalid due date '{repr}'. Expected YYYY-MM-DD."))?);
+        } else {
+            title.push(word);
+        }
+    }
+    if title.is_empty() {
+        return Err("Need a title besides the annotations.".to_string());
+    }
+
+    let created = created.map(start_of_day).unwrap_or(now);
+    let mut builder = Task::builder()
+        .title(title.join(" "))
+        .tags(tags)
+        .created(created);
+    if let Some(priority) = priority {
+        builder = builder.priority(priority);
+    }
+    if let Some(context) = context {
+        builder = builder.context(context);
+    }
+    if let Some(due) = due {
+        builder = builder.due(start_of_day(due)).all_day(true);
+    }
+    if done {
+        builder = builder.completed(completed.map(start_of_day).unwrap_or(now));
+    }
+    Ok(builder.build())
+}
+
+/// Read the JSON array written by `task export`
+///
+/// Deleted tasks and the templates of recurring tasks are read as `None`. The project becomes a
+/// tag and the wait date the start of the task.
+///
+/// * `now`: creation time of tasks without an entry date
+pub fn taskwarrior(repr: &str, now: LocalDT) -> Result<Vec<Record>, String> {
+    let records: Vec<Value> = serde_json::from_str(repr)
+        .map_err(|err| format!("Could not parse Taskwarrior export: {err}"))?;
+    Ok(records
+        .iter()
+        .enumerate()
+        .map(|(i, record)| {
+            taskwarrior_record(record, now)
+                .map(|task| {
+                    task.map(|task| Entry {
+                        line: i + 1,
+                        task,
+                        external_id: record["uuid"].as_str().map(str::to_string),
+                    })
+                })
+                .map_err(|err| (i + 1, err))
+        })
+        .collect())
+}
+
+fn taskwarrior_record(record: &Value, now: LocalDT) -> Result<Option<Task>, String> {
+    let status = record["status"].as_str().unwrap_or("pending");
+    if matches!(status, "deleted" | "recurring") {
+        return Ok(None);
+    }
+    let title = record["description"]
+        .as_str()
+        .ok_or("Missing description.".to_string())?;
+    let date = |key: &str| -> Result<Option<LocalDT>, String> {
+        record[key]
+            .as_str()
+            .map(|repr| {
+                NaiveDateTime::parse_from_str(repr, "%Y%m%dT%H%M%SZ")
+                    .map(|dt| dt.and_utc().with_timezone(&chrono::Local))
+                    .map_err(|err| format!("Invalid {key} date '{repr}': {err}"))
+            })
+            .transpose()
+    };
+
+    let mut tags = record["tags"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if let Some(project) = record["project"].as_str() {
+        tags.push(project.replace(char::is_whitespace, "-"));
+    }
+
+    let mut builder = Task::builder()
+        .title(title)
+        .tags(tags)
+        .created(date("entry")?.unwrap_or(now));
+    if let Some(priority) = match record["priority"].as_str() {
+        Some("H") => Some(Priority::High),
+        Some("M") => Some(Priority::Medium),
+        Some("L") => Some(Priority::Low),
+        _ => None,
+    } {
+        builder = builder.priority(priority);
+    }
+    if let Some(wait) = date("wait")? {
+        builder = builder.start(wait);
+    }
+    if let Some(due) = date("due")? {
+        builder = builder.due(due);
+    }
+    if status == "completed" {
+        builder = builder.completed(date("end")?.unwrap_or(now));
+    }
+    Ok(Some(builder.build()))
+}
+
+/// Read the to-dos and events of an iCalendar file
+///
+/// To-dos are due at `DUE` and start at `DTSTART`, events are due in the window from `DTSTART`
+/// to `DTEND`. Dates without a time make all-day tasks, `CATEGORIES` become tags and the `UID`
+/// the external id. Cancelled components are read as `None`.
+///
+/// * `now`: creation time of components without a `CREATED` property
+pub fn ics(text: &str, now: LocalDT) -> Vec<Record> {
+    // lines starting with whitespace continue the previous one
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut records = Vec::new();
+    let mut component: Option<(&str, Vec<(&str, &str)>)> = None;
+    for line in lines.iter() {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        match (name, component.as_mut()) {
+            ("BEGIN", None) if matches!(value, "VTODO" | "VEVENT") => {
+                component = Some((value, Vec::new()));
+            }
+            ("END", Some((kind, _))) if value == *kind => {
+                let (kind, properties) = component.take().unwrap();
+                let position = records.len() + 1;
+                let uid = properties
+                    .iter()
+                    .find(|(name, _)| *name == "UID")
+                    .map(|(_, value)| unescape(value));
+                records.push(
+                    ics_component(kind, &properties, now)
+                        .map(|task| {
+                            task.map(|task| Entry {
+                                line: position,
+                                task,
+                                external_id: uid,
+                            })
+                        })
+                        .map_err(|err| (position, err)),
+                );
+            }
+            (_, Some((_, properties))) => properties.push((name, value)),
+            _ => (),
+        }
+    }
+    records
+}
+
+fn ics_component(
+    kind: &str,
+    properties: &[(&str, &str)],
+    now: LocalDT,
+) -> Result<Option<Task>, String> {
+    // the name of a property is followed by its parameters, e.g. `DTSTART;VALUE=DATE`
+    let property = |key: &str| {
+        properties
+            .iter()
+            .find(|(name, _)| name.split(';').next() == Some(key))
+            .map(|(name, value)| (*name, *value))
+    };
+    let date = |key: &str| -> Result<Option<(LocalDT, bool)>, String> {
+        property(key)
+            .map(|(name, value)| ics_date(name, value))
+            .transpose()
+    };
+
+    if property("STATUS").is_some_and(|(_, status)| status == "CANCELLED") {
+        return Ok(None);
+    }
+    let title = property("SUMMARY")
+        .map(|(_, summary)| unescape(summary))
+        .filter(|summary| !summary.trim().is_empty())
+        .ok_or("Missing summary.".to_string())?;
+
+    let mut builder = Task::builder()
+        .title(title)
+        .created(date("CREATED")?.map_or(now, |(created, _)| created));
+    if let Some((_, description)) = property("DESCRIPTION") {
+        builder = builder.description(unescape(description));
+    }
+    let categories = properties
+        .iter()
+        .filter(|(name, _)| name.split(';').next() == Some("CATEGORIES"))
+        .flat_map(|(_, value)| split_list(value))
+        .map(|tag| tag.trim().replace(char::is_whitespace, "-"))
+        .filter(|tag| !tag.is_empty());
+    builder = builder.tags(categories);
+    // 1 to 4 are high, 5 medium and 6 to 9 low, 0 is undefined
+    if let Some((_, priority)) = property("PRIORITY") {
+        builder = match priority.trim().parse::<u8>() {
+            Ok(1..=4) => builder.priority(Priority::High),
+            Ok(5) => builder.priority(Priority::Medium),
+            Ok(6..=9) => builder.priority(Priority::Low),
+            Ok(0) => builder,
+            _ => return Err(format!("Invalid priority '{priority}'.")),
+        };
+    }
+
+    if kind == "VTODO" {
+        if let Some((start, _)) = date("DTSTART")? {
+            builder = builder.start(start);
+        }
+        if let Some((due, all_day)) = date("DUE")? {
+            builder = builder.due(due).all_day(all_day);
+        }
+    } else if let Some((start, all_day)) = date("DTSTART")? {
+        // all-day events end at the start of the day after their last one
+        let end = date("DTEND")?.map(|(end, _)| {
+            if all_day && end > start {
+                crate::start_of_day(end.date_naive() - TimeDelta::days(1))
+            } else {
+                end
+            }
+        });
+        builder = match end {
+            Some(end) if end > start => builder.due_start(start).due(end),
+            _ => builder.due(start),
+        }
+        .all_day(all_day);
+    }
+
+    let completed = property("STATUS").is_some_and(|(_, status)| status == "COMPLETED");
+    if let Some((completed, _)) = date("COMPLETED")? {
+        builder = builder.completed(completed);
+    } else if completed {
+        builder = builder.completed(now);
+    }
+    Ok(Some(builder.build()))
+}
+
+/// Date of a property and whether it is a whole day, in UTC if it ends in `Z` and in local time
+/// otherwise, which ignores a `TZID` parameter
+fn ics_date(name: &str, value: &str) -> Result<(LocalDT, bool), String> {
+    let invalid = |err| format!("Invalid date '{value}' of {name}: {err}");
+    if let Some(utc) = value.strip_suffix('Z') {
+        let datetime = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").map_err(invalid)?;
+        return Ok((datetime.and_utc().with_timezone(&chrono::Local), false));
+    }
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        let local = chrono::Local
+            .from_local_datetime(&datetime)
+            .earliest()
+            .ok_or(format!("Date '{value}' of {name} does not exist here."))?;
+        return Ok((local, false));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(invalid)?;
+    Ok((start_of_day(date), true))
+}
+
+/// Text of a property without the escapes of `\\`, `\;`, `\,` and `\n`
+fn unescape(value: &str) -> String {
+    let mut ret = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            ret.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => ret.push('\n'),
+            Some(escaped) => ret.push(escaped),
+            None => ret.push(c),
+        }
+    }
+    ret
+}
+
+/// Values of a comma-separated list property, unescaped
+fn split_list(value: &str) -> Vec<String> {
+    let mut ret = vec![String::new()];
+    let mut escaped = false;
+    for c in value.chars() {
+        match c {
+            ',' if !escaped => ret.push(String::new()),
+            '\\' if !escaped => {
+                escaped = true;
+                continue;
+            }
+            c => ret.last_mut().unwrap().push(c),
+        }
+        escaped = false;
+    }
+    ret
+}
+
+/// What became of an imported task
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Created,
+    /// a task with the same external id was already stored and is overwritten
+    Updated,
+    /// deleted in the export or duplicating an open task
+    Skipped,
+}
+
+/// Counts of what became of the tasks of an import
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    /// line and error of tasks that could not be imported
+    pub failed: Vec<(usize, String)>,
+}
+
+impl Summary {
+    pub fn add(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Created => self.created += 1,
+            Outcome::Updated => self.updated += 1,
+            Outcome::Skipped => self.skipped += 1,
+        }
+    }
+
+    pub fn merge(&mut self, other: Summary) {
+        self.created += other.created;
+        self.updated += other.updated;
+        self.skipped += other.skipped;
+        self.failed.extend(other.failed);
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} created, {} updated, {} skipped",
+            self.created, self.updated, self.skipped
+        )?;
+        if !self.failed.is_empty() {
+            write!(f, ", {} failed", self.failed.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn todo_txt_lines() {
+        let now = chrono::Local::now();
+        let text = "(A) 2024-03-01 Call Bob +flat @phone due:2024-03-04\n\nx 2024-03-02 2024-03-01 water plants\nbad due:4.3.\n+only @tags";
+        let entries = todo_txt(text, now);
+        assert_eq!(entries.len(), 4);
+
+        let call = entries[0].as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(call.line, 1);
+        assert_eq!(call.task.title, "Call Bob");
+        assert_eq!(call.task.priority, Some(Priority::High));
+        assert_eq!(call.task.tags, ["flat"]);
+        assert_eq!(call.task.context.as_deref(), Some("phone"));
+        let date = |d| start_of_day(NaiveDate::from_ymd_opt(2024, 3, d).unwrap());
+        assert_eq!(call.task.created, date(1));
+        assert_eq!(call.task.due, Some(date(4)));
+        assert!(call.task.due_all_day);
+
+        let water = entries[1].as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(water.line, 3);
+        assert_eq!(water.task.title, "water plants");
+        assert_eq!(water.task.completed, Some(date(2)));
+
+        assert_eq!(entries[2].as_ref().err().map(|(line, _)| *line), Some(4));
+        assert_eq!(entries[3].as_ref().err().map(|(line, _)| *line), Some(5));
+    }
+
+    #[test]
+    fn taskwarrior_export() {
+        let now = chrono::Local::now();
+        let repr = r#"[
+            {"uuid": "a1", "description": "pay rent", "status": "pending", "entry": "20240301T080000Z", "due": "20240305T120000Z", "priority": "H", "project": "flat", "tags": ["finance"]},
+            {"uuid": "b2", "description": "old", "status": "deleted"},
+            {"uuid": "c3", "description": "done", "status": "completed", "end": "20240302T100000Z"},
+            {"uuid": "d4", "status": "pending"}
+        ]"#;
+        let entries = taskwarrior(repr, now).unwrap();
+        assert_eq!(entries.len(), 4);
+
+        let rent = entries[0].as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(rent.external_id.as_deref(), Some("a1"));
+        assert_eq!(rent.task.priority, Some(Priority::High));
+        assert_eq!(rent.task.tags, ["finance", "flat"]);
+        assert_eq!(
+            rent.task.due.map(|d| d.with_timezone(&Utc).to_rfc3339()),
+            Some("2024-03-05T12:00:00+00:00".to_string())
+        );
+        assert!(matches!(entries[1], Ok(None)));
+        let done = entries[2].as_ref().unwrap().as_ref().unwrap();
+        assert!(done.task.completed.is_some());
+        assert_eq!(entries[3].as_ref().err().map(|(line, _)| *line), Some(4));
+
+        assert!(taskwarrior("{}", now).is_err());
+    }
+
+    #[test]
+    fn ics_components() {
+        let now = chrono::Local::now();
+        let text = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:t1\r\nSUMMARY:pay rent\\, on time\r\nDUE;VALUE=DATE:20240305\r\nPRIORITY:1\r\nCATEGORIES:flat,finance\r\nEND:VTODO\r\nBEGIN:VEVENT\r\nUID:e1\r\nSUMMARY:confer\r\n ence\r\nDTSTART;VALUE=DATE:20240310\r\nDTEND;VALUE=DATE:20240313\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nSUMMARY:call\r\nDTSTART:20240301T090000Z\r\nDTEND:20240301T093000Z\r\nEND:VEVENT\r\nBEGIN:VTODO\r\nSUMMARY:old\r\nSTATUS:CANCELLED\r\nEND:VTODO\r\nBEGIN:VTODO\r\nSUMMARY:done\r\nSTATUS:COMPLETED\r\nEND:VTODO\r\nBEGIN:VTODO\r\nDUE:tomorrow\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+        let entries = ics(text, now);
+        assert_eq!(entries.len(), 6);
+        let date = |d| start_of_day(NaiveDate::from_ymd_opt(2024, 3, d).unwrap());
+
+        let rent = entries[0].as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(rent.external_id.as_deref(), Some("t1"));
+        assert_eq!(rent.task.title, "pay rent, on time");
+        assert_eq!(rent.task.due, Some(date(5)));
+        assert!(rent.task.due_all_day);
+        assert_eq!(rent.task.priority, Some(Priority::High));
+        assert_eq!(rent.task.tags, ["flat", "finance"]);
+
+        let conference = entries[1].as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(conference.task.title, "conference");
+        assert_eq!(conference.task.due_start, Some(date(10)));
+        assert_eq!(conference.task.due, Some(date(12)));
+
+        let call = entries[2].as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(call.external_id, None);
+        assert!(!call.task.due_all_day);
+        assert_eq!(
+            call.task.due.map(|d| d.with_timezone(&Utc).to_rfc3339()),
+            Some("2024-03-01T09:30:00+00:00".to_string())
+        );
+
+        assert!(matches!(entries[3], Ok(None)));
+        let done = entries[4].as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(done.task.completed, Some(now));
+        assert_eq!(entries[5].as_ref().err().map(|(line, _)| *line), Some(6));
+    }
+}
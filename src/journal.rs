@@ -0,0 +1,395 @@
+//! Journal of the changes made by each invocation, so they can be undone and redone
+//!
+//! Temporary triggers collect for every changed row the statement reverting the change, e.g. a
+//! `DELETE` for an inserted row. [`record`] stores the statements collected so far as one
+//! operation. Undoing an operation runs its statements in reverse order, which makes the triggers
+//! collect the statements redoing it in turn.
+//!
+//! Changes rem makes on its own, like generating the tasks of reminders, are recorded as
+//! automatic operations. They are undone along with the operation before them and redone along
+//! with it, as undoing just them would only make rem repeat them.
+//!
+//! Only changes made through connections with the triggers [installed](install) are journaled,
+//! not those made with the sqlite3 shell for example.
+
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::LocalDT;
+
+/// Number of operations kept, older ones cannot be undone anymore
+pub const HISTORY_LIMIT: u64 = 100;
+
+/// Create the journal tables and the triggers collecting changes to `tables`
+///
+/// * `stamps`: columns kept up to date by triggers of their own, like modification times. They
+///   are left out of the journal, so undoing and redoing moves them forward like any change.
+pub fn install(conn: &Connection, tables: &[&str], stamps: &[&str]) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS operations (
+           id INTEGER PRIMARY KEY,
+           label TEXT NOT NULL,
+           at INTEGER NOT NULL,
+           automatic INTEGER NOT NULL DEFAULT 0,
+           undone INTEGER NOT NULL DEFAULT 0
+         );
+         CREATE TABLE IF NOT EXISTS undo_log (
+           seq INTEGER PRIMARY KEY,
+           op INTEGER NOT NULL,
+           sql TEXT NOT NULL,
+           FOREIGN KEY(op) REFERENCES operations(id) ON DELETE CASCADE
+         );
+         CREATE TEMP TABLE IF NOT EXISTS journal_pending (
+           seq INTEGER PRIMARY KEY,
+           sql TEXT NOT NULL
+         );",
+    )
+    .map_err(|err| format!("could not create journal tables: {err}"))?;
+    // rows replaced by `INSERT OR REPLACE` are only journaled with recursive triggers
+    conn.pragma_update(None, "recursive_triggers", true)
+        .map_err(|err| format!("could not enable recursive triggers: {err}"))?;
+
+    for table in tables {
+        let columns = conn
+            .prepare(&format!("PRAGMA table_info({table});"))
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>("name")?,
+                        row.get::<_, String>("type")?,
+                        row.get::<_, i64>("pk")?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(|err| format!("could not inspect {table} table: {err}"))?;
+        let columns = columns
+            .into_iter()
+            .filter(|(name, _, _)| !stamps.contains(&name.as_str()))
+            .collect::<Vec<_>>();
+        conn.execute_batch(&triggers(table, &columns))
+            .map_err(|err| format!("could not journal {table} table: {err}"))?;
+    }
+    Ok(())
+}
+
+/// Triggers collecting the reverting statements of changes to `table`
+///
+/// * `columns`: name, type and primary key index of each column. Rows are identified by their
+///   rowid, so an `INTEGER PRIMARY KEY` column aliasing it is not set separately.
+fn triggers(table: &str, columns: &[(String, String, i64)]) -> String {
+    let keys = columns.iter().filter(|(_, _, pk)| *pk > 0).count();
+    let columns = columns
+        .iter()
+        .filter(|(_, ty, pk)| !(keys == 1 && *pk == 1 && ty.eq_ignore_ascii_case("INTEGER")))
+        .map(|(name, _, _)| name.as_str())
+        .collect::<Vec<_>>();
+
+    let names = columns
+        .iter()
+        .map(|c| format!(", \"{c}\""))
+        .collect::<String>();
+    let values = columns
+        .iter()
+        .map(|c| format!(" || ', ' || quote(old.\"{c}\")"))
+        .collect::<String>();
+    let assignments = columns
+        .iter()
+        .map(|c| format!("'\"{c}\" = ' || quote(old.\"{c}\")"))
+        .collect::<Vec<_>>()
+        .join(" || ', ' || ");
+    let changed = columns
+        .iter()
+        .map(|c| format!("old.\"{c}\" IS NOT new.\"{c}\""))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+
+    format!(
+        "CREATE TEMP TRIGGER IF NOT EXISTS journal_{table}_insert AFTER INSERT ON main.{table} BEGIN
+           INSERT INTO journal_pending (sql) VALUES ('DELETE FROM {table} WHERE rowid = ' || new.rowid);
+         END;
+         CREATE TEMP TRIGGER IF NOT EXISTS journal_{table}_update AFTER UPDATE ON main.{table} WHEN {changed} BEGIN
+           INSERT INTO journal_pending (sql) VALUES ('UPDATE {table} SET ' || {assignments} || ' WHERE rowid = ' || old.rowid);
+         END;
+         CREATE TEMP TRIGGER IF NOT EXISTS journal_{table}_delete AFTER DELETE ON main.{table} BEGIN
+           INSERT INTO journal_pending (sql) VALUES ('INSERT INTO {table} (rowid{names}) VALUES (' || old.rowid{values} || ')');
+         END;"
+    )
+}
+
+/// Store the changes collected since the last call as an operation, returning its id if there
+/// were any
+///
+/// Operations undone before are forgotten, as their changes may conflict with the new ones.
+///
+/// * `automatic`: whether rem made the changes on its own rather than being told to
+pub fn record(
+    conn: &Connection,
+    label: &str,
+    automatic: bool,
+    now: LocalDT,
+) -> Result<Option<u64>, String> {
+    let pending = conn
+        .query_one("SELECT COUNT(*) FROM journal_pending;", [], |row| {
+            row.get::<_, u64>(0)
+        })
+        .map_err(|err| format!("Could not query journal: {err}"))?;
+    if pending == 0 {
+        return Ok(None);
+    }
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|err| format!("Could not start transaction: {err}"))?;
+    tx.execute_batch(
+        "DELETE FROM undo_log WHERE op IN (SELECT id FROM operations WHERE undone);
+         DELETE FROM operations WHERE undone;",
+    )
+    .map_err(|err| format!("Could not forget undone operations: {err}"))?;
+    tx.execute(
+        "INSERT INTO operations (label, at, automatic) VALUES (?1, ?2, ?3);",
+        (label, now.timestamp(), automatic),
+    )
+    .map_err(|err| format!("Could not record operation: {err}"))?;
+    let id = tx.last_insert_rowid() as u64;
+    take_pending(&tx, id)?;
+
+    let oldest = tx
+        .query_row(
+            "SELECT id FROM operations ORDER BY id DESC LIMIT 1 OFFSET ?1;",
+            [HISTORY_LIMIT - 1],
+            |row| row.get::<_, u64>(0),
+        )
+        .optional()
+        .map_err(|err| format!("Could not query journal: {err}"))?;
+    if let Some(oldest) = oldest {
+        tx.execute("DELETE FROM undo_log WHERE op < ?1;", [oldest])
+            .and_then(|_| tx.execute("DELETE FROM operations WHERE id < ?1;", [oldest]))
+            .map_err(|err| format!("Could not trim journal: {err}"))?;
+    }
+
+    tx.commit()
+        .map_err(|err| format!("Could not commit operation: {err}"))?;
+    Ok(Some(id))
+}
+
+/// Move the collected statements to operation `op`
+fn take_pending(conn: &Connection, op: u64) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO undo_log (op, sql) SELECT ?1, sql FROM journal_pending ORDER BY seq;",
+        [op],
+    )
+    .and_then(|_| conn.execute("DELETE FROM journal_pending;", []))
+    .map(|_| ())
+    .map_err(|err| format!("Could not store journal: {err}"))
+}
+
+/// Undo the last `count` operations rem was told to do and the automatic ones after them,
+/// returning their labels from the latest on
+pub fn undo(conn: &Connection, count: usize) -> Result<Vec<String>, String> {
+    let ops = operations(
+        conn,
+        "SELECT id, label, automatic FROM operations WHERE NOT undone ORDER BY id DESC;",
+    )?;
+    let mut told = 0;
+    let ops = ops
+        .into_iter()
+        .take_while(|(_, _, automatic)| {
+            let more = told < count;
+            told += usize::from(!automatic);
+            more
+        })
+        .collect::<Vec<_>>();
+    if told == 0 {
+        return Err("Nothing to undo.".to_string());
+    }
+    replay(conn, &ops, true)
+}
+
+/// Redo the last `count` undone operations rem was told to do and the automatic ones after
+/// them, returning their labels from the earliest on
+pub fn redo(conn: &Connection, count: usize) -> Result<Vec<String>, String> {
+    let ops = operations(
+        conn,
+        "SELECT id, label, automatic FROM operations WHERE undone ORDER BY id;",
+    )?;
+    let mut told = 0;
+    let ops = ops
+        .into_iter()
+        .take_while(|(_, _, automatic)| {
+            told += usize::from(!automatic);
+            told <= count
+        })
+        .collect::<Vec<_>>();
+    if ops.iter().all(|(_, _, automatic)| *automatic) {
+        return Err("Nothing to redo.".to_string());
+    }
+    replay(conn, &ops, false)
+}
+
+/// Id, label and whether it was automatic of the operations selected by `query`
+fn operations(conn: &Connection, query: &str) -> Result<Vec<(u64, String, bool)>, String> {
+    conn.prepare(query)
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|err| format!("Could not query journal: {err}"))
+}
+
+/// Run the statements of `ops` in order, replacing them with the ones reverting them again,
+/// and return the labels of the operations rem was told to do
+fn replay(
+    conn: &Connection,
+    ops: &[(u64, String, bool)],
+    undone: bool,
+) -> Result<Vec<String>, String> {
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|err| format!("Could not start transaction: {err}"))?;
+    // rows are restored before the rows they refer to if they were deleted after them
+    tx.pragma_update(None, "defer_foreign_keys", true)
+        .map_err(|err| format!("Could not defer foreign keys: {err}"))?;
+    tx.execute("DELETE FROM journal_pending;", [])
+        .map_err(|err| format!("Could not clear journal: {err}"))?;
+    for (op, label, _) in ops.iter() {
+        let statements = tx
+            .prepare("SELECT sql FROM undo_log WHERE op = ?1 ORDER BY seq DESC;")
+            .and_then(|mut stmt| {
+                stmt.query_map([op], |row| row.get::<_, String>(0))?
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(|err| format!("Could not query journal: {err}"))?;
+        for sql in statements.iter() {
+            tx.execute_batch(sql)
+                .map_err(|err| format!("Could not revert '{label}': {err}"))?;
+        }
+        tx.execute("DELETE FROM undo_log WHERE op = ?1;", [op])
+            .map_err(|err| format!("Could not update journal: {err}"))?;
+        take_pending(&tx, *op)?;
+        tx.execute(
+            "UPDATE operations SET undone = ?1 WHERE id = ?2;",
+            (undone, op),
+        )
+        .map_err(|err| format!("Could not update journal: {err}"))?;
+    }
+    tx.commit()
+        .map_err(|err| format!("Could not commit: {err}"))?;
+    Ok(ops
+        .iter()
+        .filter(|(_, _, automatic)| !automatic)
+        .map(|(_, label, _)| label.clone())
+        .collect())
+}
+
+/// Forget all operations, e.g. after changes that are not to be undone
+pub fn clear(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch("DELETE FROM journal_pending; DELETE FROM undo_log; DELETE FROM operations;")
+        .map_err(|err| format!("Could not clear journal: {err}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn titles(conn: &Connection) -> Vec<(i64, String, Option<String>)> {
+        conn.prepare("SELECT id, title, note FROM items ORDER BY id;")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn undo_redo() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, title TEXT NOT NULL, note TEXT);
+             CREATE TABLE tags (item INTEGER NOT NULL, tag TEXT NOT NULL);
+             INSERT INTO items (title) VALUES ('before');",
+        )
+        .unwrap();
+        install(&conn, &["items", "tags"], &[]).unwrap();
+        let now = chrono::Local::now();
+        assert_eq!(record(&conn, "nothing", false, now), Ok(None));
+
+        conn.execute_batch(
+            "INSERT INTO items (title, note) VALUES ('it''s', 'x');
+             INSERT INTO tags VALUES (2, 'a');",
+        )
+        .unwrap();
+        assert!(record(&conn, "add", false, now).unwrap().is_some());
+        conn.execute_batch(
+            "UPDATE items SET note = NULL WHERE id = 2;
+             UPDATE items SET title = title;
+             DELETE FROM items WHERE id = 1;
+             DELETE FROM tags;",
+        )
+        .unwrap();
+        record(&conn, "edit", false, now).unwrap();
+        assert_eq!(titles(&conn), [(2, "it's".to_string(), None)]);
+
+        assert_eq!(undo(&conn, 1).unwrap(), ["edit"]);
+        let after_add = vec![
+            (1, "before".to_string(), None),
+            (2, "it's".to_string(), Some("x".to_string())),
+        ];
+        assert_eq!(titles(&conn), after_add);
+        assert_eq!(undo(&conn, 5).unwrap(), ["add"]);
+        assert_eq!(titles(&conn), [(1, "before".to_string(), None)]);
+        assert!(undo(&conn, 1).is_err());
+
+        assert_eq!(redo(&conn, 1).unwrap(), ["add"]);
+        assert_eq!(titles(&conn), after_add);
+        let tags = conn
+            .query_one("SELECT COUNT(*) FROM tags;", [], |row| row.get::<_, u64>(0))
+            .unwrap();
+        assert_eq!(tags, 1);
+
+        // new changes discard what could be redone
+        conn.execute("INSERT INTO items (title) VALUES ('new');", [])
+            .unwrap();
+        record(&conn, "new", false, now).unwrap();
+        assert!(redo(&conn, 1).is_err());
+
+        // automatic changes go with the operation before them
+        conn.execute("INSERT INTO items (title) VALUES ('generated');", [])
+            .unwrap();
+        record(&conn, "generate", true, now).unwrap();
+        assert_eq!(undo(&conn, 1).unwrap(), ["new"]);
+        assert_eq!(titles(&conn), after_add);
+        assert_eq!(redo(&conn, 1).unwrap(), ["new"]);
+        assert_eq!(titles(&conn).len(), 4);
+        assert_eq!(undo(&conn, 2).unwrap(), ["new", "add"]);
+
+        clear(&conn).unwrap();
+        assert!(undo(&conn, 1).is_err());
+    }
+
+    #[test]
+    fn stamps() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, title TEXT NOT NULL, stamp INTEGER);
+             INSERT INTO items (title, stamp) VALUES ('before', 1);",
+        )
+        .unwrap();
+        install(&conn, &["items"], &["stamp"]).unwrap();
+        let now = chrono::Local::now();
+        let stamp = || {
+            conn.query_one("SELECT title, stamp FROM items;", [], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })
+            .unwrap()
+        };
+
+        conn.execute("UPDATE items SET title = 'after', stamp = 2;", [])
+            .unwrap();
+        record(&conn, "edit", false, now).unwrap();
+        conn.execute("UPDATE items SET stamp = 3;", []).unwrap();
+        assert_eq!(record(&conn, "touch", false, now), Ok(None));
+
+        undo(&conn, 1).unwrap();
+        assert_eq!(stamp(), ("before".to_string(), 3));
+    }
+}
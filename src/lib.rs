@@ -1,15 +1,94 @@
+pub mod bot;
+pub mod config;
+#[cfg(unix)]
+pub mod daemon;
+pub mod hyperlink;
+pub mod ics;
+pub mod import;
+pub mod journal;
+pub mod markdown;
+pub mod notify;
+pub mod parse;
+pub mod porcelain;
+pub mod quickadd;
 pub mod reminder;
+pub mod render;
+pub mod report;
+pub mod rpc;
+pub mod scope;
+pub mod score;
+pub mod serve;
+pub mod service;
+pub mod session;
+pub mod sheet;
+pub mod statusbar;
+pub mod sync;
+pub mod tags;
 pub mod task;
+pub mod template;
+pub mod theme;
+pub mod todos;
+pub mod validate;
 
-pub const DATETIME_FMT: &'static str = "%d.%m.%Y %H:%M";
+pub const DATETIME_FMT: &str = "%d.%m.%Y %H:%M";
+pub const DATE_FMT: &str = "%d.%m.%Y";
+/// RFC 3339, the profile of ISO 8601 that other tools read
+pub const ISO_DATETIME_FMT: &str = "%Y-%m-%dT%H:%M:%S%:z";
+pub const ISO_DATE_FMT: &str = "%Y-%m-%d";
 
 pub type LocalDT = chrono::DateTime<chrono::Local>;
 
-pub use reminder::Reminder;
-pub use task::Task;
+pub use config::Config;
+pub use reminder::{Backfill, CatchUp, Occurrence, Reminder};
+pub use task::{Priority, Task, WorkBit};
 
 pub fn import_datetime(x: i64) -> LocalDT {
     chrono::DateTime::from_timestamp(x, 0)
         .unwrap()
         .with_timezone(&chrono::Local)
 }
+
+pub fn start_of_day(date: chrono::NaiveDate) -> LocalDT {
+    date.and_hms_opt(0, 0, 0)
+        .expect("valid time")
+        .and_local_timezone(chrono::Local)
+        .unwrap()
+}
+
+/// Drop the seconds of a time, as occurrences of reminders are due on whole minutes
+pub fn truncate_to_minute(dt: LocalDT) -> LocalDT {
+    use chrono::Timelike;
+    dt.with_second(0)
+        .and_then(|dt| dt.with_nanosecond(0))
+        .unwrap_or(dt)
+}
+
+/// Format a duration compactly with the units understood by [`parse::parse_duration`],
+/// e.g. `1d 2h 30m`
+pub fn format_timedelta(delta: chrono::TimeDelta) -> String {
+    if delta < chrono::TimeDelta::zero() {
+        return format!("-{}", format_timedelta(-delta));
+    }
+
+    let mut minutes = delta.num_minutes();
+    let mut parts = Vec::new();
+    for (unit, len) in [("w", 7 * 24 * 60), ("d", 24 * 60), ("h", 60), ("m", 1)] {
+        if minutes >= len {
+            parts.push(format!("{}{unit}", minutes / len));
+            minutes %= len;
+        }
+    }
+
+    if parts.is_empty() {
+        "0m".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// FNV-1a hash of `bytes`, which stays the same across builds unlike the one of `std`
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
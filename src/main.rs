@@ -1,17 +1,163 @@
-use chrono::{Local, NaiveDate, NaiveTime, TimeDelta};
-use clap::{Parser, Subcommand};
+use std::collections::{HashMap, HashSet};
+use std::io::{IsTerminal, Read, Write};
+
+use chrono::{Datelike, Local, NaiveDate, NaiveTime, TimeDelta, Timelike};
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
+use colored::Colorize;
 use rusqlite::config::DbConfig;
 use rusqlite::fallible_iterator::FallibleIterator;
-use rusqlite::types::Null;
+use rusqlite::trace::{TraceEvent, TraceEventCodes};
+use rusqlite::types::{Type, Value, ValueRef};
+use rusqlite::OptionalExtension;
+
+use rem::ics;
+use rem::import::{self, Entry, Outcome, Record, Summary};
+use rem::journal;
+use rem::notify;
+use rem::parse::{
+    parse_cron, parse_date_time, parse_date_time_all_day, parse_due_after, parse_duration,
+    parse_timedelta,
+};
+use rem::reminder::{self, humanize_period, LeadTask, Occurrence, Season};
+use rem::render::{self, heatmap};
+use rem::report::{
+    activity_charts, activity_csv, adherence_csv, adherence_table, budget_table, group_tasks,
+    timesheet, timesheet_csv, timesheet_markdown, to_csv, to_table, weekly_activity, Adherence,
+    Budget, GroupReport, ReminderSummary, WeekActivity,
+};
+use rem::rpc::{self, Call};
+use rem::scope::Scope;
+use rem::score::{score, urgency, Score, Situation};
+use rem::serve::{self, Access, Metric, Response};
+use rem::session::{format_elapsed, untracked, IdleAction, Session};
+use rem::sheet::{self, Sheet};
+use rem::sync::{self, Changes};
+use rem::tags::{normalize_tag, validate_tag};
+use rem::template::Template;
+use rem::theme::State;
+use rem::{
+    config, format_timedelta, hyperlink, import_datetime, porcelain, quickadd, service,
+    start_of_day, statusbar, tags, todos, truncate_to_minute, validate, Backfill, CatchUp, Config,
+    LocalDT, Priority, Reminder, Task, WorkBit,
+};
+
+/// Offset and id of the task a due date is relative to
+type DueLink = (TimeDelta, u64);
+
+/// Agenda, top priorities and habits of a day, see [`sheet::Sheet`]
+type DaySheet = (Vec<Task>, Vec<Task>, Vec<String>);
 
-use rem::{import_datetime, LocalDT, Reminder, Task, DATETIME_FMT};
+const DATABASE_FILE: &str = "db.sqlite";
+const CONFIG_FILE: &str = "config.toml";
+const HOME_DIR: &str = "rem";
+const DATABASE_NAME: &str = "main";
 
-const DATABASE_FILE: &'static str = "db.sqlite";
-const HOME_DIR: &'static str = "rem";
-const DATABASE_NAME: &'static str = "main";
+/// How far records may be created ahead of the clock without it counting as set back, e.g. by
+/// another machine syncing with a clock that runs slightly ahead or an NTP adjustment
+const CLOCK_TOLERANCE: TimeDelta = TimeDelta::minutes(5);
 
-#[derive(Clone, PartialEq, Eq, Debug, Subcommand)]
+/// Version of the database layout, stored as the `user_version` of the database
+const SCHEMA_VERSION: i64 = 18;
+
+/// Tables created by [`App::try_init`]
+const TABLES: &[&str] = &[
+    "reminders",
+    "tasks",
+    "work_bits",
+    "tags",
+    "reminder_tags",
+    "checklist_items",
+    "reminder_checklist_items",
+    "reminder_lead_tasks",
+    "meta",
+    "sessions",
+    "templates",
+    "due_links",
+    "occurrence_shifts",
+    "tombstones",
+    "operations",
+    "undo_log",
+];
+
+/// Columns added to tables after their creation, as table, column and declaration
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("tasks", "due_start", "INTEGER"),
+    ("tasks", "due_all_day", "INTEGER NOT NULL DEFAULT 0"),
+    ("reminders", "all_day", "INTEGER NOT NULL DEFAULT 0"),
+    ("tasks", "priority", "INTEGER"),
+    ("tasks", "context", "TEXT"),
+    ("tasks", "estimate", "INTEGER"),
+    ("tasks", "parent", "INTEGER REFERENCES tasks(id)"),
+    ("reminders", "parent", "INTEGER REFERENCES reminders(id)"),
+    ("reminders", "season", "TEXT"),
+    ("tasks", "external_id", "TEXT"),
+    ("reminders", "external_id", "TEXT"),
+    ("reminders", "priority", "INTEGER"),
+    ("reminders", "context", "TEXT"),
+    (
+        "reminders",
+        "require_completion",
+        "INTEGER NOT NULL DEFAULT 0",
+    ),
+    ("reminders", "skip_before", "INTEGER"),
+    ("reminders", "catch_up", "INTEGER NOT NULL DEFAULT 0"),
+    ("reminders", "skip_stale_after", "INTEGER"),
+    ("tasks", "skipped", "INTEGER NOT NULL DEFAULT 0"),
+    ("reminders", "at", "INTEGER"),
+    ("tasks", "client", "TEXT"),
+    ("work_bits", "cwd", "TEXT"),
+    ("work_bits", "git_repo", "TEXT"),
+    ("work_bits", "git_branch", "TEXT"),
+    ("tasks", "uuid", "TEXT"),
+    ("tasks", "modified", "INTEGER"),
+    ("reminders", "uuid", "TEXT"),
+    ("reminders", "modified", "INTEGER"),
+    ("tasks", "rank", "INTEGER"),
+];
+
+#[derive(Clone, PartialEq, Debug, Subcommand)]
 enum Action {
+    #[command(about = "Set up the data directory and write a config file interactively")]
+    Init,
+    #[command(about = "Report on the configuration, database and environment")]
+    Doctor,
+    #[command(about = "Locate and inspect the database")]
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+    #[command(about = "Locate and edit the config file")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    #[command(
+        about = "Generate tasks from reminders and nudge to track time in the background, refreshing right after other rem commands change data"
+    )]
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonAction,
+    },
+    #[command(about = "Install and enable a systemd service or cron entry running the daemon")]
+    InstallService {
+        #[arg(
+            long,
+            help = "install a systemd user service instead of a system service"
+        )]
+        user: bool,
+        #[arg(
+            long,
+            conflicts_with = "user",
+            help = "add an entry to the crontab instead"
+        )]
+        cron: bool,
+        #[arg(
+            long,
+            value_parser = clap::value_parser!(u64).range(1..),
+            help = "minutes between refreshes, defaults to daemon_interval_minutes"
+        )]
+        interval: Option<u64>,
+    },
     #[command(about = "Display tasks")]
     Tasks {
         #[arg(short, long, help = "show all tasks, including completed ones")]
@@ -19,6 +165,35 @@ enum Action {
 
         #[arg(short, long, help = "show all information on the tasks")]
         verbose: bool,
+
+        #[arg(long, value_enum, default_value_t = TaskOrder::Id, help = "order of the tasks")]
+        sort: TaskOrder,
+
+        #[arg(
+            long,
+            value_enum,
+            help = "show the tasks in sections with a header each"
+        )]
+        group_by: Option<TaskGrouping>,
+
+        #[arg(long, help = "show tasks that start in the future as well")]
+        include_future: bool,
+
+        #[arg(
+            long,
+            help = "show long titles in full instead of shortening them to the width of the terminal"
+        )]
+        wide: bool,
+    },
+    #[command(about = "Show a task with all its details")]
+    Show {
+        #[arg(help = "id of the task to show")]
+        id: u64,
+        #[arg(
+            long,
+            help = "show the description as written instead of rendering its Markdown"
+        )]
+        plain: bool,
     },
     #[command(about = "Record a bit of work for a task")]
     Record {
@@ -26,6 +201,170 @@ enum Action {
         task_id: u64,
         #[arg(help = "optional description of the work bit")]
         description: Option<String>,
+        #[arg(long, help = "store the current working directory with the work bit")]
+        capture_cwd: bool,
+        #[arg(long, help = "store the git repository and branch with the work bit")]
+        capture_git: bool,
+    },
+    #[command(about = "Record work bits from git commits that mention tasks")]
+    GitHook {
+        #[command(subcommand)]
+        action: GitHookAction,
+    },
+    #[command(about = "Create tasks and reminders from templates, shareable as TOML files")]
+    Template {
+        #[command(subcommand)]
+        action: TemplateAction,
+    },
+    #[command(
+        about = "Create related tasks scheduled from a start date, e.g. a release checklist"
+    )]
+    Blueprint {
+        #[command(subcommand)]
+        action: BlueprintAction,
+    },
+    #[command(about = "Start a work session on a task, stopping the running one")]
+    Start {
+        #[arg(help = "id of the task to work on")]
+        task_id: u64,
+    },
+    #[command(about = "Stop the running work session")]
+    StopWork,
+    #[command(about = "Show the running work session")]
+    Tracking {
+        #[arg(
+            short,
+            long,
+            help = "a single line for prompts and status bars, e.g. '⏱ 00:42 report #12'"
+        )]
+        short: bool,
+    },
+    #[command(
+        about = "Remind to track time if no work session ran for a while during working hours, e.g. from cron"
+    )]
+    Nudge,
+    #[command(about = "Sum up the time of work sessions, e.g. for invoicing")]
+    Timesheet {
+        #[arg(
+            long,
+            help = "first day as DD.MM.YYYY, defaults to the start of the month"
+        )]
+        from: Option<String>,
+        #[arg(long, help = "last day as DD.MM.YYYY, defaults to today")]
+        to: Option<String>,
+        #[arg(long, value_enum, default_value_t = TimesheetBy::Day, help = "what to sum up time by")]
+        by: TimesheetBy,
+        #[arg(long, value_enum, default_value_t = TimesheetFormat::Md)]
+        format: TimesheetFormat,
+        #[arg(long, help = "round each total up to a multiple of this, e.g. '15m'")]
+        round: Option<String>,
+        #[arg(long, help = "only include work billed to this client")]
+        client: Option<String>,
+        #[arg(
+            long,
+            help = "hourly rate to compute amounts with, defaults to the rate of the client in the config"
+        )]
+        rate: Option<f64>,
+    },
+    #[command(about = "Write the whole database as JSON to stdout, e.g. for backups")]
+    Dump,
+    #[command(
+        about = "Write the open tasks with a due date as iCalendar events to stdout, e.g. for calendar applications"
+    )]
+    Ics,
+    #[command(
+        about = "Run a read-only SQL query against the database, e.g. SELECT title FROM tasks"
+    )]
+    Query {
+        #[arg(help = "SQL statement, must not modify the database")]
+        sql: String,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    #[command(about = "Restore a dump written by `rem dump` into an empty database")]
+    Load {
+        #[arg(help = "file to read the dump from, '-' for stdin")]
+        path: String,
+        #[arg(
+            long,
+            help = "merge the tasks and reminders of the dump into a database that is not empty, like rem sync"
+        )]
+        merge: bool,
+    },
+    #[command(
+        about = "Merge the tasks and reminders of another rem database, e.g. on another machine"
+    )]
+    Sync {
+        #[command(subcommand)]
+        action: SyncAction,
+    },
+    #[command(about = "Import tasks exported from todo.txt, Taskwarrior or a calendar")]
+    Import {
+        #[arg(help = "file to import, '-' for stdin")]
+        path: String,
+        #[arg(long, value_enum, help = "format of the file")]
+        format: ImportFormat,
+        #[arg(
+            long,
+            default_value_t = 500,
+            value_parser = clap::value_parser!(u64).range(1..),
+            help = "number of tasks stored per transaction"
+        )]
+        chunk_size: u64,
+        #[arg(
+            long,
+            help = "report tasks that cannot be imported instead of stopping at the first one"
+        )]
+        continue_on_error: bool,
+    },
+    #[command(about = "Set who work on a project is billed to")]
+    Client {
+        #[arg(help = "id of the project or task")]
+        id: u64,
+        #[arg(help = "client, clears the client if omitted")]
+        client: Option<String>,
+    },
+    #[command(
+        about = "Summarize open and overdue tasks and the running work session",
+        group = ArgGroup::new("format").multiple(false)
+    )]
+    Status {
+        #[arg(
+            short,
+            long,
+            group = "format",
+            help = "a single line for prompts and status bars"
+        )]
+        short: bool,
+        #[arg(
+            long,
+            group = "format",
+            help = "a single line colored by urgency for starship custom modules"
+        )]
+        starship: bool,
+        #[arg(
+            long,
+            group = "format",
+            help = "a single line colored by urgency for the tmux status line"
+        )]
+        tmux: bool,
+        #[arg(
+            long,
+            group = "format",
+            help = "a line of JSON with text, tooltip and urgency class for waybar custom modules"
+        )]
+        json: bool,
+    },
+    #[command(
+        about = "Show open tasks, refreshing them periodically and alerting when one becomes due"
+    )]
+    Watch {
+        #[arg(short, long, default_value_t = 60, help = "seconds between refreshes")]
+        interval: u64,
+        #[arg(long, help = "do not ring the terminal bell when a task becomes due")]
+        no_bell: bool,
+        #[arg(long, help = "do not flash the screen when a task becomes due")]
+        no_flash: bool,
     },
     #[command(about = "Create a task")]
     Task {
@@ -33,10 +372,172 @@ enum Action {
         title: String,
         #[arg(help = "optional detailed task description")]
         description: Option<String>,
-        #[arg(short, long, help = "optional due date/time as DD.MM.YYYY [HH:MM]")]
+        #[arg(
+            short,
+            long,
+            allow_hyphen_values = true,
+            help = "optional due date/time as DD.MM.YYYY [HH:MM], or relative to another task like '+3d after 17'"
+        )]
         due: Option<String>,
+        #[arg(
+            long,
+            help = "optional earliest acceptable completion as DD.MM.YYYY [HH:MM], making the due date the end of a window"
+        )]
+        due_start: Option<String>,
         #[arg(short, long, help = "optional scheduled start as DD.MM.YYYY [HH:MM]")]
         start: Option<String>,
+        #[arg(short, long, help = "optional priority: low, medium or high")]
+        priority: Option<Priority>,
+        #[arg(short, long, help = "optional context, e.g. home or office")]
+        context: Option<String>,
+        #[arg(
+            short,
+            long = "tag",
+            help = "tag to attach, may be given multiple times"
+        )]
+        tags: Vec<String>,
+        #[arg(short, long, help = "optional estimated effort, e.g. '1h 30m'")]
+        estimate: Option<String>,
+        #[arg(long, help = "optional id of the task this is a subtask of")]
+        parent: Option<u64>,
+        #[arg(
+            long,
+            conflicts_with = "dedupe",
+            help = "create the task even if an open task has the same title"
+        )]
+        force: bool,
+        #[arg(
+            long,
+            help = "do nothing if an open task has the same title, e.g. in scripts run from several devices"
+        )]
+        dedupe: bool,
+        #[arg(
+            long,
+            help = "key of the task in another system. Updates the task with that key if there is one"
+        )]
+        external_id: Option<String>,
+    },
+    #[command(about = "Recommend what to work on next")]
+    Next {
+        #[arg(short, long, help = "optional available time, e.g. '30m'")]
+        time: Option<String>,
+        #[arg(short, long, help = "optional context you are in, e.g. home")]
+        context: Option<String>,
+        #[arg(
+            short = 'n',
+            long,
+            default_value_t = 3,
+            help = "number of recommendations"
+        )]
+        count: usize,
+    },
+    #[command(about = "Pin a task to the top of listings, below the ones pinned before")]
+    Pin {
+        #[arg(help = "id of the task to pin")]
+        id: u64,
+    },
+    #[command(about = "Let a pinned task be sorted automatically again")]
+    Unpin {
+        #[arg(help = "id of the task to unpin")]
+        id: u64,
+    },
+    #[command(about = "Move a task among the pinned ones, pinning it if needed")]
+    Order {
+        #[arg(help = "id of the task to move")]
+        id: u64,
+        #[arg(
+            long,
+            help = "pinned task to move it in front of, pinned at the end if it was not"
+        )]
+        before: u64,
+    },
+    #[command(about = "Add a tag to tasks")]
+    Tag {
+        #[arg(help = "tag to add")]
+        tag: String,
+        #[arg(required = true, help = "ids of the tasks to tag")]
+        ids: Vec<u64>,
+    },
+    #[command(about = "Remove a tag from tasks")]
+    Untag {
+        #[arg(help = "tag to remove")]
+        tag: String,
+        #[arg(required = true, help = "ids of the tasks to untag")]
+        ids: Vec<u64>,
+    },
+    #[command(about = "List tags with their usage and offer to merge near-duplicates")]
+    Tags {
+        #[arg(
+            long,
+            alias = "prune",
+            help = "remove tags that are only on completed tasks. This loses them from the history, so `rem tags` and `rem report --by tag` no longer count those tasks"
+        )]
+        prune_completed: bool,
+    },
+    #[command(about = "Rename a tag on all tasks and reminders")]
+    Retag {
+        #[arg(long, help = "tag to rename")]
+        from: String,
+        #[arg(long, help = "new name of the tag")]
+        to: String,
+    },
+    #[command(about = "Split a task into subtasks, prompting for their titles")]
+    Split {
+        #[arg(help = "id of the task to split")]
+        id: u64,
+    },
+    #[command(about = "Change the due date of a task")]
+    Reschedule {
+        #[arg(help = "id of the task to reschedule")]
+        id: u64,
+        #[arg(
+            allow_hyphen_values = true,
+            help = "due date/time as DD.MM.YYYY [HH:MM], or relative to another task like '+3d after 17'"
+        )]
+        due: String,
+        #[arg(long, help = "move the tasks due relative to this one along")]
+        propagate: bool,
+    },
+    #[command(about = "Create a copy of a task without its work bits and completion")]
+    Duplicate {
+        #[arg(help = "id of the task to duplicate")]
+        id: u64,
+        #[arg(
+            short,
+            long,
+            help = "optional due date/time of the copy as DD.MM.YYYY [HH:MM]"
+        )]
+        due: Option<String>,
+    },
+    #[command(
+        about = "Create a task from a single line, e.g. 'Pay rent !high #finance @home due:1.7'"
+    )]
+    Add {
+        #[arg(
+            required = true,
+            help = "title with optional annotations !PRIORITY #TAG @CONTEXT due:DD.MM[.YYYY] every:PERIOD, with the period in weeks or days like 4w"
+        )]
+        text: Vec<String>,
+    },
+    #[command(
+        about = "Create a task from a line of code with a comment like 'TODO(due=friday, priority=high): fix parser', for editor keybindings. Prints the line referring to the task, like 'TODO(rem:#12): fix parser'"
+    )]
+    Quick {
+        #[arg(
+            long,
+            help = "line with a TODO, FIXME or XXX comment, optionally annotated with due=DATE, priority=PRIORITY, tag=TAG and context=CONTEXT in parentheses"
+        )]
+        line: String,
+    },
+    #[command(
+        about = "Create a task from the clipboard or stdin. The first line is the title, the rest the description"
+    )]
+    #[command(group(ArgGroup::new("source").required(true).args(["clipboard", "stdin"])))]
+    Capture {
+        #[arg(long, help = "read the task from the clipboard")]
+        clipboard: bool,
+        #[arg(long, help = "read the task from stdin")]
+        stdin: bool,
     },
     #[command(about = "Delete a task")]
     DeleteTask {
@@ -44,22 +545,137 @@ enum Action {
         id: u64,
     },
     #[command(about = "Mark a task as completed")]
+    #[command(group(ArgGroup::new("target").required(true).args(["id", "reminder"])))]
     Complete {
         #[arg(help = "id of the task to mark completed")]
-        id: u64,
+        id: Option<u64>,
+        #[arg(
+            short,
+            long,
+            help = "complete the current occurrence of the reminder with this id instead"
+        )]
+        reminder: Option<u64>,
+    },
+    #[command(about = "Undo the last changes, e.g. completing the wrong task")]
+    Undo {
+        #[arg(default_value_t = 1, help = "number of commands to undo")]
+        count: usize,
+        #[arg(
+            long,
+            conflicts_with = "count",
+            help = "forget what could be undone instead, e.g. to remove deleted tasks for good"
+        )]
+        forget: bool,
+    },
+    #[command(about = "Redo changes undone by `rem undo`")]
+    Redo {
+        #[arg(default_value_t = 1, help = "number of commands to redo")]
+        count: usize,
     },
     #[command(about = "Add a generator for recurring events")]
     Reminder {
         #[arg(help = "title")]
         title: String,
-        #[arg(help = "first due date")]
-        first_due: String,
-        #[arg(help = "recurrence period")]
-        period: String,
+        #[arg(required_unless_present = "cron", help = "first due date")]
+        first_due: Option<String>,
+        #[arg(required_unless_present = "cron", help = "recurrence period")]
+        period: Option<String>,
+        #[arg(
+            long,
+            conflicts_with_all = ["first_due", "period", "at"],
+            help = "cron expression instead of first due date and period, e.g. '0 8 * * MON'"
+        )]
+        cron: Option<String>,
         #[arg(long, short, help = "optional description")]
         description: Option<String>,
         #[arg(long, short, help = "last occurrence is before this datetime")]
         until: Option<String>,
+        #[arg(
+            short,
+            long,
+            help = "optional priority of generated tasks: low, medium or high"
+        )]
+        priority: Option<Priority>,
+        #[arg(short, long, help = "optional context of generated tasks")]
+        context: Option<String>,
+        #[arg(
+            short,
+            long = "tag",
+            help = "tag for generated tasks, may be given multiple times"
+        )]
+        tags: Vec<String>,
+        #[arg(
+            long,
+            help = "only generate the next occurrence once the previous one is completed"
+        )]
+        require_completion: bool,
+        #[arg(
+            long,
+            default_value_t = CatchUp::All,
+            help = "what to generate for missed occurrences: all or collapse into one task"
+        )]
+        catch_up: CatchUp,
+        #[arg(
+            long,
+            help = "skip open occurrences this long past their deadline once newer ones are generated, e.g. 3d"
+        )]
+        skip_stale: Option<String>,
+        #[arg(long, help = "time of day all occurrences are due at as HH:MM")]
+        at: Option<String>,
+        #[arg(
+            long,
+            help = "optional id of the reminder to group this one under, sharing its tags"
+        )]
+        parent: Option<u64>,
+        #[arg(
+            long,
+            help = "only generate occurrences within this part of every year, as DD.MM..DD.MM"
+        )]
+        season: Option<Season>,
+        #[arg(
+            long,
+            help = "key of the reminder in another system. Updates the reminder with that key if there is one"
+        )]
+        external_id: Option<String>,
+    },
+    #[command(about = "Move a single occurrence of a reminder without changing the others")]
+    ShiftOccurrence {
+        #[arg(help = "id of the reminder")]
+        reminder_id: u64,
+        #[arg(help = "due date of the occurrence as DD.MM.YYYY [HH:MM]")]
+        date: String,
+        #[arg(
+            long,
+            help = "new due date as DD.MM.YYYY [HH:MM], keeping the time of day if none is given"
+        )]
+        to: String,
+    },
+    #[command(
+        about = "Attach a checklist template to a reminder, e.g. for a weekly review. Generated tasks get a copy of the checklist"
+    )]
+    ReviewTemplate {
+        #[arg(help = "id of the reminder")]
+        reminder_id: u64,
+        #[arg(help = "checklist items in order. Leave empty to remove the template")]
+        items: Vec<String>,
+    },
+    #[command(
+        about = "Generate further tasks with every occurrence of a reminder, due relative to it, e.g. to prepare a meeting"
+    )]
+    LeadTasks {
+        #[arg(help = "id of the reminder")]
+        reminder_id: u64,
+        #[arg(
+            help = "tasks as TITLE:OFFSET, e.g. 'prepare agenda:-2d' 'send minutes:+1d'. Leave empty to remove them"
+        )]
+        tasks: Vec<LeadTask>,
+    },
+    #[command(about = "Toggle a checklist item of a task")]
+    Check {
+        #[arg(help = "task id")]
+        task_id: u64,
+        #[arg(help = "number of the checklist item, starting at 1")]
+        item: usize,
     },
     #[command(about = "Display reminders")]
     Reminders {
@@ -68,24 +684,677 @@ enum Action {
 
         #[arg(short, long, help = "show all information on the reminders")]
         verbose: bool,
+
+        #[arg(
+            short,
+            long,
+            help = "only show the reminder with this id and its group"
+        )]
+        group: Option<u64>,
+
+        #[arg(
+            long,
+            value_enum,
+            help = "order of the reminders, within their groups [default: next-due, id with --porcelain]"
+        )]
+        sort: Option<ReminderOrder>,
+
+        #[arg(long, value_enum, default_value_t = ReminderFormat::Table, help = "how to show the reminders")]
+        format: ReminderFormat,
     },
-    #[command(about = "Stop a reminder from generating new tasks")]
+    #[command(
+        about = "Stop a reminder and the reminders grouped under it from generating new tasks"
+    )]
     Stop { id: u64 },
+    #[command(
+        about = "Generate tasks from reminders, which other commands only do before showing tasks"
+    )]
+    Generate {
+        #[arg(
+            long,
+            help = "generate all occurrences due within this time from now, e.g. '30d', instead of up to the next one"
+        )]
+        horizon: Option<String>,
+        #[arg(long, help = "only list the tasks that would be generated")]
+        dry_run: bool,
+    },
+    #[command(
+        about = "Bring the reminders of the database in line with the [[reminders]] blocks of the config, which every command does first"
+    )]
+    Reconcile {
+        #[arg(
+            long,
+            help = "only show the reminders that would be created, updated or stopped"
+        )]
+        diff: bool,
+    },
+    #[command(
+        about = "Keep a task for every TODO, FIXME and XXX comment in the files of a directory, closing the tasks of comments that disappeared"
+    )]
+    ScanTodos {
+        #[arg(help = "directory to scan")]
+        dir: std::path::PathBuf,
+    },
+    #[command(
+        about = "Chart weekly activity, or show when the occurrences of a reminder were completed"
+    )]
+    Stats {
+        #[arg(long, help = "reminder to show the occurrences of")]
+        reminder: Option<u64>,
+        #[arg(long, default_value_t = 8, help = "number of weeks to chart")]
+        weeks: u32,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    #[command(about = "Show a calendar heatmap of completed tasks and work bits per day")]
+    Heatmap {
+        #[arg(long, help = "year to show, defaults to the current one")]
+        year: Option<i32>,
+    },
+    #[command(
+        about = "Write a sheet with the agenda, top priorities and habits of a day for printing"
+    )]
+    PrintSheet {
+        #[arg(
+            long,
+            default_value = "today",
+            help = "day as DD.MM[.YYYY], 'today' or 'tomorrow'"
+        )]
+        date: String,
+        #[arg(
+            short = 'n',
+            long,
+            default_value_t = 3,
+            help = "number of top priorities"
+        )]
+        count: usize,
+        #[arg(long, value_enum, default_value_t = SheetFormat::Html)]
+        format: SheetFormat,
+    },
+    #[command(
+        about = "Answer HTTP requests for tasks and reminders with JSON, for clients holding a token of the [serve] config section"
+    )]
+    Serve {
+        #[arg(
+            long,
+            help = "address and port to listen at, defaults to `listen` of the [serve] config section"
+        )]
+        listen: Option<String>,
+    },
+    #[command(
+        about = "Answer JSON-RPC 2.0 requests read line by line from stdin, for editor plugins and assistants: create_task, list_tasks, complete_task and record_work"
+    )]
+    Rpc,
+    #[command(
+        about = "Post the agenda of each day to a chat and apply commands sent there, like 'done 12' or 'add buy milk tomorrow'. Needs rem to be built with the `bot` feature"
+    )]
+    Bot {
+        #[arg(value_enum)]
+        service: BotService,
+    },
+    #[command(about = "Summarize completed, open and overdue tasks and tracked time per group")]
+    Report {
+        #[arg(long, value_enum, default_value_t = ReportBy::Tag, help = "what to group tasks by")]
+        by: ReportBy,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Clone, PartialEq, Debug, Subcommand)]
+enum SyncAction {
+    #[command(
+        about = "Merge both ways with a database file or one reached over ssh, keeping the latest change of each task and reminder"
+    )]
+    Peer {
+        #[arg(
+            help = "path of the database or ssh://[user@]host[/path], asking rem on the host for the path if none is given"
+        )]
+        peer: String,
+    },
+}
+
+#[derive(Clone, PartialEq, Debug, Subcommand)]
+enum DbAction {
+    #[command(about = "Print the path of the database")]
+    Path,
+    #[command(about = "Open the database in the sqlite3 shell")]
+    Shell,
+    #[command(about = "Compact the database and refresh its statistics, reporting its size")]
+    Vacuum,
+    #[command(
+        about = "Merge tasks generated for the same occurrence of a reminder more than once"
+    )]
+    Dedupe,
+}
+
+#[derive(Clone, PartialEq, Debug, Subcommand)]
+enum ConfigAction {
+    #[command(about = "Print the path of the config file")]
+    Path,
+    #[command(about = "Open the config file in $VISUAL or $EDITOR and check it afterwards")]
+    Edit,
+}
+
+#[derive(Clone, PartialEq, Debug, Subcommand)]
+enum DaemonAction {
+    #[command(about = "Run the daemon in the foreground, e.g. as a service")]
+    Run {
+        #[arg(
+            long,
+            value_parser = clap::value_parser!(u64).range(1..),
+            help = "minutes between refreshes, defaults to daemon_interval_minutes"
+        )]
+        interval: Option<u64>,
+        #[arg(
+            long,
+            conflicts_with = "interval",
+            help = "refresh once and exit, e.g. from cron"
+        )]
+        once: bool,
+    },
+    #[command(about = "Report whether a daemon is running and what it did last")]
+    Status,
+    #[command(about = "Stop the running daemon")]
+    Stop,
+}
+
+#[derive(Clone, PartialEq, Debug, Subcommand)]
+enum GitHookAction {
+    #[command(
+        about = "Install a post-commit hook in the current repository recording a work bit for each `rem:#<task id>` in commit messages"
+    )]
+    Install {
+        #[arg(long, help = "replace an existing post-commit hook")]
+        force: bool,
+    },
+    #[command(about = "Record work bits for the last commit, run by the installed hook")]
+    PostCommit,
+}
+
+#[derive(Clone, PartialEq, Debug, Subcommand)]
+enum TemplateAction {
+    #[command(
+        about = "Save tasks and reminders as a template, with dates relative to their creation"
+    )]
+    Save {
+        #[arg(help = "name of the template")]
+        name: String,
+        #[arg(
+            long = "task",
+            help = "id of a task to include, may be given multiple times"
+        )]
+        tasks: Vec<u64>,
+        #[arg(
+            long = "reminder",
+            help = "id of a reminder to include, may be given multiple times"
+        )]
+        reminders: Vec<u64>,
+        #[arg(long, help = "replace an existing template of that name")]
+        force: bool,
+    },
+    #[command(about = "List the saved templates")]
+    List,
+    #[command(about = "Create the tasks and reminders of a template, with dates relative to now")]
+    Apply {
+        #[arg(help = "name of the template")]
+        name: String,
+        #[arg(
+            long,
+            help = "date to count from instead of now, as DD.MM.YYYY [HH:MM]"
+        )]
+        start: Option<String>,
+    },
+    #[command(about = "Write a template as TOML to stdout")]
+    Export {
+        #[arg(help = "name of the template")]
+        name: String,
+    },
+    #[command(about = "Save a template from a TOML file")]
+    Import {
+        #[arg(help = "file to read, '-' for stdin")]
+        path: String,
+        #[arg(long, help = "name to save the template as, defaults to the file name")]
+        name: Option<String>,
+        #[arg(long, help = "replace an existing template of that name")]
+        force: bool,
+    },
+}
+
+#[derive(Clone, PartialEq, Debug, Subcommand)]
+enum BlueprintAction {
+    #[command(about = "Create the tasks of a blueprint file, with dates relative to a start date")]
+    Apply {
+        #[arg(help = "TOML file in the template format, '-' for stdin")]
+        path: String,
+        #[arg(
+            long,
+            help = "date to count from, as DD.MM.YYYY [HH:MM], defaults to now"
+        )]
+        start: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum TaskOrder {
+    Id,
+    Due,
+    Urgency,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum ReminderFormat {
+    Table,
+    /// the rule of every active reminder as cron expression with its next occurrences
+    Schedule,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum ReminderOrder {
+    Id,
+    /// next occurrence first, ended reminders last
+    NextDue,
+    Title,
+    /// most frequent first
+    Period,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum TaskGrouping {
+    /// top-level task of a task with subtasks
+    Project,
+    Tag,
+    /// week of the due date, starting on monday
+    DueWeek,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum ReportBy {
+    Tag,
+    /// top-level task of a task with subtasks
+    Project,
+    /// reminder that generated the task
+    Reminder,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum TimesheetBy {
+    Day,
+    Task,
+    Project,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum TimesheetFormat {
+    Md,
+    Csv,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum ImportFormat {
+    /// one task per line, see http://todotxt.org
+    TodoTxt,
+    /// JSON written by `task export`
+    Taskwarrior,
+    /// to-dos and events of an iCalendar file
+    Ics,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum PorcelainVersion {
+    V1,
+    V2,
+}
+
+impl From<PorcelainVersion> for porcelain::Version {
+    fn from(value: PorcelainVersion) -> Self {
+        match value {
+            PorcelainVersion::V1 => Self::V1,
+            PorcelainVersion::V2 => Self::V2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum SheetFormat {
+    /// A4 page to print from a browser
+    Html,
+    /// needs rem to be built with the `pdf` feature
+    Pdf,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum BotService {
+    /// the room `matrix_room` of the `[bot]` config section
+    Matrix,
+    /// the chat `telegram_chat` of the `[bot]` config section
+    Telegram,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum OutputFormat {
+    Table,
+    Csv,
+    Json,
 }
 
 #[derive(Parser, Debug)]
 struct Args {
+    #[arg(
+        long,
+        global = true,
+        help = "timezone to interpret and show times in, e.g. Europe/Berlin or UTC"
+    )]
+    tz: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "v1",
+        help = "machine-readable output: only the ids of created records, tasks and reminders as one line of JSON each"
+    )]
+    porcelain: Option<PorcelainVersion>,
+
+    // not global, as `-v` of `rem tasks` and `rem reminders` shows more of them
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        short_alias = 'L',
+        alias = "log",
+        action = clap::ArgAction::Count,
+        help = "log what rem does to stderr, repeat for more detail: -v info, -vv debug, -vvv SQL statements. Goes before the subcommand"
+    )]
+    log: u8,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "do not report tasks generated from reminders"
+    )]
+    quiet: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "ignore the .rem file of the working directory"
+    )]
+    no_scope: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "prefix tasks with a symbol of their state: !! overdue, ! due soon, > started, ✓ done"
+    )]
+    no_color_symbols: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "also read dates like 1.7, 1.7.25 and 07/01 besides DD.MM.YYYY and ISO 8601"
+    )]
+    lenient_dates: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "show dates as ISO 8601, e.g. 2024-07-01T09:30:00+02:00, instead of DD.MM.YYYY"
+    )]
+    iso: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "generate tasks from reminders even if the clock seems to have been set back"
+    )]
+    force_generation: bool,
+
     #[command(subcommand)]
     action: Action,
 }
 
+/// Tasks generated for the same occurrence of a reminder, merged into one
+struct Duplicates {
+    reminder: u64,
+    due: LocalDT,
+    kept: u64,
+    removed: Vec<u64>,
+    /// work bits moved to the kept task
+    work_bits: usize,
+}
+
+/// Tasks inserted by one pass of reminder generation
+#[derive(Debug, PartialEq, Eq)]
+struct Generated {
+    tasks: usize,
+    /// reminders that generated at least one task
+    reminders: usize,
+    /// tasks to review projects without activity
+    reviews: usize,
+}
+
+impl std::fmt::Display for Generated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        write!(
+            f,
+            "generated {} task{} from {} reminder{}",
+            self.tasks,
+            plural(self.tasks),
+            self.reminders,
+            plural(self.reminders)
+        )?;
+        if self.reviews > 0 {
+            write!(
+                f,
+                " and {} project review{}",
+                self.reviews,
+                plural(self.reviews)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Change bringing a reminder of the database in line with its block in the config, with the
+/// name of the block
+enum Reconcile {
+    /// declared reminder missing from the database
+    Create(String, Reminder),
+    /// reminder as stored and with the attributes of its changed block
+    Update {
+        name: String,
+        stored: Reminder,
+        declared: Box<Reminder>,
+    },
+    /// active reminder whose block was removed
+    Stop(String, Reminder),
+}
+
+/// Attributes declared in the config that differ between two reminders, with their values in
+/// `old` and `new`
+fn declared_changes(
+    old: &Reminder,
+    new: &Reminder,
+    config: &Config,
+) -> Vec<(&'static str, String, String)> {
+    let opt = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+    let datetime = |datetime: LocalDT| {
+        // stored reminders may be due at seconds the config can not declare
+        if datetime.second() == 0 {
+            datetime.format(config.datetime_fmt()).to_string()
+        } else {
+            datetime
+                .format(&format!("{}:%S", config.datetime_fmt()))
+                .to_string()
+        }
+    };
+    let tags = |reminder: &Reminder| {
+        let mut tags = reminder.tags.clone();
+        tags.sort();
+        tags
+    };
+
+    let mut changes = Vec::new();
+    let mut compare = |name, differs: bool, show: &dyn Fn(&Reminder) -> String| {
+        if differs {
+            changes.push((name, show(old), show(new)));
+        }
+    };
+    compare("title", old.title != new.title, &|r| r.title.clone());
+    compare("description", old.description != new.description, &|r| {
+        opt(r.description.clone())
+    });
+    compare("first due", old.first_due != new.first_due, &|r| {
+        if r.all_day && r.first_due.time() == NaiveTime::MIN {
+            r.first_due.format(config.date_fmt()).to_string()
+        } else {
+            datetime(r.first_due)
+        }
+    });
+    compare("all day", old.all_day != new.all_day, &|r| {
+        r.all_day.to_string()
+    });
+    compare("period", old.period != new.period, &|r| {
+        humanize_period(r.period)
+    });
+    compare("at", old.at != new.at, &|r| {
+        opt(r.at.map(|at| at.format("%H:%M").to_string()))
+    });
+    compare("until", old.until != new.until, &|r| {
+        opt(r.until.map(datetime))
+    });
+    compare("priority", old.priority != new.priority, &|r| {
+        opt(r.priority.map(|p| p.to_string()))
+    });
+    compare("context", old.context != new.context, &|r| {
+        opt(r.context.clone())
+    });
+    compare("tags", tags(old) != tags(new), &|r| tags(r).join(", "));
+    compare(
+        "require completion",
+        old.require_completion != new.require_completion,
+        &|r| r.require_completion.to_string(),
+    );
+    changes
+}
+
+/// Plan of `rem reconcile --diff`, one line per created or stopped reminder and per changed
+/// attribute
+fn format_reconcile_plan(plan: &[Reconcile], config: &Config) -> String {
+    let mut ret = String::new();
+    let (mut created, mut updated, mut stopped) = (0, 0, 0);
+    for change in plan.iter() {
+        match change {
+            Reconcile::Create(name, reminder) => {
+                ret.push_str(&format!(
+                    "+ {name}: {}, {} from {}\n",
+                    reminder.title,
+                    humanize_period(reminder.period),
+                    reminder.first_due.format(if reminder.all_day {
+                        config.date_fmt()
+                    } else {
+                        config.datetime_fmt()
+                    })
+                ));
+                created += 1;
+            }
+            Reconcile::Update {
+                name,
+                stored,
+                declared,
+            } => {
+                ret.push_str(&format!("~ {name} (reminder {}):\n", stored.id));
+                for (attribute, old, new) in declared_changes(stored, declared, config) {
+                    ret.push_str(&format!("    {attribute}: {old} -> {new}\n"));
+                }
+                updated += 1;
+            }
+            Reconcile::Stop(name, stored) => {
+                ret.push_str(&format!(
+                    "- {name} (reminder {}): {}\n",
+                    stored.id, stored.title
+                ));
+                stopped += 1;
+            }
+        }
+    }
+    if plan.is_empty() {
+        ret.push_str("Nothing to change.\n");
+    } else {
+        ret.push_str(&format!(
+            "Plan: {created} to create, {updated} to update, {stopped} to stop.\n"
+        ));
+    }
+    ret
+}
+
+/// Reminders changed to match the `[[reminders]]` blocks of the config
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Reconciled {
+    created: usize,
+    updated: usize,
+    stopped: usize,
+}
+
+impl std::fmt::Display for Reconciled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "reminders from the config: {} created, {} updated, {} stopped",
+            self.created, self.updated, self.stopped
+        )
+    }
+}
+
+/// Tasks changed to match the TODO comments of a directory, see `rem scan-todos`
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Scanned {
+    created: usize,
+    updated: usize,
+    closed: usize,
+    /// `path:line: error` of comments whose annotations were ignored
+    invalid: Vec<String>,
+}
+
+/// What a quick-add line created
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Added {
+    Task(u64),
+    /// lines with a recurrence create a reminder
+    Reminder(u64),
+}
+
+impl std::fmt::Display for Scanned {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "tasks from TODO comments: {} created, {} updated, {} closed",
+            self.created, self.updated, self.closed
+        )
+    }
+}
+
+struct TagUsage {
+    tag: String,
+    open: u64,
+    total: u64,
+    reminders: u64,
+}
+
 struct App {
     conn: rusqlite::Connection,
     now: LocalDT,
+    config: Config,
+    /// project and tag pinned by the `.rem` file of the working directory
+    scope: Scope,
 }
 
 impl App {
-    fn try_init(conn: rusqlite::Connection) -> Result<Self, String> {
+    fn try_init(conn: rusqlite::Connection, config: Config) -> Result<Self, String> {
         let now = chrono::Local::now();
 
         if !conn.table_exists(Some(DATABASE_NAME), "reminders").unwrap() {
@@ -98,7 +1367,19 @@ impl App {
                       created INTEGER NOT NULL,
                       first_due INTEGER NOT NULL,
                       period INTEGER NOT NULL,
-                      until INTEGER
+                      all_day INTEGER NOT NULL DEFAULT 0,
+                      until INTEGER,
+                      priority INTEGER,
+                      context TEXT,
+                      require_completion INTEGER NOT NULL DEFAULT 0,
+                      skip_before INTEGER,
+                      catch_up INTEGER NOT NULL DEFAULT 0,
+                      skip_stale_after INTEGER,
+                      at INTEGER,
+                      parent INTEGER,
+                      season TEXT,
+                      external_id TEXT,
+                      FOREIGN KEY(parent) REFERENCES reminders(id)
                     );",
                     [],
                 )
@@ -114,10 +1395,20 @@ impl App {
                       description TEXT,
                       created INTEGER NOT NULL,
                       start INTEGER,
+                      due_start INTEGER,
                       due INTEGER,
+                      due_all_day INTEGER NOT NULL DEFAULT 0,
                       generated_by INTEGER,
                       completed INTEGER,
-                      FOREIGN KEY(generated_by) REFERENCES reminders(id)
+                      skipped INTEGER NOT NULL DEFAULT 0,
+                      priority INTEGER,
+                      context TEXT,
+                      estimate INTEGER,
+                      parent INTEGER,
+                      client TEXT,
+                      external_id TEXT,
+                      FOREIGN KEY(generated_by) REFERENCES reminders(id),
+                      FOREIGN KEY(parent) REFERENCES tasks(id)
                     );",
                     [],
                 )
@@ -132,6 +1423,9 @@ impl App {
                       task_id INTEGER NOT NULL,
                       datetime INTEGER NOT NULL,
                       description TEXT,
+                      cwd TEXT,
+                      git_repo TEXT,
+                      git_branch TEXT,
                       FOREIGN KEY(task_id) REFERENCES tasks(id)
                     );",
                     [],
@@ -139,548 +1433,10368 @@ impl App {
                 .map_err(|err| format!("could not create work_bits table: {err}"))?;
         }
 
-        Ok(Self { conn, now })
-    }
-
-    fn add_task(
-        &mut self,
-        title: String,
-        description: Option<String>,
-        start: Option<LocalDT>,
-        due: Option<LocalDT>,
-        generated_by: Option<u64>,
-    ) -> Result<(), String> {
-        let _ = self.conn.execute(
-            "INSERT INTO tasks (title, description, created, start, due, completed, generated_by) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            (
-                title.clone(),
-                description.to_owned(),
-                self.now.timestamp(),
-                start.map(|t| t.timestamp()),
-                due.map(|t| t.timestamp()),
-                Null,
-                generated_by,
-            ),
-        ).map_err(|err| { format!("could not insert task: {err}") })?;
-        Ok(())
-    }
-
-    fn reminders_to_tasks(&mut self) -> Result<(), String> {
-        let reminders = {
-            let mut res = self
-            .conn
-            .prepare(
-                "SELECT id, title, description, created, first_due, period, until FROM reminders where until is NULL or until > ?1;",
-            )
-            .map_err(|err| format!("could not query tasks: {err}"))?;
-
-            let s = res
-                .query([self.now.timestamp()])
-                .map_err(|err| format!("Could not query database: {err}"))?
-                .map(|row| Reminder::from_db_row(row))
-                .collect::<Vec<_>>()
-                .map_err(|err| format!("Could not acquire reminders from database: {err}"))?;
-            drop(res);
-            s
-        };
+        if !conn.table_exists(Some(DATABASE_NAME), "tags").unwrap() {
+            let _ = conn
+                .execute(
+                    "CREATE TABLE IF NOT EXISTS tags (
+                      task_id INTEGER NOT NULL,
+                      tag TEXT NOT NULL,
+                      PRIMARY KEY(task_id, tag),
+                      FOREIGN KEY(task_id) REFERENCES tasks(id) ON DELETE CASCADE
+                    );",
+                    [],
+                )
+                .map_err(|err| format!("could not create tags table: {err}"))?;
+        }
 
-        for reminder in reminders.iter() {
-            let generated_tasks = {
-                let mut r = self
-                    .conn
-                    .prepare("SELECT * FROM tasks where generated_by == ?1;")
-                    .map_err(|err| format!("could not query tasks: {err}"))?;
+        if !conn
+            .table_exists(Some(DATABASE_NAME), "reminder_tags")
+            .unwrap()
+        {
+            let _ = conn
+                .execute(
+                    "CREATE TABLE IF NOT EXISTS reminder_tags (
+                      reminder_id INTEGER NOT NULL,
+                      tag TEXT NOT NULL,
+                      PRIMARY KEY(reminder_id, tag),
+                      FOREIGN KEY(reminder_id) REFERENCES reminders(id) ON DELETE CASCADE
+                    );",
+                    [],
+                )
+                .map_err(|err| format!("could not create reminder_tags table: {err}"))?;
+        }
 
-                let mut generated_tasks = r
-                    .query([reminder.id])
-                    .map_err(|err| format!("Could not query database: {err}"))?
-                    .map(|row| Task::from_db_row(row, None))
-                    .collect::<Vec<Task>>()
-                    .map_err(|err| {
-                        format!("Could not find tasks corresponding to reminder: {err}")
-                    })?;
+        if !conn
+            .table_exists(Some(DATABASE_NAME), "checklist_items")
+            .unwrap()
+        {
+            let _ = conn
+                .execute(
+                    "CREATE TABLE IF NOT EXISTS checklist_items (
+                      id INTEGER PRIMARY KEY,
+                      task_id INTEGER NOT NULL,
+                      position INTEGER NOT NULL,
+                      text TEXT NOT NULL,
+                      done INTEGER NOT NULL DEFAULT 0,
+                      FOREIGN KEY(task_id) REFERENCES tasks(id) ON DELETE CASCADE
+                    );",
+                    [],
+                )
+                .map_err(|err| format!("could not create checklist_items table: {err}"))?;
+        }
 
-                generated_tasks.sort_by_cached_key(|x| {
-                    x.due
-                        .expect("elements of recurring sequence need to have due date")
-                });
+        if !conn
+            .table_exists(Some(DATABASE_NAME), "reminder_checklist_items")
+            .unwrap()
+        {
+            let _ = conn
+                .execute(
+                    "CREATE TABLE IF NOT EXISTS reminder_checklist_items (
+                      id INTEGER PRIMARY KEY,
+                      reminder_id INTEGER NOT NULL,
+                      position INTEGER NOT NULL,
+                      text TEXT NOT NULL,
+                      FOREIGN KEY(reminder_id) REFERENCES reminders(id) ON DELETE CASCADE
+                    );",
+                    [],
+                )
+                .map_err(|err| format!("could not create reminder_checklist_items table: {err}"))?;
+        }
 
-                generated_tasks
-            };
+        if !conn
+            .table_exists(Some(DATABASE_NAME), "reminder_lead_tasks")
+            .unwrap()
+        {
+            let _ = conn
+                .execute(
+                    "CREATE TABLE IF NOT EXISTS reminder_lead_tasks (
+                      id INTEGER PRIMARY KEY,
+                      reminder_id INTEGER NOT NULL,
+                      position INTEGER NOT NULL,
+                      title TEXT NOT NULL,
+                      delta INTEGER NOT NULL,
+                      FOREIGN KEY(reminder_id) REFERENCES reminders(id) ON DELETE CASCADE
+                    );",
+                    [],
+                )
+                .map_err(|err| format!("could not create reminder_lead_tasks table: {err}"))?;
+        }
 
-            let mut next_due = reminder.first_due;
-            while next_due < self.now + reminder.period {
-                // insert a new task if the instance at next_due is missing from
-                // the list of tasks associated with this list of generated tasks
-                if generated_tasks
-                    .iter()
-                    .find(|task| {
-                        let due = task.due.expect("Recurring tasks need to have a due date");
-                        due == next_due
-                    })
-                    .is_none()
-                {
-                    self.add_task(
-                        reminder.title.clone(),
-                        reminder.description.to_owned(),
-                        Some(next_due - reminder.period),
-                        Some(next_due),
-                        Some(reminder.id),
-                    )?;
-                }
+        if !conn.table_exists(Some(DATABASE_NAME), "sessions").unwrap() {
+            let _ = conn
+                .execute(
+                    "CREATE TABLE IF NOT EXISTS sessions (
+                      id INTEGER PRIMARY KEY,
+                      task_id INTEGER NOT NULL,
+                      started INTEGER NOT NULL,
+                      stopped INTEGER,
+                      last_seen INTEGER NOT NULL,
+                      FOREIGN KEY(task_id) REFERENCES tasks(id) ON DELETE CASCADE
+                    );",
+                    [],
+                )
+                .map_err(|err| format!("could not create sessions table: {err}"))?;
+        }
 
-                next_due += reminder.period;
-            }
+        if !conn.table_exists(Some(DATABASE_NAME), "templates").unwrap() {
+            let _ = conn
+                .execute(
+                    "CREATE TABLE IF NOT EXISTS templates (
+                      name TEXT PRIMARY KEY,
+                      definition TEXT NOT NULL
+                    );",
+                    [],
+                )
+                .map_err(|err| format!("could not create templates table: {err}"))?;
         }
 
-        Ok(())
+        if !conn.table_exists(Some(DATABASE_NAME), "due_links").unwrap() {
+            let _ = conn
+                .execute(
+                    "CREATE TABLE IF NOT EXISTS due_links (
+                      task_id INTEGER PRIMARY KEY,
+                      after INTEGER NOT NULL,
+                      delta INTEGER NOT NULL,
+                      FOREIGN KEY(task_id) REFERENCES tasks(id) ON DELETE CASCADE,
+                      FOREIGN KEY(after) REFERENCES tasks(id) ON DELETE CASCADE
+                    );",
+                    [],
+                )
+                .map_err(|err| format!("could not create due_links table: {err}"))?;
+        }
+
+        if !conn
+            .table_exists(Some(DATABASE_NAME), "occurrence_shifts")
+            .unwrap()
+        {
+            let _ = conn
+                .execute(
+                    "CREATE TABLE IF NOT EXISTS occurrence_shifts (
+                      reminder_id INTEGER NOT NULL,
+                      due INTEGER NOT NULL,
+                      moved_to INTEGER NOT NULL,
+                      PRIMARY KEY(reminder_id, due),
+                      FOREIGN KEY(reminder_id) REFERENCES reminders(id) ON DELETE CASCADE
+                    );",
+                    [],
+                )
+                .map_err(|err| format!("could not create occurrence_shifts table: {err}"))?;
+        }
+
+        if !conn.table_exists(Some(DATABASE_NAME), "meta").unwrap() {
+            let _ = conn
+                .execute(
+                    "CREATE TABLE IF NOT EXISTS meta (
+                      key TEXT PRIMARY KEY,
+                      value TEXT NOT NULL
+                    );",
+                    [],
+                )
+                .map_err(|err| format!("could not create meta table: {err}"))?;
+        }
+
+        for (table, column, decl) in ADDED_COLUMNS {
+            add_column_if_missing(&conn, table, column, decl)?;
+        }
+
+        // occurrences are due on whole minutes, older versions kept the seconds of imported
+        // first due dates
+        conn.execute_batch(
+            "UPDATE tasks SET due = due - due % 60 WHERE generated_by IS NOT NULL AND due % 60 != 0;
+             UPDATE OR REPLACE occurrence_shifts SET due = due - due % 60, moved_to = moved_to - moved_to % 60
+               WHERE due % 60 != 0 OR moved_to % 60 != 0;",
+        )
+        .map_err(|err| format!("could not round occurrences to minutes: {err}"))?;
+
+        for table in ["tasks", "reminders"] {
+            conn.execute(
+                &format!("CREATE UNIQUE INDEX IF NOT EXISTS {table}_external_id ON {table}(external_id);"),
+                [],
+            )
+            .map_err(|err| format!("could not index external ids of {table}: {err}"))?;
+        }
+
+        // timestamps are stored in UTC, the timezone is kept to notice when it changes
+        if let Some(tz) = timezone_name() {
+            conn.execute(
+                "INSERT OR IGNORE INTO meta (key, value) VALUES ('timezone', ?1);",
+                [tz],
+            )
+            .map_err(|err| format!("could not store timezone: {err}"))?;
+        }
+
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)
+            .map_err(|err| format!("could not set schema version: {err}"))?;
+
+        sync::install(&conn)?;
+
+        // tombstones and modification times follow the tasks and reminders through their
+        // triggers, also when undoing, so merging passes undone changes on like any other
+        let journaled = TABLES
+            .iter()
+            .copied()
+            .filter(|table| !matches!(*table, "meta" | "tombstones" | "operations" | "undo_log"))
+            .collect::<Vec<_>>();
+        journal::install(&conn, &journaled, &["modified"])?;
+
+        Ok(Self {
+            conn,
+            now,
+            config,
+            scope: Scope::default(),
+        })
     }
 
-    fn add_reminder(
-        &mut self,
+    /// Run `change` in a transaction, or as part of the one already open, so a change is atomic
+    /// on its own and when made as part of a larger one
+    fn atomically<T>(&self, change: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+        if !self.conn.is_autocommit() {
+            return change();
+        }
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| format!("Could not start transaction: {err}"))?;
+        let ret = change()?;
+        tx.commit()
+            .map_err(|err| format!("Could not commit transaction: {err}"))?;
+        Ok(ret)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn add_task(
+        &self,
         title: String,
         description: Option<String>,
-        first_due: LocalDT,
-        period: TimeDelta,
-        until: Option<LocalDT>,
-    ) -> Result<(), String> {
-        let until = until.map(|x| x.timestamp());
-        self.conn.execute(
-            "INSERT INTO reminders (title, description, first_due, period, until, created) values (?1, ?2, ?3, ?4, ?5, ?6);",
-            (title, description, first_due.timestamp(), period.num_seconds(), until, self.now.timestamp())
-        ).map_err(|err| format!("Could not add reminder: {err}"))?;
+        start: Option<LocalDT>,
+        due_start: Option<LocalDT>,
+        due: Option<LocalDT>,
+        due_all_day: bool,
+        priority: Option<Priority>,
+        context: Option<String>,
+        estimate: Option<TimeDelta>,
+        tags: &[String],
+        generated_by: Option<u64>,
+        parent: Option<u64>,
+    ) -> Result<u64, String> {
+        let mut tags = tags.to_vec();
+        if let Some(ref tag) = self.scope.tag {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
 
-        Ok(())
+        self.insert_task(&Task {
+            id: 0,
+            title,
+            description,
+            generated_by,
+            parent: parent.or(self.scope.project),
+            priority,
+            context,
+            estimate,
+            tags,
+            client: None,
+            created: self.now,
+            start,
+            due_start,
+            due,
+            due_all_day,
+            completed: None,
+            skipped: false,
+            work_bits: Vec::new(),
+            checklist: Vec::new(),
+        })
     }
 
-    fn show_reminders(&self, all: bool, verbose: bool) -> Result<(), String> {
-        let mut res = self
-            .conn
-            .prepare(
-                "SELECT id, title, description, created, first_due, period, until FROM reminders;",
-            )
-            .map_err(|err| format!("could not query tasks: {err}"))?;
+    /// Store a task with its tags, checklist and work bits, returning its ID
+    ///
+    /// The ID of `task` is ignored.
+    fn insert_task(&self, task: &Task) -> Result<u64, String> {
+        if task.title.trim().is_empty() {
+            return Err("Task title must not be empty.".to_string());
+        }
 
-        let rows = res
-            .query([])
-            .map_err(|err| format!("Could not query database: {err}"))?
-            .map(|row| Reminder::from_db_row(row))
-            .iterator();
+        if let Some(parent) = task.parent {
+            self.get_task(parent)
+                .map_err(|err| format!("Invalid parent task: {err}"))?;
+        }
 
-        for row in rows {
-            let r = match row {
-                Ok(row) => row,
-                Err(err) => return Err(format!("Error querying database: {err}")),
-            };
-            print!("{}", r.display(all, verbose, self.now));
+        validate::task(
+            task.due_start,
+            task.due,
+            task.due_all_day,
+            self.now,
+            &self.config,
+        )?;
+
+        let _ = self.conn.execute(
+            "INSERT INTO tasks (title, description, created, start, due_start, due, due_all_day, completed, skipped, generated_by, priority, context, estimate, parent, client) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            (
+                &task.title,
+                &task.description,
+                task.created.timestamp(),
+                task.start.map(|t| t.timestamp()),
+                task.due_start.map(|t| t.timestamp()),
+                task.due.map(|t| t.timestamp()),
+                task.due.is_some() && task.due_all_day,
+                task.completed.map(|t| t.timestamp()),
+                task.completed.is_some() && task.skipped,
+                task.generated_by,
+                task.priority.map(|p| p as i64),
+                &task.context,
+                task.estimate.map(|e| e.num_seconds()),
+                task.parent,
+                &task.client,
+            ),
+        ).map_err(|err| { format!("could not insert task: {err}") })?;
+        let id = self.conn.last_insert_rowid() as u64;
+
+        for tag in task.tags.iter() {
+            let tag = validate_tag(tag)?;
+            self.conn
+                .execute(
+                    "INSERT OR IGNORE INTO tags (task_id, tag) VALUES (?1, ?2);",
+                    (id, tag),
+                )
+                .map_err(|err| format!("could not tag task: {err}"))?;
+        }
+
+        for (position, (text, done)) in task.checklist.iter().enumerate() {
+            self.conn
+                .execute(
+                    "INSERT INTO checklist_items (task_id, position, text, done) VALUES (?1, ?2, ?3, ?4);",
+                    (id, position, text, done),
+                )
+                .map_err(|err| format!("Could not add checklist item: {err}"))?;
+        }
+
+        for bit in task.work_bits.iter() {
+            self.add_work_bit(id, bit)
+                .map_err(|err| format!("Could not add work bit: {err}"))?;
+        }
+
+        Ok(id)
+    }
+
+    /// Pin all-day due dates to the configured default due time, if there is one
+    fn apply_default_due_time(&self, due: LocalDT, all_day: bool) -> (LocalDT, bool) {
+        match self.config.default_due_time {
+            Some(time) if all_day => {
+                let due = due
+                    .date_naive()
+                    .and_time(time)
+                    .and_local_timezone(Local)
+                    .unwrap();
+                (due, false)
+            }
+            _ => (due, all_day),
+        }
+    }
+
+    /// Parse a due date, either absolute or relative to the one of another task
+    ///
+    /// Returns the due date, whether it is all day and, for relative ones, the offset and the id
+    /// of the other task.
+    fn parse_due(&self, repr: &str) -> Result<(LocalDT, bool, Option<DueLink>), String> {
+        match parse_due_after(repr) {
+            Some(link) => {
+                let (offset, after) = link?;
+                let (due, all_day) = self.due_after(after, offset)?;
+                Ok((due, all_day, Some((offset, after))))
+            }
+            None => {
+                let (due, all_day) = parse_date_time_all_day(repr, self.config.lenient_dates)?;
+                let (due, all_day) = self.apply_default_due_time(due, all_day);
+                Ok((due, all_day, None))
+            }
+        }
+    }
+
+    /// Due date at `offset` from the due date of task `after`
+    ///
+    /// All-day due dates stay all day when moved by whole days.
+    fn due_after(&self, after: u64, offset: TimeDelta) -> Result<(LocalDT, bool), String> {
+        let task = self.get_task(after)?;
+        let due = task.due.ok_or(format!("Task {after} has no due date."))?;
+        if task.due_all_day && offset == TimeDelta::days(offset.num_days()) {
+            Ok((start_of_day(due.date_naive() + offset), true))
+        } else {
+            Ok((due + offset, false))
+        }
+    }
+
+    /// Offset and id of the task the due date of task `id` is relative to
+    fn due_link(&self, id: u64) -> Result<Option<DueLink>, String> {
+        self.conn
+            .query_row(
+                "SELECT delta, after FROM due_links WHERE task_id = ?1;",
+                [id],
+                |row| Ok((TimeDelta::seconds(row.get(0)?), row.get(1)?)),
+            )
+            .optional()
+            .map_err(|err| format!("Could not query due date of task {id}: {err}"))
+    }
+
+    /// Make the due date of task `id` relative to the one of another task, or absolute again for
+    /// `None`
+    fn link_due(&self, id: u64, link: Option<DueLink>) -> Result<(), String> {
+        if let Some((_, after)) = link {
+            // following the links from `after` must not lead back to `id`
+            let mut current = Some(after);
+            while let Some(other) = current {
+                if other == id {
+                    return Err(format!(
+                        "Task {after} is due relative to task {id} already."
+                    ));
+                }
+                current = self.due_link(other)?.map(|(_, after)| after);
+            }
         }
 
+        self.conn
+            .execute("DELETE FROM due_links WHERE task_id = ?1;", [id])
+            .map_err(|err| format!("Could not update due date of task {id}: {err}"))?;
+        if let Some((offset, after)) = link {
+            self.conn
+                .execute(
+                    "INSERT INTO due_links (task_id, after, delta) VALUES (?1, ?2, ?3);",
+                    (id, after, offset.num_seconds()),
+                )
+                .map_err(|err| format!("Could not update due date of task {id}: {err}"))?;
+        }
         Ok(())
     }
 
-    fn stop_reminder(&mut self, id: u64) -> Result<(), String> {
-        let until = self.now;
+    /// Ids of the tasks whose due dates are relative to the one of task `id`
+    fn due_followers(&self, id: u64) -> Result<Vec<u64>, String> {
+        self.conn
+            .prepare("SELECT task_id FROM due_links WHERE after = ?1 ORDER BY task_id;")
+            .map_err(|err| format!("Could not query due dates: {err}"))?
+            .query([id])
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(|row| row.get(0))
+            .collect()
+            .map_err(|err| format!("Error querying database: {err}"))
+    }
+
+    /// Set the due date of a task
+    ///
+    /// With `propagate`, the tasks due relative to it are moved along. Returns their ids.
+    fn reschedule(
+        &self,
+        id: u64,
+        due: LocalDT,
+        all_day: bool,
+        propagate: bool,
+    ) -> Result<Vec<u64>, String> {
+        let task = self.get_task(id)?;
+        validate::task(task.due_start, Some(due), all_day, self.now, &self.config)?;
         self.conn
             .execute(
-                "UPDATE reminders SET until = ?1 WHERE id = ?2",
-                (until.timestamp(), id),
+                "UPDATE tasks SET due = ?1, due_all_day = ?2 WHERE id = ?3;",
+                (due.timestamp(), all_day, id),
             )
-            .map_err(|err| format!("Could stop reminder: {err}"))?;
+            .map_err(|err| format!("Could not reschedule task {id}: {err}"))?;
 
-        Ok(())
+        let mut moved = Vec::new();
+        if propagate {
+            for follower in self.due_followers(id)? {
+                let (offset, _) = self.due_link(follower)?.expect("followers are linked");
+                let (due, all_day) = self.due_after(id, offset)?;
+                moved.push(follower);
+                moved.extend(self.reschedule(follower, due, all_day, true)?);
+            }
+        }
+        Ok(moved)
     }
 
-    fn show_tasks(&self, all: bool, verbose: bool) -> Result<(), String> {
-        let mut res = self
+    /// Generate the tasks of all active reminders up to their next occurrence
+    ///
+    /// * `backfill`: decides what to do when a reminder missed more occurrences than the
+    ///   configured backfill limit, given the number of missed occurrences
+    fn reminders_to_tasks(
+        &self,
+        backfill: &mut dyn FnMut(&Reminder, usize) -> Backfill,
+    ) -> Result<Generated, String> {
+        self.generate(TimeDelta::zero(), backfill)
+    }
+
+    /// Like [`App::generate`], but roll the generated tasks back and return them instead
+    fn dry_generate(
+        &self,
+        horizon: TimeDelta,
+        backfill: &mut dyn FnMut(&Reminder, usize) -> Backfill,
+    ) -> Result<Vec<Task>, String> {
+        let before = self
             .conn
-            .prepare("SELECT * FROM tasks;")
+            .query_one("SELECT coalesce(max(id), 0) FROM tasks;", [], |row| {
+                row.get::<_, u64>(0)
+            })
             .map_err(|err| format!("Could not query tasks: {err}"))?;
+        self.conn
+            .execute_batch("SAVEPOINT dry_run;")
+            .map_err(|err| format!("Could not start dry run: {err}"))?;
+        let generated = self.generate(horizon, backfill).and_then(|_| {
+            Ok(self
+                .all_tasks()?
+                .into_iter()
+                .filter(|task| task.id > before)
+                .collect())
+        });
+        self.conn
+            .execute_batch("ROLLBACK TO dry_run; RELEASE dry_run;")
+            .map_err(|err| format!("Could not roll back dry run: {err}"))?;
+        generated
+    }
 
-        let rows = res
-            .query([])
-            .map_err(|err| format!("Could not query database: {err}"))?
-            .map(|row| Task::from_db_row(row, Some(&self.conn)))
-            .iterator();
+    /// Generate the tasks of all active reminders up to their next occurrence, or up to
+    /// `horizon` from now if that is later
+    ///
+    /// See [`App::reminders_to_tasks`] for `backfill`.
+    fn generate(
+        &self,
+        horizon: TimeDelta,
+        backfill: &mut dyn FnMut(&Reminder, usize) -> Backfill,
+    ) -> Result<Generated, String> {
+        // backfill may prompt, which must not keep other rem processes waiting for the lock
+        let mut answers = HashMap::new();
+        for reminder in self.active_reminders()? {
+            if reminder.period <= TimeDelta::zero() {
+                continue;
+            }
+            let (_, missing) = self.missing_occurrences(&reminder, horizon)?;
+            let overdue = missing.iter().filter(|o| o.due < self.now).count();
+            let collapsing = reminder.catch_up == CatchUp::Collapse && overdue > 1;
+            if !collapsing && overdue > self.config.backfill_limit {
+                answers.insert(reminder.id, backfill(&reminder, overdue));
+            }
+        }
 
-        for row in rows {
-            let t = match row {
-                Ok(row) => row,
-                Err(err) => return Err(format!("Error querying database: {err}")),
-            };
-            print!("{}", t.display(all, verbose, self.now));
+        // taking the write lock before looking for missing occurrences makes overlapping
+        // passes, e.g. of cron and an interactive command, wait for each other instead of both
+        // generating the same occurrences
+        if !self.conn.is_autocommit() {
+            return self.generate_locked(horizon, &answers);
         }
-        Ok(())
+        let tx = rusqlite::Transaction::new_unchecked(
+            &self.conn,
+            rusqlite::TransactionBehavior::Immediate,
+        )
+        .map_err(|err| format!("Could not lock the database for generation: {err}"))?;
+        let ret = self.generate_locked(horizon, &answers)?;
+        tx.commit()
+            .map_err(|err| format!("Could not finish generation: {err}"))?;
+        Ok(ret)
     }
 
-    fn delete_task(&mut self, id: u64) -> Result<(), String> {
-        let res = self
+    /// Reminders that have not ended yet
+    fn active_reminders(&self) -> Result<Vec<Reminder>, String> {
+        let mut res = self
             .conn
-            .execute("DELETE FROM tasks where ID = ?1", [id])
+            .prepare("SELECT * FROM reminders where until is NULL or until > ?1;")
             .map_err(|err| format!("could not query tasks: {err}"))?;
 
-        if res == 0 {
-            Err(format!("Could not delete Task. ID not found."))
-        } else {
-            Ok(())
+        let reminders = res
+            .query([self.now.timestamp()])
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(|row| Reminder::from_db_row(row, Some(&self.conn)))
+            .collect::<Vec<_>>()
+            .map_err(|err| format!("Could not acquire reminders from database: {err}"));
+        reminders
+    }
+
+    /// Tasks generated by `reminder` so far, ordered by due date, and its occurrences missing
+    /// from them up to its next occurrence or `horizon` from now
+    fn missing_occurrences(
+        &self,
+        reminder: &Reminder,
+        horizon: TimeDelta,
+    ) -> Result<(Vec<Task>, Vec<Occurrence>), String> {
+        let generated_tasks = {
+            let mut r = self
+                .conn
+                .prepare("SELECT * FROM tasks where generated_by == ?1;")
+                .map_err(|err| format!("could not query tasks: {err}"))?;
+
+            let mut generated_tasks = r
+                .query([reminder.id])
+                .map_err(|err| format!("Could not query database: {err}"))?
+                .map(|row| Task::from_db_row(row, None))
+                .collect::<Vec<Task>>()
+                .map_err(|err| format!("Could not find tasks corresponding to reminder: {err}"))?;
+
+            generated_tasks.sort_by_cached_key(|x| {
+                x.due
+                    .expect("elements of recurring sequence need to have due date")
+            });
+
+            generated_tasks
+        };
+
+        let shifts = self.occurrence_shifts(reminder.id)?;
+        // whether the previous occurrence was completed, if there is one
+        let mut previous_completed = true;
+        let mut missing = Vec::new();
+        for occurrence in reminder.occurrences(..self.now + reminder.period.max(horizon)) {
+            if reminder
+                .skip_before
+                .is_some_and(|skip| occurrence.due < skip)
+            {
+                continue;
+            }
+            let due = shifts
+                .get(&occurrence.due)
+                .copied()
+                .unwrap_or(occurrence.due);
+
+            // collect the occurrence if it is missing from
+            // the list of tasks associated with this list of generated tasks
+            // tasks written by other tools, e.g. an older rem syncing, may still have seconds
+            let existing = generated_tasks.iter().find(|task| {
+                truncate_to_minute(task.due.expect("Recurring tasks need to have a due date"))
+                    == due
+            });
+
+            if let Some(task) = existing {
+                previous_completed = task.completed.is_some();
+            } else {
+                if reminder.require_completion && !previous_completed {
+                    // gated reminders only continue once the last occurrence is done
+                    break;
+                }
+                previous_completed = false;
+                missing.push(Occurrence { due, ..occurrence });
+            }
         }
+        Ok((generated_tasks, missing))
     }
 
-    fn complete_task(&self, id: u64) -> Result<(), String> {
-        let completed: Option<i64> = self
+    /// [`App::generate`] within a transaction holding the write lock
+    ///
+    /// * `answers`: what to do about the missed occurrences of reminders exceeding the
+    ///   backfill limit, by reminder id
+    fn generate_locked(
+        &self,
+        horizon: TimeDelta,
+        answers: &HashMap<u64, Backfill>,
+    ) -> Result<Generated, String> {
+        let reminders = self.active_reminders()?;
+
+        let started = std::time::Instant::now();
+        let mut summary = Generated {
+            tasks: 0,
+            reminders: 0,
+            reviews: 0,
+        };
+        for reminder in reminders.iter() {
+            if reminder.period <= TimeDelta::zero() {
+                // would never advance, reminders stored before periods were validated
+                eprintln!(
+                    "WARNING: skipping reminder {} with non-positive period",
+                    reminder.id
+                );
+                continue;
+            }
+
+            let (generated_tasks, mut missing) = self.missing_occurrences(reminder, horizon)?;
+
+            let overdue = missing.iter().filter(|o| o.due < self.now).count();
+            log::debug!(
+                "reminder {}: {} missing occurrences, {overdue} overdue",
+                reminder.id,
+                missing.len()
+            );
+            let decision = if reminder.catch_up == CatchUp::Collapse && overdue > 1 {
+                Some(Backfill::Collapse)
+            } else if overdue > self.config.backfill_limit {
+                // passing the limit only since asking, e.g. as time passed, keeps them all
+                Some(answers.get(&reminder.id).copied().unwrap_or(Backfill::All))
+            } else {
+                None
+            };
+            let mut caught_up = None;
+            if let Some(decision) = decision {
+                let keep = match decision {
+                    Backfill::All => overdue,
+                    Backfill::Latest(n) => n.min(overdue),
+                    Backfill::Collapse => {
+                        caught_up = Some((overdue, missing[0].due));
+                        1
+                    }
+                };
+                if keep < overdue {
+                    log::info!(
+                        "reminder {}: skipping {} missed occurrences ({decision:?})",
+                        reminder.id,
+                        overdue - keep
+                    );
+                    let skipped = missing.drain(..overdue - keep).collect::<Vec<_>>();
+                    let skip_before = skipped.last().expect("skipped some").due + reminder.period;
+                    self.conn
+                        .execute(
+                            "UPDATE reminders SET skip_before = ?1 WHERE id = ?2;",
+                            (skip_before.timestamp(), reminder.id),
+                        )
+                        .map_err(|err| format!("Could not store skipped occurrences: {err}"))?;
+                }
+            }
+
+            let mut generated = false;
+            for (idx, occurrence) in missing.into_iter().enumerate() {
+                let due = occurrence.due;
+                let description = reminder
+                    .description
+                    .as_deref()
+                    .map(|description| occurrence.expand(description));
+                let description = match caught_up {
+                    Some((count, since)) if idx == 0 => {
+                        let note = format!(
+                            "Catch-up for {count} missed occurrences since {}.",
+                            since.format(self.config.datetime_fmt())
+                        );
+                        Some(match description {
+                            Some(description) => format!("{note}\n{description}"),
+                            None => note,
+                        })
+                    }
+                    _ => description,
+                };
+
+                let mut task = Task::builder()
+                    .title(occurrence.expand(&reminder.title))
+                    .created(self.now)
+                    .generated_by(reminder.id)
+                    .start(due - reminder.period)
+                    .due(due)
+                    .all_day(reminder.all_day)
+                    .tags(&reminder.tags);
+                if let Some(description) = description {
+                    task = task.description(description);
+                }
+                if let Some(priority) = reminder.priority {
+                    task = task.priority(priority);
+                }
+                if let Some(ref context) = reminder.context {
+                    task = task.context(context);
+                }
+                for item in reminder.checklist.iter() {
+                    task = task.checklist_item(item);
+                }
+                let id = self.insert_task(&task.build())?;
+                log::debug!("reminder {}: generated task {id}", reminder.id);
+                summary.tasks += 1;
+                generated = true;
+
+                // lead tasks are not occurrences themselves, so they are linked to the
+                // occurrence by their due date instead of to the reminder
+                for lead in reminder.lead_tasks.iter() {
+                    let lead_due = due + lead.offset;
+                    let mut task = Task::builder()
+                        .title(occurrence.expand(&lead.title))
+                        .created(self.now)
+                        .start(lead_due - reminder.period)
+                        .due(lead_due)
+                        .all_day(reminder.all_day)
+                        .tags(&reminder.tags);
+                    if let Some(priority) = reminder.priority {
+                        task = task.priority(priority);
+                    }
+                    if let Some(ref context) = reminder.context {
+                        task = task.context(context);
+                    }
+                    let lead_id = self.insert_task(&task.build())?;
+                    self.link_due(lead_id, Some((lead.offset, id)))?;
+                    log::debug!("reminder {}: generated lead task {lead_id}", reminder.id);
+                    summary.tasks += 1;
+                }
+            }
+
+            if generated {
+                summary.reminders += 1;
+            }
+
+            if let (true, Some(stale)) = (generated, reminder.skip_stale_after) {
+                for task in generated_tasks.iter().filter(|t| t.completed.is_none()) {
+                    if task
+                        .deadline(&self.config)
+                        .is_some_and(|deadline| deadline + stale < self.now)
+                    {
+                        log::info!("reminder {}: skipping stale task {}", reminder.id, task.id);
+                        self.skip_task(task.id)?;
+                    }
+                }
+            }
+        }
+
+        log::info!(
+            "checked {} reminders in {:?}",
+            reminders.len(),
+            started.elapsed()
+        );
+        summary.reviews = self.review_stale_projects()?;
+        if let Some(last) = self.last_generated()? {
+            log::debug!(
+                "last generated {} before",
+                format_timedelta(self.now - last)
+            );
+        }
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('last_generated', ?1);",
+                [self.now.timestamp().to_string()],
+            )
+            .map_err(|err| format!("Could not note generation: {err}"))?;
+        Ok(summary)
+    }
+
+    /// Add a task to review every project with open tasks that saw no changes for the configured
+    /// number of weeks, unless its last review task is still open, returning how many were added
+    ///
+    /// The review task is a subtask of the project, so completing it counts as activity.
+    fn review_stale_projects(&self) -> Result<usize, String> {
+        let projects = self
             .conn
-            .query_one("select completed from tasks where id = ?1", (id,), |row| {
-                row.get(0)
+            .prepare(
+                "WITH RECURSIVE members(root, id) AS (
+                   SELECT id, id FROM tasks p WHERE parent IS NULL AND completed IS NULL
+                     AND EXISTS (SELECT 1 FROM tasks c WHERE c.parent = p.id)
+                   UNION ALL
+                   SELECT m.root, t.id FROM tasks t JOIN members m ON t.parent = m.id
+                 )
+                 SELECT m.root, r.title, coalesce(CAST(max(t.modified) AS INTEGER), max(t.created)) FROM members m
+                 JOIN tasks t ON t.id = m.id JOIN tasks r ON r.id = m.root
+                 GROUP BY m.root
+                 HAVING sum(t.completed IS NULL AND t.id != m.root) > 0;",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| {
+                    Ok((
+                        row.get::<_, u64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, i64>(2)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()
             })
-            .map_err(|err| format!("Could not get task completion status: {err}"))?;
-        let completed = completed.map(import_datetime);
+            .map_err(|err| format!("Could not query projects: {err}"))?;
 
-        if let Some(completed) = completed {
-            return Err(format!(
-                "Could not mark task {id} as completed. Already completed at {completed}",
-                completed = completed.format(DATETIME_FMT)
-            ));
+        let mut added = 0;
+        for (id, title, modified) in projects {
+            let weeks = self
+                .config
+                .project_reviews
+                .get(&title)
+                .copied()
+                .unwrap_or(self.config.project_review_weeks);
+            let stale = import_datetime(modified) + TimeDelta::weeks(weeks as i64);
+            if weeks == 0 || stale > self.now {
+                continue;
+            }
+            let key = format!("project_review:{id}");
+            let open_review = self
+                .conn
+                .query_one(
+                    "SELECT 1 FROM meta m JOIN tasks t ON t.id = CAST(m.value AS INTEGER)
+                     WHERE m.key = ?1 AND t.completed IS NULL;",
+                    [&key],
+                    |_| Ok(()),
+                )
+                .optional()
+                .map_err(|err| format!("Could not query project reviews: {err}"))?;
+            if open_review.is_some() {
+                continue;
+            }
+
+            let task = Task::builder()
+                .title(format!("Review project {title}"))
+                .description(format!(
+                    "Nothing changed in the project for {weeks} weeks. Plan the next step, or complete or drop the project."
+                ))
+                .created(self.now)
+                .parent(id)
+                .due(self.now + TimeDelta::weeks(1))
+                .build();
+            let review = self.insert_task(&task)?;
+            self.conn
+                .execute(
+                    "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2);",
+                    (&key, review.to_string()),
+                )
+                .map_err(|err| format!("Could not note project review: {err}"))?;
+            log::info!("project {id}: generated review task {review}");
+            added += 1;
         }
+        Ok(added)
+    }
+
+    /// Tasks matching the filters of `GET /tasks` and `rem rpc`, ordered by id
+    ///
+    /// * `param`: value of the parameter of the given name
+    fn api_tasks<'a>(&self, param: impl Fn(&str) -> Option<&'a str>) -> Result<Vec<Task>, String> {
+        let mut tasks = match param("status").unwrap_or("open") {
+            "open" => self.open_tasks()?,
+            "completed" => self
+                .all_tasks()?
+                .into_iter()
+                .filter(|task| task.completed.is_some())
+                .collect(),
+            "all" => self.all_tasks()?,
+            other => {
+                return Err(format!(
+                    "Invalid status '{other}', expected open, completed or all"
+                ))
+            }
+        };
+        if let Some(tag) = param("tag") {
+            tasks.retain(|task| task.tags.iter().any(|t| t == tag));
+        }
+        if let Some(context) = param("context") {
+            tasks.retain(|task| task.context.as_deref() == Some(context));
+        }
+        if let Some(priority) = param("priority") {
+            let priority = priority.parse::<Priority>()?;
+            tasks.retain(|task| task.priority == Some(priority));
+        }
+        if let Some(before) = param("due_before") {
+            let before = chrono::DateTime::parse_from_rfc3339(before)
+                .map_err(|err| format!("Invalid due_before '{before}': {err}"))?;
+            tasks.retain(|task| task.due.is_some_and(|due| due < before));
+        }
+        tasks.sort_by_key(|task| task.id);
+        Ok(tasks)
+    }
+
+    /// Counts of tasks and tracked time exposed by `rem serve` at `/metrics`
+    ///
+    /// All are gauges, as undoing and deleting lower even the completed tasks and tracked time.
+    fn metrics(&self) -> Result<Vec<Metric>, String> {
+        let open = self.open_tasks()?;
+        let overdue = open
+            .iter()
+            .filter(|task| task.is_overdue(self.now, &self.config))
+            .count();
+        let (completed, work_seconds) = self
+            .conn
+            .query_one(
+                "SELECT (SELECT COUNT(*) FROM tasks WHERE completed IS NOT NULL AND NOT skipped),
+                        (SELECT coalesce(sum(coalesce(stopped, ?1) - started), 0) FROM sessions);",
+                [self.now.timestamp()],
+                |row| Ok((row.get::<_, u64>(0)?, row.get::<_, i64>(1)?)),
+            )
+            .map_err(|err| format!("Could not query metrics: {err}"))?;
+        Ok(vec![
+            Metric {
+                name: "rem_open_tasks",
+                help: "Tasks not completed yet",
+                counter: false,
+                value: open.len() as f64,
+            },
+            Metric {
+                name: "rem_overdue_tasks",
+                help: "Open tasks past their deadline",
+                counter: false,
+                value: overdue as f64,
+            },
+            Metric {
+                name: "rem_tasks_completed",
+                help: "Tasks completed, without skipped occurrences",
+                counter: false,
+                value: completed as f64,
+            },
+            Metric {
+                name: "rem_work_seconds",
+                help: "Time tracked in work sessions, including the running one",
+                counter: false,
+                value: work_seconds.max(0) as f64,
+            },
+        ])
+    }
+
+    /// Apply a command sent to `rem bot`, returning the reply
+    #[cfg(any(feature = "bot", test))]
+    fn bot_reply(&self, command: &rem::bot::Command) -> Result<String, String> {
+        use rem::bot::Command;
+        match command {
+            Command::Done(id) => {
+                let task = self.get_task(*id)?;
+                self.complete_task(*id)?;
+                Ok(format!("Completed ({id}) {}", task.title))
+            }
+            Command::Add(line) => {
+                let (added, warnings) = self.quick_add(line)?;
+                let mut reply = match added {
+                    Added::Task(id) => format!("Added ({id}) {}", self.get_task(id)?.title),
+                    Added::Reminder(id) => {
+                        format!("Added reminder ({id}) {}", self.get_reminder(id)?.title)
+                    }
+                };
+                for warning in warnings.iter() {
+                    reply.push_str(&format!("\nWarning: {warning}"));
+                }
+                Ok(reply)
+            }
+            Command::Agenda => {
+                let date = self.now.date_naive();
+                let (agenda, priorities, habits) = self.day_sheet(date, 3)?;
+                Ok(rem::bot::agenda(&Sheet {
+                    date,
+                    agenda: agenda.iter().collect(),
+                    priorities: priorities.iter().collect(),
+                    habits,
+                    iso: self.config.iso_dates,
+                }))
+            }
+            Command::Help => Ok(rem::bot::HELP.to_string()),
+        }
+    }
+
+    /// Day that `rem bot` last posted the agenda of
+    #[cfg(feature = "bot")]
+    fn agenda_posted(&self) -> Result<Option<NaiveDate>, String> {
+        self.conn
+            .query_one(
+                "SELECT value FROM meta WHERE key = 'bot_agenda_posted';",
+                [],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .map_err(|err| format!("Could not query posted agenda: {err}"))
+            .map(|value| value.and_then(|value| value.parse().ok()))
+    }
+
+    #[cfg(feature = "bot")]
+    fn note_agenda_posted(&self) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('bot_agenda_posted', ?1);",
+                [self.now.date_naive().to_string()],
+            )
+            .map(|_| ())
+            .map_err(|err| format!("Could not note posted agenda: {err}"))
+    }
+
+    /// Time of the last pass of [`App::reminders_to_tasks`] that succeeded
+    fn last_generated(&self) -> Result<Option<LocalDT>, String> {
+        self.conn
+            .query_one(
+                "SELECT value FROM meta WHERE key = 'last_generated';",
+                [],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .map_err(|err| format!("Could not query last generation: {err}"))?
+            .map(|value| {
+                value
+                    .parse()
+                    .map(import_datetime)
+                    .map_err(|err| format!("Invalid time of last generation '{value}': {err}"))
+            })
+            .transpose()
+    }
+
+    /// Close a task without it being done
+    fn skip_task(&self, id: u64) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE tasks SET completed = ?1, skipped = 1 WHERE id = ?2 AND completed IS NULL;",
+                (self.now.timestamp(), id),
+            )
+            .map_err(|err| format!("Could not skip task {id}: {err}"))?;
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn add_reminder(
+        &self,
+        title: String,
+        description: Option<String>,
+        first_due: LocalDT,
+        all_day: bool,
+        period: TimeDelta,
+        until: Option<LocalDT>,
+        priority: Option<Priority>,
+        context: Option<String>,
+        tags: &[String],
+        require_completion: bool,
+        catch_up: CatchUp,
+        skip_stale_after: Option<TimeDelta>,
+        at: Option<NaiveTime>,
+        season: Option<Season>,
+    ) -> Result<u64, String> {
+        self.insert_reminder(&Reminder {
+            id: 0,
+            title,
+            description,
+            created: self.now,
+            first_due,
+            all_day,
+            period,
+            at,
+            until,
+            priority,
+            context,
+            tags: tags.to_vec(),
+            require_completion,
+            checklist: Vec::new(),
+            lead_tasks: Vec::new(),
+            catch_up,
+            skip_stale_after,
+            skip_before: None,
+            parent: None,
+            season,
+        })
+    }
+
+    /// Store a reminder with its tags and checklist template, returning its ID
+    ///
+    /// The ID of `reminder` is ignored.
+    fn insert_reminder(&self, reminder: &Reminder) -> Result<u64, String> {
+        if reminder.title.trim().is_empty() {
+            return Err("Reminder title must not be empty.".to_string());
+        }
+
+        if let Some(parent) = reminder.parent {
+            self.get_reminder(parent)
+                .map_err(|err| format!("Invalid group: {err}"))?;
+        }
+
+        validate::reminder(
+            reminder.first_due,
+            reminder.period,
+            reminder.at,
+            reminder.until,
+            self.now,
+            &self.config,
+        )?;
+
+        self.conn.execute(
+            "INSERT INTO reminders (title, description, first_due, all_day, period, until, created, priority, context, require_completion, catch_up, skip_stale_after, at, skip_before, parent, season) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16);",
+            (
+                &reminder.title,
+                &reminder.description,
+                reminder.first_due.timestamp(),
+                reminder.all_day,
+                reminder.period.num_seconds(),
+                reminder.until.map(|x| x.timestamp()),
+                reminder.created.timestamp(),
+                reminder.priority.map(|p| p as i64),
+                &reminder.context,
+                reminder.require_completion,
+                reminder.catch_up as i64,
+                reminder.skip_stale_after.map(|s| s.num_seconds()),
+                reminder.at.map(|t| t.num_seconds_from_midnight()),
+                reminder.skip_before.map(|x| x.timestamp()),
+                reminder.parent,
+                reminder.season.map(|season| season.to_string()),
+            ),
+        ).map_err(|err| format!("Could not add reminder: {err}"))?;
+        let id = self.conn.last_insert_rowid() as u64;
+
+        for tag in reminder.tags.iter() {
+            let tag = validate_tag(tag)?;
+            self.conn
+                .execute(
+                    "INSERT OR IGNORE INTO reminder_tags (reminder_id, tag) VALUES (?1, ?2);",
+                    (id, tag),
+                )
+                .map_err(|err| format!("Could not tag reminder: {err}"))?;
+        }
+
+        for (position, text) in reminder.checklist.iter().enumerate() {
+            self.conn
+                .execute(
+                    "INSERT INTO reminder_checklist_items (reminder_id, position, text) VALUES (?1, ?2, ?3);",
+                    (id, position, text),
+                )
+                .map_err(|err| format!("Could not add checklist template item: {err}"))?;
+        }
+
+        for (position, lead) in reminder.lead_tasks.iter().enumerate() {
+            self.conn
+                .execute(
+                    "INSERT INTO reminder_lead_tasks (reminder_id, position, title, delta) VALUES (?1, ?2, ?3, ?4);",
+                    (id, position, &lead.title, lead.offset.num_seconds()),
+                )
+                .map_err(|err| format!("Could not add lead task: {err}"))?;
+        }
+
+        Ok(id)
+    }
+
+    fn get_task(&self, id: u64) -> Result<Task, String> {
+        self.conn
+            .query_one("SELECT * FROM tasks WHERE id = ?1;", [id], |row| {
+                Task::from_db_row(row, Some(&self.conn))
+            })
+            .map_err(|err| match err {
+                rusqlite::Error::QueryReturnedNoRows => format!("Task {id} not found."),
+                err => format!("Could not query task {id}: {err}"),
+            })
+    }
+
+    /// Create a copy of a task with its attributes and unchecked checklist, but without dates,
+    /// work bits and completion
+    ///
+    /// * `due`: due date of the copy and whether it is all-day
+    fn duplicate_task(&self, id: u64, due: Option<(LocalDT, bool)>) -> Result<u64, String> {
+        let task = self.get_task(id)?;
+        self.insert_task(&Task {
+            created: self.now,
+            start: None,
+            due_start: None,
+            due: due.map(|(due, _)| due),
+            due_all_day: due.is_some_and(|(_, all_day)| all_day),
+            generated_by: None,
+            parent: None,
+            completed: None,
+            skipped: false,
+            work_bits: Vec::new(),
+            checklist: task
+                .checklist
+                .into_iter()
+                .map(|(text, _)| (text, false))
+                .collect(),
+            ..task
+        })
+    }
+
+    /// Ids of the pinned tasks in their manual order
+    fn pinned(&self) -> Result<Vec<u64>, String> {
+        self.conn
+            .prepare("SELECT id FROM tasks WHERE rank IS NOT NULL ORDER BY rank, id;")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| row.get(0))?
+                    .collect::<Result<Vec<u64>, _>>()
+            })
+            .map_err(|err| format!("Could not query pinned tasks: {err}"))
+    }
+
+    /// Pin a task in front of the pinned task `before`, or behind all pinned tasks
+    ///
+    /// A task to pin in front of that is not pinned yet is pinned at the end first.
+    fn pin(&self, id: u64, before: Option<u64>) -> Result<(), String> {
+        self.get_task(id)?;
+        let mut pinned = self.pinned()?;
+        pinned.retain(|other| *other != id);
+        let idx = match before {
+            Some(other) if other == id => {
+                return Err(format!("Cannot move task {id} in front of itself"));
+            }
+            Some(other) => match pinned.iter().position(|p| *p == other) {
+                Some(idx) => idx,
+                None => {
+                    self.get_task(other)?;
+                    pinned.push(other);
+                    pinned.len() - 1
+                }
+            },
+            None => pinned.len(),
+        };
+        pinned.insert(idx, id);
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| format!("Could not start transaction: {err}"))?;
+        for (rank, id) in pinned.iter().enumerate() {
+            tx.execute(
+                "UPDATE tasks SET rank = ?1 WHERE id = ?2 AND rank IS NOT ?1;",
+                (rank, id),
+            )
+            .map_err(|err| format!("Could not pin task {id}: {err}"))?;
+        }
+        tx.commit()
+            .map_err(|err| format!("Could not commit transaction: {err}"))
+    }
+
+    fn unpin(&self, id: u64) -> Result<(), String> {
+        let res = self
+            .conn
+            .execute(
+                "UPDATE tasks SET rank = NULL WHERE id = ?1 AND rank IS NOT NULL;",
+                [id],
+            )
+            .map_err(|err| format!("Could not unpin task {id}: {err}"))?;
+        if res == 0 {
+            Err(format!("Task {id} is not pinned."))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn tag_tasks(&self, tag: &str, ids: &[u64]) -> Result<(), String> {
+        let tag = validate_tag(tag)?;
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| format!("Could not start transaction: {err}"))?;
+        for id in ids.iter() {
+            self.get_task(*id)?;
+            tx.execute(
+                "INSERT OR IGNORE INTO tags (task_id, tag) VALUES (?1, ?2);",
+                (id, tag),
+            )
+            .map_err(|err| format!("Could not tag task {id}: {err}"))?;
+        }
+        tx.commit()
+            .map_err(|err| format!("Could not commit tags: {err}"))
+    }
+
+    fn untag_tasks(&self, tag: &str, ids: &[u64]) -> Result<(), String> {
+        let tag = normalize_tag(tag);
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| format!("Could not start transaction: {err}"))?;
+        for id in ids.iter() {
+            let res = tx
+                .execute(
+                    "DELETE FROM tags WHERE task_id = ?1 AND tag = ?2;",
+                    (id, tag),
+                )
+                .map_err(|err| format!("Could not untag task {id}: {err}"))?;
+            if res == 0 {
+                return Err(format!("Task {id} is not tagged #{tag}."));
+            }
+        }
+        tx.commit()
+            .map_err(|err| format!("Could not commit tags: {err}"))
+    }
+
+    /// All tags with the number of open and total tasks and reminders using them
+    fn tag_usage(&self) -> Result<Vec<TagUsage>, String> {
+        self.conn
+            .prepare(
+                "SELECT tag, SUM(open), SUM(total), SUM(reminders) FROM (
+                   SELECT tag, t.completed IS NULL AS open, 1 AS total, 0 AS reminders
+                     FROM tags JOIN tasks t ON t.id = tags.task_id
+                   UNION ALL
+                   SELECT tag, 0, 0, 1 FROM reminder_tags
+                 ) GROUP BY tag ORDER BY tag;",
+            )
+            .map_err(|err| format!("Could not query tags: {err}"))?
+            .query([])
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(|row| {
+                Ok(TagUsage {
+                    tag: row.get(0)?,
+                    open: row.get(1)?,
+                    total: row.get(2)?,
+                    reminders: row.get(3)?,
+                })
+            })
+            .collect()
+            .map_err(|err| format!("Error querying database: {err}"))
+    }
+
+    /// Remove tags that are only on completed tasks, dropping them from the history as well
+    ///
+    /// Returns the removed tags.
+    fn prune_tags(&self) -> Result<Vec<String>, String> {
+        let unused = self
+            .tag_usage()?
+            .into_iter()
+            .filter(|u| u.open == 0 && u.reminders == 0)
+            .map(|u| u.tag)
+            .collect::<Vec<_>>();
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| format!("Could not start transaction: {err}"))?;
+        for tag in unused.iter() {
+            tx.execute("DELETE FROM tags WHERE tag = ?1;", [tag])
+                .map_err(|err| format!("Could not remove tag #{tag}: {err}"))?;
+        }
+        tx.commit()
+            .map_err(|err| format!("Could not commit tags: {err}"))?;
+
+        Ok(unused)
+    }
+
+    /// Rename a tag on tasks and reminders, merging it into `to` where both are present
+    ///
+    /// Returns the number of renamed tags.
+    fn retag(&self, from: &str, to: &str) -> Result<usize, String> {
+        let from = normalize_tag(from);
+        let to = validate_tag(to)?;
+        if from == to {
+            return Err(format!("#{from} and #{to} are the same tag."));
+        }
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| format!("Could not start transaction: {err}"))?;
+
+        let mut count = 0;
+        for table in ["tags", "reminder_tags"] {
+            count += tx
+                .execute(
+                    &format!("UPDATE OR IGNORE {table} SET tag = ?2 WHERE tag = ?1;"),
+                    (from, to),
+                )
+                .map_err(|err| format!("Could not rename tag: {err}"))?;
+            // rows left over already carried the new tag
+            count += tx
+                .execute(&format!("DELETE FROM {table} WHERE tag = ?1;"), [from])
+                .map_err(|err| format!("Could not rename tag: {err}"))?;
+        }
+
+        if count == 0 {
+            return Err(format!("No task or reminder is tagged #{from}."));
+        }
+
+        tx.commit()
+            .map_err(|err| format!("Could not commit tags: {err}"))?;
+        Ok(count)
+    }
+
+    /// Timezone the database was first used in, if it could be determined
+    fn stored_timezone(&self) -> Result<Option<String>, String> {
+        self.conn
+            .query_one(
+                "SELECT value FROM meta WHERE key = 'timezone';",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|err| format!("Could not query timezone: {err}"))
+    }
+
+    /// Timezone the database was started in if it differs from `current` and that was not noted
+    /// for `current` before, so the note is shown once per timezone
+    fn timezone_to_note(&self, current: &str) -> Result<Option<String>, String> {
+        let Some(stored) = self.stored_timezone()? else {
+            return Ok(None);
+        };
+        let noted = self
+            .conn
+            .query_one(
+                "SELECT value FROM meta WHERE key = 'timezone_noted';",
+                [],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .map_err(|err| format!("Could not query noted timezone: {err}"))?;
+        if stored == current || noted.as_deref() == Some(current) {
+            return Ok(None);
+        }
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('timezone_noted', ?1);",
+                [current],
+            )
+            .map_err(|err| format!("Could not note timezone: {err}"))?;
+        Ok(Some(stored))
+    }
+
+    /// Latest creation time of a task or reminder if it lies more than [`CLOCK_TOLERANCE`] after
+    /// now, which happens when the clock of the machine was set back or an older snapshot of it
+    /// restored
+    ///
+    /// Generating reminders then could duplicate or miss occurrences.
+    fn clock_rolled_back(&self) -> Result<Option<LocalDT>, String> {
+        self.conn
+            .query_one(
+                "SELECT max(created) FROM (SELECT created FROM tasks UNION ALL SELECT created FROM reminders);",
+                [],
+                |row| row.get::<_, Option<i64>>(0),
+            )
+            .map_err(|err| format!("Could not query creation times: {err}"))
+            .map(|latest| {
+                latest
+                    .map(import_datetime)
+                    .filter(|latest| *latest > self.now + CLOCK_TOLERANCE)
+            })
+    }
+
+    /// Load all tasks with their details
+    fn all_tasks(&self) -> Result<Vec<Task>, String> {
+        self.conn
+            .prepare("SELECT * FROM tasks;")
+            .map_err(|err| format!("Could not query tasks: {err}"))?
+            .query([])
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(|row| Task::from_db_row(row, Some(&self.conn)))
+            .collect()
+            .map_err(|err| format!("Error querying database: {err}"))
+    }
+
+    /// Load all open tasks with their details
+    fn open_tasks(&self) -> Result<Vec<Task>, String> {
+        self.conn
+            .prepare("SELECT * FROM tasks WHERE completed IS NULL;")
+            .map_err(|err| format!("Could not query tasks: {err}"))?
+            .query([])
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(|row| Task::from_db_row(row, Some(&self.conn)))
+            .collect()
+            .map_err(|err| format!("Error querying database: {err}"))
+    }
+
+    /// Id of the task or reminder stored with the key of an external system
+    ///
+    /// * `table`: `tasks` or `reminders`
+    fn find_external(&self, table: &str, key: &str) -> Result<Option<u64>, String> {
+        self.conn
+            .query_row(
+                &format!("SELECT id FROM {table} WHERE external_id = ?1;"),
+                [key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|err| format!("Could not query external id: {err}"))
+    }
+
+    /// Store the key of an external system with a task or reminder
+    ///
+    /// * `table`: `tasks` or `reminders`
+    fn set_external_id(&self, table: &str, id: u64, key: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                &format!("UPDATE {table} SET external_id = ?1 WHERE id = ?2;"),
+                (key, id),
+            )
+            .map_err(|err| format!("Could not store external id: {err}"))?;
+        Ok(())
+    }
+
+    /// Replace the attributes of a stored task that are given on creation, keeping its dates of
+    /// creation and completion, checklist and work bits
+    fn update_task(&self, task: &Task) -> Result<(), String> {
+        if task.title.trim().is_empty() {
+            return Err("Task title must not be empty.".to_string());
+        }
+        if let Some(parent) = task.parent {
+            self.get_task(parent)
+                .map_err(|err| format!("Invalid parent task: {err}"))?;
+        }
+
+        self.atomically(|| {
+        self.conn
+            .execute(
+            "UPDATE tasks SET title = ?1, description = ?2, start = ?3, due_start = ?4, due = ?5, due_all_day = ?6, priority = ?7, context = ?8, estimate = ?9, parent = ?10 WHERE id = ?11;",
+            (
+                &task.title,
+                &task.description,
+                task.start.map(|t| t.timestamp()),
+                task.due_start.map(|t| t.timestamp()),
+                task.due.map(|t| t.timestamp()),
+                task.due.is_some() && task.due_all_day,
+                task.priority.map(|p| p as i64),
+                &task.context,
+                task.estimate.map(|e| e.num_seconds()),
+                task.parent,
+                task.id,
+            ),
+        )
+        .map_err(|err| format!("Could not update task {}: {err}", task.id))?;
+        self.conn
+            .execute("DELETE FROM tags WHERE task_id = ?1;", [task.id])
+            .map_err(|err| format!("Could not update tags: {err}"))?;
+        for tag in task.tags.iter() {
+            self.conn
+                .execute(
+                    "INSERT OR IGNORE INTO tags (task_id, tag) VALUES (?1, ?2);",
+                    (task.id, validate_tag(tag)?),
+                )
+                .map_err(|err| format!("Could not tag task: {err}"))?;
+        }
+            Ok(())
+        })
+    }
+
+    /// Replace the attributes of a stored reminder that are given on creation, keeping the tasks
+    /// it generated and its checklist template
+    fn update_reminder(&self, reminder: &Reminder) -> Result<(), String> {
+        if reminder.title.trim().is_empty() {
+            return Err("Reminder title must not be empty.".to_string());
+        }
+        validate::reminder(
+            reminder.first_due,
+            reminder.period,
+            reminder.at,
+            reminder.until,
+            self.now,
+            &self.config,
+        )?;
+
+        self.atomically(|| {
+        self.conn.execute(
+            "UPDATE reminders SET title = ?1, description = ?2, first_due = ?3, all_day = ?4, period = ?5, until = ?6, priority = ?7, context = ?8, require_completion = ?9, catch_up = ?10, skip_stale_after = ?11, at = ?12, season = ?13 WHERE id = ?14;",
+            (
+                &reminder.title,
+                &reminder.description,
+                reminder.first_due.timestamp(),
+                reminder.all_day,
+                reminder.period.num_seconds(),
+                reminder.until.map(|x| x.timestamp()),
+                reminder.priority.map(|p| p as i64),
+                &reminder.context,
+                reminder.require_completion,
+                reminder.catch_up as i64,
+                reminder.skip_stale_after.map(|s| s.num_seconds()),
+                reminder.at.map(|t| t.num_seconds_from_midnight()),
+                reminder.season.map(|season| season.to_string()),
+                reminder.id,
+            ),
+        )
+        .map_err(|err| format!("Could not update reminder {}: {err}", reminder.id))?;
+        self.conn.execute(
+            "DELETE FROM reminder_tags WHERE reminder_id = ?1;",
+            [reminder.id],
+        )
+        .map_err(|err| format!("Could not update tags: {err}"))?;
+        for tag in reminder.tags.iter() {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO reminder_tags (reminder_id, tag) VALUES (?1, ?2);",
+                (reminder.id, validate_tag(tag)?),
+            )
+            .map_err(|err| format!("Could not tag reminder: {err}"))?;
+        }
+            Ok(())
+        })
+    }
+
+    /// Store imported tasks, committing every `chunk_size` of them in a transaction
+    ///
+    /// Tasks with an external id that is already stored update the stored task, open tasks with
+    /// the title of an open task are skipped. Unless `continue_on_error` is set, the first task
+    /// that cannot be read or stored rolls back its chunk and stops the import, otherwise only
+    /// its own changes are rolled back.
+    ///
+    /// * `progress`: called after each task
+    fn import(
+        &self,
+        entries: Vec<Record>,
+        chunk_size: usize,
+        continue_on_error: bool,
+        mut progress: impl FnMut(),
+    ) -> Result<Summary, String> {
+        let mut open = self
+            .open_tasks()?
+            .iter()
+            .map(|t| t.title.trim().to_lowercase())
+            .collect::<HashSet<_>>();
+        let mut summary = Summary::default();
+        let mut entries = entries.into_iter().peekable();
+        while entries.peek().is_some() {
+            let mut tx = self
+                .conn
+                .unchecked_transaction()
+                .map_err(|err| format!("Could not start transaction: {err}"))?;
+            let mut chunk = Summary::default();
+            for entry in entries.by_ref().take(chunk_size) {
+                // dropping the savepoint rolls back what a failing task stored
+                let savepoint = tx
+                    .savepoint()
+                    .map_err(|err| format!("Could not start savepoint: {err}"))?;
+                let outcome = match entry {
+                    Ok(Some(entry)) => {
+                        let line = entry.line;
+                        self.import_entry(entry, &mut open)
+                            .map_err(|err| (line, err))
+                    }
+                    Ok(None) => Ok(Outcome::Skipped),
+                    Err(failure) => Err(failure),
+                };
+                match outcome {
+                    Ok(outcome) => {
+                        savepoint
+                            .commit()
+                            .map_err(|err| format!("Could not release savepoint: {err}"))?;
+                        chunk.add(outcome);
+                    }
+                    Err(failure) if continue_on_error => chunk.failed.push(failure),
+                    Err((line, err)) => {
+                        return Err(format!(
+                            "task {line}: {err} (rolled back its chunk, kept the earlier ones: {summary})"
+                        ));
+                    }
+                }
+                progress();
+            }
+            tx.commit()
+                .map_err(|err| format!("Could not commit transaction: {err}"))?;
+            summary.merge(chunk);
+        }
+        Ok(summary)
+    }
+
+    /// Store an imported task
+    ///
+    /// * `open`: lowercase titles of the open tasks, extended by the created ones
+    fn import_entry(&self, entry: Entry, open: &mut HashSet<String>) -> Result<Outcome, String> {
+        let mut task = entry.task;
+        if let Some(id) = entry
+            .external_id
+            .as_deref()
+            .map(|key| self.find_external("tasks", key))
+            .transpose()?
+            .flatten()
+        {
+            task.id = id;
+            self.update_task(&task)?;
+            if let Some(completed) = task.completed {
+                self.conn
+                    .execute(
+                        "UPDATE tasks SET completed = ?1 WHERE id = ?2 AND completed IS NULL;",
+                        (completed.timestamp(), id),
+                    )
+                    .map_err(|err| format!("Could not complete task {id}: {err}"))?;
+            }
+            return Ok(Outcome::Updated);
+        }
+
+        let title = task.title.trim().to_lowercase();
+        if task.completed.is_none() && open.contains(&title) {
+            return Ok(Outcome::Skipped);
+        }
+        let id = self.insert_task(&task)?;
+        if let Some(key) = entry.external_id {
+            self.set_external_id("tasks", id, &key)?;
+        }
+        if task.completed.is_none() {
+            open.insert(title);
+        }
+        Ok(Outcome::Created)
+    }
+
+    /// Id of an open task with the same title, ignoring case and surrounding whitespace
+    fn open_duplicate(&self, title: &str) -> Result<Option<u64>, String> {
+        Ok(self
+            .open_tasks()?
+            .iter()
+            .filter(|t| t.title.trim().to_lowercase() == title.trim().to_lowercase())
+            .map(|t| t.id)
+            .min())
+    }
+
+    /// Ids of tasks with open subtasks
+    fn blocked_tasks(&self) -> Result<HashSet<u64>, String> {
+        self.conn
+            .prepare(
+                "SELECT DISTINCT parent FROM tasks WHERE parent IS NOT NULL AND completed IS NULL;",
+            )
+            .map_err(|err| format!("Could not query tasks: {err}"))?
+            .query([])
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(|row| row.get::<_, u64>(0))
+            .collect()
+            .map_err(|err| format!("Error querying database: {err}"))
+    }
+
+    /// Open tasks that can be started now, best first
+    fn recommend(
+        &self,
+        available: Option<TimeDelta>,
+        context: Option<&str>,
+    ) -> Result<Vec<(Task, Score)>, String> {
+        let blocked = self.blocked_tasks()?;
+        let situation = Situation {
+            now: self.now,
+            config: &self.config,
+            available,
+            context,
+        };
+
+        let mut scored = self
+            .open_tasks()?
+            .into_iter()
+            .filter(|task| !task.starts_later(self.now))
+            .map(|task| {
+                let s = score(
+                    &task,
+                    blocked.contains(&task.id),
+                    &situation,
+                    &self.config.next,
+                );
+                (task, s)
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| b.1.total.total_cmp(&a.1.total));
+        Ok(scored)
+    }
+
+    /// Create subtasks of a task, inheriting its due date and attributes
+    ///
+    /// * `distribute`: move the estimate of the task to the subtasks in equal parts
+    fn split_task(&self, id: u64, titles: &[String], distribute: bool) -> Result<Vec<u64>, String> {
+        if titles.is_empty() {
+            return Err("Need at least one subtask.".to_string());
+        }
+
+        let task = self.get_task(id)?;
+        let estimate = match task.estimate {
+            Some(estimate) if distribute => Some(estimate / titles.len() as i32),
+            _ => None,
+        };
+
+        self.atomically(|| {
+            let ids = titles
+                .iter()
+                .map(|title| {
+                    self.add_task(
+                        title.clone(),
+                        None,
+                        None,
+                        None,
+                        task.due,
+                        task.due_all_day,
+                        task.priority,
+                        task.context.clone(),
+                        estimate,
+                        &task.tags,
+                        None,
+                        Some(id),
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if estimate.is_some() {
+                self.conn
+                    .execute("UPDATE tasks SET estimate = NULL WHERE id = ?1;", [id])
+                    .map_err(|err| format!("Could not update estimate of task {id}: {err}"))?;
+            }
+            Ok(ids)
+        })
+    }
+
+    /// Replace the checklist template of a reminder. Tasks it generates from now on get a copy.
+    fn set_review_template(&self, reminder_id: u64, items: &[String]) -> Result<(), String> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| format!("Could not start transaction: {err}"))?;
+
+        let exists = tx
+            .query_one(
+                "SELECT COUNT(*) FROM reminders WHERE id = ?1",
+                [reminder_id],
+                |row| row.get::<_, u64>(0),
+            )
+            .map_err(|err| format!("Could not query reminders: {err}"))?;
+        if exists == 0 {
+            return Err(format!("Reminder {reminder_id} not found."));
+        }
+
+        tx.execute(
+            "DELETE FROM reminder_checklist_items WHERE reminder_id = ?1",
+            [reminder_id],
+        )
+        .map_err(|err| format!("Could not clear checklist template: {err}"))?;
+
+        for (position, text) in items.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO reminder_checklist_items (reminder_id, position, text) VALUES (?1, ?2, ?3);",
+                (reminder_id, position, text),
+            )
+            .map_err(|err| format!("Could not add checklist template item: {err}"))?;
+        }
+
+        tx.commit()
+            .map_err(|err| format!("Could not commit checklist template: {err}"))
+    }
+
+    /// Replace the lead tasks of a reminder. Occurrences generated from now on get them.
+    fn set_lead_tasks(&self, reminder_id: u64, leads: &[LeadTask]) -> Result<(), String> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| format!("Could not start transaction: {err}"))?;
+
+        let exists = tx
+            .query_one(
+                "SELECT COUNT(*) FROM reminders WHERE id = ?1",
+                [reminder_id],
+                |row| row.get::<_, u64>(0),
+            )
+            .map_err(|err| format!("Could not query reminders: {err}"))?;
+        if exists == 0 {
+            return Err(format!("Reminder {reminder_id} not found."));
+        }
+
+        tx.execute(
+            "DELETE FROM reminder_lead_tasks WHERE reminder_id = ?1",
+            [reminder_id],
+        )
+        .map_err(|err| format!("Could not clear lead tasks: {err}"))?;
+
+        for (position, lead) in leads.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO reminder_lead_tasks (reminder_id, position, title, delta) VALUES (?1, ?2, ?3, ?4);",
+                (reminder_id, position, &lead.title, lead.offset.num_seconds()),
+            )
+            .map_err(|err| format!("Could not add lead task: {err}"))?;
+        }
+
+        tx.commit()
+            .map_err(|err| format!("Could not commit lead tasks: {err}"))
+    }
+
+    /// Merge the database at `path` with this one, returning the changes here and there
+    fn sync_with(&self, path: &std::path::Path) -> Result<(Changes, Changes), String> {
+        let flags = rusqlite::OpenFlags::default() - rusqlite::OpenFlags::SQLITE_OPEN_CREATE;
+        let peer = rusqlite::Connection::open_with_flags(path, flags)
+            .map_err(|err| format!("Could not open {}: {err}", path.display()))?;
+        // brings the peer to the layout of this version
+        drop(App::try_init(peer, self.config.clone())?);
+
+        self.conn
+            .execute("ATTACH DATABASE ?1 AS peer;", [path.to_string_lossy()])
+            .map_err(|err| format!("Could not attach {}: {err}", path.display()))?;
+        let merged = sync::merge(&self.conn, "peer");
+        self.conn
+            .execute("DETACH DATABASE peer;", [])
+            .map_err(|err| format!("Could not detach {}: {err}", path.display()))?;
+        merged
+    }
+
+    /// Toggle the checklist item with 1-based number `item` of a task
+    fn toggle_checklist_item(&self, task_id: u64, item: usize) -> Result<(), String> {
+        // items are numbered from 1
+        if item == 0 {
+            return Err(format!("Task {task_id} has no checklist item 0."));
+        }
+        let res = self
+            .conn
+            .execute(
+                "UPDATE checklist_items SET done = NOT done WHERE id = (
+                   SELECT id FROM checklist_items WHERE task_id = ?1 ORDER BY position LIMIT 1 OFFSET ?2
+                 );",
+                (task_id, item - 1),
+            )
+            .map_err(|err| format!("Could not update checklist item: {err}"))?;
+
+        if res == 0 {
+            Err(format!("Task {task_id} has no checklist item {item}."))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Add a task or reminder from a quick-add line, returning warnings about it
+    fn quick_add(&self, repr: &str) -> Result<(Added, Vec<String>), String> {
+        let q = quickadd::parse(repr, self.now.date_naive(), self.config.lenient_dates)?;
+        let due = q
+            .due
+            .map(|due| self.apply_default_due_time(start_of_day(due), true));
+
+        let added = match (q.every, due) {
+            (Some(period), Some((first_due, all_day))) => {
+                let warnings =
+                    validate::reminder(first_due, period, None, None, self.now, &self.config)?;
+                let id = self.add_reminder(
+                    q.title,
+                    None,
+                    first_due,
+                    all_day,
+                    period,
+                    None,
+                    q.priority,
+                    q.context,
+                    &q.tags,
+                    false,
+                    CatchUp::All,
+                    None,
+                    None,
+                    None,
+                )?;
+                (Added::Reminder(id), warnings)
+            }
+            (None, _) => {
+                let (id, warnings) = self.add_quick_task(q)?;
+                (Added::Task(id), warnings)
+            }
+            (Some(_), None) => unreachable!("quick-add grammar requires a due date for recurrence"),
+        };
+
+        Ok(added)
+    }
+
+    /// Create a task from a parsed quick-add line, ignoring its recurrence
+    ///
+    /// Returns the id of the task and warnings about it.
+    fn add_quick_task(&self, q: quickadd::QuickAdd) -> Result<(u64, Vec<String>), String> {
+        let due = q
+            .due
+            .map(|due| self.apply_default_due_time(start_of_day(due), true));
+        let warnings = validate::task(
+            None,
+            due.map(|(due, _)| due),
+            due.is_some_and(|(_, all_day)| all_day),
+            self.now,
+            &self.config,
+        )?;
+        let id = self.add_task(
+            q.title,
+            None,
+            None,
+            None,
+            due.map(|(due, _)| due),
+            due.is_some_and(|(_, all_day)| all_day),
+            q.priority,
+            q.context,
+            None,
+            &q.tags,
+            None,
+            None,
+        )?;
+        Ok((id, warnings))
+    }
+
+    fn get_reminder(&self, id: u64) -> Result<Reminder, String> {
+        self.conn
+            .query_row("SELECT * FROM reminders WHERE id = ?1;", [id], |row| {
+                Reminder::from_db_row(row, Some(&self.conn))
+            })
+            .optional()
+            .map_err(|err| format!("Could not query reminder: {err}"))?
+            .ok_or(format!("Reminder {id} not found."))
+    }
+
+    /// Occurrences of a reminder moved by `rem shift-occurrence`, by their scheduled due date
+    fn occurrence_shifts(&self, reminder_id: u64) -> Result<HashMap<LocalDT, LocalDT>, String> {
+        self.conn
+            .prepare("SELECT due, moved_to FROM occurrence_shifts WHERE reminder_id = ?1;")
+            .map_err(|err| format!("Could not query moved occurrences: {err}"))?
+            .query([reminder_id])
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(|row| Ok((import_datetime(row.get(0)?), import_datetime(row.get(1)?))))
+            .collect()
+            .map_err(|err| format!("Error querying database: {err}"))
+    }
+
+    /// Move the upcoming occurrence of a reminder due at `date` to `to`, along with its task if
+    /// it was generated already
+    ///
+    /// Dates without a time match any occurrence on that day and keep the time of day of the
+    /// occurrence when moving it. Returns the scheduled and the new due date.
+    fn shift_occurrence(
+        &self,
+        reminder_id: u64,
+        (date, date_all_day): (LocalDT, bool),
+        (to, to_all_day): (LocalDT, bool),
+    ) -> Result<(LocalDT, LocalDT), String> {
+        let reminder = self.get_reminder(reminder_id)?;
+        let shifts = self.occurrence_shifts(reminder_id)?;
+        let matches = |due: LocalDT| {
+            if date_all_day {
+                due.date_naive() == date.date_naive()
+            } else {
+                due == date
+            }
+        };
+
+        // occurrences moved already are found by their new date
+        let scheduled = match shifts.iter().find(|(_, moved_to)| matches(**moved_to)) {
+            Some((scheduled, _)) => *scheduled,
+            None => reminder
+                .occurrences(start_of_day(date.date_naive())..)
+                .map(|o| o.due)
+                .take_while(|due| due.date_naive() <= date.date_naive())
+                .find(|due| matches(*due) && !shifts.contains_key(due))
+                .ok_or(format!(
+                    "Reminder {reminder_id} has no occurrence due {}.",
+                    date.format(if date_all_day {
+                        self.config.date_fmt()
+                    } else {
+                        self.config.datetime_fmt()
+                    })
+                ))?,
+        };
+        let current = shifts.get(&scheduled).copied().unwrap_or(scheduled);
+        if current < self.now {
+            return Err(format!(
+                "The occurrence due {} is in the past.",
+                current.format(self.config.datetime_fmt())
+            ));
+        }
+
+        let to = if to_all_day && !reminder.all_day {
+            to.date_naive()
+                .and_time(current.time())
+                .and_local_timezone(Local)
+                .earliest()
+                .unwrap_or(to)
+        } else {
+            to
+        };
+        if to < self.now {
+            return Err(format!(
+                "Cannot move the occurrence to {}, which is in the past.",
+                to.format(self.config.datetime_fmt())
+            ));
+        }
+
+        self.atomically(|| {
+            self.conn
+                .execute(
+                    "INSERT OR REPLACE INTO occurrence_shifts (reminder_id, due, moved_to) VALUES (?1, ?2, ?3);",
+                    (reminder_id, scheduled.timestamp(), to.timestamp()),
+                )
+                .map_err(|err| format!("Could not move occurrence: {err}"))?;
+            // tasks completed early move too, or the occurrence would be generated again
+            self.conn
+                .execute(
+                    "UPDATE tasks SET due = ?1 WHERE generated_by = ?2 AND due = ?3;",
+                    (to.timestamp(), reminder_id, current.timestamp()),
+                )
+                .map_err(|err| format!("Could not move generated task: {err}"))?;
+            Ok((scheduled, to))
+        })
+    }
+
+    /// Group a reminder under another one, which passes on its tags
+    fn group_reminder(&self, id: u64, parent: u64) -> Result<(), String> {
+        // following the groups from `parent` must not lead back to `id`
+        let mut current = Some(parent);
+        while let Some(other) = current {
+            if other == id {
+                return Err(format!(
+                    "Reminder {parent} is in the group of {id} already."
+                ));
+            }
+            current = self.get_reminder(other)?.parent;
+        }
+
+        self.conn
+            .execute(
+                "UPDATE reminders SET parent = ?1 WHERE id = ?2;",
+                (parent, id),
+            )
+            .map_err(|err| format!("Could not group reminder {id}: {err}"))?;
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO reminder_tags (reminder_id, tag) SELECT ?1, tag FROM reminder_tags WHERE reminder_id = ?2;",
+                (id, parent),
+            )
+            .map_err(|err| format!("Could not tag reminder {id}: {err}"))?;
+        Ok(())
+    }
+
+    /// Ids of a reminder and all reminders grouped under it
+    fn reminder_group(&self, id: u64) -> Result<Vec<u64>, String> {
+        let mut ids = vec![id];
+        let mut idx = 0;
+        while let Some(&parent) = ids.get(idx) {
+            let members = self
+                .conn
+                .prepare("SELECT id FROM reminders WHERE parent = ?1 ORDER BY id;")
+                .map_err(|err| format!("Could not query reminders: {err}"))?
+                .query([parent])
+                .map_err(|err| format!("Could not query database: {err}"))?
+                .map(|row| row.get(0))
+                .collect::<Vec<u64>>()
+                .map_err(|err| format!("Error querying database: {err}"))?;
+            ids.extend(members);
+            idx += 1;
+        }
+        Ok(ids)
+    }
+
+    fn save_template(&self, name: &str, template: &Template, force: bool) -> Result<(), String> {
+        if name.trim().is_empty() {
+            return Err("Template names cannot be empty.".to_string());
+        }
+        let definition = template.to_toml()?;
+        let query = if force {
+            "INSERT OR REPLACE INTO templates (name, definition) VALUES (?1, ?2);"
+        } else {
+            "INSERT INTO templates (name, definition) VALUES (?1, ?2);"
+        };
+        self.conn
+            .execute(query, (name, definition))
+            .map_err(|err| match err.sqlite_error_code() {
+                Some(rusqlite::ErrorCode::ConstraintViolation) => {
+                    format!("Template '{name}' exists. Pass --force to replace it.")
+                }
+                _ => format!("Could not save template: {err}"),
+            })?;
+        Ok(())
+    }
+
+    fn get_template(&self, name: &str) -> Result<Template, String> {
+        let definition: String = self
+            .conn
+            .query_row(
+                "SELECT definition FROM templates WHERE name = ?1;",
+                [name],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|err| format!("Could not query template: {err}"))?
+            .ok_or(format!("Template '{name}' not found."))?;
+        Template::from_toml(&definition).map_err(|err| format!("Invalid template '{name}': {err}"))
+    }
+
+    fn template_names(&self) -> Result<Vec<String>, String> {
+        self.conn
+            .prepare("SELECT name FROM templates ORDER BY name;")
+            .map_err(|err| format!("Could not query templates: {err}"))?
+            .query([])
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(|row| row.get(0))
+            .collect()
+            .map_err(|err| format!("Error querying database: {err}"))
+    }
+
+    /// Create the tasks and reminders of a template with dates relative to `at`, returning their
+    /// ids
+    fn apply_template(
+        &self,
+        template: &Template,
+        at: LocalDT,
+    ) -> Result<(Vec<u64>, Vec<u64>), String> {
+        let (tasks, reminders) = template.instantiate(at);
+        self.atomically(|| {
+            let mut ids: Vec<u64> = Vec::new();
+            for (task, parent) in tasks.into_iter().zip(template.parents()) {
+                let parent = parent.map(|idx| ids[idx]).or(task.parent);
+                ids.push(self.insert_task(&Task { parent, ..task })?);
+            }
+            let reminders = reminders
+                .iter()
+                .map(|reminder| self.insert_reminder(reminder))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((ids, reminders))
+        })
+    }
+
+    /// Open tasks due on `date` and up to `count` recommended tasks besides them, and the titles
+    /// of active reminders recurring at least daily with an occurrence on `date`
+    ///
+    /// Tasks generated by those reminders are left to the habits.
+    fn day_sheet(&self, date: NaiveDate, count: usize) -> Result<DaySheet, String> {
+        let day = start_of_day(date)..start_of_day(date + TimeDelta::days(1));
+        let habits = self
+            .all_reminders()?
+            .into_iter()
+            .filter(|reminder| {
+                reminder.is_active(self.now)
+                    && reminder.period <= TimeDelta::days(1)
+                    && reminder.occurrences(day.clone()).next().is_some()
+            })
+            .collect::<Vec<_>>();
+        let is_habit = |task: &Task| {
+            task.generated_by
+                .is_some_and(|id| habits.iter().any(|reminder| reminder.id == id))
+        };
+
+        let mut agenda = self
+            .open_tasks()?
+            .into_iter()
+            .filter(|task| task.due.is_some_and(|due| day.contains(&due)) && !is_habit(task))
+            .collect::<Vec<_>>();
+        agenda.sort_by_key(|task| (!task.due_all_day, task.due_start.or(task.due)));
+        let priorities = self
+            .recommend(None, None)?
+            .into_iter()
+            .map(|(task, _)| task)
+            .filter(|task| !is_habit(task) && agenda.iter().all(|other| other.id != task.id))
+            .take(count)
+            .collect();
+        Ok((
+            agenda,
+            priorities,
+            habits.into_iter().map(|reminder| reminder.title).collect(),
+        ))
+    }
+
+    fn all_reminders(&self) -> Result<Vec<Reminder>, String> {
+        self.conn
+            .prepare("SELECT * FROM reminders;")
+            .map_err(|err| format!("could not query tasks: {err}"))?
+            .query([])
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(|row| Reminder::from_db_row(row, Some(&self.conn)))
+            .collect::<Vec<_>>()
+            .map_err(|err| format!("Error querying database: {err}"))
+    }
+
+    /// How the occurrences of each reminder that generated tasks were completed
+    fn reminder_summaries(&self) -> Result<HashMap<u64, ReminderSummary>, String> {
+        let tasks = self
+            .conn
+            .prepare(
+                "SELECT tasks.* FROM tasks JOIN reminders ON tasks.generated_by = reminders.id ORDER BY tasks.due;",
+            )
+            .map_err(|err| format!("Could not query generated tasks: {err}"))?
+            .query([])
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(|row| Task::from_db_row(row, None))
+            .collect::<Vec<_>>()
+            .map_err(|err| format!("Error querying database: {err}"))?;
+
+        let mut occurrences: HashMap<u64, Vec<Adherence>> = HashMap::new();
+        for task in tasks.iter() {
+            let reminder = task
+                .generated_by
+                .expect("joined on the generating reminder");
+            if let Some(adherence) = Adherence::of(task, &self.config) {
+                occurrences.entry(reminder).or_default().push(adherence);
+            }
+        }
+        Ok(occurrences
+            .into_iter()
+            .map(|(id, occurrences)| (id, ReminderSummary::of(&occurrences)))
+            .collect())
+    }
+
+    /// All reminders in the given order
+    ///
+    /// * `group`: only the reminder with this id and the ones grouped under it
+    fn sorted_reminders(
+        &self,
+        sort: ReminderOrder,
+        group: Option<u64>,
+    ) -> Result<Vec<Reminder>, String> {
+        let mut reminders = self.all_reminders()?;
+        if let Some(group) = group {
+            let ids = self.reminder_group(group)?;
+            reminders.retain(|r| ids.contains(&r.id));
+        }
+        match sort {
+            ReminderOrder::Id => reminders.sort_by_key(|r| r.id),
+            ReminderOrder::NextDue => reminders.sort_by_cached_key(|r| {
+                let next = r.next_occurrence(self.now).map(|o| o.due);
+                (next.is_none(), next)
+            }),
+            ReminderOrder::Title => reminders.sort_by_cached_key(|r| r.title.to_lowercase()),
+            ReminderOrder::Period => reminders.sort_by_key(|r| r.period),
+        }
+        Ok(reminders)
+    }
+
+    /// Rule, cron expression and next occurrences of every active reminder
+    fn show_schedule(&self, sort: ReminderOrder, group: Option<u64>) -> Result<(), String> {
+        let rows = self
+            .sorted_reminders(sort, group)?
+            .iter()
+            .filter(|r| r.is_active(self.now))
+            .map(|r| {
+                let mut rule = humanize_period(r.period);
+                if let Some(season) = r.season {
+                    rule.push_str(&format!(" in {season}"));
+                }
+                if let Some(until) = r.until {
+                    rule.push_str(&format!(" until {}", until.format(self.config.date_fmt())));
+                }
+                let due_fmt = if r.all_day {
+                    self.config.date_fmt()
+                } else {
+                    self.config.datetime_fmt()
+                };
+                let next = r
+                    .occurrences(self.now..)
+                    .take_while(|o| r.until.is_none_or(|until| o.due < until))
+                    .take(3)
+                    .map(|o| o.due.format(due_fmt).to_string())
+                    .collect::<Vec<_>>();
+                vec![
+                    format!("({}) {}", r.id, r.title),
+                    rule,
+                    r.cron().unwrap_or("-".to_string()),
+                    next.join(", "),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let table = render::table(&["reminder", "rule", "cron", "next"], &rows);
+        let mut lines = table.lines();
+        if let Some(header) = lines.next() {
+            println!("{}", header.bold());
+        }
+        for line in lines {
+            println!("{line}");
+        }
+        Ok(())
+    }
+
+    fn show_reminders(
+        &self,
+        all: bool,
+        verbose: bool,
+        group: Option<u64>,
+        sort: ReminderOrder,
+    ) -> Result<(), String> {
+        let reminders = reminder::grouped(self.sorted_reminders(sort, group)?);
+
+        if verbose {
+            let summaries = self.reminder_summaries()?;
+            for (r, _) in reminders.iter() {
+                if !all && !r.is_active(self.now) {
+                    continue;
+                }
+                print!("{}", r.display(all, verbose, self.now, &self.config));
+                let summary = summaries.get(&r.id);
+                print!(
+                    "{}",
+                    summary
+                        .unwrap_or(&ReminderSummary::of(&[]))
+                        .render(&self.config)
+                );
+            }
+            return Ok(());
+        }
+
+        let generated = self
+            .conn
+            .prepare("SELECT generated_by, COUNT(*) FROM tasks WHERE generated_by IS NOT NULL GROUP BY generated_by;")
+            .and_then(|mut stmt| {
+                stmt.query([])?
+                    .map(|row| Ok((row.get::<_, u64>(0)?, row.get::<_, u64>(1)?)))
+                    .collect::<HashMap<_, _>>()
+            })
+            .map_err(|err| format!("Could not count generated tasks: {err}"))?;
+
+        let reminders = reminders
+            .into_iter()
+            .filter(|(r, _)| all || r.is_active(self.now))
+            .collect::<Vec<_>>();
+        let rows = reminders
+            .iter()
+            .map(|(r, depth)| {
+                let due_fmt = if r.all_day {
+                    self.config.date_fmt()
+                } else {
+                    self.config.datetime_fmt()
+                };
+                let next = r.next_occurrence(self.now);
+                let remaining = match r.remaining(self.now) {
+                    Some(remaining) => remaining.to_string(),
+                    None => "∞".to_string(),
+                };
+                vec![
+                    format!("{}({}) {}", "  ".repeat(*depth), r.id, r.title),
+                    humanize_period(r.period),
+                    next.map(|o| o.due.format(due_fmt).to_string())
+                        .unwrap_or("-".to_string()),
+                    generated.get(&r.id).copied().unwrap_or(0).to_string(),
+                    remaining,
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let table = render::table(
+            &["reminder", "period", "next due", "generated", "remaining"],
+            &rows,
+        );
+        let mut lines = table.lines();
+        if let Some(header) = lines.next() {
+            println!("{}", header.bold());
+        }
+        for (line, (r, _)) in lines.zip(reminders.iter()) {
+            if r.is_active(self.now) {
+                println!("{line}");
+            } else {
+                println!("{}", self.config.theme.paint(State::Done, line.normal()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reminder as declared in a `[[reminders]]` block of the config, not stored yet
+    fn declared_reminder(&self, declared: &config::DeclaredReminder) -> Result<Reminder, String> {
+        let lenient = self.config.lenient_dates;
+        let context = |err: String| format!("reminder '{}' in the config: {err}", declared.name);
+        let (first_due, all_day) =
+            parse_date_time_all_day(&declared.first_due, lenient).map_err(context)?;
+        let (first_due, all_day) = self.apply_default_due_time(first_due, all_day);
+        let first_due = match declared.at {
+            Some(at) => first_due
+                .date_naive()
+                .and_time(at)
+                .and_local_timezone(Local)
+                .earliest()
+                .unwrap_or(first_due),
+            None => first_due,
+        };
+        let period = parse_timedelta(&declared.period).map_err(context)?;
+        let until = declared
+            .until
+            .as_ref()
+            .map(|until| parse_date_time(until, lenient))
+            .transpose()
+            .map_err(context)?;
+        validate::reminder(
+            first_due,
+            period,
+            declared.at,
+            until,
+            self.now,
+            &self.config,
+        )
+        .map_err(context)?;
+
+        let mut tags = declared.tags.clone();
+        tags.sort();
+        Ok(Reminder {
+            id: 0,
+            title: declared.title.clone(),
+            description: declared.description.clone(),
+            created: self.now,
+            first_due,
+            all_day: all_day && declared.at.is_none(),
+            period,
+            at: declared.at,
+            until,
+            priority: declared.priority,
+            context: declared.context.clone(),
+            tags,
+            require_completion: declared.require_completion,
+            checklist: Vec::new(),
+            lead_tasks: Vec::new(),
+            catch_up: CatchUp::default(),
+            skip_stale_after: None,
+            skip_before: None,
+            parent: None,
+            season: None,
+        })
+    }
+
+    /// Changes that bring the reminders of the database in line with the ones declared in the
+    /// config
+    fn reconcile_plan(&self) -> Result<Vec<Reconcile>, String> {
+        let stored = self
+            .conn
+            .prepare("SELECT id, external_id FROM reminders WHERE external_id LIKE ?1 || '%';")
+            .and_then(|mut stmt| {
+                stmt.query_map([config::DeclaredReminder::PREFIX], |row| {
+                    Ok((row.get::<_, String>(1)?, row.get::<_, u64>(0)?))
+                })?
+                .collect::<Result<HashMap<_, _>, _>>()
+            })
+            .map_err(|err| format!("Could not query declared reminders: {err}"))?;
+
+        let mut plan = Vec::new();
+        for declared in self.config.reminders.iter() {
+            let key = declared.external_id();
+            let reminder = self.declared_reminder(declared)?;
+            let Some(&id) = stored.get(&key) else {
+                plan.push(Reconcile::Create(declared.name.clone(), reminder));
+                continue;
+            };
+            let stored = self.get_reminder(id)?;
+            if !declared_changes(&stored, &reminder, &self.config).is_empty() {
+                let updated = Reminder {
+                    id: stored.id,
+                    created: stored.created,
+                    checklist: stored.checklist.clone(),
+                    lead_tasks: stored.lead_tasks.clone(),
+                    catch_up: stored.catch_up,
+                    skip_stale_after: stored.skip_stale_after,
+                    skip_before: stored.skip_before,
+                    parent: stored.parent,
+                    season: stored.season,
+                    ..reminder
+                };
+                plan.push(Reconcile::Update {
+                    name: declared.name.clone(),
+                    stored,
+                    declared: Box::new(updated),
+                });
+            }
+        }
+
+        let mut removed = stored
+            .into_iter()
+            .filter(|(key, _)| {
+                self.config
+                    .reminders
+                    .iter()
+                    .all(|declared| declared.external_id() != *key)
+            })
+            .collect::<Vec<_>>();
+        removed.sort_by_key(|(_, id)| *id);
+        for (key, id) in removed {
+            let stored = self.get_reminder(id)?;
+            if stored.is_active(self.now) {
+                let name = key[config::DeclaredReminder::PREFIX.len()..].to_string();
+                plan.push(Reconcile::Stop(name, stored));
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Create, update and stop reminders to match the `[[reminders]]` blocks of the config, all
+    /// or none
+    fn reconcile(&self) -> Result<Reconciled, String> {
+        self.atomically(|| {
+            let mut reconciled = Reconciled::default();
+            for change in self.reconcile_plan()? {
+                match change {
+                    Reconcile::Create(name, reminder) => {
+                        let id = self.insert_reminder(&reminder)?;
+                        let key = format!("{}{name}", config::DeclaredReminder::PREFIX);
+                        self.set_external_id("reminders", id, &key)?;
+                        log::info!("created reminder {id} declared as {name}");
+                        reconciled.created += 1;
+                    }
+                    Reconcile::Update { declared, .. } => {
+                        self.update_reminder(&declared)?;
+                        log::info!("updated reminder {} from the config", declared.id);
+                        reconciled.updated += 1;
+                    }
+                    Reconcile::Stop(_, stored) => {
+                        self.stop_reminder(stored.id)?;
+                        log::info!("stopped reminder {} removed from the config", stored.id);
+                        reconciled.stopped += 1;
+                    }
+                }
+            }
+            Ok(reconciled)
+        })
+    }
+
+    /// Create and update tasks for the TODO comments below `dir` and close the ones of comments
+    /// that disappeared
+    ///
+    /// Tasks completed in rem stay completed while their comment is left in place.
+    fn scan_todos(&self, dir: &std::path::Path) -> Result<Scanned, String> {
+        let todos = todos::scan(dir, self.now.date_naive(), self.config.lenient_dates)?;
+        let prefix = todos::dir_key(dir);
+        self.atomically(|| {
+        let stored = self
+            .conn
+            .prepare(
+                "SELECT id, external_id FROM tasks WHERE completed IS NULL AND substr(external_id, 1, length(?1)) = ?1;",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([&prefix], |row| {
+                    Ok((row.get::<_, String>(1)?, row.get::<_, u64>(0)?))
+                })?
+                .collect::<Result<HashMap<_, _>, _>>()
+            })
+            .map_err(|err| format!("Could not query tasks of TODO comments: {err}"))?;
+
+        let mut scanned = Scanned::default();
+        let mut seen = HashSet::new();
+        for todo in todos {
+            seen.insert(todo.key.clone());
+            // keeps its task open and unchanged until the annotations are fixed
+            if let Some(err) = todo.invalid {
+                scanned
+                    .invalid
+                    .push(format!("{}:{}: {err}", todo.path.display(), todo.line));
+                continue;
+            }
+            let description = Some(format!("{}:{}", todo.path.display(), todo.line));
+            let due = todo
+                .quick
+                .due
+                .map(|due| self.apply_default_due_time(start_of_day(due), true));
+            let Some(id) = self.find_external("tasks", &todo.key)? else {
+                let id = self.add_task(
+                    todo.quick.title,
+                    description,
+                    None,
+                    None,
+                    due.map(|(due, _)| due),
+                    due.is_some_and(|(_, all_day)| all_day),
+                    todo.quick.priority,
+                    todo.quick.context,
+                    None,
+                    &todo.quick.tags,
+                    None,
+                    None,
+                )?;
+                self.set_external_id("tasks", id, &todo.key)?;
+                scanned.created += 1;
+                continue;
+            };
+
+            let task = self.get_task(id)?;
+            if task.completed.is_some() {
+                continue;
+            }
+            let mut tags = task.tags.clone();
+            for tag in todo.quick.tags {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            let (due, due_all_day) = match due {
+                Some((due, all_day)) => (Some(due), all_day),
+                None => (task.due, task.due_all_day),
+            };
+            let priority = todo.quick.priority.or(task.priority);
+            let context = todo.quick.context.or(task.context.clone());
+            if description == task.description
+                && due == task.due
+                && due_all_day == task.due_all_day
+                && priority == task.priority
+                && context == task.context
+                && tags == task.tags
+            {
+                continue;
+            }
+            self.update_task(&Task {
+                description,
+                due,
+                due_all_day,
+                priority,
+                context,
+                tags,
+                ..task
+            })?;
+            scanned.updated += 1;
+        }
+
+        for (key, id) in stored {
+            if !seen.contains(&key) {
+                self.complete_task(id)?;
+                log::info!("closed task {id} of the removed comment {key}");
+                scanned.closed += 1;
+            }
+        }
+        Ok(scanned)
+        })
+    }
+
+    /// Stop a reminder and the active reminders grouped under it
+    fn stop_reminder(&self, id: u64) -> Result<(), String> {
+        let until = self.now;
+        self.get_reminder(id)?;
+        self.conn
+            .execute(
+                "UPDATE reminders SET until = ?1 WHERE id = ?2",
+                (until.timestamp(), id),
+            )
+            .map_err(|err| format!("Could stop reminder: {err}"))?;
+        for member in self.reminder_group(id)?.into_iter().skip(1) {
+            self.conn
+                .execute(
+                    "UPDATE reminders SET until = ?1 WHERE id = ?2 AND (until IS NULL OR until > ?1)",
+                    (until.timestamp(), member),
+                )
+                .map_err(|err| format!("Could stop reminder {member}: {err}"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Restrict listing and creating tasks to `scope`, failing if its project does not exist
+    fn set_scope(&mut self, scope: Scope) -> Result<(), String> {
+        if let Some(project) = scope.project {
+            self.get_task(project)?;
+        }
+        self.scope = scope;
+        Ok(())
+    }
+
+    /// Tasks in the scope with their urgency, in the given order
+    fn sorted_tasks(&self, sort: TaskOrder) -> Result<Vec<(Task, f64)>, String> {
+        let tasks = self.all_tasks()?;
+        let project = projects(&tasks);
+        let in_scope = tasks
+            .iter()
+            .filter(|t| self.scope.contains(t, project(t)))
+            .map(|t| t.id)
+            .collect::<HashSet<_>>();
+        drop(project);
+
+        let mut tasks = self.with_urgency(tasks)?;
+        tasks.retain(|(t, _)| in_scope.contains(&t.id));
+        match sort {
+            TaskOrder::Id => (),
+            TaskOrder::Due => tasks.sort_by_key(|(t, _)| (t.due.is_none(), t.due)),
+            TaskOrder::Urgency => tasks.sort_by(|a, b| b.1.total_cmp(&a.1)),
+        }
+        let pinned = self.pinned()?;
+        tasks.sort_by_key(|(t, _)| pinned.iter().position(|p| *p == t.id).unwrap_or(usize::MAX));
+        Ok(tasks)
+    }
+
+    fn show_tasks(
+        &self,
+        all: bool,
+        verbose: bool,
+        sort: TaskOrder,
+        group_by: Option<TaskGrouping>,
+        include_future: bool,
+        width: Option<usize>,
+    ) -> Result<(), String> {
+        let mut tasks = self.sorted_tasks(sort)?;
+        let count = tasks.len();
+        if !include_future {
+            tasks.retain(|(t, _)| !t.starts_later(self.now));
+        }
+        let hidden = count - tasks.len();
+        let all_tasks = self.all_tasks()?;
+        let project = projects(&all_tasks);
+        let show = |t: &Task, urgency: f64| {
+            let display = t
+                .display(all, verbose, self.now, &self.config)
+                .with_urgency(urgency)
+                .in_project(project(t).map(|p| p.id))
+                .truncated(width);
+            print!("{display}");
+        };
+
+        let footer = || {
+            if hidden > 0 {
+                let tasks = if hidden == 1 { "task" } else { "tasks" };
+                let text = format!("{hidden} {tasks} starting later hidden, see --include-future");
+                println!("{}", text.dimmed());
+            }
+        };
+
+        let Some(group_by) = group_by else {
+            for (t, urgency) in tasks.iter() {
+                show(t, *urgency);
+            }
+            footer();
+            return Ok(());
+        };
+        let shown = tasks.iter().filter(|(t, _)| all || t.completed.is_none());
+        let sections = render::sections(shown, |(t, _)| match group_by {
+            TaskGrouping::Project => project(t)
+                .map(|root| vec![format!("({}) {}", root.id, root.title)])
+                .unwrap_or_default(),
+            TaskGrouping::Tag => t.tags.iter().map(|tag| format!("#{tag}")).collect(),
+            TaskGrouping::DueWeek => t
+                .due
+                .map(|due| vec![render::week_of(due.date_naive())])
+                .unwrap_or_default(),
+        });
+        for (idx, (name, tasks)) in sections.iter().enumerate() {
+            if idx > 0 {
+                println!();
+            }
+            println!("{}", render::section_header(name, tasks.len()).bold());
+            for (t, urgency) in tasks.iter() {
+                show(t, *urgency);
+            }
+        }
+        footer();
+        Ok(())
+    }
+
+    /// Summarize all tasks per tag, project or reminder
+    ///
+    /// Tasks without a group are summarized as `(none)`.
+    fn report(&self, by: ReportBy) -> Result<Vec<GroupReport>, String> {
+        let tasks = self.all_tasks()?;
+        let none = || vec!["(none)".to_string()];
+        // all sessions so far
+        let mut tracked = HashMap::<u64, TimeDelta>::new();
+        for session in self.sessions_started(
+            chrono::DateTime::UNIX_EPOCH.date_naive(),
+            self.now.date_naive(),
+        )? {
+            *tracked.entry(session.task_id).or_default() += session.duration(self.now);
+        }
+
+        let reports = match by {
+            ReportBy::Tag => group_tasks(
+                &tasks,
+                |t| {
+                    if t.tags.is_empty() {
+                        none()
+                    } else {
+                        t.tags.iter().map(|tag| format!("#{tag}")).collect()
+                    }
+                },
+                &tracked,
+                self.now,
+                &self.config,
+            ),
+            ReportBy::Project => {
+                let project = projects(&tasks);
+                group_tasks(
+                    &tasks,
+                    |t| match project(t) {
+                        Some(root) => vec![format!("({}) {}", root.id, root.title)],
+                        None => none(),
+                    },
+                    &tracked,
+                    self.now,
+                    &self.config,
+                )
+            }
+            ReportBy::Reminder => {
+                let titles = self
+                    .conn
+                    .prepare("SELECT id, title FROM reminders;")
+                    .map_err(|err| format!("Could not query reminders: {err}"))?
+                    .query([])
+                    .map_err(|err| format!("Could not query database: {err}"))?
+                    .map(|row| Ok((row.get::<_, u64>(0)?, row.get::<_, String>(1)?)))
+                    .collect::<HashMap<_, _>>()
+                    .map_err(|err| format!("Error querying database: {err}"))?;
+                group_tasks(
+                    &tasks,
+                    |t| match t
+                        .generated_by
+                        .and_then(|r| titles.get(&r).map(|title| (r, title)))
+                    {
+                        Some((id, title)) => vec![format!("({id}) {title}")],
+                        None => none(),
+                    },
+                    &tracked,
+                    self.now,
+                    &self.config,
+                )
+            }
+        };
+        Ok(reports)
+    }
+
+    /// Completions and work bits per week for the last `weeks` weeks
+    fn weekly_activity(&self, weeks: u32) -> Result<Vec<WeekActivity>, String> {
+        let times = |query: &str| {
+            self.conn
+                .prepare(query)
+                .map_err(|err| format!("Could not query activity: {err}"))?
+                .query([])
+                .map_err(|err| format!("Could not query database: {err}"))?
+                .map(|row| row.get::<_, i64>(0).map(import_datetime))
+                .collect::<Vec<_>>()
+                .map_err(|err| format!("Error querying database: {err}"))
+        };
+        let completions =
+            times("SELECT completed FROM tasks WHERE completed IS NOT NULL AND NOT skipped;")?;
+        let work_bits = times("SELECT datetime FROM work_bits;")?;
+        Ok(weekly_activity(&completions, &work_bits, self.now, weeks))
+    }
+
+    /// Number of completed tasks and work bits per day between `first` and `last`, days without
+    /// activity are left out
+    fn daily_activity(
+        &self,
+        first: NaiveDate,
+        last: NaiveDate,
+    ) -> Result<Vec<(NaiveDate, u64)>, String> {
+        self.conn
+            .prepare(
+                "SELECT day, COUNT(*) FROM (
+                   SELECT date(completed, 'unixepoch', 'localtime') AS day
+                     FROM tasks WHERE completed IS NOT NULL AND NOT skipped
+                   UNION ALL
+                   SELECT date(datetime, 'unixepoch', 'localtime') FROM work_bits
+                 ) WHERE day BETWEEN ?1 AND ?2 GROUP BY day;",
+            )
+            .map_err(|err| format!("Could not query activity: {err}"))?
+            .query((first.to_string(), last.to_string()))
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(|row| {
+                let day = row.get::<_, String>(0)?;
+                let day = day.parse::<NaiveDate>().map_err(|err| {
+                    rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(err))
+                })?;
+                Ok((day, row.get(1)?))
+            })
+            .collect()
+            .map_err(|err| format!("Error querying database: {err}"))
+    }
+
+    /// Due date, completion and lateness of the generated occurrences of a reminder, oldest first
+    fn reminder_adherence(&self, reminder_id: u64) -> Result<Vec<Adherence>, String> {
+        self.conn
+            .query_one(
+                "SELECT id FROM reminders WHERE id = ?1;",
+                [reminder_id],
+                |_| Ok(()),
+            )
+            .map_err(|err| format!("Could not find reminder {reminder_id}: {err}"))?;
+
+        let tasks = self
+            .conn
+            .prepare("SELECT * FROM tasks WHERE generated_by = ?1 ORDER BY due;")
+            .map_err(|err| format!("Could not query tasks: {err}"))?
+            .query([reminder_id])
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(|row| Task::from_db_row(row, None))
+            .collect::<Vec<_>>()
+            .map_err(|err| format!("Error querying database: {err}"))?;
+
+        Ok(tasks
+            .iter()
+            .filter_map(|t| Adherence::of(t, &self.config))
+            .collect())
+    }
+
+    /// Pair tasks with their urgency
+    fn with_urgency(&self, tasks: Vec<Task>) -> Result<Vec<(Task, f64)>, String> {
+        let blocked = self.blocked_tasks()?;
+        let open = tasks
+            .iter()
+            .filter(|t| t.completed.is_none())
+            .map(|t| t.id)
+            .collect::<HashSet<_>>();
+
+        Ok(tasks
+            .into_iter()
+            .map(|t| {
+                let blocking = t.completed.is_none() && t.parent.is_some_and(|p| open.contains(&p));
+                let u = urgency(
+                    &t,
+                    blocking,
+                    blocked.contains(&t.id),
+                    self.now,
+                    &self.config,
+                    &self.config.urgency,
+                );
+                (t, u)
+            })
+            .collect())
+    }
+
+    /// Porcelain lines of tasks, with their urgency among all tasks from v2 on
+    fn porcelain_tasks(
+        &self,
+        tasks: &[Task],
+        version: porcelain::Version,
+    ) -> Result<Vec<String>, String> {
+        let urgencies = match version {
+            porcelain::Version::V1 => HashMap::new(),
+            porcelain::Version::V2 => self
+                .with_urgency(self.all_tasks()?)?
+                .into_iter()
+                .map(|(t, urgency)| (t.id, urgency))
+                .collect(),
+        };
+        Ok(tasks
+            .iter()
+            .map(|t| porcelain::task(t, urgencies.get(&t.id).copied().unwrap_or(0.0), version))
+            .collect())
+    }
+
+    fn porcelain_task(&self, task: &Task, version: porcelain::Version) -> Result<String, String> {
+        let mut lines = self.porcelain_tasks(std::slice::from_ref(task), version)?;
+        Ok(lines.remove(0))
+    }
+
+    /// Delete a task, refusing to if it still has subtasks
+    fn delete_task(&self, id: u64) -> Result<(), String> {
+        let subtasks = self
+            .conn
+            .prepare("SELECT id FROM tasks WHERE parent = ?1 ORDER BY id;")
+            .map_err(|err| format!("could not query tasks: {err}"))?
+            .query([id])
+            .map_err(|err| format!("could not query database: {err}"))?
+            .map(|row| row.get(0))
+            .collect::<Vec<u64>>()
+            .map_err(|err| format!("error querying database: {err}"))?;
+        if !subtasks.is_empty() {
+            let subtasks = subtasks
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(format!(
+                "Task {id} has the subtasks {subtasks}. Delete them first."
+            ));
+        }
+
+        let res = self
+            .conn
+            .execute("DELETE FROM tasks where ID = ?1", [id])
+            .map_err(|err| format!("could not query tasks: {err}"))?;
+        self.conn
+            .execute(
+                "DELETE FROM due_links WHERE task_id = ?1 OR after = ?1;",
+                [id],
+            )
+            .map_err(|err| format!("could not query due dates: {err}"))?;
+
+        if res == 0 {
+            Err("Could not delete Task. ID not found.".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn complete_task(&self, id: u64) -> Result<(), String> {
+        let completed: Option<i64> = self
+            .conn
+            .query_one("select completed from tasks where id = ?1", (id,), |row| {
+                row.get(0)
+            })
+            .map_err(|err| format!("Could not get task completion status: {err}"))?;
+        let completed = completed.map(import_datetime);
+
+        if let Some(completed) = completed {
+            return Err(format!(
+                "Could not mark task {id} as completed. Already completed at {completed}",
+                completed = completed.format(self.config.datetime_fmt())
+            ));
+        }
+
+        let res = self
+            .conn
+            .execute(
+                "UPDATE tasks SET completed = ?1 where id = ?2;",
+                (self.now.timestamp(), id),
+            )
+            .map_err(|err| format!("Could not mark task {id} as completed: {err}"))?;
+
+        assert_eq!(res, 1);
+
+        Ok(())
+    }
+
+    fn running_session(&self) -> Result<Option<Session>, String> {
+        self.conn
+            .query_one(
+                "SELECT * FROM sessions WHERE stopped IS NULL ORDER BY started DESC LIMIT 1;",
+                [],
+                Session::from_db_row,
+            )
+            .optional()
+            .map_err(|err| format!("Could not query work sessions: {err}"))
+    }
+
+    /// Start working on a task, stopping the running session if there is one
+    fn start_session(&self, task_id: u64) -> Result<u64, String> {
+        self.get_task(task_id)?;
+        if self.running_session()?.is_some() {
+            self.stop_session(self.now)?;
+        }
+        self.conn
+            .execute(
+                "INSERT INTO sessions (task_id, started, last_seen) VALUES (?1, ?2, ?2);",
+                (task_id, self.now.timestamp()),
+            )
+            .map_err(|err| format!("Could not start work session: {err}"))?;
+        Ok(self.conn.last_insert_rowid() as u64)
+    }
+
+    /// Stop the running session at `at`, returning it
+    fn stop_session(&self, at: LocalDT) -> Result<Session, String> {
+        let mut session = self
+            .running_session()?
+            .ok_or("No work session is running.".to_string())?;
+        self.conn
+            .execute(
+                "UPDATE sessions SET stopped = ?1 WHERE id = ?2;",
+                (at.timestamp(), session.id),
+            )
+            .map_err(|err| format!("Could not stop work session: {err}"))?;
+        session.stopped = Some(at);
+        Ok(session)
+    }
+
+    /// Work sessions started between the days `first` and `last`
+    fn sessions_started(&self, first: NaiveDate, last: NaiveDate) -> Result<Vec<Session>, String> {
+        self.conn
+            .prepare("SELECT * FROM sessions WHERE started >= ?1 AND started < ?2;")
+            .map_err(|err| format!("Could not query work sessions: {err}"))?
+            .query((
+                start_of_day(first).timestamp(),
+                start_of_day(last + TimeDelta::days(1)).timestamp(),
+            ))
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(Session::from_db_row)
+            .collect::<Vec<_>>()
+            .map_err(|err| format!("Could not read work sessions: {err}"))
+    }
+
+    /// Work sessions running at or after `start`
+    fn sessions_since(&self, start: LocalDT) -> Result<Vec<Session>, String> {
+        self.conn
+            .prepare("SELECT * FROM sessions WHERE stopped IS NULL OR stopped > ?1;")
+            .map_err(|err| format!("Could not query work sessions: {err}"))?
+            .query([start.timestamp()])
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(Session::from_db_row)
+            .collect::<Vec<_>>()
+            .map_err(|err| format!("Could not read work sessions: {err}"))
+    }
+
+    /// Tasks of the sessions running after `start` with their ancestors, and a subtask of each, so
+    /// [`projects`] tells their projects without loading all tasks
+    fn tasks_worked_since(&self, start: LocalDT) -> Result<Vec<Task>, String> {
+        self.conn
+            .prepare(
+                "WITH RECURSIVE worked(id) AS (
+                     SELECT task_id FROM sessions WHERE stopped IS NULL OR stopped > ?1
+                     UNION SELECT parent FROM tasks JOIN worked ON tasks.id = worked.id
+                         WHERE parent IS NOT NULL
+                 )
+                 SELECT * FROM tasks WHERE id IN worked
+                     OR id IN (SELECT min(id) FROM tasks WHERE parent IN worked GROUP BY parent);",
+            )
+            .map_err(|err| format!("Could not query tasks: {err}"))?
+            .query([start.timestamp()])
+            .map_err(|err| format!("Could not query database: {err}"))?
+            .map(|row| Task::from_db_row(row, Some(&self.conn)))
+            .collect()
+            .map_err(|err| format!("Error querying database: {err}"))
+    }
+
+    /// Time worked since monday on the tags and projects of the `[budgets]` config section,
+    /// tags first
+    ///
+    /// Sessions running across midnight from sunday to monday only count from monday on.
+    fn budgets(&self) -> Result<Vec<Budget>, String> {
+        let budgets = &self.config.budgets;
+        if budgets.tags.is_empty() && budgets.projects.is_empty() {
+            return Ok(Vec::new());
+        }
+        let today = self.now.date_naive();
+        let monday = today - TimeDelta::days(today.weekday().num_days_from_monday() as i64);
+        let week_start = start_of_day(monday);
+        let sessions = self.sessions_since(week_start)?;
+        let tasks = self.tasks_worked_since(week_start)?;
+        let by_id = tasks.iter().map(|t| (t.id, t)).collect::<HashMap<_, _>>();
+        let project = projects(&tasks);
+        let worked = |counts: &dyn Fn(&Task) -> bool| {
+            sessions
+                .iter()
+                .filter(|session| by_id.get(&session.task_id).is_some_and(|t| counts(t)))
+                .map(|session| {
+                    session.stopped.unwrap_or(self.now) - session.started.max(week_start)
+                })
+                .sum::<TimeDelta>()
+        };
+        let hours = |hours: f64| TimeDelta::seconds((hours * 3600.0) as i64);
+
+        let tags = budgets.tags.iter().map(|(tag, budget)| Budget {
+            group: format!("#{tag}"),
+            budget: hours(*budget),
+            worked: worked(&|t| t.tags.contains(tag)),
+        });
+        let projects = budgets.projects.iter().map(|(title, budget)| Budget {
+            group: title.clone(),
+            budget: hours(*budget),
+            worked: worked(&|t| project(t).is_some_and(|root| root.title == *title)),
+        });
+        Ok(tags.chain(projects).collect())
+    }
+
+    /// Time worked in sessions started between the days `first` and `last`, with the group
+    /// each session belongs to
+    fn worked(
+        &self,
+        first: NaiveDate,
+        last: NaiveDate,
+        by: TimesheetBy,
+        client: Option<&str>,
+    ) -> Result<Vec<(String, TimeDelta)>, String> {
+        let sessions = self.sessions_started(first, last)?;
+        let tasks = self.all_tasks()?;
+        let by_id = tasks.iter().map(|t| (t.id, t)).collect::<HashMap<_, _>>();
+        let project = projects(&tasks);
+        Ok(sessions
+            .into_iter()
+            .filter_map(|session| {
+                let task = by_id.get(&session.task_id).copied();
+                let root = task.and_then(&project);
+                if let Some(client) = client {
+                    // tasks outside of projects may be billed directly
+                    let billed = root.or(task).and_then(|t| t.client.as_deref());
+                    if billed != Some(client) {
+                        return None;
+                    }
+                }
+
+                let group = match by {
+                    TimesheetBy::Day => session.started.date_naive().to_string(),
+                    TimesheetBy::Task => task
+                        .map(|t| format!("({}) {}", t.id, t.title))
+                        .unwrap_or_else(|| format!("({})", session.task_id)),
+                    TimesheetBy::Project => root
+                        .map(|root| format!("({}) {}", root.id, root.title))
+                        .unwrap_or("(none)".to_string()),
+                };
+                Some((group, session.duration(self.now)))
+            })
+            .collect())
+    }
+
+    /// Column names and rows of a read-only SQL statement
+    fn query(&self, sql: &str) -> Result<(Vec<String>, Vec<Vec<serde_json::Value>>), String> {
+        let mut stmt = self
+            .conn
+            .prepare(sql)
+            .map_err(|err| format!("Invalid query: {err}"))?;
+        if !stmt.readonly() {
+            return Err("Only queries that do not modify the database are allowed.".to_string());
+        }
+        let columns = stmt
+            .column_names()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let rows = stmt
+            .query([])
+            .map_err(|err| format!("Could not run query: {err}"))?
+            .map(|row| {
+                (0..columns.len())
+                    .map(|idx| {
+                        Ok(match row.get_ref(idx)? {
+                            ValueRef::Null => serde_json::Value::Null,
+                            ValueRef::Integer(i) => i.into(),
+                            ValueRef::Real(f) => f.into(),
+                            ValueRef::Text(t) => String::from_utf8_lossy(t).into(),
+                            ValueRef::Blob(b) => format!("<{} bytes>", b.len()).into(),
+                        })
+                    })
+                    .collect()
+            })
+            .collect::<Vec<_>>()
+            .map_err(|err| format!("Could not run query: {err}"))?;
+        Ok((columns, rows))
+    }
+
+    /// Every row of every table, keyed by table name, with the schema version
+    fn dump(&self) -> Result<serde_json::Value, String> {
+        let mut tables = serde_json::Map::new();
+        for table in TABLES {
+            let mut stmt = self
+                .conn
+                .prepare(&format!("SELECT * FROM {table};"))
+                .map_err(|err| format!("Could not query {table}: {err}"))?;
+            let columns = stmt
+                .column_names()
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>();
+            let rows = stmt
+                .query([])
+                .map_err(|err| format!("Could not query {table}: {err}"))?
+                .map(|row| {
+                    let mut ret = serde_json::Map::new();
+                    for (idx, column) in columns.iter().enumerate() {
+                        let value = match row.get_ref(idx)? {
+                            ValueRef::Null => serde_json::Value::Null,
+                            ValueRef::Integer(i) => i.into(),
+                            ValueRef::Real(f) => f.into(),
+                            ValueRef::Text(t) => String::from_utf8_lossy(t).into(),
+                            ValueRef::Blob(_) => {
+                                return Err(rusqlite::Error::InvalidColumnType(
+                                    idx,
+                                    column.clone(),
+                                    Type::Blob,
+                                ))
+                            }
+                        };
+                        ret.insert(column.clone(), value);
+                    }
+                    Ok(serde_json::Value::Object(ret))
+                })
+                .collect::<Vec<_>>()
+                .map_err(|err| format!("Could not read {table}: {err}"))?;
+            tables.insert(table.to_string(), rows.into());
+        }
+
+        Ok(serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "tables": tables,
+        }))
+    }
+
+    /// Insert the rows of a dump, keeping their ids
+    ///
+    /// Rows have no identity beyond their ids, so dumps are only loaded into empty databases
+    /// rather than merged.
+    fn load(&self, dump: &serde_json::Value) -> Result<(), String> {
+        let version = dump["schema_version"].as_i64();
+        if version != Some(SCHEMA_VERSION) {
+            return Err(format!(
+                "dump has schema version {}, expected {SCHEMA_VERSION}",
+                version.map_or("(none)".to_string(), |v| v.to_string())
+            ));
+        }
+        let tables = dump["tables"]
+            .as_object()
+            .ok_or("dump has no tables".to_string())?;
+        if let Some(table) = tables.keys().find(|t| !TABLES.contains(&t.as_str())) {
+            return Err(format!("unknown table '{table}'"));
+        }
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| format!("Could not start transaction: {err}"))?;
+        // rows may refer to rows of tables loaded later
+        tx.pragma_update(None, "defer_foreign_keys", true)
+            .map_err(|err| format!("Could not defer foreign keys: {err}"))?;
+
+        // loading the details of tasks and reminders after them would touch their modification
+        // times, which sync relies on
+        let entities = ["reminders", "tasks"];
+        let order = TABLES
+            .iter()
+            .filter(|table| !entities.contains(table))
+            .chain(entities.iter());
+        for table in order {
+            if matches!(*table, "operations" | "undo_log") {
+                // the undo history of the dumped database replaces the one of the empty database
+                tx.execute(&format!("DELETE FROM {table};"), [])
+                    .map_err(|err| format!("Could not clear {table}: {err}"))?;
+            } else if *table != "meta" {
+                // meta is filled on initialization, dumped values take precedence
+                let count = tx
+                    .query_one(&format!("SELECT COUNT(*) FROM {table};"), [], |row| {
+                        row.get::<_, u64>(0)
+                    })
+                    .map_err(|err| format!("Could not query {table}: {err}"))?;
+                if count > 0 {
+                    return Err(format!(
+                        "the database is not empty, {table} has {count} rows"
+                    ));
+                }
+            }
+
+            let rows = tables.get(*table).and_then(|rows| rows.as_array());
+            for row in rows.into_iter().flatten() {
+                let row = row
+                    .as_object()
+                    .ok_or(format!("rows of {table} need to be objects"))?;
+                for column in row.keys() {
+                    let exists = tx
+                        .column_exists(Some(DATABASE_NAME), table, column)
+                        .map_err(|err| format!("could not inspect {table} table: {err}"))?;
+                    if !exists {
+                        return Err(format!("unknown column {table}.{column}"));
+                    }
+                }
+
+                let columns = row.keys().map(String::as_str).collect::<Vec<_>>();
+                let placeholders = (1..=columns.len())
+                    .map(|idx| format!("?{idx}"))
+                    .collect::<Vec<_>>();
+                let values = row
+                    .values()
+                    .map(|value| match value {
+                        serde_json::Value::Null => Ok(Value::Null),
+                        serde_json::Value::Bool(b) => Ok(Value::Integer(i64::from(*b))),
+                        serde_json::Value::Number(n) => match n.as_i64() {
+                            Some(i) => Ok(Value::Integer(i)),
+                            None => Ok(Value::Real(n.as_f64().unwrap_or_default())),
+                        },
+                        serde_json::Value::String(s) => Ok(Value::Text(s.clone())),
+                        _ => Err(format!("unsupported value in {table}: {value}")),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                tx.execute(
+                    &format!(
+                        "INSERT OR REPLACE INTO {table} ({}) VALUES ({});",
+                        columns.join(", "),
+                        placeholders.join(", ")
+                    ),
+                    rusqlite::params_from_iter(values),
+                )
+                .map_err(|err| format!("Could not insert into {table}: {err}"))?;
+            }
+        }
+
+        tx.commit()
+            .map_err(|err| format!("Could not commit loaded dump: {err}"))
+    }
+
+    /// Merge the tasks and reminders of a dump into this database as [`App::sync_with`] does,
+    /// returning the changes to this database
+    fn merge_dump(&self, dump: &serde_json::Value) -> Result<Changes, String> {
+        let dir = private_temp_dir()?;
+        let path = dir.join("dump.sqlite");
+        let merged = rusqlite::Connection::open(&path)
+            .map_err(|err| format!("Could not create {}: {err}", path.display()))
+            .and_then(|conn| App::try_init(conn, self.config.clone()))
+            .and_then(|peer| peer.load(dump))
+            .and_then(|_| self.sync_with(&path));
+        let _ = std::fs::remove_dir_all(&dir);
+        merged.map(|(received, _)| received)
+    }
+
+    /// Set or clear who work on a task, usually a project, is billed to
+    fn set_client(&self, id: u64, client: Option<&str>) -> Result<(), String> {
+        self.get_task(id)?;
+        self.conn
+            .execute("UPDATE tasks SET client = ?1 WHERE id = ?2;", (client, id))
+            .map_err(|err| format!("Could not set client of task {id}: {err}"))?;
+        Ok(())
+    }
+
+    /// Time without a work session that warrants a nudge, see [`untracked`]
+    fn untracked(&self) -> Result<Option<TimeDelta>, String> {
+        let running = self.running_session()?.is_some();
+        let last_stopped = self
+            .conn
+            .query_one("SELECT MAX(stopped) FROM sessions;", [], |row| {
+                row.get::<_, Option<i64>>(0)
+            })
+            .map_err(|err| format!("Could not query work sessions: {err}"))?
+            .map(import_datetime);
+        Ok(untracked(self.now, running, last_stopped, &self.config))
+    }
+
+    /// Describe the running session, if there is one
+    fn tracking(&self, short: bool) -> Result<Option<String>, String> {
+        let Some(session) = self.running_session()? else {
+            return Ok(None);
+        };
+        let task = self.get_task(session.task_id)?;
+        let elapsed = session.duration(self.now);
+        Ok(Some(if short {
+            format!("⏱ {} {} #{}", format_elapsed(elapsed), task.title, task.id)
+        } else {
+            format!(
+                "Working on ({}) {} for {}, since {}.",
+                task.id,
+                task.title,
+                format_timedelta(elapsed),
+                session.started.format(self.config.datetime_fmt())
+            )
+        }))
+    }
+
+    fn status(&self, short: bool) -> Result<String, String> {
+        let open = self.open_tasks()?;
+        let overdue = open
+            .iter()
+            .filter(|t| t.is_overdue(self.now, &self.config))
+            .count();
+        let tracking = self.tracking(short)?;
+        let budgets = self.budgets()?;
+
+        if short {
+            let mut parts = Vec::new();
+            parts.extend(tracking);
+            if overdue > 0 {
+                parts.push(format!("{overdue} overdue"));
+            }
+            let exceeded = budgets
+                .iter()
+                .filter(|b| b.exceeded())
+                .map(|b| b.group.as_str())
+                .collect::<Vec<_>>();
+            if !exceeded.is_empty() {
+                parts.push(format!("over budget: {}", exceeded.join(", ")));
+            }
+            return Ok(parts.join(" · "));
+        }
+
+        let mut ret = format!("open tasks: {}\noverdue:    {overdue}\n", open.len());
+        for budget in budgets.iter() {
+            ret.push_str(&format!("budget {budget}\n"));
+        }
+        match self.last_generated()? {
+            Some(last) => ret.push_str(&format!(
+                "last generated {} ago\n",
+                format_timedelta(self.now - last)
+            )),
+            None => ret.push_str("never generated tasks from reminders\n"),
+        }
+        match tracking {
+            Some(tracking) => ret.push_str(&tracking),
+            None => ret.push_str("No work session is running."),
+        }
+        Ok(ret)
+    }
+
+    /// The short status with how urgent the open tasks are
+    fn status_line(&self) -> Result<(String, statusbar::Level), String> {
+        let level = statusbar::Level::of(&self.open_tasks()?, self.now, &self.config);
+        Ok((self.status(true)?, level))
+    }
+
+    /// Note activity on the running session, letting `decide` handle it if it was idle for
+    /// longer than configured
+    ///
+    /// Returns the session if it was trimmed, which stops it.
+    fn check_idle_session(
+        &self,
+        decide: &mut dyn FnMut(&Session, TimeDelta) -> IdleAction,
+    ) -> Result<Option<Session>, String> {
+        let Some(session) = self.running_session()? else {
+            return Ok(None);
+        };
+
+        let idle = self.now - session.last_seen;
+        let limit = TimeDelta::minutes(self.config.session_idle_minutes as i64);
+        if self.config.session_idle_minutes > 0 && idle > limit {
+            match decide(&session, idle) {
+                IdleAction::Keep => (),
+                IdleAction::Trim => return self.stop_session(session.last_seen).map(Some),
+                IdleAction::Split => {
+                    self.stop_session(session.last_seen)?;
+                    self.start_session(session.task_id)?;
+                    return Ok(None);
+                }
+            }
+        }
+
+        self.conn
+            .execute(
+                "UPDATE sessions SET last_seen = ?1 WHERE id = ?2;",
+                (self.now.timestamp(), session.id),
+            )
+            .map_err(|err| format!("Could not update work session: {err}"))?;
+        Ok(None)
+    }
+
+    /// Note keyboard or mouse input at `at` on the running session, so its idle time is
+    /// measured from it rather than from the last run of rem
+    fn note_input(&self, at: LocalDT) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE sessions SET last_seen = ?1 WHERE stopped IS NULL AND last_seen < ?1;",
+                [at.timestamp()],
+            )
+            .map_err(|err| format!("Could not update work session: {err}"))?;
+        Ok(())
+    }
+
+    /// Open task generated by a reminder for its current period, i.e. the latest started one
+    fn current_occurrence(&self, reminder_id: u64) -> Result<u64, String> {
+        let exists = self
+            .conn
+            .query_one(
+                "SELECT COUNT(*) FROM reminders WHERE id = ?1",
+                [reminder_id],
+                |row| row.get::<_, u64>(0),
+            )
+            .map_err(|err| format!("Could not query reminders: {err}"))?;
+        if exists == 0 {
+            return Err(format!("Reminder {reminder_id} not found."));
+        }
+
+        self.conn
+            .query_one(
+                "SELECT id FROM tasks
+                 WHERE generated_by = ?1 AND completed IS NULL AND (start IS NULL OR start <= ?2)
+                 ORDER BY due DESC LIMIT 1;",
+                (reminder_id, self.now.timestamp()),
+                |row| row.get::<_, u64>(0),
+            )
+            .optional()
+            .map_err(|err| format!("Could not query occurrences: {err}"))?
+            .ok_or(format!("Reminder {reminder_id} has no open occurrence."))
+    }
+
+    /// Merge tasks generated for the same occurrence of a reminder, as overlapping generation
+    /// passes of older versions left behind
+    ///
+    /// Of each occurrence, the first completed task is kept, or the first one if none is. The
+    /// work bits, sessions and subtasks of the others move to it before they are deleted.
+    fn dedupe_occurrences(&self) -> Result<Vec<Duplicates>, String> {
+        let tasks = self
+            .conn
+            .prepare(
+                "SELECT id, generated_by, due FROM tasks t
+                 WHERE generated_by IS NOT NULL AND EXISTS (
+                   SELECT 1 FROM tasks o
+                   WHERE o.generated_by = t.generated_by AND o.due = t.due AND o.id != t.id
+                 )
+                 ORDER BY generated_by, due, completed IS NULL, id;",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| {
+                    Ok((row.get::<_, u64>(0)?, row.get::<_, u64>(1)?, row.get(2)?))
+                })?
+                .collect::<Result<Vec<(u64, u64, i64)>, _>>()
+            })
+            .map_err(|err| format!("Could not query occurrences: {err}"))?;
+
+        let mut ret: Vec<Duplicates> = Vec::new();
+        for (id, reminder, due) in tasks {
+            match ret.last_mut() {
+                Some(group) if group.reminder == reminder && group.due.timestamp() == due => {
+                    group.removed.push(id)
+                }
+                _ => ret.push(Duplicates {
+                    reminder,
+                    due: import_datetime(due),
+                    kept: id,
+                    removed: Vec::new(),
+                    work_bits: 0,
+                }),
+            }
+        }
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|err| format!("Could not start transaction: {err}"))?;
+        for group in ret.iter_mut() {
+            for removed in group.removed.iter() {
+                let merge = |sql: &str| {
+                    tx.execute(sql, (group.kept, removed))
+                        .map_err(|err| format!("Could not merge task {removed}: {err}"))
+                };
+                group.work_bits += merge("UPDATE work_bits SET task_id = ?1 WHERE task_id = ?2;")?;
+                merge("UPDATE sessions SET task_id = ?1 WHERE task_id = ?2;")?;
+                // the kept task cannot become its own parent or be due relative to itself
+                merge(
+                    "UPDATE tasks SET parent = (SELECT parent FROM tasks WHERE id = ?2)
+                     WHERE id = ?1 AND parent = ?2;",
+                )?;
+                merge("UPDATE tasks SET parent = ?1 WHERE parent = ?2;")?;
+                merge(
+                    "UPDATE OR IGNORE due_links SET after = ?1 WHERE after = ?2 AND task_id != ?1;",
+                )?;
+                tx.execute(
+                    "DELETE FROM due_links WHERE task_id = ?1 OR after = ?1;",
+                    [removed],
+                )
+                .and_then(|_| tx.execute("DELETE FROM tasks WHERE id = ?1;", [removed]))
+                .map_err(|err| format!("Could not delete task {removed}: {err}"))?;
+            }
+        }
+        tx.commit()
+            .map_err(|err| format!("Could not commit transaction: {err}"))?;
+        Ok(ret)
+    }
+
+    fn add_work_bit(&self, task_id: u64, bit: &WorkBit) -> Result<u64, String> {
+        let res = self
+            .conn
+            .execute(
+                "INSERT INTO work_bits (task_id, datetime, description, cwd, git_repo, git_branch)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
+                (
+                    task_id,
+                    bit.datetime.timestamp(),
+                    &bit.description,
+                    &bit.cwd,
+                    &bit.git_repo,
+                    &bit.git_branch,
+                ),
+            )
+            .map_err(|err| err.to_string())?;
+        assert_eq!(res, 1);
+
+        Ok(self.conn.last_insert_rowid() as u64)
+    }
+}
+
+/// Project of a task among `tasks`, i.e. its top-level parent. Tasks without parent or
+/// subtasks are in no project.
+fn projects<'a>(tasks: &'a [Task]) -> impl Fn(&Task) -> Option<&'a Task> + 'a {
+    let by_id = tasks.iter().map(|t| (t.id, t)).collect::<HashMap<_, _>>();
+    let parents = tasks
+        .iter()
+        .filter_map(|t| t.parent)
+        .collect::<HashSet<_>>();
+    move |t| {
+        let mut root = by_id.get(&t.id).copied()?;
+        while let Some(parent) = root.parent.and_then(|p| by_id.get(&p)) {
+            root = parent;
+        }
+        (root.id != t.id || parents.contains(&t.id)).then_some(root)
+    }
+}
+
+/// Add a column to an existing table, so databases created by older versions pick up new fields
+fn add_column_if_missing(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    decl: &str,
+) -> Result<(), String> {
+    let exists = conn
+        .column_exists(Some(DATABASE_NAME), table, column)
+        .map_err(|err| format!("could not inspect {table} table: {err}"))?;
+    if !exists {
+        log::info!("migrating database: adding column {column} to {table}");
+        conn.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN {column} {decl};"),
+            [],
+        )
+        .map_err(|err| format!("could not add column {column} to {table}: {err}"))?;
+    }
+    Ok(())
+}
+
+/// Default base directories of the platform
+struct PlatformDirs {
+    home: std::path::PathBuf,
+    config: std::path::PathBuf,
+    data: std::path::PathBuf,
+}
+
+impl PlatformDirs {
+    /// `~/.config` and `~/.local/share` on unix including macOS, where rem always kept its files
+    /// there, the roaming and local AppData folders on Windows. `None` if there is no home
+    /// directory.
+    fn detect() -> Option<Self> {
+        let dirs = directories::BaseDirs::new()?;
+        Some(Self::new(
+            cfg!(windows),
+            dirs.home_dir(),
+            dirs.config_dir(),
+            dirs.data_local_dir(),
+        ))
+    }
+
+    /// Directories given the home directory and the roaming and local application data
+    /// directories, which are only used on Windows
+    fn new(
+        windows: bool,
+        home: &std::path::Path,
+        roaming: &std::path::Path,
+        local: &std::path::Path,
+    ) -> Self {
+        if windows {
+            Self {
+                home: home.to_path_buf(),
+                config: roaming.to_path_buf(),
+                data: local.to_path_buf(),
+            }
+        } else {
+            Self {
+                home: home.to_path_buf(),
+                config: home.join(".config"),
+                data: home.join(".local").join("share"),
+            }
+        }
+    }
+}
+
+/// Resolve a base directory from the XDG variable overriding it or the default of the platform
+///
+/// Following the XDG spec, empty and relative values of the variable are ignored.
+///
+/// * `var`: name of the variable, e.g. `XDG_DATA_HOME`
+/// * `value`: value of the variable
+/// * `default`: directory of the platform, `None` if there is no home directory
+fn base_dir(
+    var: &str,
+    value: Option<std::ffi::OsString>,
+    default: Option<std::path::PathBuf>,
+) -> Result<std::path::PathBuf, String> {
+    match value.map(std::path::PathBuf::from) {
+        Some(path) if path.is_absolute() => Ok(path),
+        Some(path) if !path.as_os_str().is_empty() => {
+            log::warn!("ignoring {var}={}, it is not absolute", path.display());
+            default.ok_or("Could not determine home directory".to_string())
+        }
+        _ => default.ok_or("Could not determine home directory".to_string()),
+    }
+}
+
+fn config_home() -> Result<std::path::PathBuf, String> {
+    base_dir(
+        "XDG_CONFIG_HOME",
+        std::env::var_os("XDG_CONFIG_HOME"),
+        PlatformDirs::detect().map(|dirs| dirs.config),
+    )
+}
+
+fn data_home() -> Result<std::path::PathBuf, String> {
+    base_dir(
+        "XDG_DATA_HOME",
+        std::env::var_os("XDG_DATA_HOME"),
+        PlatformDirs::detect().map(|dirs| dirs.data),
+    )
+}
+
+fn home_dir() -> Result<std::path::PathBuf, String> {
+    PlatformDirs::detect()
+        .map(|dirs| dirs.home)
+        .ok_or("Could not determine home directory".to_string())
+}
+
+/// Store where rem runs with a work bit
+///
+/// * `cwd`: record the current working directory
+/// * `git`: record the top level directory and checked out branch of the enclosing git repository
+fn capture_environment(bit: &mut WorkBit, cwd: bool, git: bool) -> Result<(), String> {
+    if cwd {
+        let dir = std::env::current_dir()
+            .map_err(|err| format!("Could not get working directory: {err}"))?;
+        bit.cwd = Some(dir.to_string_lossy().into_owned());
+    }
+
+    if git {
+        let output = run_git(&["rev-parse", "--show-toplevel", "--abbrev-ref", "HEAD"])?;
+        let mut lines = output.lines();
+        bit.git_repo = lines.next().map(str::to_string);
+        // a detached HEAD has no branch
+        bit.git_branch = lines
+            .next()
+            .filter(|branch| *branch != "HEAD")
+            .map(str::to_string);
+    }
+
+    Ok(())
+}
+
+/// Run git with the given arguments, returning its trimmed output
+fn run_git(args: &[&str]) -> Result<String, String> {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|err| format!("Could not run git: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Marks hooks written by `rem git-hook install`, which may be replaced without `--force`
+const GIT_HOOK_MARKER: &str = "# installed by rem git-hook install";
+
+fn install_git_hook(force: bool) -> Result<std::path::PathBuf, String> {
+    let path =
+        std::path::PathBuf::from(run_git(&["rev-parse", "--git-path", "hooks/post-commit"])?);
+    if !force {
+        match std::fs::read_to_string(&path) {
+            Ok(existing) if !existing.contains(GIT_HOOK_MARKER) => {
+                return Err(format!(
+                    "{} already exists. Pass --force to replace it.",
+                    path.display()
+                ))
+            }
+            _ => (),
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|err| format!("Could not create {}: {err}", dir.display()))?;
+    }
+    // the hook must not wait for answers to prompts while committing
+    let hook = format!(
+        "#!/bin/sh\n{GIT_HOOK_MARKER}\nrem --quiet git-hook post-commit < /dev/null || true\n"
+    );
+    std::fs::write(&path, hook)
+        .map_err(|err| format!("Could not write {}: {err}", path.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+            .map_err(|err| format!("Could not make {} executable: {err}", path.display()))?;
+    }
+
+    Ok(path)
+}
+
+/// Ids of the tasks a commit message refers to with `rem:#<task id>`, in order and without
+/// duplicates
+fn commit_task_refs(message: &str) -> Vec<u64> {
+    let mut ids = Vec::new();
+    for (idx, marker) in message.match_indices("rem:#") {
+        let rest = &message[idx + marker.len()..];
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(rest, |end| &rest[..end]);
+        if let Ok(id) = digits.parse() {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    ids
+}
+
+/// Read the clipboard contents using the first available platform tool
+fn read_clipboard() -> Result<String, String> {
+    read_clipboard_with(&[
+        ("wl-paste", &["--no-newline"]),
+        ("xclip", &["-out", "-selection", "clipboard"]),
+        ("xsel", &["--output", "--clipboard"]),
+        ("pbpaste", &[]),
+        ("powershell", &["-NoProfile", "-Command", "Get-Clipboard"]),
+    ])
+}
+
+/// Read the clipboard contents using the first of `tools` that runs successfully, e.g. xclip if
+/// wl-paste is installed but there is no Wayland session
+fn read_clipboard_with(tools: &[(&str, &[&str])]) -> Result<String, String> {
+    let mut failures = Vec::new();
+    for (tool, args) in tools {
+        let output = match std::process::Command::new(tool).args(*args).output() {
+            Ok(output) => output,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                failures.push(format!("Could not run {tool}: {err}"));
+                continue;
+            }
+        };
+        if !output.status.success() {
+            failures.push(format!(
+                "{tool} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+            continue;
+        }
+        return String::from_utf8(output.stdout)
+            .map_err(|_| "Clipboard contents are not valid utf8".to_string());
+    }
+
+    if !failures.is_empty() {
+        return Err(failures.join("; "));
+    }
+    Err(format!(
+        "No clipboard tool found. Install one of {}.",
+        tools
+            .iter()
+            .map(|(tool, _)| *tool)
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+/// Split captured text into a title (its first non-empty line) and an optional description
+fn split_capture(text: String) -> Result<(String, Option<String>), String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("Nothing to capture. Input is empty.".to_string());
+    }
+
+    let (title, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let rest = rest.trim();
+    let description = (!rest.is_empty()).then(|| rest.to_string());
+    Ok((title.trim().to_string(), description))
+}
+
+/// Read a file, or stdin for `-`
+fn read_input(path: &str) -> Result<String, String> {
+    let mut repr = String::new();
+    let read = if path == "-" {
+        std::io::stdin().read_to_string(&mut repr).map(|_| ())
+    } else {
+        std::fs::read_to_string(path).map(|text| repr = text)
+    };
+    read.map(|_| repr)
+        .map_err(|err| format!("Could not read {path}: {err}"))
+}
+
+/// Report the id of a created record, bare in porcelain mode for use in scripts
+fn print_created(kind: &str, id: u64, porcelain: Option<porcelain::Version>) {
+    if porcelain.is_some() {
+        println!("{id}");
+    } else {
+        println!("Created {kind} {id}.");
+    }
+}
+
+/// Report the id of a record updated in place of creating it, see `--external-id`
+fn print_updated(kind: &str, id: u64, porcelain: Option<porcelain::Version>) {
+    if porcelain.is_some() {
+        println!("{id}");
+    } else {
+        println!("Updated {kind} {id}.");
+    }
+}
+
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings.iter() {
+        eprintln!("WARNING: {warning}");
+    }
+}
+
+/// Ask a yes/no question on the terminal, defaulting to no
+fn confirm(question: &str) -> bool {
+    print!("{question} [y/N] ");
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+fn config_path() -> Result<std::path::PathBuf, String> {
+    let mut path = config_home()?;
+    path.push(HOME_DIR);
+    path.push(CONFIG_FILE);
+    Ok(path)
+}
+
+fn load_config() -> Result<Config, String> {
+    let path = config_path()?;
+
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    let repr = std::fs::read_to_string(&path)
+        .map_err(|err| format!("Could not read {}: {err}", path.display()))?;
+    let config = Config::from_toml(&repr)
+        .map_err(|err| format!("Could not parse {}: {err}", path.display()))?;
+
+    let command = Args::command();
+    if let Some(name) = config
+        .aliases
+        .keys()
+        .find(|name| command.find_subcommand(name).is_some())
+    {
+        return Err(format!("alias '{name}' shadows a built-in command"));
+    }
+    Ok(config)
+}
+
+/// Ask a question on stderr, keeping it out of piped or porcelain output, returning the trimmed
+/// answer
+fn prompt(question: &str) -> Result<String, String> {
+    eprint!("{question}: ");
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .map_err(|err| format!("Could not read answer: {err}"))?;
+    Ok(answer.trim().to_string())
+}
+
+/// Ask for a time of day until a valid one or nothing is entered
+fn prompt_time(question: &str) -> Result<Option<NaiveTime>, String> {
+    loop {
+        let answer = prompt(question)?;
+        if answer.is_empty() {
+            return Ok(None);
+        }
+        match NaiveTime::parse_from_str(&answer, "%H:%M") {
+            Ok(time) => return Ok(Some(time)),
+            Err(err) => eprintln!("Invalid time '{answer}', expected HH:MM: {err}"),
+        }
+    }
+}
+
+/// Config file contents with the settings chosen in `rem init`
+fn init_config_repr(
+    iso_dates: bool,
+    lenient_dates: bool,
+    default_due_time: Option<NaiveTime>,
+    workday: Option<(NaiveTime, NaiveTime)>,
+    color: Option<bool>,
+    default_command: &str,
+) -> String {
+    let mut ret = String::from("# written by `rem init`\n");
+    if iso_dates {
+        ret.push_str("iso_dates = true\n");
+    }
+    if lenient_dates {
+        ret.push_str("lenient_dates = true\n");
+    }
+    if let Some(time) = default_due_time {
+        ret.push_str(&format!(
+            "default_due_time = \"{}\"\n",
+            time.format("%H:%M")
+        ));
+    }
+    if let Some((start, end)) = workday {
+        ret.push_str(&format!("workday_start = \"{}\"\n", start.format("%H:%M")));
+        ret.push_str(&format!("workday_end = \"{}\"\n", end.format("%H:%M")));
+    }
+    if let Some(color) = color {
+        ret.push_str(&format!("color = {color}\n"));
+    }
+    if !default_command.is_empty() {
+        let escaped = default_command.replace('\\', "\\\\").replace('"', "\\\"");
+        ret.push_str(&format!("default_command = \"{escaped}\"\n"));
+    }
+    ret
+}
+
+/// Files of other todo applications below `home` that `rem init` offers to import: todo.txt in
+/// the home directory or `~/.todo`, and the data directory of Taskwarrior
+fn importable(home: &std::path::Path) -> Vec<(ImportFormat, std::path::PathBuf)> {
+    let todo_txt = [home.join("todo.txt"), home.join(".todo/todo.txt")]
+        .into_iter()
+        .find(|path| path.is_file());
+    let taskwarrior = Some(home.join(".task")).filter(|path| path.is_dir());
+    todo_txt
+        .map(|path| (ImportFormat::TodoTxt, path))
+        .into_iter()
+        .chain(taskwarrior.map(|path| (ImportFormat::Taskwarrior, path)))
+        .collect()
+}
+
+/// Create the data directory and write a config file from the answers to a few questions
+fn init() -> Result<(), String> {
+    let data_dir = data_dir()?;
+    let config_path = config_path()?;
+    println!("Data is stored in {}", data_dir.display());
+
+    if config_path.exists() && !confirm(&format!("{} exists. Overwrite it?", config_path.display()))
+    {
+        return Ok(());
+    }
+
+    let iso_dates = loop {
+        match prompt("Date format to show (dd.mm.yyyy, iso) [dd.mm.yyyy]")?
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "dd.mm.yyyy" => break false,
+            "iso" => break true,
+            other => eprintln!("Invalid choice '{other}'"),
+        }
+    };
+    let lenient_dates =
+        confirm("Besides DD.MM.YYYY and ISO 8601, also read dates like 1.7, 1.7.25 and 07/01?");
+    let default_due_time =
+        prompt_time("Time that due dates without a time are due at (HH:MM, empty for all-day)")?;
+    let workday = loop {
+        let Some(start) = prompt_time("Start of your workday (HH:MM, empty for none)")? else {
+            break None;
+        };
+        let Some(end) = prompt_time("End of your workday (HH:MM)")? else {
+            break None;
+        };
+        if start < end {
+            break Some((start, end));
+        }
+        eprintln!("The workday needs to start before it ends.");
+    };
+    let color = loop {
+        match prompt("Colored output (auto, always, never) [auto]")?.as_str() {
+            "" | "auto" => break None,
+            "always" => break Some(true),
+            "never" => break Some(false),
+            other => eprintln!("Invalid choice '{other}'"),
+        }
+    };
+    let default_command =
+        prompt("Command to run without arguments, e.g. 'tasks --sort due' (empty for help)")?;
+
+    let imports = importable(&home_dir()?)
+        .into_iter()
+        .filter(|(format, path)| match format {
+            ImportFormat::TodoTxt | ImportFormat::Ics => {
+                confirm(&format!("Import the tasks of {}?", path.display()))
+            }
+            ImportFormat::Taskwarrior => confirm("Import the tasks of Taskwarrior?"),
+        })
+        .collect::<Vec<_>>();
+
+    let repr = init_config_repr(
+        iso_dates,
+        lenient_dates,
+        default_due_time,
+        workday,
+        color,
+        &default_command,
+    );
+    let config = Config::from_toml(&repr)?;
+
+    let dir = config_path.parent().expect("config file is in a directory");
+    std::fs::create_dir_all(dir)
+        .map_err(|err| format!("Could not create {}: {err}", dir.display()))?;
+    std::fs::write(&config_path, repr)
+        .map_err(|err| format!("Could not write {}: {err}", config_path.display()))?;
+    println!("Wrote {}", config_path.display());
+
+    if imports.is_empty() {
+        return Ok(());
+    }
+    let app =
+        get_database_connection(&config.database).and_then(|conn| App::try_init(conn, config))?;
+    for (format, path) in imports {
+        let entries = match format {
+            ImportFormat::TodoTxt => std::fs::read_to_string(&path)
+                .map_err(|err| format!("Could not read {}: {err}", path.display()))
+                .map(|repr| import::todo_txt(&repr, app.now)),
+            ImportFormat::Ics => std::fs::read_to_string(&path)
+                .map_err(|err| format!("Could not read {}: {err}", path.display()))
+                .map(|repr| import::ics(&repr, app.now)),
+            ImportFormat::Taskwarrior => std::process::Command::new("task")
+                .arg("export")
+                .output()
+                .map_err(|err| format!("Could not run `task export`: {err}"))
+                .and_then(|output| match output.status.success() {
+                    true => Ok(output),
+                    false => Err(format!("`task export` failed: {}", output.status)),
+                })
+                .and_then(|output| {
+                    import::taskwarrior(&String::from_utf8_lossy(&output.stdout), app.now)
+                }),
+        }?;
+        let summary = app.import(entries, 500, true, || ())?;
+        for (line, err) in summary.failed.iter() {
+            eprintln!("WARNING: skipped task {line}: {err}");
+        }
+        println!("Imported tasks: {summary}.");
+    }
+    journal::record(&app.conn, "rem init", false, app.now)?;
+    Ok(())
+}
+
+/// Tables and columns that [`App::try_init`] would still create
+fn pending_migrations(conn: &rusqlite::Connection) -> Result<Vec<String>, String> {
+    let mut ret = Vec::new();
+    for table in TABLES {
+        let exists = conn
+            .table_exists(Some(DATABASE_NAME), table)
+            .map_err(|err| format!("could not inspect database: {err}"))?;
+        if !exists {
+            ret.push(format!("create table {table}"));
+        }
+    }
+    for (table, column, _) in ADDED_COLUMNS {
+        let table_exists = conn
+            .table_exists(Some(DATABASE_NAME), table)
+            .map_err(|err| format!("could not inspect database: {err}"))?;
+        let exists = conn
+            .column_exists(Some(DATABASE_NAME), table, column)
+            .map_err(|err| format!("could not inspect {table} table: {err}"))?;
+        if table_exists && !exists {
+            ret.push(format!("add column {table}.{column}"));
+        }
+    }
+    Ok(ret)
+}
+
+/// Print where rem keeps its files, the state of the database and the detected environment
+/// Print what rem runs with, the time as ISO 8601 with `iso` or `iso_dates` in the config
+fn doctor(mut iso: bool) -> Result<(), String> {
+    println!("rem {}", env!("CARGO_PKG_VERSION"));
+
+    let config_path = config_path()?;
+    if config_path.exists() {
+        match load_config() {
+            Ok(config) => {
+                iso |= config.iso_dates;
+                println!("config:     {} (ok)", config_path.display())
+            }
+            Err(err) => println!(
+                "config:     {} ({})",
+                config_path.display(),
+                err.bright_red()
+            ),
+        }
+    } else {
+        println!(
+            "config:     {} (not found, using defaults)",
+            config_path.display()
+        );
+    }
+
+    let mut data_dir = data_home()?;
+    data_dir.push(HOME_DIR);
+    let writable = if !data_dir.exists() {
+        "not created yet".to_string()
+    } else {
+        let probe = data_dir.join(".doctor");
+        match std::fs::write(&probe, "") {
+            Ok(()) => {
+                let _ = std::fs::remove_file(&probe);
+                "writable".to_string()
+            }
+            Err(err) => format!("{}", format!("not writable: {err}").bright_red()),
+        }
+    };
+    println!("data dir:   {} ({writable})", data_dir.display());
+
+    let db_path = data_dir.join(DATABASE_FILE);
+    if !db_path.exists() {
+        println!("database:   {} (not created yet)", db_path.display());
+    } else {
+        let size = std::fs::metadata(&db_path)
+            .map(|m| format!("{} KiB", m.len().div_ceil(1024)))
+            .unwrap_or_else(|err| format!("unknown size: {err}"));
+        println!("database:   {} ({size})", db_path.display());
+
+        let conn = rusqlite::Connection::open_with_flags(
+            &db_path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )
+        .map_err(|err| format!("Could not open database: {err}"))?;
+        let version: i64 = conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(|err| format!("Could not read schema version: {err}"))?;
+        println!("schema:     version {version} (this rem uses {SCHEMA_VERSION})");
+
+        let stored_tz = conn
+            .query_one(
+                "SELECT value FROM meta WHERE key = 'timezone';",
+                [],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .ok()
+            .flatten()
+            .unwrap_or("unknown".to_string());
+        println!("db tz:      {stored_tz}");
+
+        let pending = pending_migrations(&conn)?;
+        if pending.is_empty() {
+            println!("migrations: none pending");
+        } else {
+            println!(
+                "migrations: {} pending, applied on the next run",
+                pending.len()
+            );
+            for migration in pending.iter() {
+                println!("  - {migration}");
+            }
+        }
+
+        let count = |query: &str| {
+            conn.query_one(query, [], |row| row.get::<_, u64>(0))
+                .map(|n| n.to_string())
+                .unwrap_or("-".to_string())
+        };
+        println!(
+            "rows:       {} tasks ({} open), {} reminders, {} work bits, {} tags",
+            count("SELECT COUNT(*) FROM tasks;"),
+            count("SELECT COUNT(*) FROM tasks WHERE completed IS NULL;"),
+            count("SELECT COUNT(*) FROM reminders;"),
+            count("SELECT COUNT(*) FROM work_bits;"),
+            count("SELECT COUNT(DISTINCT tag) FROM tags;"),
+        );
+    }
+
+    let now = Local::now();
+    let tz = std::env::var("TZ").unwrap_or("unset, using the system timezone".to_string());
+    println!("timezone:   UTC{} (TZ: {tz})", now.format("%:z"));
+    let locale = ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or("unset".to_string());
+    println!("locale:     {locale}");
+    println!(
+        "now:        {}",
+        now.format(if iso {
+            rem::ISO_DATETIME_FMT
+        } else {
+            rem::DATETIME_FMT
+        })
+    );
+    Ok(())
+}
+
+/// Name of the timezone times are shown in, e.g. `Europe/Berlin`
+fn timezone_name() -> Option<String> {
+    if let Some(tz) = std::env::var("TZ")
+        .ok()
+        .map(|tz| tz.trim_start_matches(':').to_string())
+        .filter(|tz| !tz.is_empty())
+    {
+        return Some(tz);
+    }
+    if let Ok(tz) = std::fs::read_to_string("/etc/timezone") {
+        return Some(tz.trim().to_string());
+    }
+    let target = std::fs::read_link("/etc/localtime").ok()?;
+    let target = target.to_str()?;
+    target
+        .split_once("zoneinfo/")
+        .map(|(_, name)| name.to_string())
+}
+
+/// Interpret and show all times of this invocation in the timezone `tz`
+fn set_timezone(tz: &str) -> Result<(), String> {
+    // chrono asks Windows for the local timezone and ignores TZ there
+    if cfg!(windows) {
+        return Err(
+            "--tz is not supported on Windows, change the system timezone instead".to_string(),
+        );
+    }
+    let zoneinfo = std::env::var_os("TZDIR")
+        .map(std::path::PathBuf::from)
+        .into_iter()
+        .chain(
+            [
+                "/usr/share/zoneinfo",
+                "/usr/lib/zoneinfo",
+                "/usr/share/lib/zoneinfo",
+            ]
+            .map(std::path::PathBuf::from),
+        )
+        .find(|dir| dir.is_dir());
+    check_timezone(tz, zoneinfo.as_deref())?;
+    std::env::set_var("TZ", tz);
+    Ok(())
+}
+
+/// Check that `tz` is UTC, a POSIX TZ string like `CET-1CEST,M3.5.0,M10.5.0/3` or a timezone of
+/// the database in `zoneinfo`
+///
+/// Unknown timezones would silently be taken as UTC.
+fn check_timezone(tz: &str, zoneinfo: Option<&std::path::Path>) -> Result<(), String> {
+    // a name of at least three letters or one in angle brackets, followed by the offset
+    let offset = match tz.strip_prefix('<') {
+        Some(quoted) => quoted.split_once('>').map(|(_, rest)| rest),
+        None => tz
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .filter(|len| *len >= 3)
+            .map(|len| &tz[len..]),
+    };
+    if tz == "UTC"
+        || offset.is_some_and(|rest| {
+            rest.starts_with(|c: char| c == '+' || c == '-' || c.is_ascii_digit())
+        })
+    {
+        return Ok(());
+    }
+    match zoneinfo {
+        Some(dir) if dir.join(tz).is_file() && !tz.split('/').any(|part| part == "..") => Ok(()),
+        Some(_) => Err(format!("Unknown timezone '{tz}'")),
+        None => Err(format!(
+            "No timezone database to look up '{tz}' in, give the timezone as a POSIX TZ string \
+             like CET-1CEST,M3.5.0,M10.5.0/3 instead"
+        )),
+    }
+}
+
+/// Data directory, created if it does not exist
+fn data_dir() -> Result<std::path::PathBuf, String> {
+    let mut path = data_home()?;
+    path.push(HOME_DIR);
+
+    if !path.exists() {
+        std::fs::create_dir_all(&path)
+            .map_err(|err| format!("Could not create data directory: {err}"))?;
+    } else if path.is_file() {
+        return Err("Could not get data directory. Is a file.".to_string());
+    }
+    Ok(path)
+}
+
+/// Log to stderr at the level chosen by the number of `-v` flags. `REM_LOG` takes precedence,
+/// using `env_logger` filter syntax.
+fn init_logging(verbosity: u8) {
+    let level = match verbosity {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+    let mut builder = env_logger::Builder::new();
+    match std::env::var("REM_LOG") {
+        Ok(filter) => builder.parse_filters(&filter),
+        Err(_) => builder.filter_module("rem", level),
+    };
+    builder.init();
+}
+
+/// Log every SQL statement with the time it took
+fn trace_sql(event: TraceEvent<'_>) {
+    if let TraceEvent::Profile(stmt, duration) = event {
+        log::trace!("{} ({duration:?})", stmt.sql());
+    }
+}
+
+/// Socket the daemon listens at, next to the database
+///
+/// Services get the XDG directories of [`service_env`] only, so the socket must not depend on
+/// others like the runtime directory.
+#[cfg(unix)]
+fn daemon_socket() -> Result<std::path::PathBuf, String> {
+    Ok(data_dir()?.join("daemon.sock"))
+}
+
+/// XDG directories set for this rem, so services find the same config and database
+fn service_env() -> Vec<(&'static str, String)> {
+    ["XDG_CONFIG_HOME", "XDG_DATA_HOME"]
+        .into_iter()
+        .filter_map(|var| std::env::var(var).ok().map(|value| (var, value)))
+        .collect()
+}
+
+/// Run a command, failing with its error output if it does not succeed
+fn run_command(command: &str, args: &[&str], stdin: Option<&str>) -> Result<String, String> {
+    let mut child = std::process::Command::new(command)
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Could not run {command}: {err}"))?;
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(input.as_bytes())
+            .map_err(|err| format!("Could not write to {command}: {err}"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("Could not run {command}: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{command} {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Time since the last keyboard or mouse input, if the platform tells, from `ioreg` on macOS and
+/// `xprintidle` on X11
+fn input_idle() -> Option<TimeDelta> {
+    if cfg!(target_os = "macos") {
+        let output = run_command("ioreg", &["-c", "IOHIDSystem", "-d", "4"], None).ok()?;
+        let nanos = output.lines().find_map(|line| {
+            let (_, value) = line.split_once("\"HIDIdleTime\" = ")?;
+            value.trim().parse::<i64>().ok()
+        })?;
+        Some(TimeDelta::nanoseconds(nanos))
+    } else {
+        let millis = run_command("xprintidle", &[], None).ok()?;
+        TimeDelta::try_milliseconds(millis.trim().parse().ok()?)
+    }
+}
+
+/// Host and, if given, path of the database of an `ssh://[user@]host[/path]` peer
+fn ssh_peer(peer: &str) -> Option<(&str, Option<&str>)> {
+    let peer = peer.strip_prefix("ssh://")?;
+    Some(match peer.find('/') {
+        Some(idx) if idx + 1 < peer.len() => (&peer[..idx], Some(&peer[idx..])),
+        Some(idx) => (&peer[..idx], None),
+        None => (peer, None),
+    })
+}
+
+/// Create a directory only the current user can access, to be removed by the caller
+fn private_temp_dir() -> Result<std::path::PathBuf, String> {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default();
+    for attempt in 0..16 {
+        let dir =
+            std::env::temp_dir().join(format!("rem-{}-{nanos:x}-{attempt}", std::process::id()));
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        // fails instead of reusing what someone else created under that name
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(format!("Could not create a temporary directory: {err}")),
+        }
+    }
+    Err("Could not create a temporary directory".to_string())
+}
+
+/// Copy the database of a peer reachable over ssh, merge it and copy it back
+///
+/// Both copies are made with `sqlite3` on the peer, so changes still in its write-ahead log are
+/// included and the merged database replaces it in a single transaction.
+fn sync_over_ssh(app: &App, host: &str, path: Option<&str>) -> Result<(Changes, Changes), String> {
+    let path = match path {
+        Some(path) => path.to_string(),
+        None => run_command("ssh", &[host, "rem", "db", "path"], None)?
+            .trim()
+            .to_string(),
+    };
+    let remote_dir = run_command("ssh", &[host, "mktemp", "-d"], None)?
+        .trim()
+        .to_string();
+    let remote_copy = format!("{remote_dir}/rem.sqlite");
+    let sqlite = |command: &str| {
+        let script = format!(
+            "sqlite3 {} {}",
+            service::shell_quote(&path),
+            service::shell_quote(&format!("{command} \"{remote_copy}\""))
+        );
+        run_command("ssh", &[host, &script], None)
+    };
+    let merged = private_temp_dir().and_then(|dir| {
+        let copy = dir.join("rem.sqlite");
+        let copy_arg = copy.to_string_lossy().into_owned();
+        let remote = format!("{host}:{remote_copy}");
+        let merged = sqlite(".backup")
+            .and_then(|_| run_command("scp", &["-q", &remote, &copy_arg], None))
+            .and_then(|_| app.sync_with(&copy))
+            .and_then(|changes| {
+                run_command("scp", &["-q", &copy_arg, &remote], None)?;
+                sqlite(".restore")?;
+                Ok(changes)
+            });
+        let _ = std::fs::remove_dir_all(&dir);
+        merged
+    });
+    let _ = run_command(
+        "ssh",
+        &[
+            host,
+            &format!("rm -rf {}", service::shell_quote(&remote_dir)),
+        ],
+        None,
+    );
+    merged
+}
+
+/// Write a systemd unit running the daemon and enable it, returning a description of it
+///
+/// * `user`: install a user service rather than a system service running as the current user
+fn install_systemd(user: bool, interval: u64) -> Result<String, String> {
+    let exe =
+        std::env::current_exe().map_err(|err| format!("Could not locate the rem binary: {err}"))?;
+    let (path, unit) = if user {
+        let mut path = config_home()?;
+        path.extend(["systemd", "user", "rem.service"]);
+        (
+            path,
+            service::systemd_unit(&exe, interval, &service_env(), None),
+        )
+    } else {
+        let name = std::env::var("USER")
+            .map_err(|_| "Could not determine the current user".to_string())?;
+        let mut env = service_env();
+        if let Ok(home) = home_dir() {
+            env.push(("HOME", home.display().to_string()));
+        }
+        (
+            std::path::PathBuf::from("/etc/systemd/system/rem.service"),
+            service::systemd_unit(&exe, interval, &env, Some(&name)),
+        )
+    };
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|err| format!("Could not create {}: {err}", dir.display()))?;
+    }
+    std::fs::write(&path, unit)
+        .map_err(|err| format!("Could not write {}: {err}", path.display()))?;
+
+    let scope: &[&str] = if user { &["--user"] } else { &[] };
+    run_command("systemctl", &[scope, &["daemon-reload"]].concat(), None)
+        .and_then(|_| {
+            run_command(
+                "systemctl",
+                &[scope, &["enable", "--now", "rem.service"]].concat(),
+                None,
+            )
+        })
+        .map_err(|err| format!("Wrote {} but could not enable it: {err}", path.display()))?;
+    Ok(path.display().to_string())
+}
+
+/// Write a launchd agent running the daemon and load it, returning a description of it
+fn install_launchd(interval: u64) -> Result<String, String> {
+    let exe =
+        std::env::current_exe().map_err(|err| format!("Could not locate the rem binary: {err}"))?;
+    let mut path = home_dir()?;
+    path.extend([
+        "Library",
+        "LaunchAgents",
+        &format!("{}.plist", service::LAUNCHD_LABEL),
+    ]);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|err| format!("Could not create {}: {err}", dir.display()))?;
+    }
+    let plist = service::launchd_plist(&exe, interval, &service_env());
+    std::fs::write(&path, plist)
+        .map_err(|err| format!("Could not write {}: {err}", path.display()))?;
+
+    let path_arg = path.display().to_string();
+    // loading fails if an agent installed before is still loaded
+    let _ = run_command("launchctl", &["unload", &path_arg], None);
+    run_command("launchctl", &["load", "-w", &path_arg], None)
+        .map_err(|err| format!("Wrote {path_arg} but could not load it: {err}"))?;
+    Ok(path_arg)
+}
+
+/// Create a scheduled task refreshing every `interval` minutes, replacing one created before
+fn install_schtasks(interval: u64) -> Result<String, String> {
+    let exe =
+        std::env::current_exe().map_err(|err| format!("Could not locate the rem binary: {err}"))?;
+    let args = service::schtasks_args(&exe, interval, &service_env())?;
+    run_command(
+        "schtasks",
+        &args.iter().map(String::as_str).collect::<Vec<_>>(),
+        None,
+    )?;
+    Ok(format!(
+        "the scheduled task 'rem' running every {interval} minutes"
+    ))
+}
+
+/// Add an entry refreshing every `interval` minutes to the crontab of the current user,
+/// replacing one added before
+fn install_cron(interval: u64) -> Result<String, String> {
+    let exe =
+        std::env::current_exe().map_err(|err| format!("Could not locate the rem binary: {err}"))?;
+    // crontab -l fails if the user has no crontab yet
+    let existing = run_command("crontab", &["-l"], None).unwrap_or_default();
+    let crontab = service::crontab(&existing, &exe, interval, &service_env())?;
+    run_command("crontab", &["-"], Some(&crontab))?;
+    Ok(format!("a crontab entry running every {interval} minutes"))
+}
+
+/// Tasks that became due soon or overdue since the previous refresh of `rem watch`, with their
+/// new state
+///
+/// * `before`, `after`: states of the open tasks at the previous and the current refresh. Tasks
+///   generated in between count as becoming due if they are due already.
+fn newly_due(before: &HashMap<u64, State>, after: &HashMap<u64, State>) -> Vec<(u64, State)> {
+    let mut ret = after
+        .iter()
+        .filter(|(id, state)| {
+            matches!(state, State::DueSoon | State::Overdue) && before.get(id) != Some(state)
+        })
+        .map(|(id, state)| (*id, *state))
+        .collect::<Vec<_>>();
+    ret.sort_by_key(|(id, _)| *id);
+    ret
+}
+
+/// Apply the `[[reminders]]` blocks of the config, journaled as a change of its own
+fn reconcile_config(app: &App) -> Result<Reconciled, String> {
+    let reconciled = app.reconcile()?;
+    journal::record(&app.conn, "reconcile config", true, app.now)?;
+    Ok(reconciled)
+}
+
+/// Apply the reminders of the config, generate tasks from reminders and check for untracked
+/// time, as the daemon does periodically
+fn refresh(config: &Config) -> Result<String, String> {
+    let conn = get_database_connection(&config.database)?;
+    let app = App::try_init(conn, config.clone())?;
+    if config.reconcile == config::ReconcileMode::Auto {
+        reconcile_config(&app)?;
+    }
+    let limit = config.backfill_limit;
+    let mut notes = Vec::new();
+    let mut summary = match app.clock_rolled_back()? {
+        Some(latest) => format!(
+            "skipped generation, the clock is behind a record created at {}",
+            latest.format(config.datetime_fmt())
+        ),
+        None => {
+            let generated = app.reminders_to_tasks(&mut |_, _| Backfill::Latest(limit))?;
+            if generated.tasks > 0 {
+                notes.push(format!("rem {generated}."));
+            }
+            generated.to_string()
+        }
+    };
+    if let Some(idle) = input_idle() {
+        app.note_input(app.now - idle)?;
+    }
+    journal::record(&app.conn, "daemon refresh", true, app.now)?;
+    if let Some(untracked) = app.untracked()? {
+        summary.push_str(&format!(
+            ", no work session for {}",
+            format_timedelta(untracked)
+        ));
+        notes.push(format!(
+            "No work session for {}.",
+            format_timedelta(untracked)
+        ));
+    }
+    // a missing notification tool must not stop the refreshes
+    if config.notifications && !notes.is_empty() {
+        if let Err(err) = notify::send(notify::Backend::detect(), "rem", &notes.join("\n")) {
+            log::warn!("Could not show notification: {err}");
+        }
+    }
+    Ok(summary)
+}
+
+/// Answer a request to `rem serve` with JSON, tasks and reminders as in porcelain v2
+///
+/// * `access`: what the token of the request allows
+fn handle_request(app: &mut App, request: &serve::Request, access: Access) -> Response {
+    let version = porcelain::Version::V2;
+    let read_only = || Response::error(403, "The token only allows reading");
+    let segments = request
+        .path
+        .trim_matches('/')
+        .split('/')
+        .collect::<Vec<_>>();
+    let task_id = |repr: &str| repr.parse::<u64>().ok();
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["tasks"]) => match app.api_tasks(|name| request.query(name)) {
+            Ok(tasks) => match app.porcelain_tasks(&tasks, version) {
+                Ok(lines) => serve::page(request, &lines),
+                Err(err) => Response::error(500, &err),
+            },
+            Err(err) => Response::error(400, &err),
+        },
+        ("GET", ["tasks", id]) => match task_id(id).map(|id| app.get_task(id)) {
+            Some(Ok(task)) => match app.porcelain_task(&task, version) {
+                Ok(line) => Response::json(200, line),
+                Err(err) => Response::error(500, &err),
+            },
+            Some(Err(err)) => Response::error(404, &err),
+            None => Response::error(404, &format!("Invalid task id '{id}'")),
+        },
+        ("GET", ["reminders", id]) => match task_id(id).map(|id| app.get_reminder(id)) {
+            Some(Ok(reminder)) => Response::json(200, porcelain::reminder(&reminder, version)),
+            Some(Err(err)) => Response::error(404, &err),
+            None => Response::error(404, &format!("Invalid reminder id '{id}'")),
+        },
+        ("GET", ["metrics"]) => match app.metrics() {
+            Ok(metrics) => serve::prometheus(&metrics),
+            Err(err) => Response::error(500, &err),
+        },
+        ("GET", ["reminders"]) => {
+            let active = match request.query("active").map(str::parse::<bool>) {
+                Some(Ok(active)) => Some(active),
+                Some(Err(err)) => return Response::error(400, &format!("Invalid active: {err}")),
+                None => None,
+            };
+            match app.all_reminders() {
+                Ok(mut reminders) => {
+                    reminders
+                        .retain(|r| active.is_none_or(|active| r.is_active(app.now) == active));
+                    reminders.sort_by_key(|r| r.id);
+                    serve::page(
+                        request,
+                        &reminders
+                            .iter()
+                            .map(|r| porcelain::reminder(r, version))
+                            .collect::<Vec<_>>(),
+                    )
+                }
+                Err(err) => Response::error(500, &err),
+            }
+        }
+        ("POST", ["tasks"]) if access < Access::Write => read_only(),
+        ("POST", ["tasks"]) => {
+            let text = serde_json::from_slice::<serde_json::Value>(&request.body)
+                .ok()
+                .and_then(|body| body["text"].as_str().map(String::from));
+            let Some(text) = text else {
+                return Response::error(
+                    400,
+                    "Expected a body like {\"text\": \"buy milk due:tomorrow\"}",
+                );
+            };
+            let (added, warnings) = match app.quick_add(&text) {
+                Ok(added) => added,
+                Err(err) => return Response::error(400, &err),
+            };
+            let (location, record) = match added {
+                Added::Task(id) => (
+                    format!("/tasks/{id}"),
+                    app.get_task(id)
+                        .and_then(|task| app.porcelain_task(&task, version)),
+                ),
+                Added::Reminder(id) => (
+                    format!("/reminders/{id}"),
+                    app.get_reminder(id)
+                        .map(|reminder| porcelain::reminder(&reminder, version)),
+                ),
+            };
+            match record {
+                Ok(record) => {
+                    let mut response = Response::json(201, record);
+                    response.headers.push(("Location".to_string(), location));
+                    for warning in warnings {
+                        let warning = warning.replace(['\r', '\n'], " ");
+                        response
+                            .headers
+                            .push(("X-Rem-Warning".to_string(), warning));
+                    }
+                    response
+                }
+                Err(err) => Response::error(500, &err),
+            }
+        }
+        ("POST", ["tasks", _, "complete"]) if access < Access::Write => read_only(),
+        ("POST", ["tasks", id, "complete"]) => {
+            let Some(id) = task_id(id) else {
+                return Response::error(404, &format!("Invalid task id '{id}'"));
+            };
+            if let Err(err) = app.get_task(id) {
+                return Response::error(404, &err);
+            }
+            match app.complete_task(id).and_then(|_| app.get_task(id)) {
+                Ok(task) => match app.porcelain_task(&task, version) {
+                    Ok(line) => Response::json(200, line),
+                    Err(err) => Response::error(500, &err),
+                },
+                Err(err) => Response::error(400, &err),
+            }
+        }
+        (
+            _,
+            ["tasks"]
+            | ["tasks", _]
+            | ["reminders"]
+            | ["reminders", _]
+            | ["metrics"]
+            | ["tasks", _, "complete"],
+        ) => Response::error(405, &format!("{} is not supported here", request.method)),
+        _ => Response::error(404, &format!("Nothing at {}", request.path)),
+    }
+}
+
+/// Answer a call of `rem rpc` with the result, or the code and message of the error
+fn rpc_call(app: &mut App, call: &Call) -> Result<serde_json::Value, (i64, String)> {
+    let version = porcelain::Version::V2;
+    let record = |line: String| serde_json::from_str(&line).expect("porcelain records are JSON");
+    let failed = |err: String| (rpc::FAILED, err);
+    let invalid = |err: String| (rpc::INVALID_PARAMS, err);
+    let params = &call.params;
+    let text = |name: &str| match &params[name] {
+        serde_json::Value::Null => Ok(None),
+        serde_json::Value::String(value) => Ok(Some(value.as_str())),
+        _ => Err(invalid(format!("Expected a string for {name}"))),
+    };
+    let id = |name: &str| match &params[name] {
+        serde_json::Value::Null => Ok(None),
+        value => value
+            .as_u64()
+            .map(Some)
+            .ok_or(invalid(format!("Expected a task id for {name}"))),
+    };
+    let task_id = || id("id")?.ok_or(invalid("Expected the task id in id".to_string()));
+
+    match call.method.as_str() {
+        "create_task" => {
+            let title = text("title")?
+                .filter(|title| !title.trim().is_empty())
+                .ok_or(invalid("Expected a title".to_string()))?;
+            let due = text("due")?
+                .map(|due| app.parse_due(due))
+                .transpose()
+                .map_err(invalid)?;
+            let priority = text("priority")?
+                .map(str::parse::<Priority>)
+                .transpose()
+                .map_err(invalid)?;
+            let tags = match &params["tags"] {
+                serde_json::Value::Null => Vec::new(),
+                serde_json::Value::Array(tags) => tags
+                    .iter()
+                    .map(|tag| tag.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or(invalid("Expected strings for tags".to_string()))?,
+                _ => return Err(invalid("Expected a list of tags".to_string())),
+            };
+            let (description, context, parent) =
+                (text("description")?, text("context")?, id("parent")?);
+            validate::task(
+                None,
+                due.map(|(due, _, _)| due),
+                due.is_some_and(|(_, all_day, _)| all_day),
+                app.now,
+                &app.config,
+            )
+            .map_err(invalid)?;
+            let id = app
+                .atomically(|| {
+                    let id = app.add_task(
+                        title.to_string(),
+                        description.map(str::to_string),
+                        None,
+                        None,
+                        due.map(|(due, _, _)| due),
+                        due.is_some_and(|(_, all_day, _)| all_day),
+                        priority,
+                        context.map(str::to_string),
+                        None,
+                        &tags,
+                        None,
+                        parent,
+                    )?;
+                    if let Some((_, _, Some(link))) = due {
+                        app.link_due(id, Some(link))?;
+                    }
+                    Ok(id)
+                })
+                .map_err(failed)?;
+            let task = app.get_task(id).map_err(failed)?;
+            let line = app.porcelain_task(&task, version).map_err(failed)?;
+            Ok(record(line))
+        }
+        "list_tasks" => {
+            for name in ["status", "tag", "context", "priority", "due_before"] {
+                text(name)?;
+            }
+            let tasks = app
+                .api_tasks(|name| params[name].as_str())
+                .map_err(invalid)?;
+            let lines = app.porcelain_tasks(&tasks, version).map_err(failed)?;
+            Ok(serde_json::Value::Array(
+                lines.into_iter().map(record).collect(),
+            ))
+        }
+        "complete_task" => {
+            let id = task_id()?;
+            app.get_task(id)
+                .and_then(|_| app.complete_task(id))
+                .and_then(|_| app.get_task(id))
+                .and_then(|task| app.porcelain_task(&task, version))
+                .map(record)
+                .map_err(failed)
+        }
+        "record_work" => {
+            let id = task_id()?;
+            let description = text("description")?.map(str::to_string);
+            app.get_task(id).map_err(failed)?;
+            let bit = app
+                .add_work_bit(id, &WorkBit::new(app.now, description))
+                .map_err(failed)?;
+            Ok(serde_json::json!({ "work_bit": bit }))
+        }
+        other => Err((rpc::METHOD_NOT_FOUND, format!("Unknown method '{other}'"))),
+    }
+}
+
+/// Post the agenda of each day to `chat` from the configured time on, and answer the commands
+/// sent there until failing to open the database
+#[cfg(feature = "bot")]
+fn run_bot(chat: &mut dyn rem::bot::Chat, config: &Config) -> Result<(), String> {
+    let open = || {
+        get_database_connection(&config.database)
+            .and_then(|conn| App::try_init(conn, config.clone()))
+    };
+    loop {
+        let app = open()?;
+        if app.now.time() >= config.bot.agenda_time
+            && app.agenda_posted()? != Some(app.now.date_naive())
+        {
+            match refresh(config) {
+                Ok(summary) => log::info!("{summary}"),
+                Err(err) => eprintln!("ERROR: could not refresh: {err}"),
+            }
+            let agenda = app.bot_reply(&rem::bot::Command::Agenda)?;
+            match chat.send(&agenda) {
+                Ok(()) => app.note_agenda_posted()?,
+                Err(err) => eprintln!("ERROR: could not post agenda: {err}"),
+            }
+        }
+        drop(app);
+
+        let messages = match chat.receive(std::time::Duration::from_secs(30)) {
+            Ok(messages) => messages,
+            Err(err) => {
+                eprintln!("ERROR: could not receive messages: {err}");
+                std::thread::sleep(std::time::Duration::from_secs(60));
+                continue;
+            }
+        };
+        for text in messages {
+            let app = open()?;
+            let reply = text
+                .parse()
+                .and_then(|command| app.bot_reply(&command))
+                .unwrap_or_else(|err| format!("Error: {err}"));
+            journal::record(&app.conn, &format!("rem bot: {text}"), false, app.now)?;
+            if let Err(err) = chat.send(&reply) {
+                eprintln!("ERROR: could not reply: {err}");
+            }
+        }
+    }
+}
+
+fn database_path() -> Result<std::path::PathBuf, String> {
+    let mut path = data_dir()?;
+    path.push(DATABASE_FILE);
+    Ok(path)
+}
+
+/// Run `command` with the terminal of rem, failing if it does not exit successfully
+fn run_interactive(command: &str, args: &[&str], path: &std::path::Path) -> Result<(), String> {
+    let status = std::process::Command::new(command)
+        .args(args)
+        .arg(path)
+        .status()
+        .map_err(|err| format!("Could not run {command}: {err}"))?;
+    if !status.success() {
+        return Err(format!("{command} exited with {status}"));
+    }
+    Ok(())
+}
+
+/// Open the config file in the editor of the user, reporting problems of the edited config
+fn edit_config() -> Result<(), String> {
+    let path = config_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|err| format!("Could not create {}: {err}", dir.display()))?;
+    }
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or("vi".to_string());
+    let mut words = editor.split_whitespace();
+    let command = words.next().unwrap_or("vi");
+    run_interactive(command, &words.collect::<Vec<_>>(), &path)?;
+    load_config().map(|_| ()).map_err(|err| {
+        format!("{err}\nRun `rem config edit` again to fix it or `rem init` to start over.")
+    })
+}
+
+/// Size of the database file and its write-ahead log in bytes
+fn database_size(path: &std::path::Path) -> u64 {
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+    [path.as_os_str(), &wal]
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Rebuild the database file, refresh the statistics of the query planner and checkpoint the
+/// write-ahead log, returning the size of the database before and after
+fn vacuum(conn: &rusqlite::Connection, path: &std::path::Path) -> Result<(u64, u64), String> {
+    let before = database_size(path);
+    conn.execute_batch("VACUUM; ANALYZE;")
+        .map_err(|err| format!("Could not vacuum database: {err}"))?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE);", [], |_| Ok(()))
+        .map_err(|err| format!("Could not checkpoint write-ahead log: {err}"))?;
+    Ok((before, database_size(path)))
+}
+
+/// Columns of the terminal on stdout, `None` if the output goes elsewhere
+fn terminal_width() -> Option<usize> {
+    console::Term::stdout()
+        .size_checked()
+        .map(|(_, columns)| columns as usize)
+}
+
+/// Set the journal mode, syncing and lock timeout of a connection as configured
+fn configure_connection(
+    conn: &rusqlite::Connection,
+    config: &config::Database,
+) -> Result<(), String> {
+    let (mode, synchronous) = if config.wal {
+        ("WAL", "NORMAL")
+    } else {
+        ("DELETE", "FULL")
+    };
+    // first, as switching the journal mode needs a lock another rem may hold
+    conn.busy_timeout(std::time::Duration::from_millis(config.busy_timeout_ms))
+        .map_err(|err| format!("Could not set busy timeout: {err}"))?;
+    let _: String = conn
+        .pragma_update_and_check(None, "journal_mode", mode, |row| row.get(0))
+        .map_err(|err| format!("Could not set journal mode: {err}"))?;
+    conn.pragma_update(None, "synchronous", synchronous)
+        .map_err(|err| format!("Could not set synchronous mode: {err}"))?;
+    if config.wal {
+        conn.pragma_update(None, "wal_autocheckpoint", config.wal_autocheckpoint)
+            .map_err(|err| format!("Could not set WAL checkpoint interval: {err}"))?;
+    }
+    Ok(())
+}
+
+fn get_database_connection(config: &config::Database) -> Result<rusqlite::Connection, String> {
+    let path = database_path()?;
+
+    if !path.exists() && !config_path()?.exists() {
+        eprintln!(
+            "Creating a new database in {}. Run `rem init` to configure rem.",
+            path.display()
+        );
+    }
+
+    log::debug!("opening database {}", path.display());
+    // TODO: handle the error properly
+    let conn = rusqlite::Connection::open(path)
+        .map_err(|err| format!("Could not open database connection: {err}"))?;
+    if log::log_enabled!(log::Level::Trace) {
+        conn.trace_v2(TraceEventCodes::SQLITE_TRACE_PROFILE, Some(trace_sql));
+    }
+
+    conn.set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_FKEY, true)
+        .map_err(|err| format!("Could not enable foreign key constraints: {err}"))?;
+    configure_connection(&conn, config)?;
+
+    Ok(conn)
+}
+
+/// Options of rem itself taking the next argument as their value, like `--tz Europe/Berlin`
+fn valued_options() -> Vec<String> {
+    Args::command()
+        .get_arguments()
+        .filter(|arg| {
+            !arg.is_positional() && arg.get_action().takes_values() && !arg.is_require_equals_set()
+        })
+        .flat_map(|arg| {
+            let long = arg.get_long().map(|long| format!("--{long}"));
+            let short = arg.get_short().map(|short| format!("-{short}"));
+            long.into_iter().chain(short)
+        })
+        .collect()
+}
+
+/// Whether `action` is handled by [`run_standalone`], as it needs to work without a valid config
+/// or database
+fn standalone(action: &Action) -> bool {
+    match action {
+        Action::Init | Action::Doctor | Action::Config { .. } => true,
+        Action::Db { action } => *action != DbAction::Dedupe,
+        _ => false,
+    }
+}
+
+/// Run init, doctor and the db and config commands not needing the database loaded
+fn run_standalone(action: Action, iso: bool) {
+    match action {
+        Action::Init => {
+            init().unwrap_or_else(|err| {
+                eprintln!("ERROR: could not set up rem: {err}");
+                std::process::exit(1);
+            });
+        }
+        Action::Doctor => {
+            doctor(iso).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not finish diagnostics: {err}");
+                std::process::exit(1);
+            });
+        }
+        Action::Db { action } => {
+            let path = database_path().unwrap_or_else(|err| {
+                eprintln!("ERROR: could not locate database: {err}");
+                std::process::exit(1);
+            });
+            match action {
+                DbAction::Path => println!("{}", path.display()),
+                DbAction::Shell => run_interactive("sqlite3", &[], &path).unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not open database shell: {err}");
+                    std::process::exit(1);
+                }),
+                DbAction::Dedupe => unreachable!("dedupe needs the database loaded"),
+                DbAction::Vacuum => {
+                    let sizes = if path.exists() {
+                        rusqlite::Connection::open(&path)
+                            .map_err(|err| format!("Could not open database: {err}"))
+                            .and_then(|conn| vacuum(&conn, &path))
+                    } else {
+                        Err(format!("No database at {}", path.display()))
+                    };
+                    let (before, after) = sizes.unwrap_or_else(|err| {
+                        eprintln!("ERROR: could not vacuum database: {err}");
+                        std::process::exit(1);
+                    });
+                    println!(
+                        "Vacuumed {}: {} KiB before, {} KiB after.",
+                        path.display(),
+                        before.div_ceil(1024),
+                        after.div_ceil(1024)
+                    );
+                }
+            }
+        }
+        Action::Config { action } => match action {
+            ConfigAction::Path => match config_path() {
+                Ok(path) => println!("{}", path.display()),
+                Err(err) => {
+                    eprintln!("ERROR: could not locate config: {err}");
+                    std::process::exit(1);
+                }
+            },
+            ConfigAction::Edit => edit_config().unwrap_or_else(|err| {
+                eprintln!("ERROR: could not edit config: {err}");
+                std::process::exit(1);
+            }),
+        },
+        _ => unreachable!("not a standalone command"),
+    }
+}
+
+fn main() {
+    // consoles of older Windows versions interpret ANSI colors only when asked to
+    #[cfg(windows)]
+    let _ = colored::control::set_virtual_terminal(true);
+
+    // init, doctor, db and config need to work without a valid config or database, so they are
+    // handled before loading them
+    let early = Args::try_parse();
+    if let Ok(Args {
+        tz: Some(ref tz), ..
+    }) = early
+    {
+        set_timezone(tz).unwrap_or_else(|err| {
+            eprintln!("ERROR: {err}");
+            std::process::exit(1);
+        });
+    }
+    match early.map(|args| (args.action, args.iso)) {
+        Ok((action, iso)) if standalone(&action) => {
+            run_standalone(action, iso);
+            return;
+        }
+        _ => (),
+    }
+
+    let mut config = load_config().unwrap_or_else(|err| {
+        eprintln!("ERROR: could not load config: {err}");
+        std::process::exit(1);
+    });
+
+    if let Some(color) = config.color {
+        colored::control::set_override(color);
+    }
+
+    let valued = valued_options();
+    let valued = valued.iter().map(String::as_str).collect::<Vec<_>>();
+    let args = Args::parse_from(config.expand_alias(std::env::args().collect(), &valued));
+    if let Some(ref tz) = args.tz {
+        set_timezone(tz).unwrap_or_else(|err| {
+            eprintln!("ERROR: {err}");
+            std::process::exit(1);
+        });
+    }
+    // aliases and the default command may expand to them
+    if standalone(&args.action) {
+        run_standalone(args.action, args.iso);
+        return;
+    }
+
+    init_logging(args.log);
+    if args.no_color_symbols {
+        config.theme.symbols = true;
+    }
+    if args.lenient_dates {
+        config.lenient_dates = true;
+    }
+    if args.iso {
+        config.iso_dates = true;
+    }
+    if config.hyperlinks.is_none() {
+        let supported = hyperlink::supported(|var| std::env::var(var).ok());
+        config.hyperlinks = Some(std::io::stdout().is_terminal() && supported);
+    }
+
+    let conn = get_database_connection(&config.database).unwrap_or_else(|err| {
+        eprintln!("Could not get database connection: {err}");
+        std::process::exit(1);
+    });
+
+    let mut app = App::try_init(conn, config).unwrap_or_else(|err| {
+        eprintln!("ERROR: could not initialize application: {err}");
+        std::process::exit(1);
+    });
+    let lenient = app.config.lenient_dates;
+
+    if !args.no_scope {
+        let found = std::env::current_dir()
+            .map_err(|err| format!("could not get working directory: {err}"))
+            .and_then(|dir| Scope::find(&dir));
+        match found {
+            Ok(Some((path, scope))) => {
+                log::info!("scoped to {scope:?} by {}", path.display());
+                app.set_scope(scope).unwrap_or_else(|err| {
+                    eprintln!("ERROR: invalid {}: {err}", path.display());
+                    std::process::exit(1);
+                });
+            }
+            Ok(None) => (),
+            Err(err) => {
+                eprintln!("ERROR: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if args.tz.is_none() {
+        if let Some(current) = timezone_name() {
+            if let Ok(Some(stored)) = app.timezone_to_note(&current) {
+                eprintln!(
+                    "NOTE: times are shown in {current}, the database was started in {stored}. \
+                     Use --tz {stored} to show times as there."
+                );
+            }
+        }
+    }
+
+    let interactive = std::io::stdin().is_terminal();
+    let limit = app.config.backfill_limit;
+    // changes made before undoing would make the undone ones impossible to redo
+    let undoing = matches!(args.action, Action::Undo { .. } | Action::Redo { .. });
+    // other commands leave generation to `rem generate`, the daemon and watch
+    let generating = matches!(
+        args.action,
+        Action::Tasks { .. }
+            | Action::Next { .. }
+            | Action::Complete { .. }
+            | Action::PrintSheet { .. }
+            | Action::Generate { .. }
+    );
+    let rolled_back = if args.force_generation || !generating {
+        None
+    } else {
+        app.clock_rolled_back().unwrap_or_else(|err| {
+            eprintln!("ERROR: could not check the clock: {err}");
+            None
+        })
+    };
+    if let Some(latest) = rolled_back {
+        eprintln!(
+            "WARNING: the clock ({}) is behind a task or reminder created at {}, was it set back? \
+             Skipping generation of tasks from reminders. Pass --force-generation to generate them anyway.",
+            app.now.format(app.config.datetime_fmt()),
+            latest.format(app.config.datetime_fmt())
+        );
+    }
+    // the reminders are brought in line with the config before generating their tasks
+    if generating && app.config.reconcile == config::ReconcileMode::Auto {
+        match reconcile_config(&app) {
+            Ok(reconciled) if reconciled != Reconciled::default() && !args.quiet => {
+                eprintln!("{reconciled}")
+            }
+            Ok(_) => (),
+            Err(err) => eprintln!("ERROR: could not apply the reminders of the config: {err}"),
+        }
+    }
+    let mut ask_backfill = |reminder: &Reminder, missed: usize| {
+        if !interactive {
+            return Backfill::Latest(limit);
+        }
+        let question = format!(
+            "Reminder ({id}) {title} missed {missed} occurrences. Generate [a]ll, the [l]atest {limit} or [c]ollapse them into one catch-up task? [l]",
+            id = reminder.id,
+            title = reminder.title
+        );
+        loop {
+            match prompt(&question).as_deref() {
+                Ok("a" | "all") => return Backfill::All,
+                Ok("" | "l" | "latest") | Err(_) => return Backfill::Latest(limit),
+                Ok("c" | "collapse") => return Backfill::Collapse,
+                Ok(other) => eprintln!("Invalid choice '{other}'"),
+            }
+        }
+    };
+    if generating && rolled_back.is_none() && !matches!(args.action, Action::Generate { .. }) {
+        app.reminders_to_tasks(&mut ask_backfill)
+            .map(|generated| {
+                if (generated.tasks > 0 || generated.reviews > 0) && !args.quiet {
+                    eprintln!("{generated}");
+                }
+            })
+            .unwrap_or_else(|err| eprintln!("ERROR: Could not convert tasks to reminders: {err}"));
+    }
+
+    // status bars poll these periodically, which is no sign of activity
+    let observing = matches!(
+        args.action,
+        Action::Status { .. } | Action::Tracking { .. } | Action::Nudge | Action::Watch { .. }
+    );
+    // stopping a session trimmed to its last activity leaves nothing to split
+    let stopping = matches!(args.action, Action::StopWork);
+    let mut trimmed = None;
+    if !observing && !undoing {
+        let checked = app.check_idle_session(&mut |session, idle| {
+            if !interactive {
+                eprintln!(
+                    "WARNING: the work session on task {} was idle for {}",
+                    session.task_id,
+                    format_timedelta(idle)
+                );
+                return IdleAction::Keep;
+            }
+            let choices = if stopping {
+                "[k]eep it or [t]rim it to then?"
+            } else {
+                "[k]eep it, [t]rim it to then or [s]plit it into two?"
+            };
+            let question = format!(
+                "The work session on task {task} saw no activity since {since} ({idle}). {choices} [k]",
+                task = session.task_id,
+                since = session.last_seen.format(app.config.datetime_fmt()),
+                idle = format_timedelta(idle)
+            );
+            loop {
+                match prompt(&question).as_deref() {
+                    Ok("" | "k" | "keep") | Err(_) => return IdleAction::Keep,
+                    Ok("t" | "trim") => return IdleAction::Trim,
+                    Ok("s" | "split") if !stopping => return IdleAction::Split,
+                    Ok(other) => eprintln!("Invalid choice '{other}'"),
+                }
+            }
+        });
+        match checked {
+            Ok(session) => trimmed = session,
+            Err(err) => eprintln!("ERROR: Could not check the work session: {err}"),
+        }
+    }
+
+    if let Err(err) = journal::record(&app.conn, "refresh", true, app.now) {
+        eprintln!("ERROR: {err}");
+    }
+    let label = format!(
+        "rem {}",
+        std::env::args().skip(1).collect::<Vec<_>>().join(" ")
+    );
+
+    let porcelain = args.porcelain.map(porcelain::Version::from);
+    let notify_daemon = !matches!(args.action, Action::Daemon { .. });
+    match args.action {
+        Action::Db {
+            action: DbAction::Dedupe,
+        } => {
+            let merged = app.dedupe_occurrences().unwrap_or_else(|err| {
+                eprintln!("ERROR: could not merge duplicate occurrences: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            for group in merged.iter() {
+                let removed = group
+                    .removed
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                println!(
+                    "Reminder {} due {}: kept task {}, deleted {removed}, moved {} work bits.",
+                    group.reminder,
+                    group.due.format(app.config.datetime_fmt()),
+                    group.kept,
+                    group.work_bits
+                );
+            }
+            if merged.is_empty() {
+                println!("No duplicate occurrences.");
+            }
+        }
+        Action::Init | Action::Doctor | Action::Db { .. } | Action::Config { .. } => {
+            unreachable!("init, doctor, db and config are run by `run_standalone`")
+        }
+        Action::Tasks {
+            all,
+            sort,
+            include_future,
+            ..
+        } if porcelain.is_some() => {
+            let version = porcelain.expect("porcelain output requested");
+            let tasks = app.sorted_tasks(sort).unwrap_or_else(|err| {
+                eprintln!("Could not show tasks: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            for (task, urgency) in tasks.iter().filter(|(t, _)| {
+                (all || t.completed.is_none()) && (include_future || !t.starts_later(app.now))
+            }) {
+                println!("{}", porcelain::task(task, *urgency, version));
+            }
+        }
+        Action::Show { id, plain } => {
+            let task = app.get_task(id).unwrap_or_else(|err| {
+                eprintln!("Could not show task: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            let display = task.display(true, true, app.now, &app.config);
+            if plain {
+                print!("{display}");
+            } else {
+                print!("{}", display.with_markdown());
+            }
+            if let Ok(Some((offset, after))) = app.due_link(id) {
+                let sign = if offset < TimeDelta::zero() { "-" } else { "+" };
+                println!(
+                    "  due {sign}{} after task {after}",
+                    format_timedelta(offset.abs())
+                );
+            }
+        }
+        Action::Tasks {
+            all,
+            verbose,
+            sort,
+            group_by,
+            include_future,
+            wide,
+        } => {
+            let width = if wide { None } else { terminal_width() };
+            app.show_tasks(all, verbose, sort, group_by, include_future, width)
+                .unwrap_or_else(|err| {
+                    eprintln!("Could not show tasks: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+        }
+        Action::Task {
+            title,
+            description,
+            due,
+            due_start,
+            start,
+            priority,
+            context,
+            tags,
+            estimate,
+            parent,
+            force,
+            dedupe,
+            external_id,
+        } => {
+            let existing = external_id
+                .as_deref()
+                .map(|key| app.find_external("tasks", key))
+                .transpose()
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not add task: {err}");
+                    fail(&app, &label, notify_daemon);
+                })
+                .flatten();
+            // updating the task with the external id is never a duplicate
+            let duplicate = match existing {
+                Some(_) => Ok(None),
+                None => app.open_duplicate(&title),
+            };
+            let duplicate = duplicate.unwrap_or_else(|err| {
+                eprintln!("ERROR: could not add task: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            match duplicate {
+                Some(id) if dedupe => {
+                    if porcelain.is_some() {
+                        println!("{id}");
+                    } else {
+                        println!("Task {id} with that title is open already.");
+                    }
+                    finish(&app, &label, notify_daemon);
+                    return;
+                }
+                Some(id) if !force => {
+                    eprintln!("ERROR: could not add task: task {id} with that title is open already. Pass --force to add it anyway or --dedupe to skip it.");
+                    fail(&app, &label, notify_daemon);
+                }
+                _ => (),
+            }
+
+            let estimate = estimate.map(|x| {
+                parse_duration(x).unwrap_or_else(|err| {
+                    eprintln!("Could not parse estimate: {err}");
+                    fail(&app, &label, notify_daemon);
+                })
+            });
+
+            let due = due.map(|due| {
+                app.parse_due(&due).unwrap_or_else(|err| {
+                    eprintln!("Could not parse due datetime: {}", err);
+                    fail(&app, &label, notify_daemon);
+                })
+            });
+            let (due, due_all_day, due_link) = match due {
+                Some((due, all_day, link)) => (Some(due), all_day, link),
+                None => (None, false, None),
+            };
+
+            let start = start.map(|x| parse_date_time(x, lenient)).map(|x| {
+                x.unwrap_or_else(|err| {
+                    eprintln!("Could not parse start datetime: {}", err);
+                    fail(&app, &label, notify_daemon);
+                })
+            });
+
+            let due_start = due_start.map(|x| parse_date_time(x, lenient)).map(|x| {
+                x.unwrap_or_else(|err| {
+                    eprintln!("Could not parse due window start: {}", err);
+                    fail(&app, &label, notify_daemon);
+                })
+            });
+
+            let warnings = validate::task(due_start, due, due_all_day, app.now, &app.config)
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not add task: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            print_warnings(&warnings);
+
+            if let Some(id) = existing {
+                let updated = app.atomically(|| {
+                    let task = app.get_task(id)?;
+                    app.update_task(&Task {
+                        title,
+                        description,
+                        start,
+                        due_start,
+                        due,
+                        due_all_day,
+                        priority,
+                        context,
+                        estimate,
+                        tags,
+                        parent,
+                        ..task
+                    })?;
+                    app.link_due(id, due_link)
+                });
+                updated.unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not update task: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+                print_updated("task", id, porcelain);
+                finish(&app, &label, notify_daemon);
+                return;
+            }
+
+            // the task must not be stored without its key, or the next run adds it again
+            let id = app
+                .atomically(|| {
+                    let id = app.add_task(
+                        title,
+                        description,
+                        start,
+                        due_start,
+                        due,
+                        due_all_day,
+                        priority,
+                        context,
+                        estimate,
+                        &tags,
+                        None,
+                        parent,
+                    )?;
+                    app.link_due(id, due_link)?;
+                    if let Some(ref key) = external_id {
+                        app.set_external_id("tasks", id, key)?;
+                    }
+                    Ok(id)
+                })
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not add task: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            print_created("task", id, porcelain);
+        }
+        Action::Reschedule { id, due, propagate } => {
+            let (due, all_day, link) = app.parse_due(&due).unwrap_or_else(|err| {
+                eprintln!("Could not parse due datetime: {}", err);
+                fail(&app, &label, notify_daemon);
+            });
+            let due_start = app.get_task(id).map(|task| task.due_start);
+            let warnings = due_start
+                .and_then(|due_start| {
+                    validate::task(due_start, Some(due), all_day, app.now, &app.config)
+                })
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not reschedule task: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            print_warnings(&warnings);
+
+            let moved = app
+                .link_due(id, link)
+                .and_then(|_| app.reschedule(id, due, all_day, propagate))
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not reschedule task: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            for moved in moved {
+                println!("Moved task {moved} along.");
+            }
+            let followers = app.due_followers(id).unwrap_or_default();
+            if !propagate && !followers.is_empty() {
+                let (noun, verb) = match followers.len() {
+                    1 => ("Task", "is"),
+                    _ => ("Tasks", "are"),
+                };
+                let followers = followers.iter().map(|id| id.to_string());
+                eprintln!(
+                    "{noun} {} {verb} due relative to task {id}. Pass --propagate to move dependent tasks along.",
+                    followers.collect::<Vec<_>>().join(", ")
+                );
+            }
+        }
+        Action::Add { text } => {
+            let (_, warnings) = app.quick_add(&text.join(" ")).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not add task: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            print_warnings(&warnings);
+        }
+        Action::Quick { line } => {
+            let parsed =
+                quickadd::parse_line(&line, app.now.date_naive(), lenient).unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not read line: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            // the line refers to its task already, e.g. when the keybinding is pressed twice
+            if parsed.task.is_some() {
+                println!("{line}");
+                // tasks generated on startup are journaled all the same
+                finish(&app, &label, notify_daemon);
+                return;
+            }
+            let (id, warnings) = app
+                .add_quick_task(parsed.quick.clone())
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not add task: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            print_warnings(&warnings);
+            match porcelain {
+                Some(_) => println!("{id}"),
+                None => println!("{}", parsed.rewrite(id)),
+            }
+        }
+        Action::Capture { clipboard, stdin } => {
+            assert!(clipboard ^ stdin, "clap ensures exactly one source");
+            let text = if clipboard {
+                read_clipboard()
+            } else {
+                let mut text = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut text)
+                    .map(|_| text)
+                    .map_err(|err| format!("Could not read stdin: {err}"))
+            };
+
+            let (title, description) = text.and_then(split_capture).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not capture task: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+
+            let id = app
+                .add_task(
+                    title,
+                    description,
+                    None,
+                    None,
+                    None,
+                    false,
+                    None,
+                    None,
+                    None,
+                    &[],
+                    None,
+                    None,
+                )
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not add task: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            print_created("task", id, porcelain);
+        }
+        Action::Next {
+            time,
+            context,
+            count,
+        } => {
+            let time = time.map(|x| {
+                parse_duration(x).unwrap_or_else(|err| {
+                    eprintln!("Could not parse available time: {err}");
+                    fail(&app, &label, notify_daemon);
+                })
+            });
+
+            let recommendations = app
+                .recommend(time, context.as_deref())
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not recommend tasks: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+
+            if recommendations.is_empty() {
+                println!("Nothing to do.");
+            }
+            for (task, score) in recommendations.iter().take(count) {
+                print!("{}", task.display(false, false, app.now, &app.config));
+                println!("  score {:.1}: {}", score.total, score.explain());
+            }
+        }
+        Action::Stats {
+            reminder: None,
+            weeks,
+            format,
+        } => {
+            let activity = app.weekly_activity(weeks).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not collect stats: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            match format {
+                OutputFormat::Table => print!("{}", activity_charts(&activity, &app.config)),
+                OutputFormat::Csv => print!("{}", activity_csv(&activity)),
+                OutputFormat::Json => {
+                    let json = serde_json::to_string_pretty(&activity).unwrap_or_else(|err| {
+                        eprintln!("ERROR: could not serialize stats: {err}");
+                        fail(&app, &label, notify_daemon);
+                    });
+                    println!("{json}");
+                }
+            }
+        }
+        Action::Stats {
+            reminder: Some(reminder),
+            format,
+            ..
+        } => {
+            let occurrences = app.reminder_adherence(reminder).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not collect stats: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            match format {
+                OutputFormat::Table => print!("{}", adherence_table(&occurrences, &app.config)),
+                OutputFormat::Csv => print!("{}", adherence_csv(&occurrences)),
+                OutputFormat::Json => {
+                    let json = serde_json::to_string_pretty(&occurrences).unwrap_or_else(|err| {
+                        eprintln!("ERROR: could not serialize stats: {err}");
+                        fail(&app, &label, notify_daemon);
+                    });
+                    println!("{json}");
+                }
+            }
+        }
+        Action::Heatmap { year } => {
+            let today = app.now.date_naive();
+            let year = year.unwrap_or(today.year());
+            let first = NaiveDate::from_ymd_opt(year, 1, 1).unwrap_or_else(|| {
+                eprintln!("ERROR: invalid year {year}");
+                fail(&app, &label, notify_daemon);
+            });
+            let last = NaiveDate::from_ymd_opt(year, 12, 31)
+                .expect("year is valid")
+                .min(today);
+            if last < first {
+                eprintln!("ERROR: {year} has not started yet");
+                fail(&app, &label, notify_daemon);
+            }
+
+            let activity = app.daily_activity(first, last).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not collect activity: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            print!("{}", heatmap(first, last, &activity));
+        }
+        Action::PrintSheet {
+            date,
+            count,
+            format,
+        } => {
+            let date =
+                quickadd::parse_due(&date, app.now.date_naive(), lenient).unwrap_or_else(|err| {
+                    eprintln!("ERROR: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            let (agenda, priorities, habits) = app.day_sheet(date, count).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not collect the day: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            let sheet = Sheet {
+                date,
+                agenda: agenda.iter().collect(),
+                priorities: priorities.iter().collect(),
+                habits,
+                iso: app.config.iso_dates,
+            };
+            match format {
+                SheetFormat::Html => print!("{}", sheet::html(&sheet)),
+                #[cfg(feature = "pdf")]
+                SheetFormat::Pdf => {
+                    use std::io::Write;
+                    std::io::stdout()
+                        .write_all(&sheet::pdf(&sheet))
+                        .unwrap_or_else(|err| {
+                            eprintln!("ERROR: could not write sheet: {err}");
+                            fail(&app, &label, notify_daemon);
+                        });
+                }
+                #[cfg(not(feature = "pdf"))]
+                SheetFormat::Pdf => {
+                    eprintln!("ERROR: rem was built without PDF support, rebuild it with `--features pdf` or print the HTML sheet");
+                    fail(&app, &label, notify_daemon);
+                }
+            }
+        }
+        Action::Serve { listen } => {
+            let config = app.config.clone();
+            if config.serve.read_token.is_none() && config.serve.write_token.is_none() {
+                eprintln!("ERROR: configure read_token or write_token in the [serve] section");
+                fail(&app, &label, notify_daemon);
+            }
+            let listen = listen.unwrap_or(config.serve.listen.clone());
+            let listener = std::net::TcpListener::bind(&listen).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not listen at {listen}: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            if !args.quiet {
+                eprintln!("Listening at http://{listen}");
+            }
+            serve::serve(&listener, &config.serve, |request, access| {
+                let opened = get_database_connection(&config.database)
+                    .and_then(|conn| App::try_init(conn, config.clone()));
+                let mut app = match opened {
+                    Ok(app) => app,
+                    Err(err) => return Response::error(500, &err),
+                };
+                let response = handle_request(&mut app, request, access);
+                let label = format!("rem serve: {} {}", request.method, request.path);
+                if let Err(err) = journal::record(&app.conn, &label, false, app.now) {
+                    eprintln!("ERROR: {err}");
+                }
+                response
+            });
+        }
+        Action::Rpc => {
+            let mut stdout = std::io::stdout();
+            for line in std::io::stdin().lines() {
+                let line = line.unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not read request: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+                if line.trim().is_empty() {
+                    continue;
+                }
+                app.now = Local::now();
+                let response = Call::parse(&line).map(|call| {
+                    let outcome = rpc_call(&mut app, &call);
+                    // failed calls are journaled too, so nothing they wrote joins the next call
+                    if call.method != "list_tasks" {
+                        finish(&app, &format!("rem rpc: {}", call.method), notify_daemon);
+                    }
+                    let id = call.id.as_ref()?;
+                    Some(match outcome {
+                        Ok(result) => rpc::result(id, result),
+                        Err((code, message)) => rpc::error(id, code, &message),
+                    })
+                });
+                if let Some(response) = response.unwrap_or_else(Some) {
+                    let written = writeln!(stdout, "{response}").and_then(|_| stdout.flush());
+                    if written.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        #[cfg(feature = "bot")]
+        Action::Bot { service } => {
+            let bot = &app.config.bot;
+            let chat: Result<Box<dyn rem::bot::Chat>, String> = match service {
+                BotService::Telegram => match (&bot.telegram_token, bot.telegram_chat) {
+                    (Some(token), Some(chat)) => {
+                        Ok(Box::new(rem::bot::Telegram::new(token.clone(), chat)))
+                    }
+                    _ => Err("Configure telegram_token and telegram_chat in the [bot] section".to_string()),
+                },
+                BotService::Matrix => match (&bot.matrix_homeserver, &bot.matrix_token, &bot.matrix_room, &bot.matrix_user) {
+                    (Some(homeserver), Some(token), Some(room), Some(user)) => {
+                        rem::bot::Matrix::connect(homeserver, token.clone(), room.clone(), user.clone())
+                            .map(|matrix| Box::new(matrix) as Box<dyn rem::bot::Chat>)
+                    }
+                    _ => Err("Configure matrix_homeserver, matrix_token, matrix_room and matrix_user in the [bot] section".to_string()),
+                },
+            };
+            chat.and_then(|mut chat| run_bot(chat.as_mut(), &app.config))
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: bot: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+        }
+        #[cfg(not(feature = "bot"))]
+        Action::Bot { .. } => {
+            eprintln!(
+                "ERROR: rem was built without the chat bot, rebuild it with `--features bot`"
+            );
+            fail(&app, &label, notify_daemon);
+        }
+        Action::Report { by, format } => {
+            let reports = app.report(by).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not create report: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            let budgets = app.budgets().unwrap_or_else(|err| {
+                eprintln!("ERROR: could not sum up budgets: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            for budget in budgets.iter().filter(|b| b.exceeded()) {
+                eprintln!("WARNING: over budget, {budget}");
+            }
+            match format {
+                OutputFormat::Table if !budgets.is_empty() => {
+                    print!("{}\n{}", to_table(&reports), budget_table(&budgets))
+                }
+                OutputFormat::Table => print!("{}", to_table(&reports)),
+                OutputFormat::Csv => print!("{}", to_csv(&reports)),
+                OutputFormat::Json => {
+                    let json = serde_json::to_string_pretty(&reports).unwrap_or_else(|err| {
+                        eprintln!("ERROR: could not serialize report: {err}");
+                        fail(&app, &label, notify_daemon);
+                    });
+                    println!("{json}");
+                }
+            }
+        }
+        Action::Pin { id } => {
+            app.pin(id, None).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not pin task: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+        }
+        Action::Unpin { id } => {
+            app.unpin(id).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not unpin task: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+        }
+        Action::Order { id, before } => {
+            app.pin(id, Some(before)).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not order task: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+        }
+        Action::Tag { tag, ids } => {
+            app.tag_tasks(&tag, &ids).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not tag tasks: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+        }
+        Action::Untag { tag, ids } => {
+            app.untag_tasks(&tag, &ids).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not untag tasks: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+        }
+        Action::Tags { prune_completed } => {
+            if prune_completed {
+                let pruned = app.prune_tags().unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not prune tags: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+                for tag in pruned.iter() {
+                    println!("Removed #{tag}");
+                }
+            }
+
+            let mut usage = app.tag_usage().unwrap_or_else(|err| {
+                eprintln!("ERROR: could not list tags: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            let width = usage.iter().map(|u| u.tag.len()).max().unwrap_or(0);
+            for u in usage.iter() {
+                println!(
+                    "#{tag:<width$}  {open:>4} open  {total:>4} total  {reminders:>3} reminders",
+                    tag = u.tag,
+                    open = u.open,
+                    total = u.total,
+                    reminders = u.reminders
+                );
+            }
+
+            let uses = |usage: &[TagUsage], tag: &str| {
+                usage
+                    .iter()
+                    .find(|u| u.tag == tag)
+                    .map(|u| u.total + u.reminders)
+                    .unwrap_or(0)
+            };
+            // pairs are found again after every merge, as it may remove a tag of later pairs
+            let mut declined = HashSet::new();
+            loop {
+                let names = usage.iter().map(|u| u.tag.clone()).collect::<Vec<_>>();
+                let next = tags::near_duplicates(&names)
+                    .into_iter()
+                    .find_map(|(a, b)| {
+                        // merge the less used variant into the more used one
+                        let pair = if uses(&usage, &a) < uses(&usage, &b) {
+                            (a, b)
+                        } else {
+                            (b, a)
+                        };
+                        (!declined.contains(&pair)).then_some(pair)
+                    });
+                let Some((from, to)) = next else {
+                    break;
+                };
+                if !interactive {
+                    println!("#{from} looks like a duplicate of #{to}");
+                    declined.insert((from, to));
+                } else if confirm(&format!("Merge #{from} into #{to}?")) {
+                    app.retag(&from, &to).unwrap_or_else(|err| {
+                        eprintln!("ERROR: could not merge tags: {err}");
+                        fail(&app, &label, notify_daemon);
+                    });
+                    usage = app.tag_usage().unwrap_or_else(|err| {
+                        eprintln!("ERROR: could not list tags: {err}");
+                        fail(&app, &label, notify_daemon);
+                    });
+                } else {
+                    declined.insert((from, to));
+                }
+            }
+        }
+        Action::Retag { from, to } => {
+            let count = app.retag(&from, &to).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not rename tag: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            println!(
+                "Renamed #{} to #{} on {count} tasks and reminders.",
+                normalize_tag(&from),
+                normalize_tag(&to)
+            );
+        }
+        Action::Split { id } => {
+            let task = app.get_task(id).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not split task: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+
+            println!(
+                "Subtask titles for ({id}) {}, one per line. End with an empty line:",
+                task.title
+            );
+            let mut titles = Vec::new();
+            for line in std::io::stdin().lines() {
+                let line = line.unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not read subtask title: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+                let line = line.trim();
+                if line.is_empty() {
+                    break;
+                }
+                titles.push(line.to_string());
+            }
+
+            let distribute = match task.estimate {
+                Some(estimate) if !titles.is_empty() => confirm(&format!(
+                    "Distribute the estimate of {} across {} subtasks?",
+                    format_timedelta(estimate),
+                    titles.len()
+                )),
+                _ => false,
+            };
+
+            app.split_task(id, &titles, distribute)
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not split task: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+        }
+        Action::Duplicate { id, due } => {
+            let due = due.map(|due| {
+                let (due, all_day) = parse_date_time_all_day(due, lenient).unwrap_or_else(|err| {
+                    eprintln!("Could not parse due datetime: {}", err);
+                    fail(&app, &label, notify_daemon);
+                });
+                app.apply_default_due_time(due, all_day)
+            });
+            if let Some((due, all_day)) = due {
+                let warnings = validate::task(None, Some(due), all_day, app.now, &app.config)
+                    .unwrap_or_else(|err| {
+                        eprintln!("ERROR: could not duplicate task: {err}");
+                        fail(&app, &label, notify_daemon);
+                    });
+                print_warnings(&warnings);
+            }
+
+            let id = app.duplicate_task(id, due).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not duplicate task: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            print_created("task", id, porcelain);
+        }
+        Action::DeleteTask { id } => {
+            app.delete_task(id).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not delete task: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+        }
+        Action::Start { task_id } => {
+            let id = app.start_session(task_id).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not start work session: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            print_created("work session", id, porcelain);
+        }
+        Action::StopWork => {
+            let session = trimmed
+                .map_or_else(|| app.stop_session(app.now), Ok)
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not stop work session: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            println!(
+                "Worked {} on task {}.",
+                format_timedelta(session.duration(app.now)),
+                session.task_id
+            );
+        }
+        Action::Tracking { short } => {
+            let tracking = app.tracking(short).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not show work session: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            match tracking {
+                Some(tracking) => println!("{tracking}"),
+                // status bars show nothing rather than a message
+                None if short => (),
+                None => println!("No work session is running."),
+            }
+        }
+        Action::Timesheet {
+            from,
+            to,
+            by,
+            format,
+            round,
+            client,
+            rate,
+        } => {
+            let parse_day = |repr: String| {
+                parse_date_time_all_day(repr, lenient)
+                    .map(|(dt, _)| dt.date_naive())
+                    .unwrap_or_else(|err| {
+                        eprintln!("Could not parse day: {err}");
+                        fail(&app, &label, notify_daemon);
+                    })
+            };
+            let today = app.now.date_naive();
+            let first = from.map(parse_day).unwrap_or(today.with_day(1).unwrap());
+            let last = to.map(parse_day).unwrap_or(today);
+            let round = round.map(|x| {
+                parse_duration(x).unwrap_or_else(|err| {
+                    eprintln!("Could not parse rounding: {err}");
+                    fail(&app, &label, notify_daemon);
+                })
+            });
+
+            let rate = match (rate, &client) {
+                (Some(rate), _) => Some(rate),
+                (None, Some(client)) => match app.config.rates.get(client) {
+                    Some(rate) => Some(*rate),
+                    None => {
+                        eprintln!(
+                            "ERROR: no rate configured for client '{client}'. Add it to [rates] in the config or pass --rate"
+                        );
+                        fail(&app, &label, notify_daemon);
+                    }
+                },
+                (None, None) => None,
+            };
+
+            let worked = app
+                .worked(first, last, by, client.as_deref())
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not build timesheet: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            let entries = timesheet(worked, round);
+            match format {
+                TimesheetFormat::Md => print!("{}", timesheet_markdown(&entries, rate)),
+                TimesheetFormat::Csv => print!("{}", timesheet_csv(&entries, rate)),
+            }
+        }
+        Action::Dump => {
+            let dump = app.dump().unwrap_or_else(|err| {
+                eprintln!("ERROR: could not dump database: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            println!("{dump:#}");
+        }
+        Action::Ics => {
+            let tasks = app.open_tasks().unwrap_or_else(|err| {
+                eprintln!("ERROR: could not export tasks: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            print!("{}", ics::calendar(&tasks, app.now));
+        }
+        Action::Load { path, merge } => {
+            let repr = if path == "-" {
+                let mut repr = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut repr)
+                    .map(|_| repr)
+                    .map_err(|err| format!("Could not read stdin: {err}"))
+            } else {
+                std::fs::read_to_string(&path)
+                    .map_err(|err| format!("Could not read {path}: {err}"))
+            };
+            repr.and_then(|repr| {
+                serde_json::from_str(&repr).map_err(|err| format!("Could not parse dump: {err}"))
+            })
+            .and_then(|dump| {
+                if !merge {
+                    return app.load(&dump);
+                }
+                let changes = app.merge_dump(&dump)?;
+                println!(
+                    "Merged {path}: {} copied and {} deleted.",
+                    changes.copied, changes.deleted
+                );
+                Ok(())
+            })
+            .unwrap_or_else(|err| {
+                eprintln!("ERROR: could not load dump: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+        }
+        Action::Query { sql, format } => {
+            let (columns, rows) = app.query(&sql).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not query database: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            let header = columns.iter().map(String::as_str).collect::<Vec<_>>();
+            let cells = rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|value| match value {
+                            serde_json::Value::Null => String::new(),
+                            serde_json::Value::String(s) => s.clone(),
+                            value => value.to_string(),
+                        })
+                        .collect()
+                })
+                .collect::<Vec<_>>();
+            match format {
+                OutputFormat::Table => print!("{}", render::table(&header, &cells)),
+                OutputFormat::Csv => print!("{}", render::csv(&header, &cells)),
+                OutputFormat::Json => {
+                    let rows = rows
+                        .into_iter()
+                        .map(|row| {
+                            columns
+                                .iter()
+                                .cloned()
+                                .zip(row)
+                                .collect::<serde_json::Map<_, _>>()
+                        })
+                        .collect::<Vec<_>>();
+                    println!("{:#}", serde_json::Value::from(rows));
+                }
+            }
+        }
+        Action::Sync {
+            action: SyncAction::Peer { peer },
+        } => {
+            let merged = match ssh_peer(&peer) {
+                Some((host, path)) => sync_over_ssh(&app, host, path),
+                None => app.sync_with(std::path::Path::new(&peer)),
+            };
+            let (received, sent) = merged.unwrap_or_else(|err| {
+                eprintln!("ERROR: could not sync with {peer}: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            println!(
+                "Synced with {peer}: {} received and {} deleted here, {} sent and {} deleted there.",
+                received.copied, received.deleted, sent.copied, sent.deleted
+            );
+        }
+        Action::Import {
+            path,
+            format,
+            chunk_size,
+            continue_on_error,
+        } => {
+            let entries = read_input(&path).and_then(|repr| match format {
+                ImportFormat::TodoTxt => Ok(import::todo_txt(&repr, app.now)),
+                ImportFormat::Taskwarrior => import::taskwarrior(&repr, app.now),
+                ImportFormat::Ics => Ok(import::ics(&repr, app.now)),
+            });
+            let entries = entries.unwrap_or_else(|err| {
+                eprintln!("ERROR: could not import tasks: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+
+            let bar = if std::io::stderr().is_terminal() && args.porcelain.is_none() {
+                indicatif::ProgressBar::new(entries.len() as u64)
+            } else {
+                indicatif::ProgressBar::hidden()
+            };
+            let summary = app
+                .import(entries, chunk_size as usize, continue_on_error, || {
+                    bar.inc(1)
+                })
+                .unwrap_or_else(|err| {
+                    bar.abandon();
+                    eprintln!("ERROR: could not import tasks: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            bar.finish_and_clear();
+            for (line, err) in summary.failed.iter() {
+                eprintln!("WARNING: skipped task {line}: {err}");
+            }
+            println!("Imported tasks: {summary}.");
+        }
+        Action::Client { id, client } => {
+            app.set_client(id, client.as_deref()).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not set client: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+        }
+        Action::Nudge => {
+            let untracked = app.untracked().unwrap_or_else(|err| {
+                eprintln!("ERROR: could not check work sessions: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            if let Some(untracked) = untracked {
+                println!(
+                    "No work session for {}. Start one with `rem start <task id>`.",
+                    format_timedelta(untracked)
+                );
+            }
+        }
+        Action::Status { starship: true, .. } => {
+            let (line, level) = app.status_line().unwrap_or_else(|err| {
+                eprintln!("ERROR: could not show status: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            // starship captures the output, which would otherwise disable colors
+            colored::control::set_override(true);
+            println!("{}", statusbar::starship(&line, level));
+        }
+        Action::Status { tmux: true, .. } => {
+            let (line, level) = app.status_line().unwrap_or_else(|err| {
+                eprintln!("ERROR: could not show status: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            println!("{}", statusbar::tmux(&line, level));
+        }
+        Action::Status { json: true, .. } => {
+            let status = app
+                .status_line()
+                .and_then(|(line, level)| Ok((line, app.status(false)?, level)));
+            let (line, tooltip, level) = status.unwrap_or_else(|err| {
+                eprintln!("ERROR: could not show status: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            println!("{}", statusbar::json(&line, &tooltip, level));
+        }
+        Action::Status { short, .. } => {
+            let status = app.status(short).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not show status: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            if !status.is_empty() {
+                println!("{status}");
+            }
+        }
+        Action::Watch {
+            interval,
+            no_bell,
+            no_flash,
+        } => {
+            let mut before: Option<HashMap<u64, State>> = None;
+            loop {
+                if let Err(err) = refresh(&app.config) {
+                    eprintln!("ERROR: could not refresh: {err}");
+                }
+                app.now = Local::now();
+                let tasks = app.open_tasks().unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not query tasks: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+                let states = tasks
+                    .iter()
+                    .map(|task| (task.id, task.state(app.now, &app.config)))
+                    .collect::<HashMap<_, _>>();
+                let alerts = before
+                    .as_ref()
+                    .map(|before| newly_due(before, &states))
+                    .unwrap_or_default();
+                before = Some(states);
+
+                // clear the screen and move to its top left, like watch(1)
+                print!("\x1b[2J\x1b[H");
+                println!(
+                    "{}",
+                    format!(
+                        "Every {interval}s: rem tasks, {}",
+                        app.now.format(app.config.datetime_fmt())
+                    )
+                    .dimmed()
+                );
+                println!();
+                app.show_tasks(false, false, TaskOrder::Due, None, false, terminal_width())
+                    .unwrap_or_else(|err| {
+                        eprintln!("ERROR: could not show tasks: {err}");
+                        fail(&app, &label, notify_daemon);
+                    });
+                if !alerts.is_empty() {
+                    println!();
+                    for (id, state) in alerts.iter() {
+                        let title = tasks
+                            .iter()
+                            .find(|task| task.id == *id)
+                            .map(|task| task.title.as_str())
+                            .unwrap_or_default();
+                        let became = if *state == State::Overdue {
+                            "is overdue"
+                        } else {
+                            "is due soon"
+                        };
+                        println!("{}", format!("({id}) {title} {became}").bold());
+                    }
+                    if !no_bell {
+                        print!("\x07");
+                    }
+                    if !no_flash {
+                        // reverse video of the whole screen for a moment
+                        print!("\x1b[?5h");
+                        let _ = std::io::stdout().flush();
+                        std::thread::sleep(std::time::Duration::from_millis(150));
+                        print!("\x1b[?5l");
+                    }
+                }
+                let _ = std::io::stdout().flush();
+                std::thread::sleep(std::time::Duration::from_secs(interval.max(1)));
+            }
+        }
+        Action::Complete { id, reminder } => {
+            let id = match (id, reminder) {
+                (Some(id), _) => id,
+                (None, Some(reminder)) => app.current_occurrence(reminder).unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not complete occurrence: {err}");
+                    fail(&app, &label, notify_daemon);
+                }),
+                (None, None) => unreachable!("clap requires a task or reminder"),
+            };
+            app.complete_task(id).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not complete task: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            if reminder.is_some() && porcelain.is_none() {
+                println!("Completed task {id}.");
+            }
+        }
+        Action::Reminder {
+            title,
+            description,
+            first_due,
+            period,
+            until,
+            priority,
+            context,
+            tags,
+            require_completion,
+            catch_up,
+            skip_stale,
+            at,
+            cron,
+            parent,
+            season,
+            external_id,
+        } => {
+            let (first_due, all_day, period, at) = match cron {
+                Some(cron) => {
+                    let (first_due, period, at) =
+                        parse_cron(&cron, app.now).unwrap_or_else(|err| {
+                            eprintln!("Could not parse cron expression: {err}");
+                            fail(&app, &label, notify_daemon);
+                        });
+                    (first_due, false, period, at)
+                }
+                None => {
+                    let first_due = first_due.expect("required without cron");
+                    let (first_due, all_day) = parse_date_time_all_day(first_due, lenient)
+                        .unwrap_or_else(|err| {
+                            eprintln!("Could not parse first due date: {}", err);
+                            fail(&app, &label, notify_daemon);
+                        });
+                    let (first_due, all_day) = app.apply_default_due_time(first_due, all_day);
+                    let period = parse_timedelta(period.expect("required without cron"))
+                        .unwrap_or_else(|err| {
+                            eprintln!("Could not parse period: {err}");
+                            fail(&app, &label, notify_daemon);
+                        });
+                    let at = at.map(|x| {
+                        NaiveTime::parse_from_str(&x, "%H:%M").unwrap_or_else(|err| {
+                            eprintln!("Could not parse time of day '{x}': {err}");
+                            fail(&app, &label, notify_daemon);
+                        })
+                    });
+                    (first_due, all_day, period, at)
+                }
+            };
+            let until = until.map(|x| {
+                parse_date_time(x, lenient).unwrap_or_else(|err| {
+                    eprintln!("Could not parse until time: {}", err);
+                    fail(&app, &label, notify_daemon);
+                })
+            });
+
+            // occurrences at a time of day are not all-day
+            let all_day = all_day && at.is_none();
+            let first_due = match at {
+                Some(at) => first_due
+                    .date_naive()
+                    .and_time(at)
+                    .and_local_timezone(Local)
+                    .earliest()
+                    .unwrap_or(first_due),
+                None => first_due,
+            };
+            let skip_stale = skip_stale.map(|x| {
+                parse_timedelta(x).unwrap_or_else(|err| {
+                    eprintln!("Could not parse stale duration: {err}");
+                    fail(&app, &label, notify_daemon);
+                })
+            });
+
+            let warnings = validate::reminder(first_due, period, at, until, app.now, &app.config)
+                .unwrap_or_else(|err| {
+                    eprintln!("Could not add reminder: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            print_warnings(&warnings);
+            if let Some(Err(err)) = parent.map(|parent| app.get_reminder(parent)) {
+                eprintln!("Could not add reminder: invalid group: {err}");
+                fail(&app, &label, notify_daemon);
+            }
+
+            let existing = external_id
+                .as_deref()
+                .map(|key| app.find_external("reminders", key))
+                .transpose()
+                .unwrap_or_else(|err| {
+                    eprintln!("Could not add reminder: {err}");
+                    fail(&app, &label, notify_daemon);
+                })
+                .flatten();
+            if let Some(id) = existing {
+                let updated = app.atomically(|| {
+                    let reminder = app.get_reminder(id)?;
+                    app.update_reminder(&Reminder {
+                        title,
+                        description,
+                        first_due,
+                        all_day,
+                        period,
+                        until,
+                        priority,
+                        context,
+                        tags,
+                        require_completion,
+                        catch_up,
+                        skip_stale_after: skip_stale,
+                        at,
+                        season,
+                        ..reminder
+                    })?;
+                    match parent {
+                        Some(parent) => app.group_reminder(id, parent),
+                        None => Ok(()),
+                    }
+                });
+                updated.unwrap_or_else(|err| {
+                    eprintln!("Could not update reminder: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+                print_updated("reminder", id, porcelain);
+                finish(&app, &label, notify_daemon);
+                return;
+            }
+
+            let id = app
+                .atomically(|| {
+                    let id = app.add_reminder(
+                        title,
+                        description,
+                        first_due,
+                        all_day,
+                        period,
+                        until,
+                        priority,
+                        context,
+                        &tags,
+                        require_completion,
+                        catch_up,
+                        skip_stale,
+                        at,
+                        season,
+                    )?;
+                    if let Some(parent) = parent {
+                        app.group_reminder(id, parent)?;
+                    }
+                    if let Some(ref key) = external_id {
+                        app.set_external_id("reminders", id, key)?;
+                    }
+                    Ok(id)
+                })
+                .unwrap_or_else(|err| {
+                    eprintln!("Could not add reminder: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            print_created("reminder", id, porcelain);
+        }
+        Action::ShiftOccurrence {
+            reminder_id,
+            date,
+            to,
+        } => {
+            let shifted = parse_date_time_all_day(&date, lenient).and_then(|date| {
+                let to = parse_date_time_all_day(&to, lenient)?;
+                app.shift_occurrence(reminder_id, date, to)
+            });
+            let (scheduled, to) = shifted.unwrap_or_else(|err| {
+                eprintln!("ERROR: could not move occurrence: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            let all_day = app.get_reminder(reminder_id).is_ok_and(|r| r.all_day);
+            let fmt = if all_day {
+                app.config.date_fmt()
+            } else {
+                app.config.datetime_fmt()
+            };
+            println!(
+                "Moved the occurrence due {} to {}.",
+                scheduled.format(fmt),
+                to.format(fmt)
+            );
+        }
+        Action::ReviewTemplate { reminder_id, items } => {
+            app.set_review_template(reminder_id, &items)
+                .unwrap_or_else(|err| {
+                    eprintln!("Could not set review template: {err}");
+                    fail(&app, &label, notify_daemon)
+                });
+        }
+        Action::Undo { forget: true, .. } => {
+            journal::clear(&app.conn).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not forget changes: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+        }
+        Action::Undo { count, .. } => {
+            let undone = journal::undo(&app.conn, count).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not undo: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            for label in undone {
+                println!("Undid '{label}'.");
+            }
+        }
+        Action::Redo { count } => {
+            let redone = journal::redo(&app.conn, count).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not redo: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            for label in redone {
+                println!("Redid '{label}'.");
+            }
+        }
+        Action::LeadTasks { reminder_id, tasks } => {
+            app.set_lead_tasks(reminder_id, &tasks)
+                .unwrap_or_else(|err| {
+                    eprintln!("Could not set lead tasks: {err}");
+                    fail(&app, &label, notify_daemon)
+                });
+        }
+        Action::Check { task_id, item } => {
+            app.toggle_checklist_item(task_id, item)
+                .unwrap_or_else(|err| {
+                    eprintln!("Could not check item: {err}");
+                    fail(&app, &label, notify_daemon)
+                });
+        }
+        Action::Reminders {
+            all, group, sort, ..
+        } if porcelain.is_some() => {
+            let version = porcelain.expect("porcelain output requested");
+            // the order of v1 predates sorting by next due date
+            let sort = sort.unwrap_or(ReminderOrder::Id);
+            let reminders = app.sorted_reminders(sort, group).unwrap_or_else(|err| {
+                eprintln!("Could not show reminders: {err}");
+                fail(&app, &label, notify_daemon)
+            });
+            for reminder in reminders.iter().filter(|r| all || r.is_active(app.now)) {
+                println!("{}", porcelain::reminder(reminder, version));
+            }
+        }
+        Action::Reminders {
+            group,
+            sort,
+            format: ReminderFormat::Schedule,
+            ..
+        } => {
+            let sort = sort.unwrap_or(ReminderOrder::NextDue);
+            app.show_schedule(sort, group).unwrap_or_else(|err| {
+                eprintln!("Could not show reminders: {err}");
+                fail(&app, &label, notify_daemon)
+            });
+        }
+        Action::Reminders {
+            all,
+            verbose,
+            group,
+            sort,
+            format: ReminderFormat::Table,
+        } => {
+            let sort = sort.unwrap_or(ReminderOrder::NextDue);
+            app.show_reminders(all, verbose, group, sort)
+                .unwrap_or_else(|err| {
+                    eprintln!("Could not show reminders: {err}");
+                    fail(&app, &label, notify_daemon)
+                });
+        }
+        Action::Stop { id } => {
+            app.stop_reminder(id).unwrap_or_else(|err| {
+                eprintln!("Could not stop reminder: {err}");
+                fail(&app, &label, notify_daemon)
+            });
+        }
+        Action::Generate { horizon, dry_run } => {
+            if rolled_back.is_some() {
+                fail(&app, &label, notify_daemon);
+            }
+            let horizon = horizon.map_or(Ok(TimeDelta::zero()), parse_timedelta);
+            let horizon = horizon.unwrap_or_else(|err| {
+                eprintln!("ERROR: invalid horizon: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            if dry_run {
+                let tasks = app
+                    .dry_generate(horizon, &mut ask_backfill)
+                    .unwrap_or_else(|err| {
+                        eprintln!("ERROR: could not generate tasks: {err}");
+                        fail(&app, &label, notify_daemon);
+                    });
+                for task in tasks.iter() {
+                    let due = task.due.expect("generated tasks are due");
+                    println!("{} {}", due.format(app.config.datetime_fmt()), task.title);
+                }
+                if !args.quiet {
+                    eprintln!("would generate {} tasks", tasks.len());
+                }
+            } else {
+                let generated = app
+                    .generate(horizon, &mut ask_backfill)
+                    .unwrap_or_else(|err| {
+                        eprintln!("ERROR: could not generate tasks: {err}");
+                        fail(&app, &label, notify_daemon);
+                    });
+                if !args.quiet {
+                    eprintln!("{generated}");
+                }
+            }
+        }
+        Action::Reconcile { diff } => {
+            if diff {
+                let plan = app.reconcile_plan().unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not compare the reminders of the config: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+                print!("{}", format_reconcile_plan(&plan, &app.config));
+            } else {
+                let reconciled = app.reconcile().unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not apply the reminders of the config: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+                if !args.quiet {
+                    eprintln!("{reconciled}");
+                }
+            }
+        }
+        Action::ScanTodos { dir } => {
+            let scanned = dir
+                .canonicalize()
+                .map_err(|err| format!("Could not find {}: {err}", dir.display()))
+                .and_then(|dir| app.scan_todos(&dir))
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not scan for TODO comments: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            for invalid in scanned.invalid.iter() {
+                eprintln!("WARNING: ignored the annotations of {invalid}");
+            }
+            if !args.quiet {
+                eprintln!("{scanned}");
+            }
+        }
+        Action::Template {
+            action:
+                TemplateAction::Save {
+                    name,
+                    tasks,
+                    reminders,
+                    force,
+                },
+        } => {
+            let template = tasks
+                .iter()
+                .map(|id| app.get_task(*id))
+                .collect::<Result<Vec<_>, _>>()
+                .and_then(|tasks| {
+                    let reminders = reminders
+                        .iter()
+                        .map(|id| app.get_reminder(*id))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Template::capture(&tasks, &reminders))
+                })
+                .and_then(|template| {
+                    Template::from_toml(&template.to_toml()?)?;
+                    Ok(template)
+                });
+            template
+                .and_then(|template| app.save_template(&name, &template, force))
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not save template: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+        }
+        Action::Template {
+            action: TemplateAction::List,
+        } => {
+            let names = app.template_names().unwrap_or_else(|err| {
+                eprintln!("ERROR: could not list templates: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            for name in names {
+                println!("{name}");
+            }
+        }
+        Action::Template {
+            action: TemplateAction::Apply { name, start },
+        } => {
+            let start = start
+                .map(|x| parse_date_time(x, lenient))
+                .unwrap_or(Ok(app.now));
+            let created = start.and_then(|start| {
+                app.get_template(&name)
+                    .and_then(|template| app.apply_template(&template, start))
+            });
+            let (tasks, reminders) = created.unwrap_or_else(|err| {
+                eprintln!("ERROR: could not apply template: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            for id in tasks {
+                print_created("task", id, porcelain);
+            }
+            for id in reminders {
+                print_created("reminder", id, porcelain);
+            }
+        }
+        Action::Blueprint {
+            action: BlueprintAction::Apply { path, start },
+        } => {
+            let start = start
+                .map(|x| parse_date_time(x, lenient))
+                .unwrap_or(Ok(app.now));
+            let created = start.and_then(|start| {
+                read_input(&path)
+                    .and_then(|repr| Template::from_toml(&repr))
+                    .and_then(|template| app.apply_template(&template, start))
+            });
+            let (tasks, reminders) = created.unwrap_or_else(|err| {
+                eprintln!("ERROR: could not apply blueprint: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            for id in tasks {
+                print_created("task", id, porcelain);
+            }
+            for id in reminders {
+                print_created("reminder", id, porcelain);
+            }
+        }
+        Action::Template {
+            action: TemplateAction::Export { name },
+        } => {
+            let repr = app
+                .get_template(&name)
+                .and_then(|template| template.to_toml());
+            print!(
+                "{}",
+                repr.unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not export template: {err}");
+                    fail(&app, &label, notify_daemon);
+                })
+            );
+        }
+        Action::Template {
+            action: TemplateAction::Import { path, name, force },
+        } => {
+            let name = name.unwrap_or_else(|| {
+                let path = std::path::Path::new(&path);
+                path.file_stem()
+                    .filter(|_| path != std::path::Path::new("-"))
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| {
+                        eprintln!("ERROR: could not import template: pass a --name for it");
+                        fail(&app, &label, notify_daemon);
+                    })
+            });
+            read_input(&path)
+                .and_then(|repr| Template::from_toml(&repr))
+                .and_then(|template| app.save_template(&name, &template, force))
+                .unwrap_or_else(|err| {
+                    eprintln!("ERROR: could not import template: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+            println!("Imported template '{name}'.");
+        }
+        Action::GitHook {
+            action: GitHookAction::Install { force },
+        } => {
+            let path = install_git_hook(force).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not install git hook: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            println!("Installed {}.", path.display());
+        }
+        Action::GitHook {
+            action: GitHookAction::PostCommit,
+        } => {
+            let message = run_git(&["log", "-1", "--format=%B"]).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not read commit message: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            let subject = message.lines().next().unwrap_or_default().to_string();
+            for task_id in commit_task_refs(&message) {
+                if let Err(err) = app.get_task(task_id) {
+                    eprintln!("WARNING: not recording work on task {task_id}: {err}");
+                    continue;
+                }
+                let mut bit = WorkBit::new(app.now, Some(subject.clone()));
+                if let Err(err) = capture_environment(&mut bit, false, true) {
+                    eprintln!("WARNING: {err}");
+                }
+                let id = app.add_work_bit(task_id, &bit).unwrap_or_else(|err| {
+                    eprintln!("Could not record work: {err}");
+                    fail(&app, &label, notify_daemon);
+                });
+                print_created("work bit", id, porcelain);
+            }
+        }
+        Action::Record {
+            task_id,
+            description,
+            capture_cwd,
+            capture_git,
+        } => {
+            let mut bit = WorkBit::new(app.now, description);
+            capture_environment(&mut bit, capture_cwd, capture_git).unwrap_or_else(|err| {
+                eprintln!("Could not capture environment: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            let id = app.add_work_bit(task_id, &bit).unwrap_or_else(|err| {
+                eprintln!("Could not record work: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            print_created("work bit", id, porcelain);
+        }
+        #[cfg(not(unix))]
+        Action::Daemon {
+            action: DaemonAction::Run { once: true, .. },
+        } => {
+            let summary = refresh(&app.config).unwrap_or_else(|err| {
+                eprintln!("ERROR: could not refresh: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            log::info!("{summary}");
+        }
+        #[cfg(unix)]
+        Action::Daemon { action } => {
+            let path = daemon_socket().unwrap_or_else(|err| {
+                eprintln!("ERROR: could not locate daemon socket: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            let outcome = match action {
+                DaemonAction::Run { once: true, .. } => {
+                    refresh(&app.config).map(|summary| log::info!("{summary}"))
+                }
+                DaemonAction::Run { interval, .. } => {
+                    let config = app.config.clone();
+                    let interval = interval.unwrap_or(config.daemon_interval_minutes);
+                    rem::daemon::serve(
+                        &path,
+                        std::time::Duration::from_secs(interval * 60),
+                        || {
+                            let outcome = refresh(&config);
+                            match &outcome {
+                                Ok(summary) => log::info!("{summary}"),
+                                Err(err) => eprintln!("ERROR: could not refresh: {err}"),
+                            }
+                            outcome
+                        },
+                        Local::now,
+                        config.datetime_fmt(),
+                    )
+                }
+                DaemonAction::Status => rem::daemon::send(&path, rem::daemon::Request::Status)
+                    .map(|reply| println!("{reply}")),
+                DaemonAction::Stop => rem::daemon::send(&path, rem::daemon::Request::Stop)
+                    .map(|_| println!("Stopped daemon.")),
+            };
+            outcome.unwrap_or_else(|err| {
+                eprintln!("ERROR: daemon: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+        }
+        Action::InstallService {
+            user,
+            cron,
+            interval,
+        } => {
+            let interval = interval.unwrap_or(app.config.daemon_interval_minutes);
+            let installed = if user && (cfg!(target_os = "macos") || cfg!(windows)) {
+                Err("--user applies to systemd only, launchd agents and scheduled tasks always run as the current user".to_string())
+            } else if cron {
+                install_cron(interval)
+            } else if cfg!(target_os = "macos") {
+                install_launchd(interval)
+            } else if cfg!(windows) {
+                install_schtasks(interval)
+            } else {
+                install_systemd(user, interval)
+            };
+            let installed = installed.unwrap_or_else(|err| {
+                eprintln!("ERROR: could not install service: {err}");
+                fail(&app, &label, notify_daemon);
+            });
+            println!("Installed {installed}.");
+        }
+        #[cfg(not(unix))]
+        Action::Daemon { .. } => {
+            eprintln!(
+                "ERROR: only `rem daemon run --once` is supported on this system, e.g. from `rem install-service`"
+            );
+            fail(&app, &label, notify_daemon);
+        }
+    }
+
+    finish(&app, &label, notify_daemon);
+}
+
+/// Exit with an error, journaling what the command changed before it failed
+fn fail(app: &App, label: &str, notify_daemon: bool) -> ! {
+    finish(app, label, notify_daemon);
+    std::process::exit(1);
+}
+
+/// Journal the changes of a command so they can be undone, and tell the daemon about them
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn finish(app: &App, label: &str, notify_daemon: bool) {
+    if let Err(err) = journal::record(&app.conn, label, false, app.now) {
+        eprintln!("ERROR: {err}");
+    }
+
+    // a running daemon refreshes right away instead of at the end of its interval, without
+    // the command waiting for it
+    #[cfg(unix)]
+    if notify_daemon && app.conn.total_changes() > 0 {
+        if let Ok(path) = daemon_socket() {
+            let _ = rem::daemon::notify(&path, rem::daemon::Request::Refresh);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rusqlite::Connection;
+
+    use super::*;
+    #[test]
+    fn commit_refs() {
+        assert_eq!(commit_task_refs("fix parser rem:#12"), [12]);
+        assert_eq!(
+            commit_task_refs("docs\n\nrem:#3, rem:#4 and again rem:#3"),
+            [3, 4]
+        );
+        assert!(commit_task_refs("rem:# rem:#x #12 rem12").is_empty());
+    }
+
+    #[test]
+    fn db() {
+        let conn = Connection::open_in_memory().unwrap();
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS tasks (
+                id INTEGER PRIMARY KEY,
+                title TEXT,
+                description TEXT,
+                created INTEGER,
+                due INTEGER
+        );",
+            [],
+        )
+        .unwrap();
+
+        conn.execute(
+            "INSERT INTO tasks (title, created) values (?1, ?2)",
+            ["Foo".to_string(), 0.to_string()],
+        )
+        .unwrap();
+
+        let mut stmt = conn.prepare("select * from tasks").unwrap();
+        let _ = stmt
+            .query_map([], |row| {
+                let _: Option<i64> = row.get_unwrap("due");
+                Ok(())
+            })
+            .unwrap()
+            .count();
+    }
+
+    #[test]
+    fn test_show_tasks() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+
+        app.add_task(
+            "Test".to_string(),
+            None,
+            None,
+            None,
+            None,
+            false,
+            None,
+            None,
+            None,
+            &[],
+            None,
+            None,
+        )
+        .expect("adding task");
+        let later = app
+            .add_task(
+                "Later".to_string(),
+                None,
+                Some(app.now + TimeDelta::days(3)),
+                None,
+                None,
+                false,
+                None,
+                None,
+                None,
+                &[],
+                None,
+                None,
+            )
+            .expect("adding task");
+        let later = app.get_task(later).unwrap();
+        assert!(later.starts_later(app.now));
+        assert!(!later.starts_later(app.now + TimeDelta::days(4)));
+
+        app.show_tasks(false, true, TaskOrder::Urgency, None, false, Some(20))
+            .unwrap();
+        app.show_tasks(
+            false,
+            false,
+            TaskOrder::Id,
+            Some(TaskGrouping::Tag),
+            true,
+            None,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn due_window() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+
+        let from = parse_date_time("14.10.2025 09:00", false).unwrap();
+        let to = parse_date_time("14.10.2025 17:00", false).unwrap();
+
+        assert!(app
+            .add_task(
+                "Window".to_string(),
+                None,
+                None,
+                Some(to),
+                Some(from),
+                false,
+                None,
+                None,
+                None,
+                &[],
+                None,
+                None
+            )
+            .is_err());
+        assert!(app
+            .add_task(
+                "Open".to_string(),
+                None,
+                None,
+                Some(from),
+                None,
+                false,
+                None,
+                None,
+                None,
+                &[],
+                None,
+                None
+            )
+            .is_err());
+        app.add_task(
+            "Window".to_string(),
+            None,
+            None,
+            Some(from),
+            Some(to),
+            false,
+            None,
+            None,
+            None,
+            &[],
+            None,
+            None,
+        )
+        .expect("adding task with due window");
+
+        let task = app
+            .conn
+            .query_one("SELECT * FROM tasks;", [], |row| {
+                Task::from_db_row(row, None)
+            })
+            .unwrap();
+        assert_eq!(task.due_start, Some(from));
+        assert_eq!(task.due, Some(to));
+    }
+
+    #[test]
+    fn capture_split() {
+        assert_eq!(
+            split_capture("\n  Fix bug \n\nsee mail from Bob\nthanks\n".to_string()),
+            Ok((
+                "Fix bug".to_string(),
+                Some("see mail from Bob\nthanks".to_string())
+            ))
+        );
+        assert_eq!(
+            split_capture("Title".to_string()),
+            Ok(("Title".to_string(), None))
+        );
+        assert!(split_capture(" \n ".to_string()).is_err());
+    }
+
+    #[test]
+    fn review_template() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+
+        let first_due = app.now - TimeDelta::days(1);
+        let id = app
+            .add_reminder(
+                "Weekly review".to_string(),
+                None,
+                first_due,
+                false,
+                TimeDelta::days(7),
+                None,
+                None,
+                None,
+                &[],
+                false,
+                CatchUp::All,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(app.set_review_template(id + 1, &[]).is_err());
+        app.set_review_template(id, &["Inbox zero".to_string(), "Plan week".to_string()])
+            .unwrap();
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+
+        let task = app
+            .conn
+            .query_one("SELECT * FROM tasks ORDER BY due LIMIT 1;", [], |row| {
+                Task::from_db_row(row, Some(&app.conn))
+            })
+            .unwrap();
+        assert_eq!(
+            task.checklist,
+            vec![
+                ("Inbox zero".to_string(), false),
+                ("Plan week".to_string(), false)
+            ]
+        );
+
+        app.toggle_checklist_item(task.id, 2).unwrap();
+        assert!(app.toggle_checklist_item(task.id, 3).is_err());
+        assert!(app.toggle_checklist_item(task.id, 0).is_err());
+        let done = |position: usize| -> bool {
+            app.conn
+                .query_one(
+                    "SELECT done FROM checklist_items WHERE task_id = ?1 AND position = ?2",
+                    (task.id, position),
+                    |row| row.get(0),
+                )
+                .unwrap()
+        };
+        assert!(done(1));
+        assert!(!done(0));
+    }
+
+    #[test]
+    fn generation_summary() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+
+        for title in ["water plants", "stretch"] {
+            let reminder = Reminder::builder()
+                .title(title)
+                .created(app.now)
+                .first_due(app.now - TimeDelta::hours(1))
+                .period(TimeDelta::days(1))
+                .build();
+            app.insert_reminder(&reminder).unwrap();
+        }
+
+        let generated = app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert_eq!(
+            generated,
+            Generated {
+                tasks: 4,
+                reminders: 2,
+                reviews: 0,
+            }
+        );
+        assert_eq!(generated.to_string(), "generated 4 tasks from 2 reminders");
+
+        let generated = app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert_eq!(generated.tasks, 0);
+    }
+
+    #[test]
+    fn undo() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+
+        let task = Task::builder()
+            .title("pay rent")
+            .created(app.now)
+            .tags(["finance"])
+            .checklist_item("transfer")
+            .build();
+        let id = app.insert_task(&task).unwrap();
+        journal::record(&app.conn, "rem task", false, app.now).unwrap();
+        app.delete_task(id).unwrap();
+        journal::record(&app.conn, "rem delete-task", false, app.now).unwrap();
+        assert!(app.get_task(id).is_err());
+
+        assert_eq!(journal::undo(&app.conn, 1).unwrap(), ["rem delete-task"]);
+        let restored = app.get_task(id).unwrap();
+        assert_eq!(restored.tags, ["finance"]);
+        assert_eq!(restored.checklist.len(), 1);
+        assert_eq!(journal::undo(&app.conn, 1).unwrap(), ["rem task"]);
+        assert!(app.all_tasks().unwrap().is_empty());
+        assert_eq!(journal::redo(&app.conn, 2).unwrap().len(), 2);
+        assert!(app.get_task(id).is_err());
+
+        // restored tasks need to be newer than their tombstones, or merging deletes them again
+        let id = app
+            .insert_task(&Task::builder().title("call").created(app.now).build())
+            .unwrap();
+        journal::record(&app.conn, "rem task", false, app.now).unwrap();
+        app.conn
+            .execute("UPDATE tasks SET modified = 1000;", [])
+            .unwrap();
+        app.delete_task(id).unwrap();
+        journal::record(&app.conn, "rem delete-task", false, app.now).unwrap();
+        journal::undo(&app.conn, 1).unwrap();
+        let modified = app
+            .conn
+            .query_one("SELECT modified FROM tasks WHERE id = ?1;", [id], |row| {
+                row.get::<_, f64>(0)
+            })
+            .unwrap();
+        assert_eq!(app.get_task(id).unwrap().title, "call");
+        assert!(modified > 1000.0);
+    }
+
+    #[test]
+    fn watch_alerts() {
+        let before = HashMap::from([
+            (1, State::Open),
+            (2, State::DueSoon),
+            (3, State::DueSoon),
+            (4, State::Started),
+        ]);
+        let after = HashMap::from([
+            (1, State::DueSoon),
+            (2, State::DueSoon),
+            (3, State::Overdue),
+            (4, State::Started),
+            (5, State::Overdue),
+            (6, State::Open),
+        ]);
+        assert_eq!(
+            newly_due(&before, &after),
+            [
+                (1, State::DueSoon),
+                (3, State::Overdue),
+                (5, State::Overdue)
+            ]
+        );
+        assert!(newly_due(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn day_sheet() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let tomorrow = app.now.date_naive() + TimeDelta::days(1);
+        let day = start_of_day(tomorrow);
+
+        let task = |title: &str| Task::builder().title(title).created(day).build();
+        app.insert_task(&Task {
+            due: Some(day + TimeDelta::hours(15)),
+            ..task("call mom")
+        })
+        .unwrap();
+        app.insert_task(&Task {
+            due: Some(day),
+            due_all_day: true,
+            ..task("pay rent")
+        })
+        .unwrap();
+        app.insert_task(&Task {
+            priority: Some(Priority::High),
+            ..task("write report")
+        })
+        .unwrap();
+        let stretch = Reminder::builder()
+            .title("stretch")
+            .created(app.now)
+            .first_due(day + TimeDelta::hours(7))
+            .period(TimeDelta::days(1))
+            .build();
+        let id = app.insert_reminder(&stretch).unwrap();
+        app.insert_task(&Task {
+            generated_by: Some(id),
+            due: Some(day + TimeDelta::hours(7)),
+            ..task("stretch")
+        })
+        .unwrap();
+
+        let (agenda, priorities, habits) = app.day_sheet(tomorrow, 3).unwrap();
+        let titles = |tasks: &[Task]| tasks.iter().map(|t| t.title.clone()).collect::<Vec<_>>();
+        assert_eq!(titles(&agenda), ["pay rent", "call mom"]);
+        assert_eq!(titles(&priorities), ["write report"]);
+        assert_eq!(habits, ["stretch"]);
+    }
+
+    #[test]
+    fn declared_reminders() {
+        let declared = |blocks: &str| Config::from_toml(blocks).unwrap().reminders;
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        app.config.reminders = declared(
+            r#"
+            [[reminders]]
+            name = "rent"
+            title = "Pay rent"
+            first_due = "01.01.2025"
+            period = "4w"
+            tags = ["home", "finance"]
+
+            [[reminders]]
+            name = "standup"
+            title = "Standup"
+            first_due = "06.01.2025"
+            period = "1d"
+            at = "09:30"
+            "#,
+        );
+        let reconciled = app.reconcile().unwrap();
+        assert_eq!(
+            reconciled,
+            Reconciled {
+                created: 2,
+                updated: 0,
+                stopped: 0
+            }
+        );
+        assert_eq!(app.reconcile().unwrap(), Reconciled::default());
+        let rent = app
+            .find_external("reminders", "config:rent")
+            .unwrap()
+            .unwrap();
+        let standup = app
+            .find_external("reminders", "config:standup")
+            .unwrap()
+            .unwrap();
+        let reminder = app.get_reminder(standup).unwrap();
+        assert_eq!(reminder.at, NaiveTime::from_hms_opt(9, 30, 0));
+        assert!(!reminder.all_day);
+
+        // a checklist added by hand survives changes of the block
+        app.set_review_template(rent, &["check account".to_string()])
+            .unwrap();
+        app.config.reminders = declared(
+            r#"
+            [[reminders]]
+            name = "rent"
+            title = "Pay the rent"
+            first_due = "01.01.2025"
+            period = "4w"
+            tags = ["finance", "home"]
+            "#,
+        );
+        let plan = format_reconcile_plan(&app.reconcile_plan().unwrap(), &app.config);
+        assert_eq!(
+            plan,
+            format!(
+                "~ rent (reminder {rent}):\n    title: Pay rent -> Pay the rent\n\
+                 - standup (reminder {standup}): Standup\n\
+                 Plan: 0 to create, 1 to update, 1 to stop.\n"
+            )
+        );
+        assert_eq!(
+            app.reconcile().unwrap(),
+            Reconciled {
+                created: 0,
+                updated: 1,
+                stopped: 1
+            }
+        );
+        let reminder = app.get_reminder(rent).unwrap();
+        assert_eq!(reminder.title, "Pay the rent");
+        assert_eq!(reminder.checklist, ["check account"]);
+        assert!(!app.get_reminder(standup).unwrap().is_active(app.now));
+        assert_eq!(app.reconcile().unwrap(), Reconciled::default());
+        assert_eq!(
+            format_reconcile_plan(&app.reconcile_plan().unwrap(), &app.config),
+            "Nothing to change.\n"
+        );
+        // changes not visible in whole minutes are updated as well
+        app.conn
+            .execute(
+                "UPDATE reminders SET first_due = first_due + 30, all_day = 0 WHERE id = ?1;",
+                [rent],
+            )
+            .unwrap();
+        let plan = format_reconcile_plan(&app.reconcile_plan().unwrap(), &app.config);
+        assert!(plan.contains("    first due: 01.01.2025 00:00:30 -> 01.01.2025\n"));
+        assert!(plan.contains("    all day: false -> true\n"));
+        app.reconcile().unwrap();
+        assert_eq!(app.reconcile_plan().unwrap().len(), 0);
+
+        assert_eq!(
+            Config::from_toml("reconcile = \"manual\"")
+                .unwrap()
+                .reconcile,
+            config::ReconcileMode::Manual
+        );
+        assert!(Config::from_toml(
+            "[[reminders]]\nname = \"a\"\ntitle = \"A\"\nfirst_due = \"01.01.2025\"\nperiod = \"1w\"\n\
+             [[reminders]]\nname = \"a\"\ntitle = \"B\"\nfirst_due = \"01.01.2025\"\nperiod = \"1w\""
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn serve_requests() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        let id = app
+            .insert_task(&Task::builder().title("pay rent").created(app.now).build())
+            .unwrap();
+        let request = |method: &str, path: &str, body: &str| serve::Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            body: body.as_bytes().to_vec(),
+            ..serve::Request::default()
+        };
+        let json = |response: &Response| {
+            serde_json::from_slice::<serde_json::Value>(&response.body).unwrap()
+        };
+
+        let response = handle_request(&mut app, &request("GET", "/tasks", ""), Access::Read);
+        assert_eq!(response.status, 200);
+        assert_eq!(json(&response)[0]["title"], "pay rent");
+        let path = format!("/tasks/{id}/complete");
+        let response = handle_request(&mut app, &request("POST", &path, ""), Access::Read);
+        assert_eq!(response.status, 403);
+        assert!(app.get_task(id).unwrap().completed.is_none());
+        let response = handle_request(&mut app, &request("POST", &path, ""), Access::Write);
+        assert_eq!(response.status, 200);
+        assert!(json(&response)["completed"].is_string());
+        let response = handle_request(&mut app, &request("POST", &path, ""), Access::Write);
+        assert_eq!(response.status, 400);
+
+        let body = r#"{"text": "buy milk due:tomorrow"}"#;
+        let response = handle_request(&mut app, &request("POST", "/tasks", body), Access::Write);
+        assert_eq!(response.status, 201);
+        let location = response
+            .headers
+            .iter()
+            .find(|(name, _)| name == "Location")
+            .map(|(_, value)| value.clone())
+            .unwrap();
+        assert_eq!(location, format!("/tasks/{}", json(&response)["id"]));
+        let created = handle_request(&mut app, &request("GET", &location, ""), Access::Read);
+        assert_eq!(json(&created)["title"], "buy milk");
+        let body = r#"{"text": "water plants due:today every:1w"}"#;
+        let response = handle_request(&mut app, &request("POST", "/tasks", body), Access::Write);
+        assert_eq!(response.status, 201);
+        assert!(response.headers.contains(&(
+            "Location".to_string(),
+            format!("/reminders/{}", json(&response)["id"])
+        )));
+        assert_eq!(json(&response)["period_seconds"], 7 * 24 * 3600);
+        let response = handle_request(&mut app, &request("GET", "/tasks", ""), Access::Read);
+        assert_eq!(json(&response)[0]["title"], "buy milk");
+        let response = handle_request(&mut app, &request("POST", "/tasks", "milk"), Access::Write);
+        assert_eq!(response.status, 400);
+
+        let mut titles = |query: &[(&str, &str)]| {
+            let request = serve::Request {
+                query: query
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+                ..request("GET", "/tasks", "")
+            };
+            let response = handle_request(&mut app, &request, Access::Read);
+            if response.status != 200 {
+                return Err(response.status);
+            }
+            Ok(json(&response)
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|task| task["title"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>())
+        };
+        assert_eq!(
+            titles(&[("status", "all")]),
+            Ok(vec!["pay rent".to_string(), "buy milk".to_string()])
+        );
+        assert_eq!(
+            titles(&[("status", "completed")]),
+            Ok(vec!["pay rent".to_string()])
+        );
+        assert_eq!(
+            titles(&[("status", "all"), ("limit", "1"), ("offset", "1")]),
+            Ok(vec!["buy milk".to_string()])
+        );
+        assert_eq!(
+            titles(&[("status", "all"), ("due_before", "2000-01-01T00:00:00Z")]),
+            Ok(vec![])
+        );
+        assert_eq!(titles(&[("tag", "home")]), Ok(vec![]));
+        assert_eq!(titles(&[("status", "done")]), Err(400));
+        assert_eq!(titles(&[("priority", "urgent")]), Err(400));
+
+        app.conn
+            .execute(
+                "INSERT INTO sessions (task_id, started, stopped, last_seen) VALUES (?1, ?2, ?3, ?3);",
+                (id, app.now.timestamp() - 90, app.now.timestamp()),
+            )
+            .unwrap();
+        let response = handle_request(&mut app, &request("GET", "/metrics", ""), Access::Read);
+        let metrics = String::from_utf8(response.body).unwrap();
+        for line in [
+            "rem_open_tasks 1\n",
+            "rem_overdue_tasks 0\n",
+            "# TYPE rem_tasks_completed gauge\n",
+            "rem_tasks_completed 1\n",
+            "rem_work_seconds 90\n",
+        ] {
+            assert!(metrics.contains(line), "{metrics}");
+        }
+
+        for (method, path, status) in [
+            ("GET", "/tasks/999", 404),
+            ("GET", "/tasks/abc", 404),
+            ("DELETE", "/tasks", 405),
+            ("GET", "/nothing", 404),
+        ] {
+            let response = handle_request(&mut app, &request(method, path, ""), Access::Write);
+            assert_eq!(response.status, status, "{method} {path}");
+        }
+    }
+
+    #[test]
+    fn rpc_create_atomic() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        let task = Task::builder()
+            .title("freeze")
+            .created(app.now)
+            .due(app.now + TimeDelta::days(3))
+            .build();
+        let id = app.insert_task(&task).unwrap();
+        app.conn
+            .execute_batch(
+                "CREATE TEMP TRIGGER refuse BEFORE INSERT ON due_links
+                 BEGIN SELECT RAISE(ABORT, 'refused'); END;",
+            )
+            .unwrap();
+        let line = format!(
+            r#"{{"jsonrpc": "2.0", "id": 1, "method": "create_task", "params": {{"title": "release", "due": "+1d after {id}"}}}}"#
+        );
+        let outcome = rpc_call(&mut app, &Call::parse(&line).unwrap());
+        assert_eq!(outcome.unwrap_err().0, rpc::FAILED);
+        assert_eq!(app.all_tasks().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rpc_calls() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        let mut call = |line: &str| rpc_call(&mut app, &Call::parse(line).unwrap());
+
+        let task = call(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "create_task", "params": {"title": "fix parser", "due": "2030-01-02", "tags": ["work"], "priority": "high"}}"#,
+        )
+        .unwrap();
+        assert_eq!(task["title"], "fix parser");
+        assert_eq!(task["tags"][0], "work");
+        assert_eq!(task["priority"], "high");
+        let id = task["id"].as_u64().unwrap();
+        let tasks = call(
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "list_tasks", "params": {"tag": "work"}}"#,
+        )
+        .unwrap();
+        assert_eq!(tasks[0]["id"], id);
+        assert!(tasks[0]["urgency"].as_f64().unwrap() > 0.0);
+        let tasks = call(
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "list_tasks", "params": {"tag": "home"}}"#,
+        )
+        .unwrap();
+        assert_eq!(tasks, serde_json::json!([]));
+
+        let line = format!(
+            r#"{{"jsonrpc": "2.0", "id": 4, "method": "record_work", "params": {{"id": {id}}}}}"#
+        );
+        assert!(call(&line).unwrap()["work_bit"].is_u64());
+        let line = format!(
+            r#"{{"jsonrpc": "2.0", "id": 5, "method": "complete_task", "params": {{"id": {id}}}}}"#
+        );
+        assert!(call(&line).unwrap()["completed"].is_string());
+        assert_eq!(call(&line).unwrap_err().0, rpc::FAILED);
+
+        let code = |result: Result<serde_json::Value, (i64, String)>| result.unwrap_err().0;
+        assert_eq!(
+            code(call(
+                r#"{"jsonrpc": "2.0", "id": 6, "method": "create_task", "params": {}}"#
+            )),
+            rpc::INVALID_PARAMS
+        );
+        assert_eq!(
+            code(call(
+                r#"{"jsonrpc": "2.0", "id": 6, "method": "create_task", "params": {"title": " "}}"#
+            )),
+            rpc::INVALID_PARAMS
+        );
+        assert_eq!(
+            code(call(
+                r#"{"jsonrpc": "2.0", "id": 7, "method": "complete_task", "params": {"id": "x"}}"#
+            )),
+            rpc::INVALID_PARAMS
+        );
+        assert_eq!(
+            code(call(
+                r#"{"jsonrpc": "2.0", "id": 8, "method": "delete_task"}"#
+            )),
+            rpc::METHOD_NOT_FOUND
+        );
+        assert_eq!(app.get_task(id).unwrap().work_bits.len(), 1);
+    }
+
+    #[test]
+    fn bot_commands() {
+        use rem::bot::Command;
+
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let today = start_of_day(app.now.date_naive());
+        let id = app
+            .insert_task(
+                &Task::builder()
+                    .title("pay rent")
+                    .created(app.now)
+                    .due(today)
+                    .all_day(true)
+                    .build(),
+            )
+            .unwrap();
+
+        let agenda = app.bot_reply(&Command::Agenda).unwrap();
+        assert!(
+            agenda.contains(&format!("[{id}] all day pay rent")),
+            "{agenda}"
+        );
+        assert_eq!(
+            app.bot_reply(&Command::Done(id)).unwrap(),
+            format!("Completed ({id}) pay rent")
+        );
+        assert!(app.get_task(id).unwrap().completed.is_some());
+        assert!(app.bot_reply(&Command::Done(id)).is_err());
+
+        let command = "add buy milk tomorrow".parse().unwrap();
+        let reply = app.bot_reply(&command).unwrap();
+        let milk = app
+            .open_tasks()
+            .unwrap()
+            .into_iter()
+            .find(|task| task.title == "buy milk")
+            .unwrap();
+        assert_eq!(reply, format!("Added ({}) buy milk", milk.id));
+        assert_eq!(
+            milk.due.unwrap().date_naive(),
+            app.now.date_naive() + TimeDelta::days(1)
+        );
+    }
+
+    #[test]
+    fn sync_peers() {
+        let path = |name: &str| {
+            std::env::temp_dir().join(format!("rem-{name}-{}.sqlite", std::process::id()))
+        };
+        let (laptop_path, desktop_path) = (path("laptop"), path("desktop"));
+        let open = |path: &std::path::Path| {
+            let _ = std::fs::remove_file(path);
+            App::try_init(Connection::open(path).unwrap(), Config::default()).unwrap()
+        };
+        let laptop = open(&laptop_path);
+        let desktop = open(&desktop_path);
+
+        let now = laptop.now;
+        let task = |title: &str| Task::builder().title(title).created(now).build();
+        laptop
+            .insert_task(&Task {
+                tags: vec!["finance".to_string()],
+                ..task("pay rent")
+            })
+            .unwrap();
+        desktop.insert_task(&task("water plants")).unwrap();
+        let (received, sent) = laptop.sync_with(&desktop_path).unwrap();
+        assert_eq!((received.copied, sent.copied), (1, 1));
+        let titles = |app: &App| {
+            let mut titles = app
+                .all_tasks()
+                .unwrap()
+                .into_iter()
+                .map(|task| task.title)
+                .collect::<Vec<_>>();
+            titles.sort();
+            titles
+        };
+        assert_eq!(titles(&laptop), ["pay rent", "water plants"]);
+        assert_eq!(titles(&desktop), ["pay rent", "water plants"]);
+
+        let find = |app: &App, title: &str| {
+            app.all_tasks()
+                .unwrap()
+                .into_iter()
+                .find(|task| task.title == title)
+                .unwrap()
+        };
+        desktop
+            .complete_task(find(&desktop, "pay rent").id)
+            .unwrap();
+        laptop
+            .delete_task(find(&laptop, "water plants").id)
+            .unwrap();
+        let (received, sent) = laptop.sync_with(&desktop_path).unwrap();
+        assert_eq!(
+            received,
+            Changes {
+                copied: 1,
+                deleted: 0
+            }
+        );
+        assert_eq!(
+            sent,
+            Changes {
+                copied: 0,
+                deleted: 1
+            }
+        );
+        let rent = find(&laptop, "pay rent");
+        assert!(rent.completed.is_some());
+        assert_eq!(rent.tags, ["finance"]);
+        assert_eq!(titles(&desktop), ["pay rent"]);
+        assert_eq!(laptop.sync_with(&desktop_path).unwrap(), Default::default());
+
+        // edits made at the same time on both sides still settle on one of them
+        laptop
+            .conn
+            .execute_batch("UPDATE tasks SET title = 'pay the rent', modified = 2000;")
+            .unwrap();
+        desktop
+            .conn
+            .execute_batch("UPDATE tasks SET title = 'pay rent now', modified = 2000;")
+            .unwrap();
+        laptop.sync_with(&desktop_path).unwrap();
+        assert_eq!(titles(&laptop), titles(&desktop));
+        assert_eq!(laptop.sync_with(&desktop_path).unwrap(), Default::default());
+
+        drop((laptop, desktop));
+        for path in [laptop_path, desktop_path] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn sync_declared_reminders() {
+        let path = |name: &str| {
+            std::env::temp_dir().join(format!("rem-declared-{name}-{}.sqlite", std::process::id()))
+        };
+        let (laptop_path, desktop_path) = (path("laptop"), path("desktop"));
+        let config = Config::from_toml(
+            r#"
+            [[reminders]]
+            name = "rent"
+            title = "Pay rent"
+            first_due = "01.01.2025"
+            period = "4w"
+            "#,
+        )
+        .unwrap();
+        let open = |path: &std::path::Path| {
+            let _ = std::fs::remove_file(path);
+            let app = App::try_init(Connection::open(path).unwrap(), config.clone()).unwrap();
+            app.reconcile().unwrap();
+            app
+        };
+        // both machines share the config and create the reminder on their own
+        let laptop = open(&laptop_path);
+        let desktop = open(&desktop_path);
+        laptop
+            .reminders_to_tasks(&mut |_, _| Backfill::Latest(1))
+            .unwrap();
+
+        laptop.sync_with(&desktop_path).unwrap();
+        assert_eq!(laptop.sync_with(&desktop_path).unwrap(), Default::default());
+        let uuid = |app: &App| {
+            app.conn
+                .query_row("SELECT uuid FROM reminders;", [], |row| {
+                    row.get::<_, String>(0)
+                })
+                .unwrap()
+        };
+        assert_eq!(uuid(&laptop), uuid(&desktop));
+        let generated_by = desktop.all_tasks().unwrap()[0].generated_by;
+        assert_eq!(
+            generated_by,
+            desktop.find_external("reminders", "config:rent").unwrap()
+        );
+
+        drop((laptop, desktop));
+        for path in [laptop_path, desktop_path] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn lead_tasks() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+
+        let reminder = Reminder::builder()
+            .title("team meeting")
+            .created(app.now)
+            .first_due(app.now + TimeDelta::days(3))
+            .period(TimeDelta::weeks(1))
+            .tag("work")
+            .lead_task("prepare agenda", TimeDelta::days(-2))
+            .build();
+        let id = app.insert_reminder(&reminder).unwrap();
+        app.set_lead_tasks(
+            id,
+            &[
+                "prepare agenda:-2d".parse().unwrap(),
+                "send minutes:+1d".parse().unwrap(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(app.get_reminder(id).unwrap().lead_tasks.len(), 2);
+        assert!(app.set_lead_tasks(id + 1, &[]).is_err());
+
+        let generated = app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert_eq!(generated.tasks, 3);
+        let tasks = app.all_tasks().unwrap();
+        let meeting = tasks.iter().find(|t| t.generated_by == Some(id)).unwrap();
+        let minutes = tasks.iter().find(|t| t.title == "send minutes").unwrap();
+        assert_eq!(minutes.generated_by, None);
+        assert_eq!(minutes.tags, ["work"]);
+        assert_eq!(minutes.due, meeting.due.map(|due| due + TimeDelta::days(1)));
+        assert_eq!(
+            app.due_link(minutes.id).unwrap(),
+            Some((TimeDelta::days(1), meeting.id))
+        );
+
+        // lead tasks do not count as occurrences
+        let generated = app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert_eq!(generated.tasks, 0);
+    }
+
+    #[test]
+    fn builders() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+
+        let parent = app
+            .insert_task(&Task::builder().title("taxes").created(app.now).build())
+            .unwrap();
+        let task = Task::builder()
+            .title("collect receipts")
+            .created(app.now)
+            .parent(parent)
+            .due(app.now + TimeDelta::days(2))
+            .all_day(true)
+            .tags(["finance", "home"])
+            .build();
+        let id = app.insert_task(&task).unwrap();
+        let stored = app.get_task(id).unwrap();
+        assert_eq!(stored.parent, Some(parent));
+        assert!(stored.due_all_day);
+        assert_eq!(stored.tags, ["finance", "home"]);
+
+        assert!(app.insert_task(&Task::builder().build()).is_err());
+        let orphan = Task::builder().title("x").parent(id + 1).build();
+        assert!(app.insert_task(&orphan).is_err());
+
+        let reminder = Reminder::builder()
+            .title("water plants")
+            .created(app.now)
+            .first_due(app.now + TimeDelta::hours(1))
+            .period(TimeDelta::days(3))
+            .tag("home")
+            .checklist_item("ferns")
+            .build();
+        let id = app.insert_reminder(&reminder).unwrap();
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        let generated = app
+            .all_tasks()
+            .unwrap()
+            .into_iter()
+            .find(|t| t.generated_by == Some(id))
+            .unwrap();
+        assert_eq!(generated.tags, ["home"]);
+        assert_eq!(generated.checklist, [("ferns".to_string(), false)]);
+
+        let invalid = Reminder::builder()
+            .title("never")
+            .period(TimeDelta::zero())
+            .build();
+        assert!(app.insert_reminder(&invalid).is_err());
+    }
+
+    #[test]
+    fn duplicate() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+
+        let task = Task::builder()
+            .title("Invoice")
+            .description("client A")
+            .created(app.now)
+            .due(app.now)
+            .priority(Priority::High)
+            .estimate(TimeDelta::minutes(30))
+            .tag("work")
+            .checklist_item("write")
+            .checklist_item("send")
+            .build();
+        let id = app.insert_task(&task).unwrap();
+        app.toggle_checklist_item(id, 1).unwrap();
+        app.complete_task(id).unwrap();
+        app.add_work_bit(id, &WorkBit::new(app.now, None)).unwrap();
+
+        let copy = app.duplicate_task(id, None).unwrap();
+        assert!(app.duplicate_task(copy + 1, None).is_err());
+        let copy = app.get_task(copy).unwrap();
+        assert_eq!(copy.title, "Invoice");
+        assert_eq!(copy.description.as_deref(), Some("client A"));
+        assert_eq!(copy.due, None);
+        assert_eq!(copy.completed, None);
+        assert_eq!(copy.priority, Some(Priority::High));
+        assert_eq!(copy.estimate, Some(TimeDelta::minutes(30)));
+        assert_eq!(copy.tags, vec!["work".to_string()]);
+        assert!(copy.work_bits.is_empty());
+        assert_eq!(
+            copy.checklist,
+            vec![("write".to_string(), false), ("send".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn idle_sessions() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        let task = app
+            .insert_task(&Task::builder().title("report").created(app.now).build())
+            .unwrap();
+
+        assert!(app.stop_session(app.now).is_err());
+        app.start_session(task).unwrap();
+        let started = app.now;
+
+        // regular use keeps the session alive
+        app.now += TimeDelta::minutes(90);
+        app.check_idle_session(&mut |_, _| panic!("not idle"))
+            .unwrap();
+
+        // e.g. a suspended machine
+        app.now += TimeDelta::hours(9);
+        app.check_idle_session(&mut |_, idle| {
+            assert_eq!(idle.num_minutes(), 9 * 60);
+            IdleAction::Split
+        })
+        .unwrap();
+
+        let sessions = app
+            .conn
+            .prepare("SELECT * FROM sessions ORDER BY id;")
+            .unwrap()
+            .query([])
+            .unwrap()
+            .map(Session::from_db_row)
+            .collect::<Vec<_>>()
+            .unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].duration(app.now).num_minutes(), 90);
+        assert_eq!(sessions[0].started.timestamp(), started.timestamp());
+        assert_eq!(sessions[1].stopped, None);
+
+        // input noted by the daemon counts as activity
+        app.now += TimeDelta::hours(3);
+        app.note_input(app.now - TimeDelta::minutes(5)).unwrap();
+        app.check_idle_session(&mut |_, _| panic!("not idle"))
+            .unwrap();
+
+        app.now += TimeDelta::hours(3);
+        let trimmed = app
+            .check_idle_session(&mut |_, _| IdleAction::Trim)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            trimmed.stopped.unwrap().timestamp(),
+            (app.now - TimeDelta::hours(3)).timestamp()
+        );
+        assert!(app.running_session().unwrap().is_none());
+        assert!(app.stop_session(app.now).is_err());
+    }
+
+    #[test]
+    fn budgets() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut config = Config::default();
+        config.budgets.tags.insert("admin".to_string(), 1.0);
+        config.budgets.projects.insert("thesis".to_string(), 10.0);
+        let mut app = App::try_init(conn, config).unwrap();
+        // a wednesday
+        app.now =
+            start_of_day(NaiveDate::from_ymd_opt(2026, 10, 14).unwrap()) + TimeDelta::hours(9);
+        let thesis = app
+            .insert_task(&Task::builder().title("thesis").created(app.now).build())
+            .unwrap();
+        let chapter = Task::builder()
+            .title("write chapter")
+            .created(app.now)
+            .parent(thesis)
+            .tags(["admin"])
+            .build();
+        let chapter = app.insert_task(&chapter).unwrap();
+
+        app.start_session(chapter).unwrap();
+        app.now += TimeDelta::minutes(90);
+        app.stop_session(app.now).unwrap();
+        let budgets = app.budgets().unwrap();
+        assert_eq!(budgets[0].group, "#admin");
+        assert_eq!(budgets[0].worked, TimeDelta::minutes(90));
+        assert!(budgets[0].exceeded());
+        assert_eq!(budgets[1].group, "thesis");
+        assert!(!budgets[1].exceeded());
+        assert_eq!(app.status(true).unwrap(), "over budget: #admin");
+
+        // the budgets start over on monday, splitting a session running at midnight
+        // sunday, 23:00
+        app.now += TimeDelta::days(4) + TimeDelta::minutes(12 * 60 + 30);
+        app.start_session(chapter).unwrap();
+        app.now += TimeDelta::hours(2);
+        assert_eq!(app.budgets().unwrap()[0].worked, TimeDelta::hours(1));
+        app.stop_session(app.now).unwrap();
+        app.now += TimeDelta::days(1);
+        assert_eq!(app.budgets().unwrap()[0].worked, TimeDelta::hours(1));
+
+        // a project worked on directly, with only the tasks worked on loaded
+        let unrelated = app
+            .insert_task(&Task::builder().title("thesis").created(app.now).build())
+            .unwrap();
+        let slides = Task::builder()
+            .title("slides")
+            .created(app.now)
+            .parent(thesis)
+            .build();
+        app.insert_task(&slides).unwrap();
+        app.start_session(thesis).unwrap();
+        app.now += TimeDelta::minutes(30);
+        app.stop_session(app.now).unwrap();
+        let week_start = app.now - TimeDelta::hours(1);
+        let loaded = app
+            .tasks_worked_since(week_start)
+            .unwrap()
+            .iter()
+            .map(|t| t.id)
+            .collect::<Vec<_>>();
+        assert_eq!(loaded, [thesis, chapter]);
+        assert!(!loaded.contains(&unrelated));
+        assert_eq!(
+            app.budgets().unwrap()[1].worked,
+            TimeDelta::hours(1) + TimeDelta::minutes(30)
+        );
+    }
+
+    #[test]
+    fn worked() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        let parent = app
+            .insert_task(&Task::builder().title("website").created(app.now).build())
+            .unwrap();
+        let task = Task::builder()
+            .title("logo")
+            .created(app.now)
+            .parent(parent)
+            .build();
+        let task = app.insert_task(&task).unwrap();
+
+        let today = app.now.date_naive();
+        app.start_session(task).unwrap();
+        app.now += TimeDelta::minutes(40);
+        app.stop_session(app.now).unwrap();
+
+        let by_project = app
+            .worked(today, today, TimesheetBy::Project, None)
+            .unwrap();
+        assert_eq!(by_project.len(), 1);
+        assert_eq!(by_project[0].0, format!("({parent}) website"));
+        assert_eq!(by_project[0].1.num_minutes(), 40);
+
+        let by_task = app.worked(today, today, TimesheetBy::Task, None).unwrap();
+        assert_eq!(by_task[0].0, format!("({task}) logo"));
+
+        let yesterday = today - TimeDelta::days(1);
+        assert!(app
+            .worked(yesterday, yesterday, TimesheetBy::Day, None)
+            .unwrap()
+            .is_empty());
+
+        let billed = |client| app.worked(today, today, TimesheetBy::Day, Some(client));
+        assert!(billed("acme").unwrap().is_empty());
+        app.set_client(parent, Some("acme")).unwrap();
+        assert_eq!(billed("acme").unwrap().len(), 1);
+        assert!(billed("globex").unwrap().is_empty());
+    }
+
+    #[test]
+    fn open_duplicate() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let task = Task::builder().title("Pay rent").created(app.now).build();
+        let id = app.insert_task(&task).unwrap();
+
+        assert_eq!(app.open_duplicate(" pay RENT").unwrap(), Some(id));
+        assert_eq!(app.open_duplicate("pay bills").unwrap(), None);
+        app.complete_task(id).unwrap();
+        assert_eq!(app.open_duplicate("Pay rent").unwrap(), None);
+    }
+
+    #[test]
+    fn external_ids() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let task = Task::builder()
+            .title("Review PR")
+            .tag("work")
+            .created(app.now)
+            .build();
+        let id = app.insert_task(&task).unwrap();
+        app.set_external_id("tasks", id, "gh-42").unwrap();
+        assert_eq!(app.find_external("tasks", "gh-42").unwrap(), Some(id));
+        assert_eq!(app.find_external("reminders", "gh-42").unwrap(), None);
+
+        let other = app.insert_task(&task).unwrap();
+        assert!(app.set_external_id("tasks", other, "gh-42").is_err());
+
+        let task = app.get_task(id).unwrap();
+        app.update_task(&Task {
+            title: "Review PR #42".to_string(),
+            tags: vec!["review".to_string()],
+            ..task
+        })
+        .unwrap();
+        let task = app.get_task(id).unwrap();
+        assert_eq!(task.title, "Review PR #42");
+        assert_eq!(task.tags, ["review"]);
+    }
+
+    #[test]
+    fn vacuum_database() {
+        let path = std::env::temp_dir().join(format!("rem-vacuum-{}.sqlite", std::process::id()));
+        let conn = Connection::open(&path).unwrap();
+        configure_connection(&conn, &config::Database::default()).unwrap();
+        let mode: String = conn
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+        let synchronous: i64 = conn
+            .pragma_query_value(None, "synchronous", |row| row.get(0))
+            .unwrap();
+        assert_eq!(synchronous, 1);
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let task = Task::builder()
+            .title("filler")
+            .description("x".repeat(4096))
+            .created(app.now)
+            .build();
+        for _ in 0..100 {
+            app.insert_task(&task).unwrap();
+        }
+        app.conn.execute("DELETE FROM tasks;", []).unwrap();
+
+        let (before, after) = vacuum(&app.conn, &path).unwrap();
+        assert!(after < before / 2, "{after} >= {before} / 2");
+        drop(app);
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    #[test]
+    fn base_dirs() {
+        use std::path::PathBuf;
+        let default = || Some(PathBuf::from(r"C:\Users\me\AppData\Local"));
+        assert_eq!(
+            base_dir("XDG_DATA_HOME", None, default()).unwrap(),
+            PathBuf::from(r"C:\Users\me\AppData\Local")
+        );
+        let set = std::env::temp_dir().join("data");
+        assert_eq!(
+            base_dir("XDG_DATA_HOME", Some(set.clone().into()), default()).unwrap(),
+            set
+        );
+        assert_eq!(
+            base_dir("XDG_DATA_HOME", Some("".into()), default()),
+            base_dir("XDG_DATA_HOME", None, default())
+        );
+        assert_eq!(
+            base_dir("XDG_DATA_HOME", Some("relative/data".into()), default()),
+            base_dir("XDG_DATA_HOME", None, default())
+        );
+        assert!(base_dir("XDG_DATA_HOME", None, None).is_err());
+    }
+
+    #[test]
+    fn query() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let task = Task::builder().title("pay rent").created(app.now).build();
+        let id = app.insert_task(&task).unwrap();
+
+        let (columns, rows) = app.query("SELECT id, title, completed FROM tasks").unwrap();
+        assert_eq!(columns, ["id", "title", "completed"]);
+        assert_eq!(
+            rows,
+            [vec![id.into(), "pay rent".into(), serde_json::Value::Null]]
+        );
+        assert!(app.query("DELETE FROM tasks").is_err());
+        assert!(app.query("SELECT nothing FROM tasks").is_err());
+        assert_eq!(app.open_tasks().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn imports() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let task = Task::builder().title("Call Bob").created(app.now).build();
+        app.insert_task(&task).unwrap();
+
+        let export = r#"[
+            {"uuid": "a1", "description": "pay rent", "status": "pending"},
+            {"uuid": "b2", "description": "old", "status": "deleted"},
+            {"uuid": "c3", "description": "call bob", "status": "pending"},
+            {"uuid": "d4", "status": "pending"},
+            {"uuid": "e5", "description": "water plants", "status": "pending"},
+            {"uuid": "f6", "description": "file taxes", "status": "pending", "tags": ["two words"]}
+        ]"#;
+        let now = app.now;
+        let entries = || import::taskwarrior(export, now).unwrap();
+        let mut calls = 0;
+        let summary = app.import(entries(), 2, true, || calls += 1).unwrap();
+        assert_eq!(calls, 6);
+        assert_eq!(
+            (summary.created, summary.updated, summary.skipped),
+            (2, 0, 2)
+        );
+        assert_eq!(
+            summary
+                .failed
+                .iter()
+                .map(|(line, _)| *line)
+                .collect::<Vec<_>>(),
+            [4, 6]
+        );
+        // the task stored before its tag failed is rolled back
+        assert!(app
+            .open_tasks()
+            .unwrap()
+            .iter()
+            .all(|t| t.title != "file taxes"));
+
+        // the uuids are known now, the second run updates instead of creating
+        let summary = app.import(entries(), 2, true, || ()).unwrap();
+        assert_eq!(
+            (summary.created, summary.updated, summary.skipped),
+            (0, 2, 2)
+        );
+        assert_eq!(app.open_tasks().unwrap().len(), 3);
+
+        // without --continue-on-error the chunk of the failing task is rolled back
+        let text = "buy milk\nbuy bread\nbuy eggs\nbad due:tomorrow";
+        let entries = import::todo_txt(text, app.now);
+        assert!(app.import(entries, 2, false, || ()).is_err());
+        let titles = app
+            .open_tasks()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.title)
+            .collect::<Vec<_>>();
+        assert!(titles.contains(&"buy bread".to_string()));
+        assert!(!titles.contains(&"buy eggs".to_string()));
+    }
+
+    #[test]
+    fn due_links() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let due = start_of_day(app.now.date_naive() + TimeDelta::days(7));
+        let freeze = Task::builder()
+            .title("code freeze")
+            .created(app.now)
+            .due(due)
+            .all_day(true)
+            .build();
+        let freeze = app.insert_task(&freeze).unwrap();
+        let (due, all_day, link) = app.parse_due(&format!("+3d after {freeze}")).unwrap();
+        assert!(all_day);
+        let release = Task::builder()
+            .title("release")
+            .created(app.now)
+            .due(due)
+            .all_day(true)
+            .build();
+        let release = app.insert_task(&release).unwrap();
+        app.link_due(release, link).unwrap();
+        assert!(app
+            .link_due(freeze, Some((TimeDelta::days(1), release)))
+            .is_err());
+
+        let later = start_of_day(app.now.date_naive() + TimeDelta::days(14));
+        assert!(app
+            .reschedule(freeze, later, true, false)
+            .unwrap()
+            .is_empty());
+        assert_eq!(app.get_task(release).unwrap().due.unwrap(), due);
+        assert_eq!(
+            app.reschedule(freeze, later, true, true).unwrap(),
+            [release]
+        );
+        let moved = app.get_task(release).unwrap().due.unwrap();
+        assert_eq!(moved.date_naive(), later.date_naive() + TimeDelta::days(3));
+    }
+
+    #[test]
+    fn templates() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        let task = Task::builder()
+            .title("send contract")
+            .created(app.now)
+            .due(app.now + TimeDelta::days(2))
+            .build();
+        let id = app.insert_task(&task).unwrap();
+        let task = app.get_task(id).unwrap();
+        let template = Template::capture(&[task], &[]);
+        app.save_template("onboarding", &template, false).unwrap();
+        let subtask = Task::builder()
+            .title("countersign")
+            .parent(id)
+            .created(app.now)
+            .build();
+        let subtask = app.insert_task(&subtask).unwrap();
+        let tasks = [app.get_task(id).unwrap(), app.get_task(subtask).unwrap()];
+        let (copies, _) = app
+            .apply_template(&Template::capture(&tasks, &[]), app.now)
+            .unwrap();
+        assert_eq!(app.get_task(copies[1]).unwrap().parent, Some(copies[0]));
+        assert!(app.save_template("onboarding", &template, false).is_err());
+        app.save_template("onboarding", &template, true).unwrap();
+        assert_eq!(app.template_names().unwrap(), ["onboarding"]);
+
+        app.now += TimeDelta::days(7);
+        let template = app.get_template("onboarding").unwrap();
+        let (tasks, reminders) = app.apply_template(&template, app.now).unwrap();
+        assert!(reminders.is_empty());
+        let copy = app.get_task(tasks[0]).unwrap();
+        assert_eq!(copy.title, "send contract");
+        assert_eq!(
+            copy.due.unwrap().timestamp(),
+            (app.now + TimeDelta::days(2)).timestamp()
+        );
+        assert!(app.get_template("offboarding").is_err());
+
+        let template = Template::from_toml(
+            "[[tasks]]\ntitle = \"pack\"\n[[reminders]]\ntitle = \"water\"\nperiod = \"1d\"",
+        )
+        .unwrap();
+        let count = app.all_tasks().unwrap().len();
+        app.conn
+            .execute_batch(
+                "CREATE TEMP TRIGGER refuse BEFORE INSERT ON reminders
+                 BEGIN SELECT RAISE(ABORT, 'refused'); END;",
+            )
+            .unwrap();
+        assert!(app.apply_template(&template, app.now).is_err());
+        assert_eq!(app.all_tasks().unwrap().len(), count);
+    }
+
+    #[test]
+    fn dump_load() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let parent = app
+            .insert_task(&Task::builder().title("move").created(app.now).build())
+            .unwrap();
+        let task = Task::builder()
+            .title("pack, \"carefully\"")
+            .created(app.now)
+            .parent(parent)
+            .tag("home")
+            .checklist_item("books")
+            .build();
+        let task = app.insert_task(&task).unwrap();
+        let mut bit = WorkBit::new(app.now, Some("kitchen".to_string()));
+        bit.git_repo = Some("/home/me/house".to_string());
+        bit.git_branch = Some("main".to_string());
+        app.add_work_bit(task, &bit).unwrap();
+        let reminder = Reminder::builder()
+            .title("water plants")
+            .created(app.now)
+            .first_due(app.now)
+            .build();
+        app.insert_reminder(&reminder).unwrap();
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        journal::record(&app.conn, "generate", true, app.now).unwrap();
+        let dump = app.dump().unwrap();
+        assert_eq!(dump["tables"]["operations"][0]["label"], "generate");
+
+        let conn = Connection::open_in_memory().unwrap();
+        let copy = App::try_init(conn, Config::default()).unwrap();
+        copy.load(&dump).unwrap();
+        assert_eq!(copy.dump().unwrap(), dump);
+        let loaded = copy.get_task(task).unwrap();
+        assert_eq!(loaded.parent, Some(parent));
+        assert_eq!(loaded.tags, ["home"]);
+        assert_eq!(loaded.work_bits.len(), 1);
+        assert_eq!(loaded.work_bits[0].git_branch.as_deref(), Some("main"));
+        assert_eq!(loaded.work_bits[0].cwd, None);
+
+        // ids would clash, merging matches them by uuid instead
+        assert!(copy.load(&dump).is_err());
+        copy.insert_task(&Task::builder().title("unpack").created(app.now).build())
+            .unwrap();
+        app.complete_task(parent).unwrap();
+        let merged = copy.merge_dump(&app.dump().unwrap()).unwrap();
+        assert_eq!(merged.copied, 1);
+        assert!(copy.get_task(parent).unwrap().completed.is_some());
+        assert_eq!(copy.all_tasks().unwrap().len(), 5);
+        assert!(copy
+            .load(&serde_json::json!({"schema_version": 1}))
+            .is_err());
+    }
+
+    #[test]
+    fn status() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        let task = Task::builder()
+            .title("report")
+            .created(app.now)
+            .due(app.now - TimeDelta::hours(1))
+            .build();
+        let id = app.insert_task(&task).unwrap();
+
+        assert_eq!(app.tracking(true).unwrap(), None);
+        assert_eq!(app.status(true).unwrap(), "1 overdue");
+
+        app.start_session(id).unwrap();
+        app.now += TimeDelta::minutes(42);
+        assert_eq!(
+            app.status(true).unwrap(),
+            format!("⏱ 00:42 report #{id} · 1 overdue")
+        );
+        assert!(app.status(false).unwrap().contains("Working on"));
+        assert!(app
+            .status(false)
+            .unwrap()
+            .contains("never generated tasks from reminders\n"));
+
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        app.now += TimeDelta::hours(2);
+        assert!(app
+            .status(false)
+            .unwrap()
+            .contains("last generated 2h ago\n"));
+    }
+
+    #[test]
+    fn complete_by_reminder() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+
+        let reminder = Reminder::builder()
+            .title("stretch")
+            .created(app.now)
+            .first_due(app.now - TimeDelta::hours(30))
+            .period(TimeDelta::days(1))
+            .build();
+        let id = app.insert_reminder(&reminder).unwrap();
+        assert!(app.current_occurrence(id).is_err());
+        assert!(app.current_occurrence(id + 1).is_err());
+
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        let current = app.current_occurrence(id).unwrap();
+        let task = app.get_task(current).unwrap();
+        assert!(task.start.unwrap() <= app.now && app.now < task.due.unwrap());
+
+        app.complete_task(current).unwrap();
+        let previous = app.current_occurrence(id).unwrap();
+        assert!(app.get_task(previous).unwrap().due.unwrap() < app.now);
+        app.complete_task(previous).unwrap();
+        let first = app.current_occurrence(id).unwrap();
+        app.complete_task(first).unwrap();
+        assert!(app.current_occurrence(id).is_err());
+    }
+
+    #[test]
+    fn split() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+
+        let id = app
+            .add_task(
+                "Move".to_string(),
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                Some(TimeDelta::hours(6)),
+                &["home".to_string()],
+                None,
+                None,
+            )
+            .unwrap();
+        assert!(app.split_task(id, &[], true).is_err());
+        // nothing is kept of a split that fails halfway
+        assert!(app
+            .split_task(id, &["Pack".to_string(), " ".to_string()], true)
+            .is_err());
+        assert_eq!(app.all_tasks().unwrap().len(), 1);
+        assert_eq!(
+            app.get_task(id).unwrap().estimate,
+            Some(TimeDelta::hours(6))
+        );
+
+        let ids = app
+            .split_task(id, &["Pack".to_string(), "Drive".to_string()], true)
+            .unwrap();
+
+        assert_eq!(app.get_task(id).unwrap().estimate, None);
+        for sub in ids.iter() {
+            let sub = app.get_task(*sub).unwrap();
+            assert_eq!(sub.parent, Some(id));
+            assert_eq!(sub.estimate, Some(TimeDelta::hours(3)));
+            assert_eq!(sub.tags, vec!["home".to_string()]);
+        }
+
+        assert!(app.delete_task(id).unwrap_err().contains("subtasks"));
+        for sub in ids {
+            app.delete_task(sub).unwrap();
+        }
+        app.delete_task(id).unwrap();
+    }
+
+    #[test]
+    fn recommend() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let add = |title: &str, due, priority, estimate, parent| {
+            app.add_task(
+                title.to_string(),
+                None,
+                None,
+                None,
+                due,
+                false,
+                priority,
+                None,
+                estimate,
+                &[],
+                None,
+                parent,
+            )
+            .unwrap()
+        };
+
+        let now = chrono::Local::now();
+        let overdue = add("overdue", Some(now - TimeDelta::hours(1)), None, None, None);
+        let long = add(
+            "long",
+            None,
+            Some(Priority::High),
+            Some(TimeDelta::hours(4)),
+            None,
+        );
+        let quick = add(
+            "quick",
+            None,
+            Some(Priority::High),
+            Some(TimeDelta::minutes(20)),
+            None,
+        );
+        let blocked = add("blocked", Some(now), Some(Priority::High), None, None);
+        add("sub", None, None, None, Some(blocked));
+
+        let order = app
+            .recommend(Some(TimeDelta::minutes(30)), None)
+            .unwrap()
+            .into_iter()
+            .map(|(task, _)| task.id)
+            .collect::<Vec<_>>();
+        assert_eq!(order[0], overdue);
+        assert_eq!(order[1], quick);
+        assert!(order.iter().position(|id| *id == long) > Some(1));
+        assert!(order.iter().position(|id| *id == blocked) > Some(1));
+    }
+
+    #[test]
+    fn urgency_order() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let add = |title: &str, due, priority, parent| {
+            app.add_task(
+                title.to_string(),
+                None,
+                None,
+                None,
+                due,
+                false,
+                priority,
+                None,
+                None,
+                &[],
+                None,
+                parent,
+            )
+            .unwrap()
+        };
+
+        let now = chrono::Local::now();
+        let plain = add("plain", None, None, None);
+        let high = add("high", None, Some(Priority::High), None);
+        let overdue = add("overdue", Some(now - TimeDelta::days(7)), None, None);
+        let sub = add("sub", None, None, Some(plain));
+
+        let tasks = app.open_tasks().unwrap();
+        let urgencies = app
+            .with_urgency(tasks)
+            .unwrap()
+            .into_iter()
+            .map(|(t, u)| (t.id, u))
+            .collect::<std::collections::HashMap<_, _>>();
+
+        assert_eq!(urgencies[&overdue], 12.0);
+        assert_eq!(urgencies[&high], 6.0);
+        assert_eq!(urgencies[&sub], 8.0);
+        assert_eq!(urgencies[&plain], -5.0);
+    }
+
+    #[test]
+    fn require_completion() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+
+        let id = app
+            .add_reminder(
+                "Follow-up".to_string(),
+                None,
+                app.now - TimeDelta::days(20),
+                false,
+                TimeDelta::days(7),
+                None,
+                None,
+                None,
+                &[],
+                true,
+                CatchUp::All,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        let count = |app: &App| {
+            app.conn
+                .query_one(
+                    "SELECT COUNT(*) FROM tasks WHERE generated_by = ?1",
+                    [id],
+                    |row| row.get::<_, u64>(0),
+                )
+                .unwrap()
+        };
+
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert_eq!(count(&app), 1);
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert_eq!(count(&app), 1);
+
+        let first = app.open_tasks().unwrap()[0].id;
+        app.complete_task(first).unwrap();
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert_eq!(count(&app), 2);
+    }
+
+    #[test]
+    fn occurrences_on_minutes() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let first = truncate_to_minute(app.now) - TimeDelta::days(2);
+
+        let reminder = Reminder::builder()
+            .title("water plants")
+            .created(first)
+            .first_due(first + TimeDelta::seconds(42))
+            .period(TimeDelta::days(1))
+            .build();
+        let id = app.insert_reminder(&reminder).unwrap();
+        // generated by an older version, keeping other seconds
+        app.insert_task(&Task {
+            generated_by: Some(id),
+            due: Some(first + TimeDelta::seconds(17)),
+            ..Task::builder().title("water plants").created(first).build()
+        })
+        .unwrap();
+
+        let generated = app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        // the day before, today and tomorrow
+        assert_eq!(generated.tasks, 3);
+        let tasks = app.all_tasks().unwrap();
+        assert_eq!(tasks.len(), 4);
+        assert!(tasks[1..]
+            .iter()
+            .all(|task| task.due.is_some_and(|due| due.second() == 0)));
+    }
+
+    #[test]
+    fn generate_horizon() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let first = truncate_to_minute(app.now) + TimeDelta::hours(1);
+        let reminder = Reminder::builder()
+            .title("team meeting")
+            .created(app.now)
+            .first_due(first)
+            .period(TimeDelta::weeks(1))
+            .build();
+        app.insert_reminder(&reminder).unwrap();
+
+        let planned = app
+            .dry_generate(TimeDelta::days(30), &mut |_, _| Backfill::All)
+            .unwrap();
+        assert_eq!(planned.len(), 5);
+        assert_eq!(planned[4].due, Some(first + TimeDelta::weeks(4)));
+        assert!(app.all_tasks().unwrap().is_empty());
+        assert_eq!(app.last_generated().unwrap(), None);
+
+        let generated = app
+            .generate(TimeDelta::days(30), &mut |_, _| Backfill::All)
+            .unwrap();
+        assert_eq!(generated.tasks, 5);
+        let generated = app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert_eq!(generated.tasks, 0);
+    }
+
+    #[test]
+    fn project_reviews() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        let project = |app: &mut App, title: &str| {
+            let root = app
+                .insert_task(&Task::builder().title(title).created(app.now).build())
+                .unwrap();
+            app.insert_task(
+                &Task::builder()
+                    .title("first step")
+                    .created(app.now)
+                    .parent(root)
+                    .build(),
+            )
+            .unwrap();
+            root
+        };
+        let garden = project(&mut app, "garden");
+        let thesis = project(&mut app, "thesis");
+        project(&mut app, "move");
+        app.config.project_reviews.insert("thesis".to_string(), 8);
+        app.config.project_reviews.insert("move".to_string(), 0);
+        let generate = |app: &mut App| {
+            app.reminders_to_tasks(&mut |_, _| Backfill::All)
+                .unwrap()
+                .reviews
+        };
+        assert_eq!(generate(&mut app), 0);
+
+        let weeks_ago = (app.now - TimeDelta::weeks(5)).timestamp();
+        app.conn
+            .execute("UPDATE tasks SET modified = ?1;", [weeks_ago])
+            .unwrap();
+        assert_eq!(generate(&mut app), 1);
+        let review = app
+            .all_tasks()
+            .unwrap()
+            .into_iter()
+            .find(|t| t.title == "Review project garden")
+            .unwrap();
+        assert_eq!(review.parent, Some(garden));
+        // the open review is not repeated, also when the project stays untouched
+        app.now += TimeDelta::weeks(4);
+        assert_eq!(generate(&mut app), 1);
+        let reviewed = app.all_tasks().unwrap();
+        assert!(reviewed
+            .iter()
+            .any(|t| t.title == "Review project thesis" && t.parent == Some(thesis)));
+        assert!(!reviewed.iter().any(|t| t.title == "Review project move"));
+    }
+
+    #[test]
+    fn pinned_tasks() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let ids = ["a", "b", "c", "d"].map(|title| {
+            app.insert_task(&Task::builder().title(title).created(app.now).build())
+                .unwrap()
+        });
+        let order = |app: &App| {
+            app.sorted_tasks(TaskOrder::Id)
+                .unwrap()
+                .into_iter()
+                .map(|(t, _)| t.title)
+                .collect::<Vec<_>>()
+        };
+
+        app.pin(ids[2], None).unwrap();
+        app.pin(ids[1], None).unwrap();
+        assert_eq!(order(&app), ["c", "b", "a", "d"]);
+        app.pin(ids[1], Some(ids[2])).unwrap();
+        assert_eq!(order(&app), ["b", "c", "a", "d"]);
+        // the task in front of which to move is pinned at the end first
+        app.pin(ids[0], Some(ids[3])).unwrap();
+        assert_eq!(order(&app), ["b", "c", "a", "d"]);
+        assert_eq!(app.pinned().unwrap(), [ids[1], ids[2], ids[0], ids[3]]);
+        assert!(app.pin(ids[0], Some(ids[0])).is_err());
+
+        app.unpin(ids[1]).unwrap();
+        assert!(app.unpin(ids[1]).is_err());
+        assert_eq!(order(&app), ["c", "a", "d", "b"]);
+    }
+
+    #[test]
+    fn truncated_titles() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let task = Task {
+            id: 12,
+            ..Task::builder()
+                .title("write the quarterly report for the board meeting")
+                .created(app.now)
+                .tag("work")
+                .build()
+        };
+        let heading = |width| {
+            let display = task.display(false, false, app.now, &app.config);
+            console::strip_ansi_codes(&display.truncated(width).to_string()).into_owned()
+        };
+        assert_eq!(
+            heading(Some(40)),
+            "- [ ] (12) write the quarterly re… #work\n"
+        );
+        assert_eq!(heading(Some(80)), heading(None));
+        assert!(heading(None).contains("board meeting #work"));
+        // the tags are kept even if nothing of the title fits
+        assert_eq!(heading(Some(5)), "- [ ] (12) … #work\n");
+    }
+
+    #[test]
+    fn sorted_reminders() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        for (title, first_due, period, until) in [
+            ("water plants", 2, 3, None),
+            ("Backup", 1, 7, None),
+            ("call grandma", 5, 1, Some(app.now - TimeDelta::days(1))),
+        ] {
+            let mut reminder = Reminder::builder()
+                .title(title)
+                .created(app.now)
+                .first_due(app.now + TimeDelta::days(first_due))
+                .period(TimeDelta::days(period));
+            if let Some(until) = until {
+                reminder = reminder.until(until);
+            }
+            app.insert_reminder(&reminder.build()).unwrap();
+        }
+
+        let titles = |sort| {
+            app.sorted_reminders(sort, None)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.title)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            titles(ReminderOrder::NextDue),
+            ["Backup", "water plants", "call grandma"]
+        );
+        assert_eq!(
+            titles(ReminderOrder::Title),
+            ["Backup", "call grandma", "water plants"]
+        );
+        assert_eq!(
+            titles(ReminderOrder::Period),
+            ["call grandma", "water plants", "Backup"]
+        );
+        assert_eq!(
+            titles(ReminderOrder::Id),
+            ["water plants", "Backup", "call grandma"]
+        );
+    }
+
+    #[test]
+    fn dedupe_occurrences() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let reminder = Reminder::builder()
+            .title("stretch")
+            .created(app.now)
+            .first_due(truncate_to_minute(app.now) + TimeDelta::hours(1))
+            .period(TimeDelta::days(1))
+            .build();
+        let reminder_id = app.insert_reminder(&reminder).unwrap();
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        let first = app.all_tasks().unwrap().remove(0);
+        assert!(app.dedupe_occurrences().unwrap().is_empty());
+
+        // left behind by two passes generating at once
+        let second = app
+            .insert_task(&Task {
+                generated_by: Some(reminder_id),
+                due: first.due,
+                ..Task::builder().title("stretch").created(app.now).build()
+            })
+            .unwrap();
+        let third = app
+            .insert_task(&Task {
+                generated_by: Some(reminder_id),
+                due: first.due,
+                ..Task::builder().title("stretch").created(app.now).build()
+            })
+            .unwrap();
+        app.complete_task(second).unwrap();
+        app.add_work_bit(first.id, &WorkBit::new(app.now, None))
+            .unwrap();
+        app.add_work_bit(third, &WorkBit::new(app.now, None))
+            .unwrap();
+        app.link_due(second, Some((TimeDelta::zero(), first.id)))
+            .unwrap();
+        app.link_due(third, Some((TimeDelta::zero(), first.id)))
+            .unwrap();
+
+        let merged = app.dedupe_occurrences().unwrap();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].reminder, reminder_id);
+        assert_eq!(merged[0].kept, second);
+        assert_eq!(merged[0].removed, vec![first.id, third]);
+        assert_eq!(merged[0].work_bits, 2);
+        let tasks = app.all_tasks().unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].work_bits.len(), 2);
+        assert_eq!(app.due_link(second).unwrap(), None);
+        assert!(app.dedupe_occurrences().unwrap().is_empty());
+    }
+
+    #[test]
+    fn generation_lock() {
+        let path = std::env::temp_dir().join(format!("rem-lock-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let app = App::try_init(Connection::open(&path).unwrap(), Config::default()).unwrap();
+        let reminder = Reminder::builder()
+            .title("stretch")
+            .created(app.now)
+            .first_due(app.now + TimeDelta::hours(1))
+            .period(TimeDelta::days(1))
+            .build();
+        app.insert_reminder(&reminder).unwrap();
+
+        app.conn.busy_timeout(std::time::Duration::ZERO).unwrap();
+        let other = Connection::open(&path).unwrap();
+        other.execute_batch("BEGIN IMMEDIATE;").unwrap();
+        let locked = app.reminders_to_tasks(&mut |_, _| Backfill::All);
+        assert!(locked.unwrap_err().contains("lock"));
+        assert!(app.conn.is_autocommit());
+        other.execute_batch("COMMIT;").unwrap();
+
+        let generated = app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert_eq!(generated.tasks, 1);
+        assert!(app.conn.is_autocommit());
+
+        // asking about backfilling leaves the database to others while waiting for an answer
+        let missed = Reminder::builder()
+            .title("water plants")
+            .created(app.now - TimeDelta::weeks(8))
+            .first_due(app.now - TimeDelta::weeks(8))
+            .period(TimeDelta::days(1))
+            .build();
+        app.insert_reminder(&missed).unwrap();
+        let mut unlocked = false;
+        let generated = app
+            .reminders_to_tasks(&mut |_, _| {
+                unlocked = other.execute_batch("BEGIN IMMEDIATE; COMMIT;").is_ok();
+                Backfill::Latest(1)
+            })
+            .unwrap();
+        assert!(unlocked);
+        assert_eq!(generated.tasks, 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn placeholders_in_generated_tasks() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let first = truncate_to_minute(app.now) - TimeDelta::weeks(1);
+
+        let reminder = Reminder::builder()
+            .title("Timesheet KW{week}")
+            .description("sheet #{index}")
+            .created(first)
+            .first_due(first)
+            .period(TimeDelta::weeks(1))
+            .build();
+        app.insert_reminder(&reminder).unwrap();
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        let tasks = app.all_tasks().unwrap();
+        assert_eq!(tasks.len(), 3);
+        for (idx, task) in tasks.iter().enumerate() {
+            let week = task.due.unwrap().iso_week().week();
+            assert_eq!(task.title, format!("Timesheet KW{week}"));
+            assert_eq!(task.description, Some(format!("sheet #{}", idx + 1)));
+        }
+    }
+
+    #[test]
+    fn reminder_adherence() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+
+        let id = app
+            .add_reminder(
+                "Water plants".to_string(),
+                None,
+                app.now - TimeDelta::days(20),
+                false,
+                TimeDelta::days(7),
+                None,
+                None,
+                None,
+                &[],
+                false,
+                CatchUp::All,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        let first = app.open_tasks().unwrap()[0].id;
+        app.complete_task(first).unwrap();
+
+        let occurrences = app.reminder_adherence(id).unwrap();
+        assert_eq!(occurrences.len(), 4);
+        assert_eq!(
+            occurrences[0].due,
+            truncate_to_minute(app.now - TimeDelta::days(20))
+        );
+        assert_eq!(occurrences[0].lateness_minutes, Some(20 * 24 * 60));
+        assert!(occurrences[1..].iter().all(|o| o.completed.is_none()));
+
+        let summary = &app.reminder_summaries().unwrap()[&id];
+        assert_eq!((summary.generated, summary.completed), (4, 1));
+        assert_eq!(summary.average_lateness, Some(TimeDelta::days(20)));
+
+        assert!(app.reminder_adherence(id + 1).is_err());
+    }
+
+    #[test]
+    fn daily_activity() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let id = app
+            .add_task(
+                "Task".to_string(),
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                None,
+                &[],
+                None,
+                None,
+            )
+            .unwrap();
+        app.add_work_bit(id, &WorkBit::new(app.now, None)).unwrap();
+        app.complete_task(id).unwrap();
+
+        let today = app.now.date_naive();
+        assert_eq!(app.daily_activity(today, today).unwrap(), vec![(today, 2)]);
+        let yesterday = today.pred_opt().unwrap();
+        assert!(app.daily_activity(yesterday, yesterday).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reminder_groups() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        let add = |app: &mut App, title: &str, parent: Option<u64>| {
+            let reminder = Reminder::builder()
+                .title(title)
+                .created(app.now)
+                .first_due(app.now + TimeDelta::days(1))
+                .period(TimeDelta::weeks(26))
+                .tag("car")
+                .build();
+            let id = app.insert_reminder(&reminder).unwrap();
+            if let Some(parent) = parent {
+                app.group_reminder(id, parent).unwrap();
+            }
+            id
+        };
+        let car = add(&mut app, "Car maintenance", None);
+        app.conn
+            .execute(
+                "INSERT INTO reminder_tags (reminder_id, tag) VALUES (?1, 'garage');",
+                [car],
+            )
+            .unwrap();
+        let oil = add(&mut app, "Oil change", Some(car));
+        let tires = add(&mut app, "Swap tires", Some(oil));
+        let other = add(&mut app, "Water plants", None);
+
+        assert_eq!(app.get_reminder(tires).unwrap().tags, ["car", "garage"]);
+        assert_eq!(app.reminder_group(car).unwrap(), [car, oil, tires]);
+        assert!(app.group_reminder(car, tires).is_err());
+
+        app.stop_reminder(car).unwrap();
+        app.now += TimeDelta::seconds(1);
+        for id in [car, oil, tires] {
+            assert!(!app.get_reminder(id).unwrap().is_active(app.now));
+        }
+        assert!(app.get_reminder(other).unwrap().is_active(app.now));
+    }
+
+    #[test]
+    fn shift_occurrence() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let today = app.now.date_naive();
+        let reminder = Reminder::builder()
+            .title("Team meeting")
+            .created(app.now)
+            .first_due(start_of_day(today) + TimeDelta::hours(10))
+            .period(TimeDelta::weeks(1))
+            .build();
+        let id = app.insert_reminder(&reminder).unwrap();
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+
+        let next = start_of_day(today + TimeDelta::weeks(1));
+        let moved = start_of_day(today + TimeDelta::days(9));
+        let (scheduled, to) = app
+            .shift_occurrence(id, (next, true), (moved, true))
+            .unwrap();
+        assert_eq!(scheduled, next + TimeDelta::hours(10));
+        assert_eq!(to, moved + TimeDelta::hours(10));
+        assert!(app
+            .shift_occurrence(
+                id,
+                (start_of_day(today - TimeDelta::days(7)), true),
+                (moved, true)
+            )
+            .is_err());
+
+        let dues = |app: &App| {
+            let mut dues = app
+                .all_tasks()
+                .unwrap()
+                .iter()
+                .filter_map(|t| t.due)
+                .collect::<Vec<_>>();
+            dues.sort();
+            dues
+        };
+        let before = dues(&app);
+        assert!(before.contains(&to) && !before.contains(&scheduled));
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert_eq!(dues(&app), before);
+
+        // moved again by its new date
+        let later = moved + TimeDelta::days(1);
+        let (again, to) = app
+            .shift_occurrence(id, (moved, true), (later, true))
+            .unwrap();
+        assert_eq!(again, scheduled);
+
+        // an occurrence completed early keeps its task when moved
+        let task = app
+            .all_tasks()
+            .unwrap()
+            .into_iter()
+            .find(|t| t.due == Some(to))
+            .unwrap();
+        app.complete_task(task.id).unwrap();
+        app.shift_occurrence(id, (later, true), (later + TimeDelta::days(1), true))
+            .unwrap();
+        let before = dues(&app);
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert_eq!(dues(&app), before);
+        assert_eq!(
+            app.get_task(task.id).unwrap().due,
+            Some(to + TimeDelta::days(1))
+        );
+    }
+
+    #[test]
+    fn zero_period() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        let add = |app: &mut App, period| {
+            app.add_reminder(
+                "Spin".to_string(),
+                None,
+                app.now - TimeDelta::days(1),
+                false,
+                period,
+                None,
+                None,
+                None,
+                &[],
+                false,
+                CatchUp::All,
+                None,
+                None,
+                None,
+            )
+        };
+        assert!(add(&mut app, TimeDelta::zero()).is_err());
+
+        // stored before validation, must not hang generation
+        let id = add(&mut app, TimeDelta::days(1)).unwrap();
+        app.conn
+            .execute("UPDATE reminders SET period = 0 WHERE id = ?1;", [id])
+            .unwrap();
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert!(app.open_tasks().unwrap().is_empty());
+    }
+
+    #[test]
+    fn backfill_limit() {
+        let conn = Connection::open_in_memory().unwrap();
+        let config = Config {
+            backfill_limit: 5,
+            ..Config::default()
+        };
+        let mut app = App::try_init(conn, config).unwrap();
+        let add = |app: &mut App| {
+            app.add_reminder(
+                "Water plants".to_string(),
+                Some("all of them".to_string()),
+                app.now - TimeDelta::days(100) + TimeDelta::minutes(1),
+                false,
+                TimeDelta::days(1),
+                None,
+                None,
+                None,
+                &[],
+                false,
+                CatchUp::All,
+                None,
+                None,
+                None,
+            )
+            .unwrap()
+        };
+        let count = |app: &App, id: u64| {
+            app.conn
+                .query_one(
+                    "SELECT COUNT(*) FROM tasks WHERE generated_by = ?1",
+                    [id],
+                    |row| row.get::<_, usize>(0),
+                )
+                .unwrap()
+        };
+
+        let latest = add(&mut app);
+        let mut asked = Vec::new();
+        app.reminders_to_tasks(&mut |r, missed| {
+            asked.push((r.id, missed));
+            Backfill::Latest(3)
+        })
+        .unwrap();
+        assert_eq!(asked, vec![(latest, 100)]);
+        // three overdue and the upcoming occurrence
+        assert_eq!(count(&app, latest), 4);
+
+        // skipped occurrences are not asked for again
+        app.reminders_to_tasks(&mut |_, _| panic!("already caught up"))
+            .unwrap();
+        assert_eq!(count(&app, latest), 4);
+
+        let collapsed = add(&mut app);
+        app.reminders_to_tasks(&mut |_, _| Backfill::Collapse)
+            .unwrap();
+        assert_eq!(count(&app, collapsed), 2);
+        let description: String = app
+            .conn
+            .query_one(
+                "SELECT description FROM tasks WHERE generated_by = ?1 ORDER BY due LIMIT 1;",
+                [collapsed],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(description.starts_with("Catch-up for 100 missed occurrences"));
+        assert!(description.ends_with("all of them"));
+    }
+
+    #[test]
+    fn catch_up_collapse() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let id = app
+            .add_reminder(
+                "Water plants".to_string(),
+                None,
+                app.now - TimeDelta::days(10) + TimeDelta::minutes(1),
+                false,
+                TimeDelta::days(1),
+                None,
+                None,
+                None,
+                &[],
+                false,
+                CatchUp::Collapse,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+
+        app.reminders_to_tasks(&mut |_, _| panic!("below the backfill limit"))
+            .unwrap();
+        let tasks = app.open_tasks().unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert!(tasks.iter().all(|t| t.generated_by == Some(id)));
+        assert!(tasks.iter().any(|t| t
+            .description
+            .as_deref()
+            .is_some_and(|d| d.contains("10 missed"))));
+    }
+
+    #[test]
+    fn skip_stale() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        let id = app
+            .add_reminder(
+                "Stretch".to_string(),
+                None,
+                app.now - TimeDelta::days(7) + TimeDelta::minutes(1),
+                false,
+                TimeDelta::days(1),
+                None,
+                None,
+                None,
+                &[],
+                false,
+                CatchUp::All,
+                Some(TimeDelta::days(2)),
+                None,
+                None,
+            )
+            .unwrap();
+
+        // nothing is stale before the first generation
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert_eq!(app.open_tasks().unwrap().len(), 8);
+
+        app.now += TimeDelta::days(1);
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        // deadlines more than two days ago are skipped, the last two and the new one remain
+        assert_eq!(app.open_tasks().unwrap().len(), 3);
+
+        let occurrences = app.reminder_adherence(id).unwrap();
+        assert!(occurrences[0].skipped);
+        assert_eq!(occurrences[0].lateness_minutes, None);
+        assert!(!occurrences[8].skipped);
+    }
+
+    #[test]
+    fn reminder_at() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let at = NaiveTime::from_hms_opt(7, 30, 0).unwrap();
+        app.add_reminder(
+            "Take pills".to_string(),
+            None,
+            app.now - TimeDelta::days(3),
+            false,
+            TimeDelta::days(1),
+            None,
+            None,
+            None,
+            &[],
+            false,
+            CatchUp::All,
+            None,
+            Some(at),
+            None,
+        )
+        .unwrap();
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+
+        let tasks = app.open_tasks().unwrap();
+        assert!(tasks.len() >= 4);
+        assert!(tasks.iter().all(|t| t.due.unwrap().time() == at));
+
+        // generating again finds the adjusted occurrences
+        app.reminders_to_tasks(&mut |_, _| Backfill::All).unwrap();
+        assert_eq!(app.open_tasks().unwrap().len(), tasks.len());
+    }
 
-        let res = self
-            .conn
-            .execute(
-                "UPDATE tasks SET completed = ?1 where id = ?2;",
-                (self.now.timestamp(), id),
+    #[test]
+    fn tagging() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let add = |tags: &[String]| {
+            app.add_task(
+                "Task".to_string(),
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                None,
+                tags,
+                None,
+                None,
             )
-            .map_err(|err| format!("Could not mark task {id} as completed: {err}"))?;
+            .unwrap()
+        };
+        let a = add(&[]);
+        let b = add(&["fin".to_string(), "finance".to_string()]);
 
-        assert_eq!(res, 1);
+        app.tag_tasks("#fin", &[a, b]).unwrap();
+        assert!(app.tag_tasks("fin", &[b + 1]).is_err());
+        assert!(app.tag_tasks("two words", &[a]).is_err());
+        assert_eq!(app.get_task(a).unwrap().tags, vec!["fin".to_string()]);
 
-        Ok(())
+        assert_eq!(app.retag("#fin", "finance").unwrap(), 2);
+        assert!(app.retag("fin", "finance").is_err());
+        assert!(app.retag("finance", "#finance").is_err());
+        assert_eq!(app.get_task(a).unwrap().tags, vec!["finance".to_string()]);
+        assert_eq!(app.get_task(b).unwrap().tags, vec!["finance".to_string()]);
+
+        app.untag_tasks("#finance", &[a]).unwrap();
+        assert!(app.untag_tasks("finance", &[a]).is_err());
+        assert!(app.get_task(a).unwrap().tags.is_empty());
     }
 
-    fn add_work_bit(&self, task_id: u64, description: Option<String>) -> Result<(), String> {
-        if let Some(description) = description {
-            let res = self
-                .conn
-                .execute(
-                    "INSERT INTO work_bits (task_id, datetime, description) values (?1, ?2, ?3);",
-                    (task_id, self.now.timestamp(), description),
-                )
-                .map_err(|err| err.to_string())?;
-            assert_eq!(res, 1);
-        } else {
-            let res = self
-                .conn
-                .execute(
-                    "INSERT INTO work_bits (task_id, datetime) values (?1, ?2);",
-                    (task_id, self.now.timestamp()),
-                )
-                .map_err(|err| err.to_string())?;
-            assert_eq!(res, 1);
-        }
+    #[test]
+    fn tag_usage_and_prune() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let add = |tags: &[String]| {
+            app.add_task(
+                "Task".to_string(),
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                None,
+                tags,
+                None,
+                None,
+            )
+            .unwrap()
+        };
+        let done = add(&["old".to_string(), "work".to_string()]);
+        add(&["work".to_string()]);
+        app.complete_task(done).unwrap();
 
-        Ok(())
+        let usage = app.tag_usage().unwrap();
+        assert_eq!(usage.len(), 2);
+        assert_eq!((usage[1].open, usage[1].total), (1, 2));
+
+        assert_eq!(app.prune_tags().unwrap(), vec!["old".to_string()]);
+        assert_eq!(app.tag_usage().unwrap().len(), 1);
     }
-}
 
-fn get_database_connection() -> Result<rusqlite::Connection, String> {
-    let mut path = match std::env::var("XDG_DATA_HOME") {
-        Ok(v) => std::path::PathBuf::from(v),
-        Err(v) => match v {
-            std::env::VarError::NotPresent => std::env::home_dir()
-                .map(|mut x| {
-                    x.push(".local");
-                    x.push("share");
-                    x
-                })
-                .ok_or(format!("Could not determine home directory"))?,
-            std::env::VarError::NotUnicode(_) => {
-                return Err(format!(
-                    "Could not get config home directory. Returned string was not unicode."
-                ));
-            }
-        },
-    };
-    path.push(HOME_DIR);
+    #[test]
+    fn report() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let parent = app
+            .add_task(
+                "Move".to_string(),
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                None,
+                &["home".to_string()],
+                None,
+                None,
+            )
+            .unwrap();
+        let sub = app
+            .split_task(parent, &["Pack".to_string(), "Clean".to_string()], false)
+            .unwrap();
+        app.complete_task(sub[0]).unwrap();
+        app.add_work_bit(sub[0], &WorkBit::new(app.now, None))
+            .unwrap();
+        app.start_session(sub[0]).unwrap();
+        app.stop_session(app.now + TimeDelta::minutes(25)).unwrap();
 
-    if !path.exists() {
-        std::fs::create_dir_all(&path)
-            .map_err(|err| format!("Could not create data directory: {err}"))?;
-    } else {
-        if path.is_file() {
-            return Err(format!("Could not get data directory. Is a file."));
-        }
-    };
-    path.push(DATABASE_FILE);
+        let by_tag = app.report(ReportBy::Tag).unwrap();
+        assert_eq!(by_tag.len(), 1);
+        assert_eq!(by_tag[0].group, "#home");
+        assert_eq!((by_tag[0].completed, by_tag[0].open), (1, 2));
+        assert_eq!(by_tag[0].work_bits, 1);
+        assert_eq!(by_tag[0].tracked, TimeDelta::minutes(25));
 
-    // TODO: handle the error properly
-    let conn = rusqlite::Connection::open(path)
-        .map_err(|err| format!("Could not open database connection: {err}"))?;
+        let by_project = app.report(ReportBy::Project).unwrap();
+        assert_eq!(by_project.len(), 1);
+        assert_eq!(by_project[0].group, format!("({parent}) Move"));
+        assert_eq!(by_project[0].open, 2);
 
-    let ret = conn
-        .set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_FKEY, true)
-        .map_err(|err| format!("Could not enable foreign key constraints: {err}"))?;
+        let by_reminder = app.report(ReportBy::Reminder).unwrap();
+        assert_eq!(by_reminder[0].group, "(none)");
+    }
 
-    Ok(conn)
-}
+    #[test]
+    fn init_config() {
+        let time = |h| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+        let repr = init_config_repr(
+            true,
+            false,
+            Some(time(8)),
+            Some((time(9), time(17))),
+            Some(false),
+            "tasks --sort \"due\"",
+        );
+        let config = Config::from_toml(&repr).unwrap();
+        assert_eq!(config.default_due_time, Some(time(8)));
+        assert_eq!(config.workday_end, Some(time(17)));
+        assert_eq!(config.color, Some(false));
+        assert!(config.iso_dates && !config.lenient_dates);
+        assert_eq!(
+            config.default_command.as_deref(),
+            Some("tasks --sort \"due\"")
+        );
 
-/// Parse a duration expression with weeks and days
-///
-/// parsing examples:
-/// '1w 2d' => TimeDelta()
-///
-/// * `repr`: timedelta to parse
-fn parse_timedelta(repr: impl AsRef<str>) -> Result<TimeDelta, String> {
-    let mut weeks = None;
-    let mut days = None;
-    for part in repr.as_ref().trim().split(' ') {
-        let bytes = part.as_bytes();
-        let idx = bytes.iter().take_while(|x| x.is_ascii_digit()).count();
-        let (num, desc) = bytes.split_at(idx);
-        if desc.len() > 1 {
-            return Err(format!(
-                "invalid duration specifier '{desc}'. Expected 'w' or 'd'.",
-                desc = std::str::from_utf8(desc).expect("rest of input is utf8")
-            ));
-        }
-        let desc = desc[0];
+        let config =
+            Config::from_toml(&init_config_repr(false, false, None, None, None, "")).unwrap();
+        assert_eq!(config, Config::default());
+    }
 
-        let num = std::str::from_utf8(num).expect("used is_ascii_digit to find end of num");
-        let num = num
-            .parse::<i64>()
-            .map_err(|err| format!("Could not parse number from '{num}': {err}"))?;
+    #[test]
+    fn importable_files() {
+        let home = std::env::temp_dir().join(format!("rem-init-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(home.join(".todo")).unwrap();
+        assert!(importable(&home).is_empty());
 
-        match desc as char {
-            'w' => {
-                if let Some(weeks) = weeks {
-                    return Err(format!("Cannot specify weeks twice. Already got {weeks}."));
-                } else {
-                    weeks = Some(num);
-                }
-            }
-            'd' => {
-                if let Some(days) = days {
-                    return Err(format!("Cannot specify days twice. Already got {days}."));
-                } else {
-                    days = Some(num);
-                }
-            }
-            _ => {
-                return Err(format!(
-                    "Invalid duration specifier '{desc}.' Expected 'w' or 'd'."
-                ))
-            }
-        }
+        std::fs::write(home.join(".todo/todo.txt"), "(A) call mom\n").unwrap();
+        std::fs::create_dir_all(home.join(".task")).unwrap();
+        assert_eq!(
+            importable(&home),
+            [
+                (ImportFormat::TodoTxt, home.join(".todo/todo.txt")),
+                (ImportFormat::Taskwarrior, home.join(".task"))
+            ]
+        );
+        std::fs::remove_dir_all(&home).unwrap();
     }
 
-    if weeks.is_none() && days.is_none() {
-        return Err(format!(
-            "Need to specify either number of days or number of weeks."
-        ));
+    #[test]
+    fn migrations() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(pending_migrations(&conn).unwrap().len(), TABLES.len());
+
+        conn.execute("CREATE TABLE tasks (id INTEGER PRIMARY KEY);", [])
+            .unwrap();
+        let pending = pending_migrations(&conn).unwrap();
+        assert!(pending.contains(&"add column tasks.due_start".to_string()));
+        assert!(!pending.contains(&"create table tasks".to_string()));
+
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        assert!(pending_migrations(&app.conn).unwrap().is_empty());
+        let version: i64 = app
+            .conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
     }
 
-    let days = days.map(TimeDelta::days).unwrap_or(TimeDelta::days(0));
-    let weeks = weeks.map(TimeDelta::days).unwrap_or(TimeDelta::days(0)) * 7;
+    #[test]
+    fn clock_rollback() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        assert_eq!(app.clock_rolled_back().unwrap(), None);
+        app.insert_task(
+            &Task::builder()
+                .title("write report")
+                .created(app.now)
+                .build(),
+        )
+        .unwrap();
+        assert_eq!(app.clock_rolled_back().unwrap(), None);
 
-    Ok(days + weeks)
-}
+        let created = app.now;
+        // small adjustments of the clock are tolerated
+        app.now -= TimeDelta::minutes(2);
+        assert_eq!(app.clock_rolled_back().unwrap(), None);
+        app.now = created;
+        app.now -= TimeDelta::hours(3);
+        assert_eq!(
+            app.clock_rolled_back().unwrap(),
+            Some(import_datetime(created.timestamp()))
+        );
+    }
 
-fn parse_date_time(repr: impl AsRef<str>) -> Result<LocalDT, String> {
-    if let Some((date, time)) = repr.as_ref().split_once(" ") {
-        let date = NaiveDate::parse_from_str(date, "%d.%m.%Y")
-            .map_err(|err| format!("Could not parse date: {err}"))?;
-        let time = NaiveTime::parse_from_str(time, "%H:%M")
-            .map_err(|err| format!("Could not parse time: {err}"))?;
-        let dt = date.and_time(time).and_local_timezone(Local).unwrap();
-        Ok(dt)
-    } else {
-        let date = NaiveDate::parse_from_str(repr.as_ref(), "%d.%m.%Y")
-            .map_err(|err| format!("Could not parse date: {err}"))?;
-        let dt = date
-            .and_hms_opt(8, 0, 0)
-            .expect("valid time")
-            .and_local_timezone(Local)
+    #[test]
+    fn scope_project() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut app = App::try_init(conn, Config::default()).unwrap();
+        let project = app
+            .add_task(
+                "rem".to_string(),
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                None,
+                &[],
+                None,
+                None,
+            )
             .unwrap();
-        Ok(dt)
+        let missing = Scope {
+            project: Some(project + 1),
+            tag: None,
+        };
+        assert!(app.set_scope(missing).is_err());
+        assert!(app.scope.is_empty());
+        let scope = Scope {
+            project: Some(project),
+            tag: Some("cli".to_string()),
+        };
+        app.set_scope(scope.clone()).unwrap();
+        assert_eq!(app.scope, scope);
     }
-}
 
-fn main() {
-    let conn = get_database_connection().unwrap_or_else(|err| {
-        eprintln!("Could not get database connection: {err}");
-        std::process::exit(1);
-    });
+    #[test]
+    fn stored_timezone() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        assert_eq!(app.stored_timezone().unwrap(), timezone_name());
 
-    let mut app = App::try_init(conn).unwrap_or_else(|err| {
-        eprintln!("ERROR: could not initialize application: {err}");
-        std::process::exit(1);
-    });
+        app.conn
+            .execute(
+                "UPDATE meta SET value = 'Asia/Tokyo' WHERE key = 'timezone';",
+                [],
+            )
+            .unwrap();
+        let app = App::try_init(app.conn, Config::default()).unwrap();
+        if timezone_name().is_some() {
+            assert_eq!(
+                app.stored_timezone().unwrap().as_deref(),
+                Some("Asia/Tokyo")
+            );
+            assert_eq!(
+                app.timezone_to_note("Europe/Berlin").unwrap().as_deref(),
+                Some("Asia/Tokyo")
+            );
+            assert_eq!(app.timezone_to_note("Europe/Berlin").unwrap(), None);
+            assert_eq!(app.timezone_to_note("Asia/Tokyo").unwrap(), None);
+            assert!(app.timezone_to_note("UTC").unwrap().is_some());
+        }
+    }
 
-    app.reminders_to_tasks()
-        .unwrap_or_else(|err| eprintln!("ERROR: Could not convert tasks to reminders: {err}"));
+    #[test]
+    fn timezone_check() {
+        let dir = std::env::temp_dir().join(format!("rem-zoneinfo-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("Europe")).unwrap();
+        std::fs::write(dir.join("Europe/Berlin"), "TZif").unwrap();
 
-    match Args::parse().action {
-        Action::Tasks { all, verbose } => {
-            app.show_tasks(all, verbose).unwrap_or_else(|err| {
-                eprintln!("Could not show tasks: {err}");
-                std::process::exit(1);
-            });
+        assert!(check_timezone("Europe/Berlin", Some(&dir)).is_ok());
+        assert!(check_timezone("Europe/Bonn", Some(&dir)).is_err());
+        assert!(check_timezone("../Europe/Berlin", Some(&dir.join("Europe"))).is_err());
+        for tz in ["UTC", "CET-1CEST,M3.5.0,M10.5.0/3", "<+03>-3", "EST5EDT"] {
+            assert!(check_timezone(tz, None).is_ok(), "{tz}");
         }
-        Action::Task {
-            title,
-            description,
-            due,
-            start,
-        } => {
-            let due = due.map(parse_date_time).map(|x| {
-                x.unwrap_or_else(|err| {
-                    eprintln!("Could not parse due datetime: {}", err);
-                    std::process::exit(1);
-                })
-            });
+        assert!(check_timezone("Europe/Berlin", None).is_err());
+        assert!(check_timezone("CE1", None).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 
-            let start = start.map(parse_date_time).map(|x| {
-                x.unwrap_or_else(|err| {
-                    eprintln!("Could not parse start datetime: {}", err);
-                    std::process::exit(1);
-                })
-            });
+    #[test]
+    fn todo_comments() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let dir = std::env::temp_dir().join(format!("rem-scan-todos-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("lib.rs");
+        std::fs::write(
+            &file,
+            "// TODO: parse args\n// FIXME(priority=high): leaks\n",
+        )
+        .unwrap();
 
-            app.add_task(title, description, start, due, None)
-                .unwrap_or_else(|err| {
-                    eprintln!("ERROR: could not add task: {err}");
-                    std::process::exit(1);
-                });
-        }
-        Action::DeleteTask { id } => {
-            app.delete_task(id).unwrap_or_else(|err| {
-                eprintln!("ERROR: could not delete task: {err}");
-                std::process::exit(1);
-            });
-        }
-        Action::Complete { id } => {
-            app.complete_task(id).unwrap_or_else(|err| {
-                eprintln!("ERROR: could not delete task: {err}");
-                std::process::exit(1);
-            });
-        }
-        Action::Reminder {
-            title,
-            description,
-            first_due,
-            period,
-            until,
-        } => {
-            let first_due = parse_date_time(first_due).unwrap_or_else(|err| {
-                eprintln!("Could not parse first due date: {}", err);
-                std::process::exit(1);
-            });
-            let until = until.map(|x| {
-                parse_date_time(x).unwrap_or_else(|err| {
-                    eprintln!("Could not parse until time: {}", err);
-                    std::process::exit(1);
-                })
-            });
+        let scanned = app.scan_todos(&dir).unwrap();
+        assert_eq!(
+            (scanned.created, scanned.updated, scanned.closed),
+            (2, 0, 0)
+        );
+        assert_eq!(app.scan_todos(&dir).unwrap(), Scanned::default());
+        let open = app.open_tasks().unwrap();
+        assert_eq!(open.len(), 2);
+        let leaks = open.iter().find(|task| task.title == "leaks").unwrap();
+        assert_eq!(leaks.priority, Some(Priority::High));
+        assert_eq!(leaks.description, Some(format!("{}:2", file.display())));
 
-            let period = parse_timedelta(period).unwrap_or_else(|err| {
-                eprintln!("Could not parse period: {err}");
-                std::process::exit(1);
-            });
+        std::fs::write(&file, "\n// FIXME(tag=mem): leaks\n").unwrap();
+        let scanned = app.scan_todos(&dir).unwrap();
+        assert_eq!(
+            (scanned.created, scanned.updated, scanned.closed),
+            (0, 1, 1)
+        );
+        let open = app.open_tasks().unwrap();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].id, leaks.id);
+        assert_eq!(open[0].tags, ["mem"]);
+        assert_eq!(open[0].priority, Some(Priority::High));
 
-            app.add_reminder(title, description, first_due, period, until)
-                .unwrap_or_else(|err| {
-                    eprintln!("Could not add reminder: {err}");
-                    std::process::exit(1);
-                });
-        }
-        Action::Reminders { all, verbose } => {
-            app.show_reminders(all, verbose).unwrap_or_else(|err| {
-                eprintln!("Could not show reminders: {err}");
-                std::process::exit(1)
-            });
-        }
-        Action::Stop { id } => {
-            app.stop_reminder(id).unwrap_or_else(|err| {
-                eprintln!("Could not stop reminder: {err}");
-                std::process::exit(1)
-            });
-        }
-        Action::Record {
-            task_id,
-            description,
-        } => app
-            .add_work_bit(task_id, description)
-            .unwrap_or_else(|err| {
-                eprintln!("Could not record work: {err}");
-                std::process::exit(1);
-            }),
+        // a typo in the annotations neither closes nor changes the task
+        std::fs::write(&file, "\n// FIXME(tag=mem, due=31.2): leaks\n").unwrap();
+        let scanned = app.scan_todos(&dir).unwrap();
+        assert_eq!(
+            (scanned.created, scanned.updated, scanned.closed),
+            (0, 0, 0)
+        );
+        assert_eq!(scanned.invalid.len(), 1);
+        assert!(scanned.invalid[0].starts_with(&format!("{}:2: ", file.display())));
+        assert_eq!(app.open_tasks().unwrap().len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
     }
-}
 
-#[cfg(test)]
-mod test {
-    use rusqlite::Connection;
+    #[test]
+    fn quick_lines() {
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        let line = quickadd::parse_line(
+            "// TODO(due=tomorrow, tag=parser): fix parser",
+            app.now.date_naive(),
+            false,
+        )
+        .unwrap();
+        let (id, _) = app.add_quick_task(line.quick.clone()).unwrap();
+        assert_eq!(line.rewrite(id), format!("// TODO(rem:#{id}): fix parser"));
+        let task = app.get_task(id).unwrap();
+        assert_eq!(task.title, "fix parser");
+        assert_eq!(task.tags, ["parser"]);
+        assert!(task.due_all_day);
+    }
 
-    use super::*;
     #[test]
-    fn db() {
+    fn quick_add() {
         let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS tasks (
-                id INTEGER PRIMARY KEY,
-                title TEXT,
-                description TEXT,
-                created INTEGER,
-                due INTEGER
-        );",
-            [],
+        app.quick_add("Pay rent !high #finance #home @desk due:tomorrow")
+            .unwrap();
+        let task = app
+            .conn
+            .query_one("SELECT * FROM tasks;", [], |row| {
+                Task::from_db_row(row, Some(&app.conn))
+            })
+            .unwrap();
+        assert_eq!(task.title, "Pay rent");
+        assert_eq!(task.priority, Some(Priority::High));
+        assert_eq!(task.context.as_deref(), Some("desk"));
+        assert_eq!(task.tags, vec!["finance".to_string(), "home".to_string()]);
+        assert!(task.due_all_day);
+
+        app.quick_add("Water plants #home due:today every:1w")
+            .unwrap();
+        let reminder = app
+            .conn
+            .query_one("SELECT * FROM reminders;", [], |row| {
+                Reminder::from_db_row(row, Some(&app.conn))
+            })
+            .unwrap();
+        assert_eq!(reminder.title, "Water plants");
+        assert_eq!(reminder.period, TimeDelta::days(7));
+        assert_eq!(reminder.tags, vec!["home".to_string()]);
+    }
+
+    #[test]
+    fn all_day_due() {
+        let (dt, all_day) = parse_date_time_all_day("14.10.2025", false).unwrap();
+        assert!(all_day);
+        assert_eq!(dt, parse_date_time("14.10.2025 00:00", false).unwrap());
+
+        let (_, all_day) = parse_date_time_all_day("14.10.2025 08:00", false).unwrap();
+        assert!(!all_day);
+
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, Config::default()).unwrap();
+        app.add_task(
+            "All day".to_string(),
+            None,
+            None,
+            None,
+            Some(dt),
+            true,
+            None,
+            None,
+            None,
+            &[],
+            None,
+            None,
         )
         .unwrap();
+        let task = app
+            .conn
+            .query_one("SELECT * FROM tasks;", [], |row| {
+                Task::from_db_row(row, None)
+            })
+            .unwrap();
+        assert!(task.due_all_day);
+        let config = Config::default();
+        assert!(!task.is_overdue(parse_date_time("14.10.2025 23:59", false).unwrap(), &config));
+        assert!(task.is_overdue(parse_date_time("15.10.2025 00:00", false).unwrap(), &config));
+    }
 
-        conn.execute(
-            "INSERT INTO tasks (title, created) values (?1, ?2)",
-            ["Foo".to_string(), 0.to_string()],
+    #[test]
+    fn workday_config() {
+        let config = Config::from_toml(
+            "default_due_time = \"10:30\"
+            workday_start = \"09:00\"
+            workday_end = \"17:00\"
+            working_days = [\"Mon\", \"Tue\", \"Wed\", \"Thu\"]",
         )
         .unwrap();
+        assert!(Config::from_toml("workday_start = \"18:00\"\nworkday_end = \"17:00\"").is_err());
 
-        let mut stmt = conn.prepare("select * from tasks").unwrap();
-        let _ = stmt
-            .query_map([], |row| {
-                let _: Option<i64> = row.get_unwrap("due");
-                Ok(())
+        let conn = Connection::open_in_memory().unwrap();
+        let app = App::try_init(conn, config.clone()).unwrap();
+        let (due, all_day) = parse_date_time_all_day("14.10.2025", false).unwrap();
+        let (pinned, pinned_all_day) = app.apply_default_due_time(due, all_day);
+        assert!(!pinned_all_day);
+        assert_eq!(pinned, parse_date_time("14.10.2025 10:30", false).unwrap());
+
+        // tuesday, a working day
+        app.add_task(
+            "All day".to_string(),
+            None,
+            None,
+            None,
+            Some(due),
+            all_day,
+            None,
+            None,
+            None,
+            &[],
+            None,
+            None,
+        )
+        .unwrap();
+        let mut task = app
+            .conn
+            .query_one("SELECT * FROM tasks;", [], |row| {
+                Task::from_db_row(row, None)
             })
-            .unwrap()
-            .count();
+            .unwrap();
+        assert!(!task.is_overdue(parse_date_time("14.10.2025 16:59", false).unwrap(), &config));
+        assert!(task.is_overdue(parse_date_time("14.10.2025 17:00", false).unwrap(), &config));
+
+        // friday is not a working day here
+        task.due = Some(parse_date_time("17.10.2025", false).unwrap());
+        assert!(!task.is_overdue(parse_date_time("17.10.2025 23:59", false).unwrap(), &config));
     }
 
     #[test]
-    fn test_show_tasks() {
-        let conn = Connection::open_in_memory().unwrap();
-        let mut app = App::try_init(conn).unwrap();
-
-        app.add_task("Test".to_string(), None, None, None, None)
-            .expect("adding task");
+    fn alias_to_standalone() {
+        let config = Config::from_toml(
+            r#"
+            [aliases]
+            d = "doctor"
+            "#,
+        )
+        .unwrap();
+        let args = Args::parse_from(config.expand_alias(vec!["rem".into(), "d".into()], &[]));
+        assert!(matches!(args.action, Action::Doctor));
+        assert!(standalone(&args.action));
+    }
 
-        app.show_tasks(false, true).unwrap();
+    #[test]
+    fn prune_alias() {
+        let args = Args::parse_from(["rem", "tags", "--prune"]);
+        assert!(matches!(
+            args.action,
+            Action::Tags {
+                prune_completed: true
+            }
+        ));
     }
 
     #[test]
-    fn parse_timedelta_week() {
-        assert_eq!(parse_timedelta("1w"), Ok(TimeDelta::days(7)));
+    fn valued_options() {
+        assert_eq!(super::valued_options(), ["--tz"]);
     }
 
     #[test]
-    fn parse_timedelta_day() {
-        assert_eq!(parse_timedelta("1d"), Ok(TimeDelta::days(1)));
+    fn verbosity() {
+        let args = Args::parse_from(["rem", "-vv", "tasks", "-v"]);
+        assert_eq!(args.log, 2);
+        assert!(matches!(args.action, Action::Tasks { verbose: true, .. }));
+        assert_eq!(Args::parse_from(["rem", "-L", "tasks"]).log, 1);
     }
 
+    #[cfg(unix)]
     #[test]
-    fn parse_timedelta_fail() {
-        assert!(parse_timedelta("1wf 2d").is_err());
-        assert!(parse_timedelta("1w 1w").is_err());
-        assert!(parse_timedelta("1d 1d").is_err());
+    fn clipboard_fallback() {
+        let missing = ("rem-no-such-clipboard-tool", &[][..]);
+        let failing = ("false", &[][..]);
+        let working = ("echo", &["-n", "pasted"][..]);
+        assert_eq!(
+            read_clipboard_with(&[missing, failing, working]).unwrap(),
+            "pasted"
+        );
+        assert_eq!(
+            read_clipboard_with(&[missing, failing]).unwrap_err(),
+            "false failed: "
+        );
+        assert!(read_clipboard_with(&[missing])
+            .unwrap_err()
+            .starts_with("No clipboard tool found"));
     }
 
     #[test]
-    fn parse_timedelta_mixed() {
-        assert_eq!(parse_timedelta("1w 2d"), Ok(TimeDelta::days(9)));
-        assert_eq!(parse_timedelta("2w 1d"), Ok(TimeDelta::days(15)));
+    fn platform_dirs() {
+        let home = std::path::Path::new("/home/user");
+        let roaming = std::path::Path::new("/home/user/Library/Application Support");
+        let local = std::path::Path::new("/home/user/Library/Caches");
+        let dirs = PlatformDirs::new(false, home, roaming, local);
+        assert_eq!(dirs.home, home);
+        assert_eq!(dirs.config, home.join(".config"));
+        assert_eq!(dirs.data, home.join(".local/share"));
+
+        let home = std::path::Path::new(r"C:\Users\user");
+        let roaming = home.join(r"AppData\Roaming");
+        let local = home.join(r"AppData\Local");
+        let dirs = PlatformDirs::new(true, home, &roaming, &local);
+        assert_eq!(dirs.home, home);
+        assert_eq!(dirs.config, roaming);
+        assert_eq!(dirs.data, local);
     }
 }
@@ -0,0 +1,125 @@
+//! Terminal rendering of the Markdown commonly used in task descriptions
+//!
+//! Only headings, lists, quotes, code and the inline styles bold, italic, code and links are
+//! understood. Everything else is kept as written.
+
+use colored::{ColoredString, Colorize};
+
+use crate::hyperlink;
+
+/// Render Markdown with terminal styles instead of its markup
+///
+/// * `links`: make links clickable, see [`crate::hyperlink`]
+/// * `color`: style the text, otherwise only the markup is replaced
+pub fn render(text: &str, links: bool, color: bool) -> String {
+    let mut ret = String::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        let rendered = if in_code {
+            format!("    {}", paint(line, color, |t| t.cyan()))
+        } else if let Some(heading) = heading(trimmed) {
+            paint(&inline(heading, links, color), color, |t| {
+                t.bold().underline()
+            })
+        } else if let Some(item) = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|marker| trimmed.strip_prefix(marker))
+        {
+            format!("{indent}• {}", inline(item, links, color))
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            let quote = inline(quote.trim_start(), links, color);
+            format!("{indent}│ {}", paint(&quote, color, |t| t.italic()))
+        } else {
+            format!("{indent}{}", inline(trimmed, links, color))
+        };
+        ret.push_str(&rendered);
+        ret.push('\n');
+    }
+    ret
+}
+
+/// Text of an ATX heading like `## Notes`
+fn heading(line: &str) -> Option<&str> {
+    let text = line.trim_start_matches('#');
+    let level = line.len() - text.len();
+    ((1..=6).contains(&level) && text.starts_with(' ')).then(|| text.trim())
+}
+
+/// `text` in the style of `style` if `color` is set
+fn paint(text: &str, color: bool, style: impl Fn(&str) -> ColoredString) -> String {
+    if color {
+        style(text).to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+fn inline(text: &str, links: bool, color: bool) -> String {
+    let mut ret = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some((inner, after)) = delimited(rest, "**").or_else(|| delimited(rest, "__")) {
+            ret.push_str(&paint(inner, color, |t| t.bold()));
+            rest = after;
+        } else if let Some((inner, after)) = delimited(rest, "`") {
+            ret.push_str(&paint(inner, color, |t| t.cyan()));
+            rest = after;
+        } else if let Some((inner, after)) = delimited(rest, "*") {
+            ret.push_str(&paint(inner, color, |t| t.italic()));
+            rest = after;
+        } else if let Some((label, url, after)) = link(rest) {
+            let label = paint(label, color, |t| t.underline());
+            if links {
+                ret.push_str(&hyperlink::wrap(url, &label));
+            } else {
+                ret.push_str(&format!("{label} ({})", paint(url, color, |t| t.dimmed())));
+            }
+            rest = after;
+        } else if let Some((url, after)) = hyperlink::split_url(rest).filter(|_| links) {
+            ret.push_str(&hyperlink::wrap(url, url));
+            rest = after;
+        } else {
+            ret.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    ret
+}
+
+/// Split off text enclosed in `delim` at the start of `text`
+fn delimited<'a>(text: &'a str, delim: &str) -> Option<(&'a str, &'a str)> {
+    let rest = text.strip_prefix(delim)?;
+    let end = rest.find(delim)?;
+    let inner = &rest[..end];
+    (!inner.is_empty() && !inner.starts_with(' ') && !inner.ends_with(' '))
+        .then(|| (inner, &rest[end + delim.len()..]))
+}
+
+/// Split off a link like `[label](url)` at the start of `text`
+fn link(text: &str) -> Option<(&str, &str, &str)> {
+    let rest = text.strip_prefix('[')?;
+    let (label, rest) = rest.split_once("](")?;
+    let (url, rest) = rest.split_once(')')?;
+    Some((label, url, rest))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plain() {
+        let text = "# Steps\n\n1. call **Bob**\n- check `invoice.pdf`\n  * see [ticket](https://example.com/1)\n> 2 * 3 * 4\n```\n# kept\n```";
+        assert_eq!(
+            render(text, false, false),
+            "Steps\n\n1. call Bob\n• check invoice.pdf\n  • see ticket (https://example.com/1)\n│ 2 * 3 * 4\n    # kept\n"
+        );
+    }
+}
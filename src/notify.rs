@@ -0,0 +1,118 @@
+//! Desktop notifications of the daemon, shown by the tool of each platform: `notify-send` on
+//! Linux and other unixes, `osascript` on macOS and a WinRT toast through PowerShell on Windows
+
+/// Application id PowerShell shows toasts under, toasts of unregistered ids are dropped
+const POWERSHELL_APP_ID: &str =
+    r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Backend {
+    NotifySend,
+    Osascript,
+    Toast,
+}
+
+impl Backend {
+    /// Backend of the platform rem is built for
+    pub fn detect() -> Self {
+        if cfg!(target_os = "macos") {
+            Self::Osascript
+        } else if cfg!(windows) {
+            Self::Toast
+        } else {
+            Self::NotifySend
+        }
+    }
+
+    /// Program and arguments showing a notification
+    pub fn command(&self, title: &str, body: &str) -> (&'static str, Vec<String>) {
+        match self {
+            Self::NotifySend => (
+                "notify-send",
+                vec![
+                    "--app-name=rem".to_string(),
+                    "--".to_string(),
+                    title.to_string(),
+                    body.to_string(),
+                ],
+            ),
+            Self::Osascript => {
+                let quote =
+                    |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+                (
+                    "osascript",
+                    vec![
+                        "-e".to_string(),
+                        format!(
+                            "display notification {} with title {}",
+                            quote(body),
+                            quote(title)
+                        ),
+                    ],
+                )
+            }
+            Self::Toast => {
+                let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+                let script = [
+                    "$manager = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]".to_string(),
+                    "$toast = $manager::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02)".to_string(),
+                    "$texts = $toast.GetElementsByTagName('text')".to_string(),
+                    format!("$texts.Item(0).AppendChild($toast.CreateTextNode({})) > $null", quote(title)),
+                    format!("$texts.Item(1).AppendChild($toast.CreateTextNode({})) > $null", quote(body)),
+                    format!("$manager::CreateToastNotifier({}).Show([Windows.UI.Notifications.ToastNotification]::new($toast))", quote(POWERSHELL_APP_ID)),
+                ]
+                .join("; ");
+                (
+                    "powershell",
+                    vec![
+                        "-NoProfile".to_string(),
+                        "-NonInteractive".to_string(),
+                        "-Command".to_string(),
+                        script,
+                    ],
+                )
+            }
+        }
+    }
+}
+
+/// Show a notification, failing if the tool of `backend` is missing or fails
+pub fn send(backend: Backend, title: &str, body: &str) -> Result<(), String> {
+    let (program, args) = backend.command(title, body);
+    let output = std::process::Command::new(program)
+        .args(&args)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .map_err(|err| format!("Could not run {program}: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        let (program, args) = Backend::NotifySend.command("rem", "-1 task");
+        assert_eq!(program, "notify-send");
+        assert_eq!(args, ["--app-name=rem", "--", "rem", "-1 task"]);
+
+        let (program, args) = Backend::Osascript.command("rem", r#"pay "rent" \ now"#);
+        assert_eq!(program, "osascript");
+        assert_eq!(
+            args[1],
+            r#"display notification "pay \"rent\" \\ now" with title "rem""#
+        );
+
+        let (program, args) = Backend::Toast.command("rem", "Bob's task");
+        assert_eq!(program, "powershell");
+        assert!(args[3].contains("CreateTextNode('Bob''s task')"));
+        assert!(args[3].contains("CreateToastNotifier('{1AC14E77"));
+    }
+}
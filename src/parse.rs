@@ -0,0 +1,511 @@
+use chrono::{
+    DateTime, Datelike, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta,
+    Timelike, Weekday,
+};
+
+use crate::{start_of_day, LocalDT, DATE_FMT, ISO_DATE_FMT};
+
+/// Parse a duration expression with weeks and days, e.g. the period of a reminder
+///
+/// parsing examples:
+/// '1w 2d' => TimeDelta::days(9)
+///
+/// * `repr`: timedelta to parse
+pub fn parse_timedelta(repr: impl AsRef<str>) -> Result<TimeDelta, String> {
+    parse_units(repr.as_ref(), &UNITS[..2])
+}
+
+/// Parse a duration expression with weeks, days, hours and minutes, e.g. an estimate
+///
+/// parsing examples:
+/// '1h 30m' => TimeDelta::minutes(90)
+///
+/// Unlike [`parse_timedelta`], `m` is valid here, for durations that are not mistaken for a
+/// number of months.
+pub fn parse_duration(repr: impl AsRef<str>) -> Result<TimeDelta, String> {
+    parse_units(repr.as_ref(), &UNITS)
+}
+
+/// Specifiers of duration units with their names and lengths in minutes
+const UNITS: [(char, &str, i64); 4] = [
+    ('w', "weeks", 7 * 24 * 60),
+    ('d', "days", 24 * 60),
+    ('h', "hours", 60),
+    ('m', "minutes", 1),
+];
+
+/// Parse a duration from parts with one of `units` each
+fn parse_units(repr: &str, units: &[(char, &str, i64)]) -> Result<TimeDelta, String> {
+    let specifiers = units
+        .iter()
+        .map(|(unit, _, _)| format!("'{unit}'"))
+        .collect::<Vec<_>>();
+    let (last, rest) = specifiers.split_last().expect("at least one unit");
+    let expected = format!("{} or {last}", rest.join(", "));
+
+    let mut counts = vec![None; units.len()];
+    for part in repr.split_whitespace() {
+        let bytes = part.as_bytes();
+        let idx = bytes.iter().take_while(|x| x.is_ascii_digit()).count();
+        let (num, desc) = bytes.split_at(idx);
+        if desc.len() != 1 {
+            return Err(format!(
+                "invalid duration specifier '{desc}'. Expected {expected}.",
+                desc = std::str::from_utf8(desc).expect("rest of input is utf8")
+            ));
+        }
+        let desc = desc[0] as char;
+
+        let num = std::str::from_utf8(num).expect("used is_ascii_digit to find end of num");
+        let num = num
+            .parse::<i64>()
+            .map_err(|err| format!("Could not parse number from '{num}': {err}"))?;
+
+        let Some(idx) = units.iter().position(|(unit, _, _)| *unit == desc) else {
+            return Err(format!(
+                "Invalid duration specifier '{desc}'. Expected {expected}."
+            ));
+        };
+        if let Some(prev) = counts[idx] {
+            return Err(format!(
+                "Cannot specify {} twice. Already got {prev}.",
+                units[idx].1
+            ));
+        }
+        counts[idx] = Some(num);
+    }
+
+    if counts.iter().all(Option::is_none) {
+        let names = units.iter().map(|(_, name, _)| *name).collect::<Vec<_>>();
+        let (last, rest) = names.split_last().expect("at least one unit");
+        return Err(format!(
+            "Need to specify a number of {} or {last}.",
+            rest.join(", ")
+        ));
+    }
+
+    counts
+        .iter()
+        .zip(units)
+        .try_fold(TimeDelta::zero(), |sum, (count, (_, _, len))| {
+            count
+                .unwrap_or(0)
+                .checked_mul(*len)
+                .and_then(TimeDelta::try_minutes)
+                .and_then(|delta| sum.checked_add(&delta))
+        })
+        .ok_or(format!("Duration '{repr}' is too long."))
+}
+
+/// Parse a date as DD.MM.YYYY or ISO 8601 YYYY-MM-DD or, if `lenient`, in one of the other
+/// common forms
+///
+/// Lenient parsing also accepts `D.M.YY` in this century, US `MM/DD/YYYY` and, for the next
+/// such day from `today` on, `D.M` and `MM/DD`.
+pub fn parse_date(repr: &str, lenient: bool, today: NaiveDate) -> Result<NaiveDate, String> {
+    let invalid = || format!("Invalid date '{repr}'");
+    if !lenient {
+        return NaiveDate::parse_from_str(repr, DATE_FMT)
+            .or_else(|_| NaiveDate::parse_from_str(repr, ISO_DATE_FMT))
+            .map_err(|err| {
+                format!("Could not parse date '{repr}': {err}. Expected DD.MM.YYYY or YYYY-MM-DD.")
+            });
+    }
+
+    let num = |part: &str| {
+        part.parse::<u32>()
+            .map_err(|err| format!("Could not parse date '{repr}': {err}"))
+    };
+    let year = |part: &str| match part.len() {
+        2 => num(part).map(|year| 2000 + year as i32),
+        4 => num(part).map(|year| year as i32),
+        _ => Err(format!(
+            "Invalid year in date '{repr}'. Expected YY or YYYY."
+        )),
+    };
+    let (day, month, year) = if let Some((y, rest)) = repr.split_once('-') {
+        let (m, d) = rest.split_once('-').ok_or_else(invalid)?;
+        if y.len() != 4 {
+            return Err(format!("Invalid ISO date '{repr}'. Expected YYYY-MM-DD."));
+        }
+        (num(d)?, num(m)?, Some(year(y)?))
+    } else if repr.contains('/') {
+        match repr.split('/').collect::<Vec<_>>().as_slice() {
+            [m, d] => (num(d)?, num(m)?, None),
+            [m, d, y] => (num(d)?, num(m)?, Some(year(y)?)),
+            _ => return Err(format!("Invalid date '{repr}'. Expected MM/DD[/YYYY].")),
+        }
+    } else {
+        match repr.split('.').collect::<Vec<_>>().as_slice() {
+            [d, m] | [d, m, ""] => (num(d)?, num(m)?, None),
+            [d, m, y] => (num(d)?, num(m)?, Some(year(y)?)),
+            _ => return Err(format!("Invalid date '{repr}'. Expected DD.MM[.YYYY].")),
+        }
+    };
+    match year {
+        Some(year) => NaiveDate::from_ymd_opt(year, month, day).ok_or_else(invalid),
+        None => [today.year(), today.year() + 1]
+            .into_iter()
+            .filter_map(|year| NaiveDate::from_ymd_opt(year, month, day))
+            .find(|date| *date >= today)
+            .ok_or_else(invalid),
+    }
+}
+
+/// Parse a datetime as DD.MM.YYYY [HH:MM] with a date in any form of [`parse_date`], or as
+/// ISO 8601 like `2024-07-01T09:30`, optionally with seconds and an offset
+///
+/// Returns the datetime and whether it refers to a whole day, i.e. no time was given.
+/// All-day values are placed at the beginning of their day.
+///
+/// * `repr`: datetime to parse
+pub fn parse_date_time_all_day(
+    repr: impl AsRef<str>,
+    lenient: bool,
+) -> Result<(LocalDT, bool), String> {
+    let today = Local::now().date_naive();
+    if repr.as_ref().contains('T') {
+        return parse_iso_date_time(repr.as_ref()).map(|dt| (dt, false));
+    }
+    if let Some((date, time)) = repr.as_ref().split_once(" ") {
+        let date = parse_date(date, lenient, today)?;
+        let time = NaiveTime::parse_from_str(time, "%H:%M")
+            .map_err(|err| format!("Could not parse time: {err}"))?;
+        let dt = date.and_time(time).and_local_timezone(Local).unwrap();
+        Ok((dt, false))
+    } else {
+        let date = parse_date(repr.as_ref(), lenient, today)?;
+        Ok((start_of_day(date), true))
+    }
+}
+
+/// Parse an ISO 8601 datetime, in local time if it has no offset
+fn parse_iso_date_time(repr: &str) -> Result<LocalDT, String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(repr) {
+        return Ok(dt.with_timezone(&Local));
+    }
+    let (date_time, offset) = match repr.rfind(['+', '-', 'Z']) {
+        Some(idx) if idx > repr.find('T').unwrap_or(0) => repr.split_at(idx),
+        _ => (repr, ""),
+    };
+    let naive = NaiveDateTime::parse_from_str(date_time, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(date_time, "%Y-%m-%dT%H:%M"))
+        .map_err(|err| format!("Could not parse ISO 8601 datetime '{repr}': {err}"))?;
+    if offset.is_empty() {
+        return naive
+            .and_local_timezone(Local)
+            .earliest()
+            .ok_or(format!("{repr} does not exist in the local timezone"));
+    }
+    let offset = if offset == "Z" {
+        FixedOffset::east_opt(0)
+    } else {
+        // +HH:MM, +HHMM or +HH
+        let sign = if offset.starts_with('-') { -1 } else { 1 };
+        let digits = offset[1..].replace(':', "");
+        let num = |part: Option<&str>| part.and_then(|p| p.parse::<i32>().ok());
+        match digits.len() {
+            2 | 4 => num(digits.get(..2))
+                .zip(num(digits.get(2..).filter(|m| !m.is_empty()).or(Some("0"))))
+                .and_then(|(h, m)| FixedOffset::east_opt(sign * (h * 3600 + m * 60))),
+            _ => None,
+        }
+    }
+    .ok_or(format!("Invalid offset in ISO 8601 datetime '{repr}'"))?;
+    naive
+        .and_local_timezone(offset)
+        .single()
+        .map(|dt| dt.with_timezone(&Local))
+        .ok_or(format!("Invalid ISO 8601 datetime '{repr}'"))
+}
+
+pub fn parse_date_time(repr: impl AsRef<str>, lenient: bool) -> Result<LocalDT, String> {
+    parse_date_time_all_day(repr, lenient).map(|(dt, _)| dt)
+}
+
+/// Parse a due date relative to the one of another task, like `+3d after 17`
+///
+/// Returns the offset and the id of the other task, or `None` if `repr` does not refer to
+/// another task. Offsets starting with `-` lie before the due date of the other task.
+pub fn parse_due_after(repr: impl AsRef<str>) -> Option<Result<(TimeDelta, u64), String>> {
+    let (offset, id) = repr.as_ref().split_once(" after ")?;
+    let parse = || {
+        let offset = offset.trim();
+        let (sign, offset) = match offset.strip_prefix('-') {
+            Some(offset) => (-1, offset),
+            None => (1, offset.strip_prefix('+').unwrap_or(offset)),
+        };
+        let offset = parse_timedelta(offset)? * sign;
+        let id = id
+            .trim()
+            .parse()
+            .map_err(|err| format!("Could not parse task id from '{id}': {err}"))?;
+        Ok((offset, id))
+    };
+    Some(parse())
+}
+
+/// Field of a cron expression
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CronField {
+    Value(u32),
+    /// every `step`th value from `offset` on, `*` being a step of 1
+    Step(u32, u32),
+}
+
+fn parse_cron_field(repr: &str, max: u32) -> Result<CronField, String> {
+    let number = |repr: &str| {
+        repr.parse::<u32>()
+            .ok()
+            .filter(|value| *value <= max)
+            .ok_or(format!("invalid cron field '{repr}'"))
+    };
+    let (range, step) = match repr.split_once('/') {
+        Some((range, step)) => (range, number(step)?.max(1)),
+        None if repr == "*" => return Ok(CronField::Step(0, 1)),
+        None => return Ok(CronField::Value(number(repr)?)),
+    };
+    match range.split_once('-') {
+        _ if range == "*" => Ok(CronField::Step(0, step)),
+        Some((offset, end)) if number(end)? == max => Ok(CronField::Step(number(offset)?, step)),
+        _ => Err(format!("invalid cron field '{repr}'")),
+    }
+}
+
+/// Parse a cron expression like `0 8 * * MON` into its first occurrence from `now` on, its
+/// period and, for daily and weekly expressions, the time of day
+///
+/// Only expressions repeating at a fixed period are supported: a time every day or on one day
+/// of the week, and steps dividing a day or an hour like `30 */6 * * *` or `*/15 * * * *`.
+pub fn parse_cron(
+    repr: &str,
+    now: LocalDT,
+) -> Result<(LocalDT, TimeDelta, Option<NaiveTime>), String> {
+    let fields = repr.split_whitespace().collect::<Vec<_>>();
+    let [minute, hour, day, month, weekday] = fields[..] else {
+        return Err(format!(
+            "cron expression '{repr}' needs five fields: minute, hour, day of month, month and day of week"
+        ));
+    };
+    let unsupported = || {
+        format!(
+            "cron expression '{repr}' does not repeat at a fixed period. Supported are a time every day or on one day of the week and steps dividing a day or an hour."
+        )
+    };
+    if day != "*" || month != "*" {
+        return Err(unsupported());
+    }
+    let weekday = match weekday {
+        "*" => None,
+        repr => Some(match repr.parse::<u8>() {
+            Ok(0 | 7) => Weekday::Sun,
+            Ok(n @ 1..=6) => Weekday::try_from(n - 1).expect("monday to saturday"),
+            _ => repr
+                .parse::<Weekday>()
+                .map_err(|_| format!("invalid day of week '{repr}'"))?,
+        }),
+    };
+
+    let at = |hour: u32, minute: u32| NaiveTime::from_hms_opt(hour, minute, 0).expect("valid time");
+    let today = now.date_naive();
+    let (anchor, period, time) = match (
+        parse_cron_field(minute, 59)?,
+        parse_cron_field(hour, 23)?,
+        weekday,
+    ) {
+        (CronField::Value(m), CronField::Value(h), None) => {
+            (today.and_time(at(h, m)), TimeDelta::days(1), Some(at(h, m)))
+        }
+        (CronField::Value(m), CronField::Value(h), Some(weekday)) => {
+            let ahead =
+                (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+            let date = today + TimeDelta::days(ahead.into());
+            (date.and_time(at(h, m)), TimeDelta::weeks(1), Some(at(h, m)))
+        }
+        (CronField::Value(m), CronField::Step(offset, step), None)
+            if 24 % step == 0 && offset < step =>
+        {
+            (
+                today.and_time(at(offset, m)),
+                TimeDelta::hours(step.into()),
+                None,
+            )
+        }
+        (CronField::Step(offset, step), CronField::Step(0, 1), None)
+            if 60 % step == 0 && offset < step =>
+        {
+            (
+                today.and_time(at(now.hour(), offset)),
+                TimeDelta::minutes(step.into()),
+                None,
+            )
+        }
+        _ => return Err(unsupported()),
+    };
+    let mut first = anchor
+        .and_local_timezone(Local)
+        .earliest()
+        .ok_or(format!("{anchor} does not exist in the local timezone"))?;
+    while first < now {
+        first += period;
+    }
+    Ok((first, period, time))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parse_cron_expressions() {
+        // a wednesday
+        let now = Local.with_ymd_and_hms(2025, 1, 8, 10, 7, 0).unwrap();
+        let at = |d, h, m| Local.with_ymd_and_hms(2025, 1, d, h, m, 0).unwrap();
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0);
+
+        assert_eq!(
+            parse_cron("0 8 * * MON", now),
+            Ok((at(13, 8, 0), TimeDelta::weeks(1), time(8, 0)))
+        );
+        assert_eq!(
+            parse_cron("30 12 * * 3", now),
+            Ok((at(8, 12, 30), TimeDelta::weeks(1), time(12, 30)))
+        );
+        assert_eq!(
+            parse_cron("0 8 * * *", now),
+            Ok((at(9, 8, 0), TimeDelta::days(1), time(8, 0)))
+        );
+        assert_eq!(
+            parse_cron("30 2-23/6 * * *", now),
+            Ok((at(8, 14, 30), TimeDelta::hours(6), None))
+        );
+        assert_eq!(
+            parse_cron("*/15 * * * *", now),
+            Ok((at(8, 10, 15), TimeDelta::minutes(15), None))
+        );
+        assert!(parse_cron("0 8 1 * *", now).is_err());
+        assert!(parse_cron("0 8,12 * * *", now).is_err());
+        assert!(parse_cron("0 */5 * * *", now).is_err());
+        assert!(parse_cron("0 8 * *", now).is_err());
+        assert!(parse_cron("0 8 * * FUNDAY", now).is_err());
+    }
+
+    #[test]
+    fn parse_timedelta_week() {
+        assert_eq!(parse_timedelta("1w"), Ok(TimeDelta::days(7)));
+    }
+
+    #[test]
+    fn parse_timedelta_day() {
+        assert_eq!(parse_timedelta("1d"), Ok(TimeDelta::days(1)));
+    }
+
+    #[test]
+    fn parse_timedelta_fail() {
+        assert!(parse_timedelta("1wf 2d").is_err());
+        assert!(parse_timedelta("1w 1w").is_err());
+        assert!(parse_timedelta("1d 1d").is_err());
+    }
+
+    #[test]
+    fn parse_duration_hours_minutes() {
+        assert_eq!(parse_duration("1h 30m"), Ok(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("45m"), Ok(TimeDelta::minutes(45)));
+        assert_eq!(parse_duration("1w 1d"), Ok(TimeDelta::days(8)));
+        assert!(parse_duration("1h 1h").is_err());
+        assert!(parse_duration("12").is_err());
+        // minutes could be mistaken for months in periods
+        assert!(parse_timedelta("1m").is_err());
+        assert!(parse_timedelta("2h").is_err());
+        assert!(parse_duration("99999999999999w").is_err());
+    }
+
+    #[test]
+    fn parse_due_after_task() {
+        assert_eq!(
+            parse_due_after("+3d after 17"),
+            Some(Ok((TimeDelta::days(3), 17)))
+        );
+        assert_eq!(
+            parse_due_after("-1w after 2"),
+            Some(Ok((TimeDelta::days(-7), 2)))
+        );
+        assert!(parse_due_after("3d after x").unwrap().is_err());
+        assert!(parse_due_after("14.10.2025").is_none());
+    }
+
+    #[test]
+    fn parse_dates() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        // input, whether lenient and the date read, if any
+        let cases = [
+            ("01.07.2025", false, Some((2025, 7, 1))),
+            ("2025-07-01", false, Some((2025, 7, 1))),
+            ("1.7", false, None),
+            ("07/01", false, None),
+            ("1.7.2025.3", false, None),
+            ("01.07.2025", true, Some((2025, 7, 1))),
+            ("1.7", true, Some((2025, 7, 1))),
+            ("1.7.", true, Some((2025, 7, 1))),
+            ("1.3", true, Some((2025, 3, 1))),
+            ("1.2", true, Some((2026, 2, 1))),
+            ("31.2", true, None),
+            ("1.7.25", true, Some((2025, 7, 1))),
+            ("1.7.99", true, Some((2099, 7, 1))),
+            ("1.7.202", true, None),
+            ("2024-07-01", true, Some((2024, 7, 1))),
+            ("24-07-01", true, None),
+            ("2024-07", true, None),
+            ("07/01", true, Some((2025, 7, 1))),
+            ("02/01", true, Some((2026, 2, 1))),
+            ("07/01/2024", true, Some((2024, 7, 1))),
+            ("13/01", true, None),
+        ];
+        for (repr, lenient, expected) in cases {
+            let expected = expected.map(|(y, m, d)| NaiveDate::from_ymd_opt(y, m, d).unwrap());
+            assert_eq!(
+                parse_date(repr, lenient, today).ok(),
+                expected,
+                "{repr}, lenient: {lenient}"
+            );
+        }
+
+        let (dt, all_day) = parse_date_time_all_day("2024-07-01 09:30", true).unwrap();
+        assert!(!all_day);
+        assert_eq!(dt, parse_date_time("01.07.2024 09:30", false).unwrap());
+        assert!(parse_date_time("07/01/2024 09:30", false).is_err());
+    }
+
+    #[test]
+    fn parse_iso_date_times() {
+        let local = parse_date_time("01.07.2024 09:30", false).unwrap();
+        assert_eq!(parse_date_time("2024-07-01T09:30", false), Ok(local));
+        assert_eq!(parse_date_time("2024-07-01T09:30:00", false), Ok(local));
+        assert_eq!(parse_date_time("2024-07-01 09:30", false), Ok(local));
+        let (_, all_day) = parse_date_time_all_day("2024-07-01", false).unwrap();
+        assert!(all_day);
+
+        let utc = chrono::Utc.with_ymd_and_hms(2024, 7, 1, 7, 30, 0).unwrap();
+        for repr in [
+            "2024-07-01T07:30:00Z",
+            "2024-07-01T09:30:00+02:00",
+            "2024-07-01T09:30+02:00",
+            "2024-07-01T02:30-0500",
+        ] {
+            assert_eq!(
+                parse_date_time(repr, false),
+                Ok(utc.with_timezone(&Local)),
+                "{repr}"
+            );
+        }
+        assert!(parse_date_time("2024-07-01T25:00", false).is_err());
+        assert!(parse_date_time("2024-07-01T09:30+2x", false).is_err());
+    }
+
+    #[test]
+    fn parse_timedelta_mixed() {
+        assert_eq!(parse_timedelta("1w 2d"), Ok(TimeDelta::days(9)));
+        assert_eq!(parse_timedelta("2w 1d"), Ok(TimeDelta::days(15)));
+    }
+}
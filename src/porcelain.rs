@@ -0,0 +1,178 @@
+//! Machine-readable output of tasks and reminders for scripts
+//!
+//! Every record is printed as one line of JSON. The fields of a version are frozen: they are
+//! never removed, renamed or change their meaning, changes need a new version. Times are RFC 3339
+//! and unset values are `null`.
+
+use serde::Serialize;
+
+use crate::{Reminder, Task};
+
+/// Versions of the porcelain output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Version {
+    V1,
+    /// v1 with the urgency of tasks
+    V2,
+}
+
+#[derive(Serialize)]
+struct TaskV1<'a> {
+    id: u64,
+    title: &'a str,
+    description: Option<&'a str>,
+    parent: Option<u64>,
+    generated_by: Option<u64>,
+    priority: Option<String>,
+    context: Option<&'a str>,
+    tags: &'a [String],
+    estimate_minutes: Option<i64>,
+    created: String,
+    start: Option<String>,
+    due_start: Option<String>,
+    due: Option<String>,
+    all_day: bool,
+    completed: Option<String>,
+    skipped: bool,
+}
+
+#[derive(Serialize)]
+struct TaskV2<'a> {
+    #[serde(flatten)]
+    v1: TaskV1<'a>,
+    urgency: f64,
+}
+
+#[derive(Serialize)]
+struct ReminderV1<'a> {
+    id: u64,
+    title: &'a str,
+    description: Option<&'a str>,
+    priority: Option<String>,
+    context: Option<&'a str>,
+    tags: &'a [String],
+    created: String,
+    first_due: String,
+    all_day: bool,
+    period_seconds: i64,
+    until: Option<String>,
+}
+
+/// * `urgency`: of the task, see `score::urgency`, only part of v2 and later
+pub fn task(task: &Task, urgency: f64, version: Version) -> String {
+    let v1 = TaskV1 {
+        id: task.id,
+        title: &task.title,
+        description: task.description.as_deref(),
+        parent: task.parent,
+        generated_by: task.generated_by,
+        priority: task.priority.map(|p| p.to_string()),
+        context: task.context.as_deref(),
+        tags: &task.tags,
+        estimate_minutes: task.estimate.map(|e| e.num_minutes()),
+        created: task.created.to_rfc3339(),
+        start: task.start.map(|t| t.to_rfc3339()),
+        due_start: task.due_start.map(|t| t.to_rfc3339()),
+        due: task.due.map(|t| t.to_rfc3339()),
+        all_day: task.due_all_day,
+        completed: task.completed.map(|t| t.to_rfc3339()),
+        skipped: task.skipped,
+    };
+    let line = match version {
+        Version::V1 => serde_json::to_string(&v1),
+        Version::V2 => serde_json::to_string(&TaskV2 { v1, urgency }),
+    };
+    line.expect("task fields serialize")
+}
+
+pub fn reminder(reminder: &Reminder, version: Version) -> String {
+    let line = match version {
+        Version::V1 | Version::V2 => serde_json::to_string(&ReminderV1 {
+            id: reminder.id,
+            title: &reminder.title,
+            description: reminder.description.as_deref(),
+            priority: reminder.priority.map(|p| p.to_string()),
+            context: reminder.context.as_deref(),
+            tags: &reminder.tags,
+            created: reminder.created.to_rfc3339(),
+            first_due: reminder.first_due.to_rfc3339(),
+            all_day: reminder.all_day,
+            period_seconds: reminder.period.num_seconds(),
+            until: reminder.until.map(|t| t.to_rfc3339()),
+        }),
+    };
+    line.expect("reminder fields serialize")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn keys(line: &str) -> Vec<String> {
+        let value: serde_json::Value = serde_json::from_str(line).unwrap();
+        value.as_object().unwrap().keys().cloned().collect()
+    }
+
+    #[test]
+    fn frozen_v1() {
+        let line = task(&Task::builder().title("a\tb").build(), 1.5, Version::V1);
+        assert!(!line.contains('\n'));
+        assert_eq!(
+            keys(&line),
+            [
+                "all_day",
+                "completed",
+                "context",
+                "created",
+                "description",
+                "due",
+                "due_start",
+                "estimate_minutes",
+                "generated_by",
+                "id",
+                "parent",
+                "priority",
+                "skipped",
+                "start",
+                "tags",
+                "title"
+            ]
+        );
+
+        let line = reminder(&Reminder::builder().title("r").build(), Version::V1);
+        assert_eq!(
+            keys(&line),
+            [
+                "all_day",
+                "context",
+                "created",
+                "description",
+                "first_due",
+                "id",
+                "period_seconds",
+                "priority",
+                "tags",
+                "title",
+                "until"
+            ]
+        );
+    }
+
+    #[test]
+    fn urgency_v2() {
+        let task = Task::builder().title("t").build();
+        let v1 = keys(&super::task(&task, 1.5, Version::V1));
+        let line = super::task(&task, 1.5, Version::V2);
+        let mut v2 = keys(&line);
+        v2.retain(|key| key != "urgency");
+        assert_eq!(v1, v2);
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["urgency"], 1.5);
+
+        let reminder = Reminder::builder().title("r").build();
+        assert_eq!(
+            super::reminder(&reminder, Version::V1),
+            super::reminder(&reminder, Version::V2)
+        );
+    }
+}
@@ -0,0 +1,372 @@
+//! Quick-add grammar for capturing a task in a single line
+//!
+//! Words of the input are either annotations or part of the title:
+//!
+//! * `!high`, `!medium`, `!low`: priority
+//! * `#tag`: tag, may be given multiple times
+//! * `@context`: context
+//! * `due:DD.MM[.YYYY]`, `due:today`, `due:tomorrow`, the next weekday like `due:friday` or
+//!   another form read by [`parse_date`], e.g. `due:2025-07-01`: all-day due date
+//! * `every:PERIOD`: recurrence period in weeks and days as accepted by [`parse_timedelta`],
+//!   e.g. `every:2w`
+//!
+//! Everything else makes up the title, e.g. `Pay rent !high #finance @home due:1.7 every:4w`.
+//!
+//! Lines of code are read by [`parse_line`] instead, which takes the annotations from the
+//! parentheses of a `TODO` comment and the rest of the comment as the title, e.g.
+//! `// TODO(due=friday, priority=high): fix parser`.
+
+use chrono::{Datelike, NaiveDate, TimeDelta, Weekday};
+
+use crate::parse::{parse_date, parse_timedelta};
+use crate::Priority;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuickAdd {
+    pub title: String,
+    pub priority: Option<Priority>,
+    pub tags: Vec<String>,
+    pub context: Option<String>,
+    pub due: Option<NaiveDate>,
+    pub every: Option<TimeDelta>,
+}
+
+/// Parse a quick-add line
+///
+/// * `repr`: line to parse
+/// * `today`: date that relative due dates refer to
+/// * `lenient`: read due dates in all forms of [`parse_date`]
+pub fn parse(repr: impl AsRef<str>, today: NaiveDate, lenient: bool) -> Result<QuickAdd, String> {
+    let mut title = Vec::new();
+    let mut priority = None;
+    let mut tags: Vec<String> = Vec::new();
+    let mut context = None;
+    let mut due = None;
+    let mut every = None;
+
+    for word in repr.as_ref().split_whitespace() {
+        if let Some(p) = word.strip_prefix('!').filter(|p| !p.is_empty()) {
+            if priority.is_some() {
+                return Err(format!("Cannot specify priority twice. Got '{word}'."));
+            }
+            priority = Some(p.parse::<Priority>()?);
+        } else if let Some(tag) = word.strip_prefix('#').filter(|t| !t.is_empty()) {
+            if !tags.iter().any(|t| t == tag) {
+                tags.push(tag.to_string());
+            }
+        } else if let Some(ctx) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+            if context.is_some() {
+                return Err(format!("Cannot specify context twice. Got '{word}'."));
+            }
+            context = Some(ctx.to_string());
+        } else if let Some(date) = word.strip_prefix("due:") {
+            if due.is_some() {
+                return Err(format!("Cannot specify due date twice. Got '{word}'."));
+            }
+            due = Some(parse_due(date, today, lenient)?);
+        } else if let Some(period) = word.strip_prefix("every:") {
+            if every.is_some() {
+                return Err(format!("Cannot specify recurrence twice. Got '{word}'."));
+            }
+            every = Some(parse_timedelta(period)?);
+        } else {
+            title.push(word);
+        }
+    }
+
+    if title.is_empty() {
+        return Err("Need a title besides the annotations.".to_string());
+    }
+
+    if every.is_some() && due.is_none() {
+        return Err("A recurring task needs a first due date. Specify 'due:'.".to_string());
+    }
+
+    Ok(QuickAdd {
+        title: title.join(" "),
+        priority,
+        tags,
+        context,
+        due,
+        every,
+    })
+}
+
+/// Parse a due date, picking the next matching date if the year is omitted from `DD.MM`
+///
+/// Weekdays like `friday` or `fri` are the next one after today. `DD.MM` is read like
+/// [`parse_date`] does when lenient, other forms by [`parse_date`] with `lenient`.
+pub fn parse_due(repr: &str, today: NaiveDate, lenient: bool) -> Result<NaiveDate, String> {
+    match repr {
+        "today" => return Ok(today),
+        "tomorrow" => return Ok(today.succ_opt().expect("date is in range")),
+        _ => (),
+    }
+    if let Ok(weekday) = repr.parse::<Weekday>() {
+        let ahead =
+            (weekday.num_days_from_monday() + 6 - today.weekday().num_days_from_monday()) % 7;
+        return Ok(today + TimeDelta::days(ahead as i64 + 1));
+    }
+
+    let day_month = repr
+        .trim_end_matches('.')
+        .split_once('.')
+        .is_some_and(|(_, month)| !month.contains('.'));
+    parse_date(repr, lenient || day_month, today).map_err(|err| {
+        format!(
+            "{}. A weekday, 'today' or 'tomorrow' work as well.",
+            err.trim_end_matches('.')
+        )
+    })
+}
+
+/// Comment markers [`parse_line`] looks for
+pub const MARKERS: [&str; 3] = ["TODO", "FIXME", "XXX"];
+
+/// Line of code with a `TODO` comment, see [`parse_line`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    /// text up to and including the marker, e.g. `    // TODO`
+    pub head: String,
+    /// items in the parentheses that are no annotations, e.g. the author in `TODO(alice)`
+    pub kept: Vec<String>,
+    /// task the line refers to already with `rem:#ID`
+    pub task: Option<u64>,
+    pub quick: QuickAdd,
+    /// end of a block comment after the title, e.g. ` */`
+    pub tail: String,
+}
+
+impl Line {
+    /// The line referring to task `id` in place of its annotations, e.g.
+    /// `// TODO(rem:#12): fix parser`
+    pub fn rewrite(&self, id: u64) -> String {
+        let mut items = self.kept.clone();
+        items.push(format!("rem:#{id}"));
+        format!(
+            "{}({}): {}{}",
+            self.head,
+            items.join(", "),
+            self.quick.title,
+            self.tail
+        )
+    }
+}
+
+/// Parse a line of code with a `TODO`, `FIXME` or `XXX` comment
+///
+/// Annotations are given in parentheses after the marker as `due=DATE` in the forms of
+/// [`parse_due`], `priority=PRIORITY`, `tag=TAG` and `context=CONTEXT`, separated by commas.
+/// Other items are kept, as is `rem:#ID` referring to a task. The rest of the comment is the
+/// title, e.g. `// TODO(due=friday): fix parser`.
+///
+/// * `lenient`: read due dates in all forms of [`parse_date`]
+pub fn parse_line(line: &str, today: NaiveDate, lenient: bool) -> Result<Line, String> {
+    let word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let (start, marker) = MARKERS
+        .iter()
+        .flat_map(|marker| line.match_indices(marker))
+        .filter(|(idx, marker)| {
+            !word(line[..*idx].chars().next_back())
+                && !word(line[idx + marker.len()..].chars().next())
+        })
+        .min_by_key(|(idx, _)| *idx)
+        .ok_or(format!("Expected a TODO, FIXME or XXX comment in '{line}'"))?;
+    let head = &line[..start + marker.len()];
+    let mut rest = &line[head.len()..];
+
+    let mut items = Vec::new();
+    if let Some(inner) = rest.strip_prefix('(') {
+        let (inner, after) = inner
+            .split_once(')')
+            .ok_or(format!("Missing ')' after the annotations of '{line}'"))?;
+        items = inner
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect();
+        rest = after;
+    }
+    let rest = rest.trim_start_matches(':').trim();
+    let (title, tail) = ["*/", "-->"]
+        .iter()
+        .find_map(|end| {
+            rest.strip_suffix(end)
+                .map(|title| (title, &rest[title.len()..]))
+        })
+        .unwrap_or((rest, ""));
+    let title = title.trim_end();
+    if title.is_empty() {
+        return Err(format!("Need a title after the {marker} of '{line}'"));
+    }
+
+    let mut parsed = Line {
+        head: head.to_string(),
+        kept: Vec::new(),
+        task: None,
+        quick: QuickAdd {
+            title: title.to_string(),
+            priority: None,
+            tags: Vec::new(),
+            context: None,
+            due: None,
+            every: None,
+        },
+        tail: if tail.is_empty() {
+            String::new()
+        } else {
+            format!(" {tail}")
+        },
+    };
+    for item in items {
+        let twice = || Err(format!("Cannot specify '{item}' twice"));
+        let quick = &mut parsed.quick;
+        match item.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+            Some(("due", _)) if quick.due.is_some() => return twice(),
+            Some(("due", date)) => quick.due = Some(parse_due(date, today, lenient)?),
+            Some(("priority", _)) if quick.priority.is_some() => return twice(),
+            Some(("priority", priority)) => quick.priority = Some(priority.parse()?),
+            Some(("tag", tag)) => {
+                if !quick.tags.iter().any(|t| t == tag) {
+                    quick.tags.push(tag.to_string());
+                }
+            }
+            Some(("context", _)) if quick.context.is_some() => return twice(),
+            Some(("context", context)) => quick.context = Some(context.to_string()),
+            Some((key, _)) => {
+                return Err(format!(
+                    "Unknown annotation '{key}', expected due, priority, tag or context"
+                ))
+            }
+            None => match item.strip_prefix("rem:#").map(str::parse) {
+                Some(Ok(id)) => parsed.task = Some(id),
+                Some(Err(err)) => return Err(format!("Invalid task reference '{item}': {err}")),
+                None => parsed.kept.push(item.to_string()),
+            },
+        }
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, 15).unwrap()
+    }
+
+    #[test]
+    fn full_line() {
+        let q = parse(
+            "Pay rent !high #finance @home due:1.7 every:4w",
+            today(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(q.title, "Pay rent");
+        assert_eq!(q.priority, Some(Priority::High));
+        assert_eq!(q.tags, vec!["finance".to_string()]);
+        assert_eq!(q.context, Some("home".to_string()));
+        assert_eq!(q.due, NaiveDate::from_ymd_opt(2025, 7, 1));
+        assert_eq!(q.every, Some(TimeDelta::days(28)));
+    }
+
+    #[test]
+    fn title_only() {
+        let q = parse("  call   mom ", today(), false).unwrap();
+        assert_eq!(q.title, "call mom");
+        assert_eq!(q.priority, None);
+        assert!(q.tags.is_empty());
+        assert_eq!(q.due, None);
+    }
+
+    #[test]
+    fn due_dates() {
+        assert_eq!(parse_due("today", today(), false), Ok(today()));
+        assert_eq!(
+            parse_due("tomorrow", today(), false),
+            NaiveDate::from_ymd_opt(2025, 6, 16).ok_or(String::new())
+        );
+        assert_eq!(
+            parse_due("1.3", today(), false),
+            NaiveDate::from_ymd_opt(2026, 3, 1).ok_or(String::new())
+        );
+        assert_eq!(
+            parse_due("01.03.2025", today(), false),
+            NaiveDate::from_ymd_opt(2025, 3, 1).ok_or(String::new())
+        );
+        assert_eq!(
+            parse_due("2025-07-01", today(), false),
+            NaiveDate::from_ymd_opt(2025, 7, 1).ok_or(String::new())
+        );
+        assert!(parse_due("31.2", today(), false).is_err());
+        // today() is a sunday
+        assert_eq!(
+            parse_due("friday", today(), false),
+            NaiveDate::from_ymd_opt(2025, 6, 20).ok_or(String::new())
+        );
+        assert_eq!(
+            parse_due("sun", today(), false),
+            NaiveDate::from_ymd_opt(2025, 6, 22).ok_or(String::new())
+        );
+        assert!(parse_due("someday", today(), false).is_err());
+
+        // the other forms only when lenient, reading `1.3` the same way
+        assert!(parse_due("07/01", today(), false).is_err());
+        assert_eq!(
+            parse_due("07/01", today(), true),
+            NaiveDate::from_ymd_opt(2025, 7, 1).ok_or(String::new())
+        );
+        assert_eq!(
+            parse_due("1.3", today(), true),
+            parse_date("1.3", true, today())
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse("!high #tag", today(), false).is_err());
+        assert!(parse("foo !urgent", today(), false).is_err());
+        assert!(parse("foo @a @b", today(), false).is_err());
+        assert!(parse("foo every:1w", today(), false).is_err());
+        assert!(parse("Pay rent due:1.7 every:1m", today(), false).is_err());
+    }
+
+    #[test]
+    fn lines() {
+        let line = parse_line(
+            "    // TODO(due=friday, priority=high): fix parser",
+            today(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(line.head, "    // TODO");
+        assert_eq!(line.quick.title, "fix parser");
+        assert_eq!(line.quick.due, NaiveDate::from_ymd_opt(2025, 6, 20));
+        assert_eq!(line.quick.priority, Some(Priority::High));
+        assert_eq!(line.rewrite(12), "    // TODO(rem:#12): fix parser");
+
+        let line = parse_line(
+            "/* FIXME(alice, tag=db, context=work) leaks */",
+            today(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(line.quick.title, "leaks");
+        assert_eq!(line.quick.tags, ["db"]);
+        assert_eq!(line.quick.context.as_deref(), Some("work"));
+        assert_eq!(line.rewrite(3), "/* FIXME(alice, rem:#3): leaks */");
+
+        let line = parse_line("# XXX: cache this", today(), false).unwrap();
+        assert_eq!((line.quick.title.as_str(), line.task), ("cache this", None));
+        let line = parse_line("// TODO(rem:#7): fix parser", today(), false).unwrap();
+        assert_eq!(line.task, Some(7));
+
+        assert!(parse_line("let todo = TODOS;", today(), false).is_err());
+        assert!(parse_line("// TODO(due=friday):", today(), false).is_err());
+        assert!(parse_line("// TODO(due=friday fix parser", today(), false).is_err());
+        assert!(parse_line("// TODO(owner=bob): fix", today(), false).is_err());
+        assert!(parse_line("// TODO(due=1.7, due=2.7): fix", today(), false).is_err());
+    }
+}
@@ -1,8 +1,13 @@
-use chrono::TimeDelta;
+use std::ops::{Bound, RangeBounds};
+
+use chrono::{Datelike, NaiveDate, NaiveTime, TimeDelta, Timelike};
 use colored::Colorize;
-use rusqlite::Row;
+use rusqlite::fallible_iterator::FallibleIterator;
+use rusqlite::{Connection, Row};
 
-use crate::{import_datetime, LocalDT, DATETIME_FMT};
+use crate::{
+    format_timedelta, import_datetime, truncate_to_minute, Config, LocalDT, Priority, DATE_FMT,
+};
 
 pub struct Reminder {
     pub id: u64,
@@ -11,35 +16,616 @@ pub struct Reminder {
 
     pub created: LocalDT,
     pub first_due: LocalDT,
+    /// occurrences are due on a day rather than at a specific time
+    pub all_day: bool,
     pub period: TimeDelta,
+    /// wall-clock time all occurrences are due at, regardless of drift from period arithmetic
+    pub at: Option<NaiveTime>,
 
     pub until: Option<LocalDT>,
+
+    /// attributes passed on to generated tasks
+    pub priority: Option<Priority>,
+    pub context: Option<String>,
+    pub tags: Vec<String>,
+    /// only generate an occurrence once the previous one is completed
+    pub require_completion: bool,
+    /// checklist template instantiated on every generated task
+    pub checklist: Vec<String>,
+    /// further tasks generated with every occurrence, e.g. to prepare a meeting
+    pub lead_tasks: Vec<LeadTask>,
+    pub catch_up: CatchUp,
+    /// open occurrences whose deadline passed longer ago than this are skipped when newer ones
+    /// are generated
+    pub skip_stale_after: Option<TimeDelta>,
+    /// occurrences due before this were skipped when catching up and are not generated
+    pub skip_before: Option<LocalDT>,
+    /// reminder this one is grouped under, stopped and listed together with it
+    pub parent: Option<u64>,
+    /// part of the year occurrences are generated in
+    pub season: Option<Season>,
+}
+
+/// Task generated along with every occurrence of a reminder, due relative to the occurrence
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LeadTask {
+    pub title: String,
+    /// from the due date of the occurrence, negative for tasks due before it
+    pub offset: TimeDelta,
+}
+
+impl std::str::FromStr for LeadTask {
+    type Err = String;
+
+    /// Parse `TITLE:OFFSET`, e.g. `prepare agenda:-2d` or `send minutes:+1d`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (title, offset) = s.rsplit_once(':').ok_or(format!(
+            "invalid lead task '{s}'. Expected TITLE:OFFSET, e.g. 'prepare agenda:-2d'"
+        ))?;
+        let title = title.trim();
+        if title.is_empty() {
+            return Err(format!("lead task '{s}' needs a title"));
+        }
+        let offset = offset.trim();
+        let (sign, offset) = match offset.strip_prefix('-') {
+            Some(offset) => (-1, offset),
+            None => (1, offset.strip_prefix('+').unwrap_or(offset)),
+        };
+        Ok(Self {
+            title: title.to_string(),
+            offset: crate::parse::parse_duration(offset)? * sign,
+        })
+    }
+}
+
+impl std::fmt::Display for LeadTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.offset < TimeDelta::zero() {
+            write!(
+                f,
+                "{}, {} before",
+                self.title,
+                format_timedelta(-self.offset)
+            )
+        } else {
+            write!(f, "{}, {} after", self.title, format_timedelta(self.offset))
+        }
+    }
+}
+
+/// Yearly window like `01.04..31.10`, from its first to its last day. Windows may span the turn
+/// of the year, like `01.11..31.03`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Season {
+    /// month and day
+    start: (u32, u32),
+    end: (u32, u32),
+}
+
+impl Season {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        let day = (date.month(), date.day());
+        if self.start <= self.end {
+            self.start <= day && day <= self.end
+        } else {
+            self.start <= day || day <= self.end
+        }
+    }
+}
+
+impl Season {
+    /// Spans of `year` within the season, the ends excluded
+    fn spans(&self, year: i32) -> Vec<(LocalDT, LocalDT)> {
+        // 29.02 is the 01.03 in common years, the only day not existing every year
+        let Some(march) = NaiveDate::from_ymd_opt(year, 3, 1) else {
+            return Vec::new();
+        };
+        let day = |(month, day)| NaiveDate::from_ymd_opt(year, month, day);
+        let start = day(self.start).unwrap_or(march);
+        let end = day(self.end)
+            .and_then(|end| end.succ_opt())
+            .unwrap_or(march);
+        let (Some(first), Some(next_year)) = (
+            NaiveDate::from_ymd_opt(year, 1, 1),
+            NaiveDate::from_ymd_opt(year + 1, 1, 1),
+        ) else {
+            return Vec::new();
+        };
+        let span = |start, end| (crate::start_of_day(start), crate::start_of_day(end));
+        if self.start <= self.end {
+            vec![span(start, end)]
+        } else {
+            vec![span(first, end), span(start, next_year)]
+        }
+    }
+}
+
+impl std::str::FromStr for Season {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let day = |repr: &str| {
+            // a leap year, so 29.02 is accepted
+            NaiveDate::parse_from_str(&format!("{}.2000", repr.trim()), "%d.%m.%Y")
+                .map(|date| (date.month(), date.day()))
+                .map_err(|err| format!("Could not parse day '{repr}' as DD.MM: {err}"))
+        };
+        let (start, end) = s
+            .split_once("..")
+            .ok_or(format!("invalid season '{s}'. Expected DD.MM..DD.MM"))?;
+        Ok(Self {
+            start: day(start)?,
+            end: day(end)?,
+        })
+    }
+}
+
+impl std::fmt::Display for Season {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}.{:02}..{:02}.{:02}",
+            self.start.1, self.start.0, self.end.1, self.end.0
+        )
+    }
+}
+
+/// A single, scheduled occurrence of a reminder
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Occurrence {
+    pub reminder_id: u64,
+    /// number of periods since the first occurrence
+    pub index: u64,
+    /// on a whole minute, also if the first due date of the reminder has seconds
+    pub due: LocalDT,
+}
+
+impl Occurrence {
+    /// Replace the placeholders of a title or description of the reminder with the values of
+    /// this occurrence
+    ///
+    /// Understood are `{date}` (always DD.MM.YYYY, as the text is stored), `{week}` (ISO week number), `{month}`, `{year}` and `{index}`
+    /// (number of the occurrence, counted from 1). Other text in braces is kept as written.
+    pub fn expand(&self, text: &str) -> String {
+        let mut ret = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('{') {
+            ret.push_str(&rest[..start]);
+            let candidate = &rest[start..];
+            let end = candidate.find('}').map(|end| end + 1);
+            let value = end.and_then(|end| match &candidate[1..end - 1] {
+                "date" => Some(self.due.format(DATE_FMT).to_string()),
+                "week" => Some(self.due.iso_week().week().to_string()),
+                "month" => Some(format!("{:02}", self.due.month())),
+                "year" => Some(self.due.year().to_string()),
+                "index" => Some((self.index + 1).to_string()),
+                _ => None,
+            });
+            match (value, end) {
+                (Some(value), Some(end)) => {
+                    ret.push_str(&value);
+                    rest = &candidate[end..];
+                }
+                _ => {
+                    ret.push('{');
+                    rest = &candidate[1..];
+                }
+            }
+        }
+        ret.push_str(rest);
+        ret
+    }
+}
+
+/// Iterator over the occurrences of a reminder, see [`Reminder::occurrences`]
+pub struct Occurrences<'a> {
+    reminder: &'a Reminder,
+    index: u64,
+    start: Bound<LocalDT>,
+    end: Bound<LocalDT>,
+}
+
+impl Iterator for Occurrences<'_> {
+    type Item = Occurrence;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reminder = self.reminder;
+        if reminder.period <= TimeDelta::zero() {
+            return None;
+        }
+
+        // occurrences may never fall into the season, e.g. yearly ones outside of it
+        let mut out_of_season_since = None;
+        loop {
+            let occurrence = Occurrence {
+                reminder_id: reminder.id,
+                index: self.index,
+                due: reminder.due(self.index)?,
+            };
+
+            let before_end = match self.end {
+                Bound::Included(end) => occurrence.due <= end,
+                Bound::Excluded(end) => occurrence.due < end,
+                Bound::Unbounded => true,
+            };
+            if !before_end {
+                return None;
+            }
+            self.index += 1;
+
+            if let Some(season) = reminder.season {
+                if !season.contains(occurrence.due.date_naive()) {
+                    let since = *out_of_season_since.get_or_insert(occurrence.due);
+                    if occurrence.due - since > TimeDelta::days(10 * 366) {
+                        return None;
+                    }
+                    continue;
+                }
+            }
+
+            let after_start = match self.start {
+                Bound::Included(start) => occurrence.due >= start,
+                Bound::Excluded(start) => occurrence.due > start,
+                Bound::Unbounded => true,
+            };
+            if after_start {
+                return Some(occurrence);
+            }
+        }
+    }
+}
+
+/// Describe a period in words, e.g. `every 2 weeks`
+pub fn humanize_period(period: TimeDelta) -> String {
+    let minutes = period.num_minutes();
+    if minutes <= 0 || period != TimeDelta::minutes(minutes) {
+        return format!("every {}", format_timedelta(period));
+    }
+
+    for (unit, len) in [("week", 7 * 24 * 60), ("day", 24 * 60), ("hour", 60)] {
+        if minutes % len == 0 {
+            return match minutes / len {
+                1 => format!("every {unit}"),
+                n => format!("every {n} {unit}s"),
+            };
+        }
+    }
+
+    if minutes < 60 {
+        match minutes {
+            1 => "every minute".to_string(),
+            n => format!("every {n} minutes"),
+        }
+    } else {
+        format!("every {}", format_timedelta(period))
+    }
+}
+
+/// What to generate for occurrences missed since the last run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CatchUp {
+    /// a task for every missed occurrence, up to the backfill limit
+    #[default]
+    All = 0,
+    /// a single task noting how many occurrences were missed
+    Collapse = 1,
+}
+
+impl CatchUp {
+    pub fn from_db(value: i64) -> Option<Self> {
+        match value {
+            0 => Some(Self::All),
+            1 => Some(Self::Collapse),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for CatchUp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "all" => Ok(Self::All),
+            "collapse" => Ok(Self::Collapse),
+            _ => Err(format!(
+                "invalid catch-up mode '{s}'. Expected 'all' or 'collapse'."
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for CatchUp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::All => f.write_str("all"),
+            Self::Collapse => f.write_str("collapse"),
+        }
+    }
+}
+
+/// How to handle more missed occurrences of a reminder than the backfill limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backfill {
+    /// generate every missed occurrence
+    All,
+    /// generate only the given number of most recent occurrences
+    Latest(usize),
+    /// generate only the most recent occurrence, noting how many were missed
+    Collapse,
 }
 
 impl Reminder {
-    pub fn from_db_row(row: &Row<'_>) -> Result<Self, rusqlite::Error> {
+    /// Start building a daily reminder created now and first due now, without any optional
+    /// attributes
+    pub fn builder() -> ReminderBuilder {
+        let now = chrono::Local::now();
+        ReminderBuilder {
+            reminder: Reminder {
+                id: 0,
+                title: String::new(),
+                description: None,
+                created: now,
+                first_due: now,
+                all_day: false,
+                period: TimeDelta::days(1),
+                at: None,
+                until: None,
+                priority: None,
+                context: None,
+                tags: Vec::new(),
+                require_completion: false,
+                checklist: Vec::new(),
+                lead_tasks: Vec::new(),
+                catch_up: CatchUp::All,
+                skip_stale_after: None,
+                skip_before: None,
+                parent: None,
+                season: None,
+            },
+        }
+    }
+
+    pub fn from_db_row(
+        row: &Row<'_>,
+        conn_if_details: Option<&Connection>,
+    ) -> Result<Self, rusqlite::Error> {
         let id: u64 = row.get("id")?;
         let title: String = row.get("title")?;
         let description: Option<String> = row.get("description")?;
 
         let created = import_datetime(row.get("created")?);
         let first_due = import_datetime(row.get::<_, i64>("first_due")?);
+        let all_day: bool = row.get("all_day")?;
         let period =
             TimeDelta::new(row.get::<_, i64>("period")?, 0).expect("duration is in bounds");
 
+        let at = row
+            .get::<_, Option<u32>>("at")?
+            .and_then(|secs| NaiveTime::from_num_seconds_from_midnight_opt(secs, 0));
+
         let until = row.get::<_, Option<i64>>("until")?.map(import_datetime);
 
+        let priority = row
+            .get::<_, Option<i64>>("priority")?
+            .and_then(Priority::from_db);
+        let context: Option<String> = row.get("context")?;
+        let require_completion: bool = row.get("require_completion")?;
+        let catch_up = CatchUp::from_db(row.get("catch_up")?).unwrap_or_default();
+        let skip_stale_after = row
+            .get::<_, Option<i64>>("skip_stale_after")?
+            .map(TimeDelta::seconds);
+        let skip_before = row
+            .get::<_, Option<i64>>("skip_before")?
+            .map(import_datetime);
+        let parent: Option<u64> = row.get("parent")?;
+        let season = row
+            .get::<_, Option<String>>("season")?
+            .and_then(|season| season.parse().ok());
+        let tags = if let Some(conn) = conn_if_details {
+            conn.prepare("SELECT tag FROM reminder_tags WHERE reminder_id = ?1 ORDER BY tag")?
+                .query([id])?
+                .map(|x| x.get::<_, String>("tag"))
+                .collect()?
+        } else {
+            Vec::new()
+        };
+
+        let checklist = if let Some(conn) = conn_if_details {
+            conn.prepare(
+                "SELECT text FROM reminder_checklist_items WHERE reminder_id = ?1 ORDER BY position",
+            )?
+            .query([id])?
+            .map(|x| x.get::<_, String>("text"))
+            .collect()?
+        } else {
+            Vec::new()
+        };
+
+        let lead_tasks = if let Some(conn) = conn_if_details {
+            conn.prepare(
+                "SELECT title, delta FROM reminder_lead_tasks WHERE reminder_id = ?1 ORDER BY position",
+            )?
+            .query([id])?
+            .map(|x| {
+                Ok(LeadTask {
+                    title: x.get("title")?,
+                    offset: TimeDelta::seconds(x.get("delta")?),
+                })
+            })
+            .collect()?
+        } else {
+            Vec::new()
+        };
+
         Ok(Self {
             id,
             title,
             description,
             created,
             first_due,
+            all_day,
             period,
+            at,
             until,
+            priority,
+            context,
+            tags,
+            require_completion,
+            checklist,
+            lead_tasks,
+            catch_up,
+            skip_stale_after,
+            skip_before,
+            parent,
+            season,
         })
     }
 
+    /// Due date of the occurrence `periods` after the first one, scheduled at `base` by period
+    /// arithmetic, moved to the configured time of day if there is one
+    ///
+    /// Periods with a time of day are whole days, which are counted on the calendar, as the
+    /// date of `base` moves when the clocks change around midnight.
+    fn occurrence_due(&self, periods: i32, base: LocalDT) -> LocalDT {
+        let Some(at) = self.at else {
+            return base;
+        };
+        let Some(date) = TimeDelta::try_days(self.period.num_days() * periods as i64)
+            .and_then(|days| self.first_due.date_naive().checked_add_signed(days))
+        else {
+            return base;
+        };
+        // falls back to the unadjusted time if `at` does not exist on that day due to DST
+        date.and_time(at)
+            .and_local_timezone(chrono::Local)
+            .earliest()
+            .unwrap_or(base)
+    }
+
+    /// Due date of the occurrence at `index`, on a whole minute
+    fn due(&self, index: u64) -> Option<LocalDT> {
+        let periods = i32::try_from(index).ok()?;
+        let base = self
+            .first_due
+            .checked_add_signed(self.period.checked_mul(periods)?)?;
+        Some(truncate_to_minute(self.occurrence_due(periods, base)))
+    }
+
+    /// Index of the first occurrence due at or after `time`, regardless of the season
+    fn index_at(&self, time: LocalDT) -> u64 {
+        let mut index = if time > self.first_due {
+            ((time - self.first_due).num_seconds() / self.period.num_seconds().max(1)) as u64
+        } else {
+            0
+        };
+        // the time of day and the truncation to minutes move occurrences a little
+        while index > 0 && self.due(index - 1).is_some_and(|due| due >= time) {
+            index -= 1;
+        }
+        while self.due(index).is_some_and(|due| due < time) {
+            index += 1;
+        }
+        index
+    }
+
+    /// Number of occurrences due from `now` until the end of the reminder, `None` if it does
+    /// not end
+    ///
+    /// Counted from the indices of the occurrences at both ends rather than by stepping through
+    /// them, so short periods ending far in the future are cheap. Seasons are counted span by
+    /// span, one or two per year.
+    pub fn remaining(&self, now: LocalDT) -> Option<u64> {
+        let until = self.until?;
+        if self.period <= TimeDelta::zero() {
+            return Some(0);
+        }
+        let count = |start: LocalDT, end: LocalDT| {
+            let start = start.max(now);
+            let end = end.min(until);
+            if start < end {
+                self.index_at(end) - self.index_at(start)
+            } else {
+                0
+            }
+        };
+        let Some(season) = self.season else {
+            return Some(count(now, until));
+        };
+        Some(
+            (now.year()..=until.year())
+                .flat_map(|year| season.spans(year))
+                .map(|(start, end)| count(start, end))
+                .sum(),
+        )
+    }
+
+    /// Occurrences due within `range`, in order
+    ///
+    /// The end of the reminder is not taken into account. Reminders with a non-positive period
+    /// have no occurrences.
+    pub fn occurrences(&self, range: impl RangeBounds<LocalDT>) -> Occurrences<'_> {
+        let start = range.start_bound().cloned();
+        let end = range.end_bound().cloned();
+
+        // jump close to the start of the range instead of stepping through all occurrences
+        let index = match start {
+            Bound::Included(start) | Bound::Excluded(start)
+                if start > self.first_due && self.period > TimeDelta::zero() =>
+            {
+                let periods = (start - self.first_due).num_seconds() / self.period.num_seconds();
+                // the time of day may move an occurrence before its scheduled time
+                periods.saturating_sub(1).max(0) as u64
+            }
+            _ => 0,
+        };
+
+        Occurrences {
+            reminder: self,
+            index,
+            start,
+            end,
+        }
+    }
+
+    /// First occurrence due from `now` on, if the reminder has not ended before
+    pub fn next_occurrence(&self, now: LocalDT) -> Option<Occurrence> {
+        self.occurrences(now..)
+            .next()
+            .filter(|o| self.until.is_none_or(|until| o.due < until))
+    }
+
+    /// Cron expression firing at the occurrences of the reminder, if cron can express its period
+    ///
+    /// Daily and weekly periods translate, as do periods dividing an hour or a day. Seasons and
+    /// ends of reminders are not part of the expression.
+    pub fn cron(&self) -> Option<String> {
+        let first = self.occurrences(..).next()?.due;
+        let (minute, hour) = (first.minute(), first.hour());
+        let minutes = self.period.num_minutes();
+        if self.period != TimeDelta::minutes(minutes) {
+            return None;
+        }
+        let step = |offset: i64, max: i64, step: i64| match (offset, step) {
+            (_, 1) => "*".to_string(),
+            (0, _) => format!("*/{step}"),
+            _ => format!("{offset}-{max}/{step}"),
+        };
+        match minutes {
+            10080 => {
+                let weekday = first.weekday().to_string().to_uppercase();
+                Some(format!("{minute} {hour} * * {weekday}"))
+            }
+            1440 => Some(format!("{minute} {hour} * * *")),
+            n if n % 60 == 0 && 1440 % n == 0 => {
+                let hours = step(hour as i64 % (n / 60), 23, n / 60);
+                Some(format!("{minute} {hours} * * *"))
+            }
+            n if 60 % n == 0 => Some(format!("{} * * * *", step(minute as i64 % n, 59, n))),
+            _ => None,
+        }
+    }
+
     pub fn is_active(&self, now: LocalDT) -> bool {
         self.until.map(|until| now < until).unwrap_or(true)
     }
@@ -50,6 +636,7 @@ impl Reminder {
         all: bool,
         verbose: bool,
         now: LocalDT,
+        config: &Config,
     ) -> std::fmt::Result {
         let active = self.is_active(now);
         if !all && !active {
@@ -63,22 +650,74 @@ impl Reminder {
             title = self.title
         )
         .bold();
-        if !verbose {
-            if !active {
-                heading = heading.green();
-            }
+        if !verbose && !active {
+            heading = heading.green();
         }
         writeln!(f, "{heading}")?;
-        writeln!(f, "  created:   {}", self.created.format(DATETIME_FMT))?;
-        writeln!(f, "  first due: {}", self.first_due.format(DATETIME_FMT))?;
+        writeln!(
+            f,
+            "  created:   {}",
+            self.created.format(config.datetime_fmt())
+        )?;
+        if let Some(parent) = self.parent {
+            writeln!(f, "  group:     {parent}")?;
+        }
+        let due_fmt = if self.all_day {
+            config.date_fmt()
+        } else {
+            config.datetime_fmt()
+        };
+        writeln!(f, "  first due: {}", self.first_due.format(due_fmt))?;
+        writeln!(f, "  repeats:   {}", humanize_period(self.period))?;
         if let Some(until) = self.until {
-            writeln!(f, "  until:     {}", until.format(DATETIME_FMT))?;
+            writeln!(f, "  until:     {}", until.format(config.datetime_fmt()))?;
+        }
+        if let Some(at) = self.at {
+            writeln!(f, "  at:        {}", at.format("%H:%M"))?;
+        }
+        if let Some(season) = self.season {
+            writeln!(f, "  season:    {season}")?;
+        }
+        if let Some(next) = self.next_occurrence(now) {
+            writeln!(f, "  next due:  {}", next.due.format(due_fmt))?;
+        }
+
+        if let Some(priority) = self.priority {
+            writeln!(f, "  priority:  {priority}")?;
+        }
+        if let Some(ref context) = self.context {
+            writeln!(f, "  context:   {context}")?;
+        }
+        if !self.tags.is_empty() {
+            writeln!(f, "  tags:      {}", self.tags.join(", "))?;
+        }
+        if self.require_completion {
+            writeln!(f, "  continues once the previous occurrence is completed")?;
+        }
+        if self.catch_up == CatchUp::Collapse {
+            writeln!(f, "  missed occurrences are collapsed into one task")?;
         }
-        let mut next_due = self.first_due;
-        while next_due < now {
-            next_due += self.period;
+        if let Some(stale) = self.skip_stale_after {
+            writeln!(
+                f,
+                "  occurrences open {} past their deadline are skipped",
+                format_timedelta(stale)
+            )?;
+        }
+
+        if !self.checklist.is_empty() {
+            writeln!(f, "  checklist:")?;
+            for (idx, text) in self.checklist.iter().enumerate() {
+                writeln!(f, "  {no}. {text}", no = idx + 1)?;
+            }
+        }
+
+        if !self.lead_tasks.is_empty() {
+            writeln!(f, "  lead tasks:")?;
+            for (idx, lead) in self.lead_tasks.iter().enumerate() {
+                writeln!(f, "  {no}. {lead}", no = idx + 1)?;
+            }
         }
-        writeln!(f, "  next due:  {}", next_due.format(DATETIME_FMT))?;
 
         if let Some(ref description) = self.description {
             writeln!(f, "  {description}")?;
@@ -86,25 +725,444 @@ impl Reminder {
         Ok(())
     }
 
-    pub fn display<'a>(&'a self, all: bool, verbose: bool, now: LocalDT) -> ReminderDisplay<'a> {
+    pub fn display<'a>(
+        &'a self,
+        all: bool,
+        verbose: bool,
+        now: LocalDT,
+        config: &'a Config,
+    ) -> ReminderDisplay<'a> {
         ReminderDisplay {
             inner: self,
             all,
             verbose,
             now,
+            config,
         }
     }
 }
 
+/// Builder for reminders that are not stored yet, see [`Reminder::builder`]
+pub struct ReminderBuilder {
+    reminder: Reminder,
+}
+
+impl ReminderBuilder {
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.reminder.title = title.into();
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.reminder.description = Some(description.into());
+        self
+    }
+
+    pub fn created(mut self, created: LocalDT) -> Self {
+        self.reminder.created = created;
+        self
+    }
+
+    pub fn first_due(mut self, first_due: LocalDT) -> Self {
+        self.reminder.first_due = first_due;
+        self
+    }
+
+    /// Occurrences are due some time on their day
+    pub fn all_day(mut self, all_day: bool) -> Self {
+        self.reminder.all_day = all_day;
+        self
+    }
+
+    pub fn period(mut self, period: TimeDelta) -> Self {
+        self.reminder.period = period;
+        self
+    }
+
+    pub fn at(mut self, at: NaiveTime) -> Self {
+        self.reminder.at = Some(at);
+        self
+    }
+
+    pub fn until(mut self, until: LocalDT) -> Self {
+        self.reminder.until = Some(until);
+        self
+    }
+
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.reminder.priority = Some(priority);
+        self
+    }
+
+    pub fn context(mut self, context: impl Into<String>) -> Self {
+        self.reminder.context = Some(context.into());
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.reminder.tags.push(tag.into());
+        self
+    }
+
+    pub fn tags(mut self, tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.reminder.tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn require_completion(mut self, require_completion: bool) -> Self {
+        self.reminder.require_completion = require_completion;
+        self
+    }
+
+    pub fn checklist_item(mut self, text: impl Into<String>) -> Self {
+        self.reminder.checklist.push(text.into());
+        self
+    }
+
+    pub fn lead_task(mut self, title: impl Into<String>, offset: TimeDelta) -> Self {
+        self.reminder.lead_tasks.push(LeadTask {
+            title: title.into(),
+            offset,
+        });
+        self
+    }
+
+    pub fn catch_up(mut self, catch_up: CatchUp) -> Self {
+        self.reminder.catch_up = catch_up;
+        self
+    }
+
+    pub fn skip_stale_after(mut self, stale: TimeDelta) -> Self {
+        self.reminder.skip_stale_after = Some(stale);
+        self
+    }
+
+    pub fn parent(mut self, parent: u64) -> Self {
+        self.reminder.parent = Some(parent);
+        self
+    }
+
+    pub fn season(mut self, season: Season) -> Self {
+        self.reminder.season = Some(season);
+        self
+    }
+
+    pub fn build(self) -> Reminder {
+        self.reminder
+    }
+}
+
+/// Reminders ordered so each group is followed by its members, with their depth in the groups
+///
+/// Members of groups that are not among `reminders` are shown at the top level.
+pub fn grouped(reminders: Vec<Reminder>) -> Vec<(Reminder, usize)> {
+    let ids = reminders.iter().map(|r| r.id).collect::<Vec<_>>();
+    let (mut members, roots): (Vec<_>, Vec<_>) = reminders
+        .into_iter()
+        .partition(|r| r.parent.is_some_and(|parent| ids.contains(&parent)));
+
+    let mut ret = Vec::new();
+    let mut stack = roots.into_iter().rev().map(|r| (r, 0)).collect::<Vec<_>>();
+    while let Some((reminder, depth)) = stack.pop() {
+        let (children, rest) = members
+            .into_iter()
+            .partition::<Vec<_>, _>(|r| r.parent == Some(reminder.id));
+        members = rest;
+        stack.extend(children.into_iter().rev().map(|r| (r, depth + 1)));
+        ret.push((reminder, depth));
+    }
+    ret
+}
+
 pub struct ReminderDisplay<'a> {
     inner: &'a Reminder,
     all: bool,
     verbose: bool,
     now: LocalDT,
+    config: &'a Config,
 }
 
 impl std::fmt::Display for ReminderDisplay<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.inner.fmt(f, self.all, self.verbose, self.now)
+        self.inner
+            .fmt(f, self.all, self.verbose, self.now, self.config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn reminder(first_due: LocalDT, period: TimeDelta) -> Reminder {
+        Reminder {
+            id: 7,
+            title: "water plants".to_string(),
+            description: None,
+            created: first_due,
+            first_due,
+            all_day: false,
+            period,
+            at: None,
+            until: None,
+            priority: None,
+            context: None,
+            tags: Vec::new(),
+            require_completion: false,
+            checklist: Vec::new(),
+            lead_tasks: Vec::new(),
+            catch_up: CatchUp::All,
+            skip_stale_after: None,
+            skip_before: None,
+            parent: None,
+            season: None,
+        }
+    }
+
+    #[test]
+    fn lead_tasks() {
+        let lead = "prepare: agenda:-2d".parse::<LeadTask>().unwrap();
+        assert_eq!(lead.title, "prepare: agenda");
+        assert_eq!(lead.offset, TimeDelta::days(-2));
+        assert_eq!(lead.to_string(), "prepare: agenda, 2d before");
+        let lead = "send minutes: +1d 2h".parse::<LeadTask>().unwrap();
+        assert_eq!(lead.offset, TimeDelta::hours(26));
+        assert_eq!(lead.to_string(), "send minutes, 1d 2h after");
+        assert!("no offset".parse::<LeadTask>().is_err());
+        assert!(":1d".parse::<LeadTask>().is_err());
+        assert!("minutes:soon".parse::<LeadTask>().is_err());
+    }
+
+    #[test]
+    fn groups() {
+        let now = chrono::Local::now();
+        let with = |id, parent: Option<u64>| Reminder {
+            id,
+            parent,
+            ..reminder(now, TimeDelta::days(1))
+        };
+        let reminders = vec![
+            with(1, None),
+            with(2, Some(4)),
+            with(3, None),
+            with(4, Some(1)),
+            with(5, Some(1)),
+            with(6, Some(9)),
+        ];
+        let order = grouped(reminders)
+            .into_iter()
+            .map(|(r, depth)| (r.id, depth))
+            .collect::<Vec<_>>();
+        assert_eq!(order, [(1, 0), (4, 1), (2, 2), (5, 1), (3, 0), (6, 0)]);
+    }
+
+    #[test]
+    fn occurrences() {
+        let first = crate::start_of_day(chrono::NaiveDate::from_ymd_opt(2025, 6, 2).unwrap());
+        let r = reminder(first, TimeDelta::days(2));
+
+        let all = r
+            .occurrences(..first + TimeDelta::days(6))
+            .collect::<Vec<_>>();
+        assert_eq!(all.iter().map(|o| o.index).collect::<Vec<_>>(), [0, 1, 2]);
+        assert!(all.iter().all(|o| o.reminder_id == 7));
+        assert_eq!(all[2].due, first + TimeDelta::days(4));
+
+        let later = r
+            .occurrences(first + TimeDelta::days(3)..=first + TimeDelta::days(8))
+            .map(|o| o.index)
+            .collect::<Vec<_>>();
+        assert_eq!(later, [2, 3, 4]);
+        let second = r.occurrences(first + TimeDelta::days(1)..).next().unwrap();
+        assert_eq!(second.index, 1);
+
+        let next = r.occurrences(first + TimeDelta::days(40)..).next().unwrap();
+        assert_eq!((next.index, next.due), (20, first + TimeDelta::days(40)));
+    }
+
+    #[test]
+    fn cron_expressions() {
+        // a monday
+        let first = crate::start_of_day(NaiveDate::from_ymd_opt(2025, 1, 6).unwrap())
+            + TimeDelta::minutes(8 * 60 + 30);
+        let cron = |period| reminder(first, period).cron();
+        assert_eq!(cron(TimeDelta::weeks(1)).as_deref(), Some("30 8 * * MON"));
+        assert_eq!(cron(TimeDelta::days(1)).as_deref(), Some("30 8 * * *"));
+        assert_eq!(
+            cron(TimeDelta::hours(6)).as_deref(),
+            Some("30 2-23/6 * * *")
+        );
+        assert_eq!(cron(TimeDelta::hours(1)).as_deref(), Some("30 * * * *"));
+        assert_eq!(
+            cron(TimeDelta::minutes(15)).as_deref(),
+            Some("*/15 * * * *")
+        );
+        assert_eq!(
+            cron(TimeDelta::minutes(20)).as_deref(),
+            Some("10-59/20 * * * *")
+        );
+        assert_eq!(cron(TimeDelta::days(2)), None);
+        assert_eq!(cron(TimeDelta::hours(5)), None);
+    }
+
+    #[test]
+    fn placeholders() {
+        let due = crate::start_of_day(chrono::NaiveDate::from_ymd_opt(2025, 1, 2).unwrap());
+        let occurrence = Occurrence {
+            reminder_id: 1,
+            index: 4,
+            due,
+        };
+        assert_eq!(occurrence.expand("Timesheet KW{week}"), "Timesheet KW1");
+        assert_eq!(
+            occurrence.expand("#{index} on {date}, {year}-{month}"),
+            "#5 on 02.01.2025, 2025-01"
+        );
+        assert_eq!(occurrence.expand("{unknown} {week"), "{unknown} {week");
+        assert_eq!(occurrence.expand("{{week}}"), "{1}");
+        assert_eq!(occurrence.expand("no placeholders"), "no placeholders");
+    }
+
+    #[test]
+    fn occurrences_on_minutes() {
+        let first = crate::start_of_day(chrono::NaiveDate::from_ymd_opt(2025, 6, 2).unwrap());
+        let r = reminder(first + TimeDelta::milliseconds(90_500), TimeDelta::days(1));
+        let dues = r
+            .occurrences(..first + TimeDelta::days(2))
+            .map(|o| o.due)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            dues,
+            [
+                first + TimeDelta::minutes(1),
+                first + TimeDelta::days(1) + TimeDelta::minutes(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn occurrences_at() {
+        let first = crate::start_of_day(chrono::NaiveDate::from_ymd_opt(2025, 6, 2).unwrap());
+        let mut r = reminder(first + TimeDelta::hours(20), TimeDelta::days(1));
+        r.at = NaiveTime::from_hms_opt(8, 0, 0);
+
+        // the first occurrence is moved to the morning, before the start of the range
+        let next = r
+            .occurrences(first + TimeDelta::hours(12)..)
+            .next()
+            .unwrap();
+        assert_eq!(next.index, 1);
+        assert_eq!(next.due, first + TimeDelta::hours(32));
+    }
+
+    /// Occurrences at a time of day stay on their days when the clocks change around midnight
+    #[test]
+    fn occurrences_at_dst() {
+        // central European time, changing on the last sundays of March and October
+        const TZ: &str = "CET-1CEST,M3.5.0/0,M10.5.0/0";
+        // the timezone of the process is changed in a process of its own, not under other tests
+        if std::env::var("TZ").as_deref() != Ok(TZ) {
+            let output = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["reminder::test::occurrences_at_dst", "--exact"])
+                .env("TZ", TZ)
+                .output()
+                .unwrap();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(output.status.success(), "{stdout}");
+            assert!(stdout.contains("1 passed"), "{stdout}");
+            return;
+        }
+
+        let local = |d, m, h| {
+            NaiveDate::from_ymd_opt(2025, m, d)
+                .unwrap()
+                .and_hms_opt(h, 30, 0)
+                .unwrap()
+                .and_local_timezone(chrono::Local)
+                .unwrap()
+        };
+        for dues in [
+            // over a day of 23 hours
+            [local(29, 3, 23), local(30, 3, 23), local(31, 3, 23)],
+            // over a day of 25 hours
+            [local(25, 10, 0), local(26, 10, 0), local(27, 10, 0)],
+        ] {
+            let mut r = reminder(dues[0], TimeDelta::days(1));
+            r.at = Some(dues[0].time());
+            let occurrences = r.occurrences(..).take(3).map(|o| o.due);
+            assert_eq!(occurrences.collect::<Vec<_>>(), dues);
+        }
+    }
+
+    #[test]
+    fn seasons() {
+        let date = |d, m| NaiveDate::from_ymd_opt(2025, m, d).unwrap();
+        let summer: Season = "01.04..31.10".parse().unwrap();
+        assert!(summer.contains(date(1, 4)) && summer.contains(date(31, 10)));
+        assert!(!summer.contains(date(1, 11)));
+        let winter: Season = "1.11..31.3".parse().unwrap();
+        assert_eq!(winter.to_string(), "01.11..31.03");
+        assert!(winter.contains(date(2, 1)) && !winter.contains(date(1, 4)));
+        assert!("31.04..01.05".parse::<Season>().is_err());
+        assert!("01.04".parse::<Season>().is_err());
+
+        let first = crate::start_of_day(date(1, 3));
+        let mut r = reminder(first, TimeDelta::weeks(4));
+        r.season = Some(summer);
+        let dues = r
+            .occurrences(..first + TimeDelta::weeks(40))
+            .map(|o| o.due.date_naive())
+            .collect::<Vec<_>>();
+        assert_eq!(dues.first(), Some(&date(26, 4)));
+        assert_eq!(dues.last(), Some(&date(11, 10)));
+
+        // yearly occurrences outside of the season end instead of searching forever
+        let mut r = reminder(first, TimeDelta::days(364));
+        r.season = Some("01.09..02.09".parse().unwrap());
+        assert_eq!(r.occurrences(..).next(), None);
+    }
+
+    #[test]
+    fn humanized_periods() {
+        assert_eq!(humanize_period(TimeDelta::days(1)), "every day");
+        assert_eq!(humanize_period(TimeDelta::weeks(2)), "every 2 weeks");
+        assert_eq!(humanize_period(TimeDelta::days(10)), "every 10 days");
+        assert_eq!(humanize_period(TimeDelta::hours(36)), "every 36 hours");
+        assert_eq!(humanize_period(TimeDelta::minutes(45)), "every 45 minutes");
+        assert_eq!(humanize_period(TimeDelta::minutes(90)), "every 1h 30m");
+    }
+
+    #[test]
+    fn no_occurrences_without_period() {
+        let first = crate::start_of_day(chrono::NaiveDate::from_ymd_opt(2025, 6, 2).unwrap());
+        let r = reminder(first, TimeDelta::zero());
+        assert_eq!(r.occurrences(..).next(), None);
+    }
+
+    #[test]
+    fn remaining() {
+        let first = crate::start_of_day(NaiveDate::from_ymd_opt(2025, 6, 2).unwrap());
+        let now = first + TimeDelta::days(3) + TimeDelta::hours(5);
+        let stepped = |r: &Reminder| r.occurrences(now..r.until.unwrap()).count() as u64;
+
+        let mut r = reminder(first, TimeDelta::days(2));
+        assert_eq!(r.remaining(now), None);
+        r.until = Some(first + TimeDelta::days(30));
+        assert_eq!(r.remaining(now), Some(stepped(&r)));
+        assert_eq!(r.remaining(first + TimeDelta::days(40)), Some(0));
+
+        r.at = NaiveTime::from_hms_opt(7, 15, 0);
+        r.until = Some(first + TimeDelta::days(800));
+        assert_eq!(r.remaining(now), Some(stepped(&r)));
+
+        r.season = Some("01.11..28.02".parse().unwrap());
+        assert_eq!(r.remaining(now), Some(stepped(&r)));
+        r.season = Some("01.07..29.02".parse().unwrap());
+        assert_eq!(r.remaining(now), Some(stepped(&r)));
+
+        // minutely for centuries, too many to step through
+        let mut r = reminder(first, TimeDelta::minutes(1));
+        r.until = Some(first + TimeDelta::days(200 * 365));
+        let expected = (r.until.unwrap() - now).num_minutes() as u64;
+        assert_eq!(r.remaining(now), Some(expected));
     }
 }
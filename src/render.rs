@@ -0,0 +1,295 @@
+//! Plain text rendering of tabular data and simple charts
+
+use chrono::{Datelike, NaiveDate, TimeDelta, Weekday};
+
+/// Render rows as aligned columns below a header
+///
+/// The first column is left-aligned, all others are right-aligned.
+pub fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths = header.iter().map(|h| h.chars().count()).collect::<Vec<_>>();
+    for row in rows.iter() {
+        for (width, col) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(col.chars().count());
+        }
+    }
+
+    let header = header.iter().map(|h| h.to_string()).collect::<Vec<_>>();
+    let mut ret = String::new();
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let mut line = String::new();
+        for (idx, (col, width)) in row.iter().zip(widths.iter()).enumerate() {
+            if idx == 0 {
+                line.push_str(&format!("{col:<width$}"));
+            } else {
+                line.push_str(&format!("  {col:>width$}"));
+            }
+        }
+        ret.push_str(line.trim_end());
+        ret.push('\n');
+    }
+    ret
+}
+
+/// Render rows as CSV below a header
+pub fn csv(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut ret = header.join(",");
+    ret.push('\n');
+    for row in rows.iter() {
+        let row = row.iter().map(|col| csv_field(col)).collect::<Vec<_>>();
+        ret.push_str(&row.join(","));
+        ret.push('\n');
+    }
+    ret
+}
+
+/// Quote a CSV field if needed
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Render rows as a Markdown table below a header, right-aligning all but the first column
+pub fn markdown(header: &[&str], rows: &[Vec<String>]) -> String {
+    let escape = |col: &str| col.replace('|', "\\|");
+    let mut ret = format!("| {} |\n", header.join(" | "));
+    let align = (0..header.len())
+        .map(|idx| if idx == 0 { "---" } else { "---:" })
+        .collect::<Vec<_>>();
+    ret.push_str(&format!("| {} |\n", align.join(" | ")));
+    for row in rows.iter() {
+        let row = row.iter().map(|col| escape(col)).collect::<Vec<_>>();
+        ret.push_str(&format!("| {} |\n", row.join(" | ")));
+    }
+    ret
+}
+
+/// Split items into sections, ordered by name and keeping the order of the items within each
+///
+/// * `keys`: sections an item belongs to. Items may be in several sections, e.g. with several
+///   tags, and are collected in a trailing `(none)` section if they are in none.
+pub fn sections<'a, T>(
+    items: impl IntoIterator<Item = &'a T>,
+    keys: impl Fn(&T) -> Vec<String>,
+) -> Vec<(String, Vec<&'a T>)> {
+    let mut ret: Vec<(String, Vec<&'a T>)> = Vec::new();
+    let mut none = Vec::new();
+    for item in items {
+        let keys = keys(item);
+        if keys.is_empty() {
+            none.push(item);
+        }
+        for key in keys {
+            match ret.binary_search_by(|(name, _)| name.cmp(&key)) {
+                Ok(idx) => ret[idx].1.push(item),
+                Err(idx) => ret.insert(idx, (key, vec![item])),
+            }
+        }
+    }
+    if !none.is_empty() {
+        ret.push(("(none)".to_string(), none));
+    }
+    ret
+}
+
+/// Header of a section of `count` items
+pub fn section_header(name: &str, count: usize) -> String {
+    format!("{name} ({count})")
+}
+
+/// Name of the week a date falls into, sorting in chronological order
+pub fn week_of(date: NaiveDate) -> String {
+    format!(
+        "week of {}",
+        date.week(Weekday::Mon).first_day().format("%Y-%m-%d")
+    )
+}
+
+/// Eighths of a block, for bars with sub-character resolution
+const PARTIAL_BLOCKS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// Render a horizontal bar chart with one line per labelled value
+///
+/// The largest value spans `width` characters, the value itself is printed after the bar.
+pub fn bars(values: &[(String, f64)], width: usize) -> String {
+    let max = values.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+    let label_width = values
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut ret = String::new();
+    for (label, value) in values.iter() {
+        let eighths = if max > 0.0 {
+            (value.max(0.0) / max * width as f64 * 8.0).round() as usize
+        } else {
+            0
+        };
+        let mut bar = "█".repeat(eighths / 8);
+        if eighths % 8 != 0 {
+            bar.push(PARTIAL_BLOCKS[eighths % 8]);
+        }
+        let line = format!("{label:<label_width$}  {bar:<width$} {value}");
+        ret.push_str(line.trim_end());
+        ret.push('\n');
+    }
+    ret
+}
+
+/// Shades of heatmap cells from no activity to the most active
+const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+/// Render a calendar heatmap with a row per weekday and a column per week
+///
+/// * `counts`: activity per day, days missing are treated as inactive. Days outside of
+///   `first..=last` are ignored.
+pub fn heatmap(first: NaiveDate, last: NaiveDate, counts: &[(NaiveDate, u64)]) -> String {
+    let start = first.week(Weekday::Mon).first_day();
+    let weeks = ((last - start).num_days() / 7 + 1) as usize;
+    let max = counts.iter().map(|(_, c)| *c).max().unwrap_or(0);
+    let count = |date: NaiveDate| {
+        counts
+            .iter()
+            .find(|(d, _)| *d == date)
+            .map(|(_, c)| *c)
+            .unwrap_or(0)
+    };
+
+    // month names above the week their first day falls into
+    let mut months = " ".repeat(4 + 2 * weeks);
+    let mut free_from = 0;
+    for week in 0..weeks {
+        let monday = start + TimeDelta::weeks(week as i64);
+        let first_of_month = (0..7)
+            .map(|d| monday + TimeDelta::days(d))
+            .find(|d| d.day() == 1 && *d >= first && *d <= last);
+        let col = 4 + 2 * week;
+        if let Some(date) = first_of_month.filter(|_| col >= free_from) {
+            let name = date.format("%b").to_string();
+            months.replace_range(col..col + name.len(), &name);
+            free_from = col + name.len() + 1;
+        }
+    }
+    let mut ret = months.trim_end().to_string();
+    ret.push('\n');
+
+    for weekday in 0..7 {
+        let mut line = (start + TimeDelta::days(weekday)).format("%a ").to_string();
+        for week in 0..weeks {
+            let date = start + TimeDelta::days(7 * week as i64 + weekday);
+            if date < first || date > last {
+                line.push_str("  ");
+                continue;
+            }
+            let c = count(date);
+            // spread active days over the remaining shades, the most active day is darkest
+            let levels = SHADES.len() as u64 - 1;
+            let shade = (c * levels).div_ceil(max.max(1)) as usize;
+            line.push(SHADES[shade]);
+            line.push(' ');
+        }
+        ret.push_str(line.trim_end());
+        ret.push('\n');
+    }
+
+    ret.push_str(&format!(
+        "less {} more\n",
+        SHADES.map(String::from).join(" ")
+    ));
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rows() -> Vec<Vec<String>> {
+        vec![
+            vec!["finance".to_string(), "12".to_string()],
+            vec!["a, \"b\"".to_string(), "3".to_string()],
+        ]
+    }
+
+    #[test]
+    fn aligned() {
+        assert_eq!(
+            table(&["group", "completed"], &rows()),
+            "group    completed\n\
+             finance         12\n\
+             a, \"b\"           3\n"
+        );
+    }
+
+    #[test]
+    fn quoted() {
+        assert_eq!(
+            csv(&["group", "completed"], &rows()),
+            "group,completed\nfinance,12\n\"a, \"\"b\"\"\",3\n"
+        );
+    }
+
+    #[test]
+    fn markdown_table() {
+        assert_eq!(
+            markdown(
+                &["group", "completed"],
+                &[vec!["a|b".to_string(), "1".to_string()]]
+            ),
+            "| group | completed |\n| --- | ---: |\n| a\\|b | 1 |\n"
+        );
+    }
+
+    #[test]
+    fn bar_chart() {
+        let values = [
+            ("a".to_string(), 4.0),
+            ("bb".to_string(), 1.0),
+            ("c".to_string(), 0.0),
+        ];
+        assert_eq!(bars(&values, 4), "a   ████ 4\nbb  █    1\nc        0\n");
+
+        let values = [("a".to_string(), 8.0), ("b".to_string(), 3.0)];
+        assert_eq!(bars(&values, 2), "a  ██ 8\nb  ▊  3\n");
+    }
+
+    #[test]
+    fn grouped() {
+        let items = [("a", vec!["x", "y"]), ("b", vec![]), ("c", vec!["x"])];
+        let sections = sections(items.iter(), |(_, keys)| {
+            keys.iter().map(|k| k.to_string()).collect()
+        });
+        let names = sections
+            .iter()
+            .map(|(name, items)| {
+                let items = items.iter().map(|(i, _)| *i).collect::<String>();
+                format!("{} {items}", section_header(name, items.len()))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["x (2) ac", "y (1) a", "(none) (1) b"]);
+
+        let date = |d| NaiveDate::from_ymd_opt(2025, 6, d).unwrap();
+        assert_eq!(week_of(date(4)), "week of 2025-06-02");
+        assert_eq!(week_of(date(1)), "week of 2025-05-26");
+    }
+
+    #[test]
+    fn calendar() {
+        let date = |m, d| NaiveDate::from_ymd_opt(2025, m, d).unwrap();
+        // thursday to the following wednesday
+        let map = heatmap(
+            date(5, 29),
+            date(6, 4),
+            &[(date(5, 29), 1), (date(6, 2), 4)],
+        );
+        let lines = map.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], "    Jun");
+        assert_eq!(lines[1], "Mon   █");
+        assert_eq!(lines[4], "Thu ░");
+        assert_eq!(lines[6], "Sat ·");
+        assert_eq!(lines[8], "less · ░ ▒ ▓ █ more");
+    }
+}
@@ -0,0 +1,658 @@
+//! Summaries of tasks grouped by tag, project or reminder, of activity over time, of reminder
+//! adherence, of time worked and of weekly budgets
+
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, TimeDelta, Weekday};
+use serde::Serialize;
+
+use crate::{format_timedelta, render, Config, LocalDT, Task};
+
+/// Counts of the tasks in one group
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct GroupReport {
+    pub group: String,
+    pub completed: u64,
+    pub open: u64,
+    pub overdue: u64,
+    /// work bits recorded on the tasks of the group
+    pub work_bits: u64,
+    /// time of the work sessions on the tasks of the group, in whole minutes in JSON
+    #[serde(rename = "tracked_minutes", serialize_with = "minutes")]
+    pub tracked: TimeDelta,
+}
+
+fn minutes<S: serde::Serializer>(delta: &TimeDelta, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_i64(delta.num_minutes())
+}
+
+/// Summarize tasks per group, ordered by group name
+///
+/// * `groups`: groups a task belongs to. A task may be part of multiple groups, e.g. with
+///   several tags.
+/// * `tracked`: time of the work sessions per task id
+pub fn group_tasks<'a>(
+    tasks: impl IntoIterator<Item = &'a Task>,
+    groups: impl Fn(&Task) -> Vec<String>,
+    tracked: &HashMap<u64, TimeDelta>,
+    now: LocalDT,
+    config: &Config,
+) -> Vec<GroupReport> {
+    let mut ret: Vec<GroupReport> = Vec::new();
+    for task in tasks {
+        for group in groups(task) {
+            let idx = match ret.binary_search_by(|r| r.group.cmp(&group)) {
+                Ok(idx) => idx,
+                Err(idx) => {
+                    ret.insert(
+                        idx,
+                        GroupReport {
+                            group,
+                            completed: 0,
+                            open: 0,
+                            overdue: 0,
+                            work_bits: 0,
+                            tracked: TimeDelta::zero(),
+                        },
+                    );
+                    idx
+                }
+            };
+
+            let report = &mut ret[idx];
+            // skipped occurrences are closed without being done
+            if task.completed.is_none() {
+                report.open += 1;
+            } else if !task.skipped {
+                report.completed += 1;
+            }
+            if task.is_overdue(now, config) {
+                report.overdue += 1;
+            }
+            report.work_bits += task.work_bits.len() as u64;
+            report.tracked += tracked.get(&task.id).copied().unwrap_or_default();
+        }
+    }
+    ret
+}
+
+const HEADER: [&str; 6] = [
+    "group",
+    "completed",
+    "open",
+    "overdue",
+    "work bits",
+    "tracked",
+];
+
+/// * `tracked`: renders the tracked time of a group
+fn rows(reports: &[GroupReport], tracked: impl Fn(TimeDelta) -> String) -> Vec<Vec<String>> {
+    reports
+        .iter()
+        .map(|report| {
+            vec![
+                report.group.clone(),
+                report.completed.to_string(),
+                report.open.to_string(),
+                report.overdue.to_string(),
+                report.work_bits.to_string(),
+                tracked(report.tracked),
+            ]
+        })
+        .collect()
+}
+
+/// Render as an aligned table with a header line
+pub fn to_table(reports: &[GroupReport]) -> String {
+    render::table(
+        &HEADER,
+        &rows(reports, |tracked| {
+            format_timedelta(TimeDelta::minutes(tracked.num_minutes()))
+        }),
+    )
+}
+
+/// Render as CSV with the names of the JSON fields as header line and tracked time in minutes
+pub fn to_csv(reports: &[GroupReport]) -> String {
+    let header = [
+        "group",
+        "completed",
+        "open",
+        "overdue",
+        "work_bits",
+        "tracked_minutes",
+    ];
+    render::csv(
+        &header,
+        &rows(reports, |tracked| tracked.num_minutes().to_string()),
+    )
+}
+
+/// Punctuality of one occurrence of a reminder
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Adherence {
+    pub due: LocalDT,
+    /// completion or the time the occurrence was skipped
+    pub completed: Option<LocalDT>,
+    pub skipped: bool,
+    /// minutes between deadline and completion, negative if completed early. Unset for skipped
+    /// occurrences.
+    pub lateness_minutes: Option<i64>,
+}
+
+impl Adherence {
+    pub fn of(task: &Task, config: &Config) -> Option<Self> {
+        let due = task.due?;
+        let deadline = task.deadline(config)?;
+        Some(Self {
+            due,
+            completed: task.completed,
+            skipped: task.skipped,
+            lateness_minutes: task
+                .completed
+                .filter(|_| !task.skipped)
+                .map(|completed| (completed - deadline).num_minutes()),
+        })
+    }
+}
+
+/// How the occurrences of a reminder were completed, shown with its details
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReminderSummary {
+    pub generated: usize,
+    /// completed rather than skipped
+    pub completed: usize,
+    pub on_time: usize,
+    pub average_lateness: Option<TimeDelta>,
+    pub last_completed: Option<LocalDT>,
+}
+
+impl ReminderSummary {
+    pub fn of(occurrences: &[Adherence]) -> Self {
+        let lateness = occurrences
+            .iter()
+            .filter_map(|o| o.lateness_minutes)
+            .collect::<Vec<_>>();
+        Self {
+            generated: occurrences.len(),
+            completed: lateness.len(),
+            on_time: lateness.iter().filter(|m| **m <= 0).count(),
+            average_lateness: (!lateness.is_empty())
+                .then(|| TimeDelta::minutes(lateness.iter().sum::<i64>() / lateness.len() as i64)),
+            last_completed: occurrences
+                .iter()
+                .filter(|o| !o.skipped)
+                .filter_map(|o| o.completed)
+                .max(),
+        }
+    }
+
+    /// Lines shown below a reminder
+    pub fn render(&self, config: &Config) -> String {
+        let mut ret = format!(
+            "  generated: {}, {} completed, {} on time\n",
+            self.generated, self.completed, self.on_time
+        );
+        if let Some(lateness) = self.average_lateness {
+            ret.push_str(&format!(
+                "  lateness:  {} on average\n",
+                format_timedelta(lateness)
+            ));
+        }
+        if let Some(last) = self.last_completed {
+            ret.push_str(&format!(
+                "  last done: {}\n",
+                last.format(config.datetime_fmt())
+            ));
+        }
+        ret
+    }
+}
+
+/// Activity during one week
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct WeekActivity {
+    /// monday of the week
+    pub week: NaiveDate,
+    pub completed: u64,
+    pub work_bits: u64,
+}
+
+/// Count completions and work bits per week for the `weeks` weeks up to and including `now`
+pub fn weekly_activity(
+    completions: &[LocalDT],
+    work_bits: &[LocalDT],
+    now: LocalDT,
+    weeks: u32,
+) -> Vec<WeekActivity> {
+    let this_week = now.date_naive().week(Weekday::Mon).first_day();
+    let mut ret = (0..weeks)
+        .rev()
+        .map(|ago| WeekActivity {
+            week: this_week - TimeDelta::weeks(ago.into()),
+            completed: 0,
+            work_bits: 0,
+        })
+        .collect::<Vec<_>>();
+
+    let index = |dt: &LocalDT| {
+        let week = dt.date_naive().week(Weekday::Mon).first_day();
+        ret.iter().position(|w| w.week == week)
+    };
+    let completed = completions.iter().filter_map(index).collect::<Vec<_>>();
+    let worked = work_bits.iter().filter_map(index).collect::<Vec<_>>();
+    for idx in completed {
+        ret[idx].completed += 1;
+    }
+    for idx in worked {
+        ret[idx].work_bits += 1;
+    }
+    ret
+}
+
+/// Render weekly activity as bar charts
+pub fn activity_charts(weeks: &[WeekActivity], config: &Config) -> String {
+    let chart = |value: fn(&WeekActivity) -> u64| {
+        let values = weeks
+            .iter()
+            .map(|w| {
+                (
+                    w.week.format(config.date_fmt()).to_string(),
+                    value(w) as f64,
+                )
+            })
+            .collect::<Vec<_>>();
+        render::bars(&values, CHART_WIDTH)
+    };
+    format!(
+        "completed tasks per week\n{}\nwork bits per week\n{}",
+        chart(|w| w.completed),
+        chart(|w| w.work_bits)
+    )
+}
+
+/// Render weekly activity as CSV
+pub fn activity_csv(weeks: &[WeekActivity]) -> String {
+    let rows = weeks
+        .iter()
+        .map(|w| {
+            vec![
+                w.week.to_string(),
+                w.completed.to_string(),
+                w.work_bits.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    render::csv(&["week", "completed", "work_bits"], &rows)
+}
+
+const CHART_WIDTH: usize = 40;
+
+const ADHERENCE_HEADER: [&str; 3] = ["due", "completed", "lateness"];
+
+/// Render occurrences as an aligned table with human readable times
+pub fn adherence_table(occurrences: &[Adherence], config: &Config) -> String {
+    let rows = occurrences
+        .iter()
+        .map(|o| {
+            vec![
+                o.due.format(config.datetime_fmt()).to_string(),
+                match o.completed {
+                    Some(_) if o.skipped => "skipped".to_string(),
+                    Some(c) => c.format(config.datetime_fmt()).to_string(),
+                    None => "-".to_string(),
+                },
+                o.lateness_minutes
+                    .map(|m| format_timedelta(TimeDelta::minutes(m)))
+                    .unwrap_or("-".to_string()),
+            ]
+        })
+        .collect::<Vec<_>>();
+    render::table(&ADHERENCE_HEADER, &rows)
+}
+
+/// Render occurrences as CSV with RFC 3339 times and lateness in minutes
+pub fn adherence_csv(occurrences: &[Adherence]) -> String {
+    let rows = occurrences
+        .iter()
+        .map(|o| {
+            vec![
+                o.due.to_rfc3339(),
+                o.completed.map(|c| c.to_rfc3339()).unwrap_or_default(),
+                o.skipped.to_string(),
+                o.lateness_minutes
+                    .map(|m| m.to_string())
+                    .unwrap_or_default(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    render::csv(&["due", "completed", "skipped", "lateness_minutes"], &rows)
+}
+
+/// Time worked in one group of a timesheet
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimesheetEntry {
+    pub group: String,
+    pub worked: TimeDelta,
+}
+
+/// Sum up time worked per group, ordered by group name
+///
+/// * `round`: round the total of each group up to a multiple of this, e.g. 15 minutes
+pub fn timesheet(
+    sessions: impl IntoIterator<Item = (String, TimeDelta)>,
+    round: Option<TimeDelta>,
+) -> Vec<TimesheetEntry> {
+    let mut ret: Vec<TimesheetEntry> = Vec::new();
+    for (group, worked) in sessions {
+        match ret.binary_search_by(|e| e.group.cmp(&group)) {
+            Ok(idx) => ret[idx].worked += worked,
+            Err(idx) => ret.insert(idx, TimesheetEntry { group, worked }),
+        }
+    }
+
+    if let Some(round) = round.filter(|r| r.num_seconds() > 0) {
+        let unit = round.num_seconds();
+        for entry in ret.iter_mut() {
+            let secs = entry.worked.num_seconds();
+            entry.worked = TimeDelta::seconds((secs + unit - 1).div_euclid(unit) * unit);
+        }
+    }
+    ret
+}
+
+fn hours(worked: TimeDelta) -> f64 {
+    worked.num_seconds() as f64 / 3600.0
+}
+
+/// Render a timesheet as a Markdown table with a total line, with amounts if a `rate` per
+/// hour is given
+pub fn timesheet_markdown(entries: &[TimesheetEntry], rate: Option<f64>) -> String {
+    let total = entries.iter().map(|e| e.worked).sum::<TimeDelta>();
+    let rows = entries
+        .iter()
+        .map(|e| (e.group.clone(), e.worked))
+        .chain(std::iter::once(("total".to_string(), total)))
+        .map(|(group, worked)| {
+            let mut row = vec![
+                group,
+                format_timedelta(worked),
+                format!("{:.2}", hours(worked)),
+            ];
+            row.extend(rate.map(|rate| format!("{:.2}", hours(worked) * rate)));
+            row
+        })
+        .collect::<Vec<_>>();
+
+    let mut header = vec!["group", "time", "hours"];
+    header.extend(rate.map(|_| "amount"));
+    render::markdown(&header, &rows)
+}
+
+/// Render a timesheet as CSV with minutes and decimal hours and a total line, with amounts if a
+/// `rate` per hour is given
+pub fn timesheet_csv(entries: &[TimesheetEntry], rate: Option<f64>) -> String {
+    let total = entries.iter().map(|e| e.worked).sum::<TimeDelta>();
+    let rows = entries
+        .iter()
+        .map(|e| (e.group.clone(), e.worked))
+        .chain(std::iter::once(("total".to_string(), total)))
+        .map(|(group, worked)| {
+            let mut row = vec![
+                group,
+                worked.num_minutes().to_string(),
+                format!("{:.2}", hours(worked)),
+            ];
+            row.extend(rate.map(|rate| format!("{:.2}", hours(worked) * rate)));
+            row
+        })
+        .collect::<Vec<_>>();
+
+    let mut header = vec!["group", "minutes", "hours"];
+    header.extend(rate.map(|_| "amount"));
+    render::csv(&header, &rows)
+}
+
+/// Time worked in the current week on a tag or project with a budget
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Budget {
+    /// `#tag` or the title of the project
+    pub group: String,
+    pub budget: TimeDelta,
+    pub worked: TimeDelta,
+}
+
+impl Budget {
+    pub fn exceeded(&self) -> bool {
+        self.worked > self.budget
+    }
+}
+
+impl std::fmt::Display for Budget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} of {}",
+            self.group,
+            format_timedelta(self.worked),
+            format_timedelta(self.budget)
+        )?;
+        if self.exceeded() {
+            write!(
+                f,
+                ", exceeded by {}",
+                format_timedelta(self.worked - self.budget)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Render budgets as an aligned table with the share of each that is used up
+pub fn budget_table(budgets: &[Budget]) -> String {
+    let rows = budgets
+        .iter()
+        .map(|b| {
+            let used = match b.budget.num_seconds() {
+                0 => "-".to_string(),
+                budget => format!("{}%", b.worked.num_seconds() * 100 / budget),
+            };
+            vec![
+                b.group.clone(),
+                format_timedelta(b.worked),
+                format_timedelta(b.budget),
+                used,
+            ]
+        })
+        .collect::<Vec<_>>();
+    render::table(&["budget", "worked", "per week", "used"], &rows)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn group_table() {
+        let report = GroupReport {
+            group: "#finance".to_string(),
+            completed: 12,
+            open: 1,
+            overdue: 0,
+            work_bits: 2,
+            tracked: TimeDelta::seconds(90 * 60 + 59),
+        };
+        assert_eq!(
+            to_table(std::slice::from_ref(&report)),
+            "group     completed  open  overdue  work bits  tracked\n\
+             #finance         12     1        0          2   1h 30m\n"
+        );
+        assert_eq!(
+            to_csv(&[report]),
+            "group,completed,open,overdue,work_bits,tracked_minutes\n#finance,12,1,0,2,90\n"
+        );
+    }
+
+    #[test]
+    fn skipped_not_completed() {
+        let now = chrono::Local::now();
+        let closed = || {
+            Task::builder()
+                .title("pay rent")
+                .created(now)
+                .tag("finance")
+                .completed(now)
+                .build()
+        };
+        let done = closed();
+        let skipped = Task {
+            skipped: true,
+            ..closed()
+        };
+        let open = Task {
+            id: 1,
+            ..Task::builder()
+                .title("file taxes")
+                .created(now)
+                .tag("finance")
+                .build()
+        };
+        // sessions shorter than a minute add up before they are counted in minutes
+        let tracked = HashMap::from([
+            (done.id, TimeDelta::seconds(40)),
+            (open.id, TimeDelta::seconds(40)),
+        ]);
+        let report = group_tasks(
+            [&done, &open],
+            |task| task.tags.clone(),
+            &tracked,
+            now,
+            &Config::default(),
+        );
+        assert_eq!(report[0].tracked, TimeDelta::seconds(80));
+        assert!(to_csv(&report).ends_with(",1\n"));
+        let report = group_tasks(
+            [&done, &skipped, &open],
+            |task| task.tags.clone(),
+            &HashMap::new(),
+            now,
+            &Config::default(),
+        );
+        assert_eq!((report[0].completed, report[0].open), (1, 1));
+    }
+
+    #[test]
+    fn adherence_csv_minutes() {
+        let due = crate::start_of_day(chrono::NaiveDate::from_ymd_opt(2025, 6, 2).unwrap());
+        let occurrences = [
+            Adherence {
+                due,
+                completed: Some(due + TimeDelta::minutes(90)),
+                skipped: false,
+                lateness_minutes: Some(90),
+            },
+            Adherence {
+                due,
+                completed: None,
+                skipped: false,
+                lateness_minutes: None,
+            },
+        ];
+        let csv = adherence_csv(&occurrences);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "due,completed,skipped,lateness_minutes");
+        assert!(lines[1].ends_with(",90"));
+        assert!(lines[2].ends_with(",false,"));
+        // JSON keeps RFC 3339 whatever dates are shown as
+        let json = serde_json::to_value(&occurrences[0]).unwrap();
+        let repr = json["due"].as_str().unwrap();
+        assert_eq!(chrono::DateTime::parse_from_rfc3339(repr).unwrap(), due);
+    }
+
+    #[test]
+    fn reminder_summary() {
+        let due = crate::start_of_day(chrono::NaiveDate::from_ymd_opt(2025, 6, 2).unwrap());
+        let occurrence = |lateness: Option<i64>, skipped| Adherence {
+            due,
+            completed: lateness.map(|m| due + TimeDelta::minutes(m)),
+            skipped,
+            lateness_minutes: lateness.filter(|_| !skipped),
+        };
+        let summary = ReminderSummary::of(&[
+            occurrence(Some(-30), false),
+            occurrence(Some(90), false),
+            occurrence(Some(600), true),
+            occurrence(None, false),
+        ]);
+        assert_eq!(summary.generated, 4);
+        assert_eq!((summary.completed, summary.on_time), (2, 1));
+        assert_eq!(summary.average_lateness, Some(TimeDelta::minutes(30)));
+        assert_eq!(summary.last_completed, Some(due + TimeDelta::minutes(90)));
+        assert_eq!(ReminderSummary::of(&[]).average_lateness, None);
+    }
+
+    #[test]
+    fn rounded_timesheet() {
+        let sessions = [
+            ("b".to_string(), TimeDelta::minutes(20)),
+            ("a".to_string(), TimeDelta::minutes(50)),
+            ("b".to_string(), TimeDelta::minutes(25)),
+        ];
+        let entries = timesheet(sessions.clone(), None);
+        assert_eq!(entries[0].group, "a");
+        assert_eq!(entries[1].worked, TimeDelta::minutes(45));
+
+        let entries = timesheet(sessions, Some(TimeDelta::minutes(15)));
+        assert_eq!(entries[0].worked, TimeDelta::minutes(60));
+        assert_eq!(entries[1].worked, TimeDelta::minutes(45));
+        assert!(timesheet_markdown(&entries, None).ends_with("| total | 1h 45m | 1.75 |\n"));
+        assert!(timesheet_markdown(&entries, Some(90.0))
+            .ends_with("| total | 1h 45m | 1.75 | 157.50 |\n"));
+        assert_eq!(
+            timesheet_csv(&entries, None),
+            "group,minutes,hours\na,60,1.00\nb,45,0.75\ntotal,105,1.75\n"
+        );
+        assert!(timesheet_csv(&entries, Some(90.0)).ends_with("\ntotal,105,1.75,157.50\n"));
+    }
+
+    #[test]
+    fn weeks() {
+        let day = |d| crate::start_of_day(NaiveDate::from_ymd_opt(2025, 6, d).unwrap());
+        // wednesday
+        let now = day(18) + TimeDelta::hours(12);
+        let activity = weekly_activity(&[day(16), day(17), day(9), day(1)], &[day(12)], now, 2);
+        assert_eq!(activity.len(), 2);
+        assert_eq!(
+            activity[0].week,
+            NaiveDate::from_ymd_opt(2025, 6, 9).unwrap()
+        );
+        assert_eq!((activity[0].completed, activity[0].work_bits), (1, 1));
+        assert_eq!((activity[1].completed, activity[1].work_bits), (2, 0));
+    }
+
+    #[test]
+    fn budgets() {
+        let budget = Budget {
+            group: "#admin".to_string(),
+            budget: TimeDelta::hours(10),
+            worked: TimeDelta::minutes(150),
+        };
+        assert!(!budget.exceeded());
+        assert_eq!(budget.to_string(), "#admin: 2h 30m of 10h");
+        let over = Budget {
+            worked: TimeDelta::minutes(690),
+            ..budget.clone()
+        };
+        assert!(over.exceeded());
+        assert_eq!(
+            over.to_string(),
+            "#admin: 11h 30m of 10h, exceeded by 1h 30m"
+        );
+        assert_eq!(
+            budget_table(&[budget, over]),
+            "budget   worked  per week  used\n\
+             #admin   2h 30m       10h   25%\n\
+             #admin  11h 30m       10h  115%\n"
+        );
+    }
+}
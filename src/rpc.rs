@@ -0,0 +1,106 @@
+//! JSON-RPC 2.0 protocol of `rem rpc` for editor plugins and assistants
+//!
+//! Every line on stdin is a request like
+//! `{"jsonrpc": "2.0", "id": 1, "method": "complete_task", "params": {"id": 12}}` and is
+//! answered by one line on stdout, unless it is a notification without an `id`. Results are
+//! records of `--porcelain v1`.
+
+use serde_json::{json, Value};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// the method was called correctly but failed, e.g. for a task that does not exist
+pub const FAILED: i64 = -32000;
+
+/// Request read from a line
+#[derive(Clone, Debug, PartialEq)]
+pub struct Call {
+    /// `None` for notifications, which are not answered
+    pub id: Option<Value>,
+    pub method: String,
+    /// object of named parameters, empty if none were given
+    pub params: Value,
+}
+
+impl Call {
+    /// Read a request, or the response to send if it is invalid
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let value = serde_json::from_str::<Value>(line)
+            .map_err(|err| error(&Value::Null, PARSE_ERROR, &format!("Invalid JSON: {err}")))?;
+        let id = value.get("id").cloned();
+        let invalid = |message: &str| {
+            error(
+                id.as_ref().unwrap_or(&Value::Null),
+                INVALID_REQUEST,
+                message,
+            )
+        };
+        if value["jsonrpc"] != "2.0" {
+            return Err(invalid("Expected \"jsonrpc\": \"2.0\""));
+        }
+        let Some(method) = value["method"].as_str() else {
+            return Err(invalid("Expected the name of a method"));
+        };
+        let params = match &value["params"] {
+            Value::Null => json!({}),
+            params @ Value::Object(_) => params.clone(),
+            _ => return Err(invalid("Expected params by name")),
+        };
+        Ok(Self {
+            id,
+            method: method.to_string(),
+            params,
+        })
+    }
+}
+
+/// Response carrying the result of a call
+pub fn result(id: &Value, result: Value) -> String {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string()
+}
+
+/// Response to a call that failed
+pub fn error(id: &Value, code: i64, message: &str) -> String {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }).to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn calls() {
+        let call = Call::parse(
+            r#"{"jsonrpc": "2.0", "id": 7, "method": "complete_task", "params": {"id": 12}}"#,
+        )
+        .unwrap();
+        assert_eq!(call.id, Some(json!(7)));
+        assert_eq!(call.method, "complete_task");
+        assert_eq!(call.params["id"], 12);
+        let notification = Call::parse(r#"{"jsonrpc": "2.0", "method": "list_tasks"}"#).unwrap();
+        assert_eq!(notification.id, None);
+        assert_eq!(notification.params, json!({}));
+
+        let code = |line: &str| {
+            let response = serde_json::from_str::<Value>(&Call::parse(line).unwrap_err()).unwrap();
+            response["error"]["code"].as_i64().unwrap()
+        };
+        assert_eq!(code("{"), PARSE_ERROR);
+        assert_eq!(
+            code(r#"{"id": 1, "method": "list_tasks"}"#),
+            INVALID_REQUEST
+        );
+        assert_eq!(code(r#"{"jsonrpc": "2.0", "id": 1}"#), INVALID_REQUEST);
+        assert_eq!(
+            code(r#"{"jsonrpc": "2.0", "id": 1, "method": "x", "params": [1]}"#),
+            INVALID_REQUEST
+        );
+
+        assert_eq!(
+            result(&json!("a"), json!([])),
+            r#"{"id":"a","jsonrpc":"2.0","result":[]}"#
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::tags::validate_tag;
+use crate::Task;
+
+/// Name of the file scoping rem to a directory and its subdirectories
+pub const SCOPE_FILE: &str = ".rem";
+
+/// Project and tag pinned by a `.rem` file
+///
+/// Listing tasks below the file only shows tasks in the scope, and tasks created there are put
+/// into it.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scope {
+    /// id of the top-level task of the project
+    pub project: Option<u64>,
+    pub tag: Option<String>,
+}
+
+impl Scope {
+    pub fn from_toml(repr: &str) -> Result<Self, String> {
+        let mut scope: Self = toml::from_str(repr).map_err(|err| err.to_string())?;
+        if let Some(tag) = scope.tag.take() {
+            scope.tag = Some(validate_tag(&tag)?.to_string());
+        }
+        Ok(scope)
+    }
+
+    /// Read the closest `.rem` file in `dir` or one of its ancestors
+    pub fn find(dir: &Path) -> Result<Option<(PathBuf, Self)>, String> {
+        for dir in dir.ancestors() {
+            let path = dir.join(SCOPE_FILE);
+            if !path.is_file() {
+                continue;
+            }
+            let repr = std::fs::read_to_string(&path)
+                .map_err(|err| format!("could not read {}: {err}", path.display()))?;
+            let scope = Self::from_toml(&repr)
+                .map_err(|err| format!("invalid {}: {err}", path.display()))?;
+            return Ok(Some((path, scope)));
+        }
+        Ok(None)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.project.is_none() && self.tag.is_none()
+    }
+
+    /// Whether a task is in the scope
+    ///
+    /// * `project`: top-level task of the task, if it is part of a project
+    pub fn contains(&self, task: &Task, project: Option<&Task>) -> bool {
+        let in_project = self
+            .project
+            .is_none_or(|id| task.id == id || project.is_some_and(|project| project.id == id));
+        let tagged = self.tag.as_ref().is_none_or(|tag| task.tags.contains(tag));
+        in_project && tagged
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let scope = Scope::from_toml("project = 12\ntag = \"rem\"").unwrap();
+        assert_eq!(scope.project, Some(12));
+        assert_eq!(scope.tag.as_deref(), Some("rem"));
+        assert!(Scope::from_toml("").unwrap().is_empty());
+        assert!(Scope::from_toml("tag = \"two words\"").is_err());
+        let scope = Scope::from_toml("tag = \"#rem\"").unwrap();
+        assert_eq!(scope.tag.as_deref(), Some("rem"));
+        assert!(Scope::from_toml("projekt = 12").is_err());
+    }
+
+    #[test]
+    fn contains() {
+        let now = chrono::Local::now();
+        let mut project = Task::builder().title("rem").created(now).build();
+        project.id = 12;
+        let mut task = Task::builder()
+            .title("scoping")
+            .parent(12)
+            .tag("cli")
+            .created(now)
+            .build();
+        task.id = 13;
+
+        let scope = Scope {
+            project: Some(12),
+            tag: None,
+        };
+        assert!(scope.contains(&project, None));
+        assert!(scope.contains(&task, Some(&project)));
+        assert!(!scope.contains(&task, None));
+
+        let scope = Scope {
+            project: Some(12),
+            tag: Some("cli".to_string()),
+        };
+        assert!(scope.contains(&task, Some(&project)));
+        assert!(!scope.contains(&project, None));
+    }
+}
@@ -0,0 +1,223 @@
+//! Scoring of open tasks to recommend what to work on next and their urgency
+//!
+//! Every component of a score is kept with a short reason, so recommendations can be explained.
+
+use chrono::TimeDelta;
+use serde::Deserialize;
+
+use crate::{format_timedelta, Config, LocalDT, Priority, Task};
+
+/// Weights of the score components. Each component is scaled to roughly [-1, 1] before weighting.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Weights {
+    /// closeness of the deadline, full weight when overdue
+    pub due: f64,
+    pub priority: f64,
+    /// estimate fits into the available time
+    pub fits_time: f64,
+    /// task is in the requested context
+    pub context: f64,
+    /// task waits for open subtasks
+    pub blocked: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            due: 10.0,
+            priority: 5.0,
+            fits_time: 3.0,
+            context: 2.0,
+            blocked: 8.0,
+        }
+    }
+}
+
+/// Situation a task is scored in
+pub struct Situation<'a> {
+    pub now: LocalDT,
+    pub config: &'a Config,
+    /// time available to work, if known
+    pub available: Option<TimeDelta>,
+    /// context the user is in, if known
+    pub context: Option<&'a str>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Score {
+    pub total: f64,
+    pub reasons: Vec<(String, f64)>,
+}
+
+impl Score {
+    fn add(&mut self, reason: String, value: f64) {
+        if value != 0.0 {
+            self.total += value;
+            self.reasons.push((reason, value));
+        }
+    }
+
+    /// Reasons ordered by their impact, e.g. `overdue (+10.0), high priority (+5.0)`
+    pub fn explain(&self) -> String {
+        let mut reasons = self.reasons.clone();
+        reasons.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+        reasons
+            .iter()
+            .map(|(reason, value)| format!("{reason} ({value:+.1})"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Horizon within which an approaching deadline raises the score
+const DUE_HORIZON_DAYS: f64 = 14.0;
+
+/// Score a task for working on it now
+///
+/// * `blocked`: the task has open subtasks
+pub fn score(task: &Task, blocked: bool, situation: &Situation, weights: &Weights) -> Score {
+    let mut score = Score {
+        total: 0.0,
+        reasons: Vec::new(),
+    };
+
+    if let Some(deadline) = task.deadline(situation.config) {
+        let left = deadline - situation.now;
+        if left <= TimeDelta::zero() {
+            score.add("overdue".to_string(), weights.due);
+        } else {
+            let days = left.num_minutes() as f64 / (24.0 * 60.0);
+            let closeness = (1.0 - days / DUE_HORIZON_DAYS).max(0.0);
+            score.add(
+                format!("due in {}", format_timedelta(left)),
+                weights.due * closeness,
+            );
+        }
+    }
+
+    if let Some(priority) = task.priority {
+        let factor = match priority {
+            Priority::High => 1.0,
+            Priority::Medium => 0.6,
+            Priority::Low => 0.2,
+        };
+        score.add(format!("{priority} priority"), weights.priority * factor);
+    }
+
+    if let (Some(available), Some(estimate)) = (situation.available, task.estimate) {
+        if estimate <= available {
+            score.add(
+                format!("fits in {}", format_timedelta(available)),
+                weights.fits_time,
+            );
+        } else {
+            score.add(
+                format!("needs {}", format_timedelta(estimate)),
+                -weights.fits_time,
+            );
+        }
+    }
+
+    if let (Some(wanted), Some(context)) = (situation.context, task.context.as_deref()) {
+        if wanted == context {
+            score.add(format!("@{context}"), weights.context);
+        } else {
+            score.add(format!("not @{wanted}"), -weights.context);
+        }
+    }
+
+    if blocked {
+        score.add("open subtasks".to_string(), -weights.blocked);
+    }
+
+    score
+}
+
+/// Coefficients of the urgency components, modelled after taskwarrior
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UrgencyCoefficients {
+    pub due: f64,
+    pub priority_high: f64,
+    pub priority_medium: f64,
+    pub priority_low: f64,
+    /// scaled by age up to a year
+    pub age: f64,
+    /// scaled by the number of tags, reaching full weight at three tags
+    pub tags: f64,
+    /// other tasks wait for this one
+    pub blocking: f64,
+    /// this task waits for others
+    pub blocked: f64,
+}
+
+impl Default for UrgencyCoefficients {
+    fn default() -> Self {
+        Self {
+            due: 12.0,
+            priority_high: 6.0,
+            priority_medium: 3.9,
+            priority_low: 1.8,
+            age: 2.0,
+            tags: 1.0,
+            blocking: 8.0,
+            blocked: -5.0,
+        }
+    }
+}
+
+/// Urgency of a task, higher is more urgent
+///
+/// * `blocking`: the task is a subtask of an open task
+/// * `blocked`: the task has open subtasks
+pub fn urgency(
+    task: &Task,
+    blocking: bool,
+    blocked: bool,
+    now: LocalDT,
+    config: &Config,
+    coefficients: &UrgencyCoefficients,
+) -> f64 {
+    let mut ret = 0.0;
+
+    if let Some(deadline) = task.deadline(config) {
+        // ramps from 0.2 two weeks ahead to 1.0 a week overdue, like taskwarrior
+        let days_overdue = (now - deadline).num_minutes() as f64 / (24.0 * 60.0);
+        let factor = if days_overdue >= 7.0 {
+            1.0
+        } else if days_overdue >= -14.0 {
+            ((days_overdue + 14.0) * 0.8 / 21.0) + 0.2
+        } else {
+            0.2
+        };
+        ret += coefficients.due * factor;
+    }
+
+    ret += match task.priority {
+        Some(Priority::High) => coefficients.priority_high,
+        Some(Priority::Medium) => coefficients.priority_medium,
+        Some(Priority::Low) => coefficients.priority_low,
+        None => 0.0,
+    };
+
+    let age_days = (now - task.created).num_days().max(0) as f64;
+    ret += coefficients.age * (age_days / 365.0).min(1.0);
+
+    let tag_factor = match task.tags.len() {
+        0 => 0.0,
+        1 => 0.8,
+        2 => 0.9,
+        _ => 1.0,
+    };
+    ret += coefficients.tags * tag_factor;
+
+    if blocking {
+        ret += coefficients.blocking;
+    }
+    if blocked {
+        ret += coefficients.blocked;
+    }
+
+    ret
+}
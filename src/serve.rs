@@ -0,0 +1,630 @@
+//! Minimal HTTP/1.1 server of `rem serve`, answering one request per connection
+//!
+//! Clients authenticate with one of the tokens of the `[serve]` config section, either as a
+//! bearer token or as the `token` query parameter for dashboards opened in a browser. There is
+//! no TLS, so the server is meant for trusted networks or to be put behind a reverse proxy.
+//!
+//! The endpoints are described by the OpenAPI document [`OPENAPI`], served at `/openapi.json`.
+//! Successful `GET` responses carry an ETag, so clients can poll with `If-None-Match` cheaply.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use crate::config;
+
+/// Largest request body read, larger ones are refused
+const MAX_BODY: usize = 1 << 20;
+
+/// Longest request line or header and most headers read, larger requests are refused
+const MAX_LINE: usize = 8 << 10;
+const MAX_HEADERS: usize = 100;
+
+/// Time a client has to send its whole request, and to take each write of the response
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Records per page of list endpoints if the client does not ask for a `limit`, and the most it
+/// may ask for
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+/// Description of the endpoints, the contract for third-party clients
+pub const OPENAPI: &str = include_str!("openapi.json");
+
+/// What a token allows, ordered by privilege
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Access {
+    Read,
+    /// read and change tasks
+    Write,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// path without the query
+    pub path: String,
+    /// decoded query parameters in order
+    pub query: Vec<(String, String)>,
+    /// headers with lowercase names
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Read a request from a client
+    pub fn read(reader: &mut impl BufRead) -> Result<Self, String> {
+        let mut line = String::new();
+        read_line(reader, &mut line).map_err(|err| format!("Could not read request: {err}"))?;
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target), Some(_)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!("Invalid request line '{}'", line.trim_end()));
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let mut request = Self {
+            method: method.to_string(),
+            path: percent_decode(path)?,
+            query: query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    Ok((percent_decode(name)?, percent_decode(value)?))
+                })
+                .collect::<Result<_, String>>()?,
+            ..Self::default()
+        };
+
+        loop {
+            line.clear();
+            read_line(reader, &mut line).map_err(|err| format!("Could not read headers: {err}"))?;
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if request.headers.len() == MAX_HEADERS {
+                return Err(format!("More than {MAX_HEADERS} headers"));
+            }
+            let (name, value) = header
+                .split_once(':')
+                .ok_or(format!("Invalid header '{header}'"))?;
+            request
+                .headers
+                .push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+
+        let length = match request.header("content-length") {
+            Some(length) => length
+                .parse::<usize>()
+                .map_err(|err| format!("Invalid content length '{length}': {err}"))?,
+            None => 0,
+        };
+        if length > MAX_BODY {
+            return Err(format!("Body of {length} bytes is too large"));
+        }
+        request.body = vec![0; length];
+        reader
+            .read_exact(&mut request.body)
+            .map_err(|err| format!("Could not read body: {err}"))?;
+        Ok(request)
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// What the token of the request allows, if anything
+    ///
+    /// An empty token never matches, even if the config sets one.
+    pub fn access(&self, tokens: &config::Serve) -> Option<Access> {
+        let token = self
+            .header("authorization")
+            .and_then(|auth| auth.strip_prefix("Bearer "))
+            .or(self.query("token"))?
+            .trim();
+        if token.is_empty() {
+            return None;
+        }
+        let matches = |expected: &Option<String>| {
+            expected
+                .as_deref()
+                .is_some_and(|expected| constant_time_eq(expected, token))
+        };
+        if matches(&tokens.write_token) {
+            Some(Access::Write)
+        } else if matches(&tokens.read_token) {
+            Some(Access::Read)
+        } else {
+            None
+        }
+    }
+}
+
+/// Compare tokens in a time that does not depend on where they differ
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+fn percent_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut ret = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'%' => {
+                let byte = text
+                    .get(idx + 1..idx + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or(format!("Invalid escape in '{text}'"))?;
+                ret.push(byte);
+                idx += 3;
+            }
+            b'+' => {
+                ret.push(b' ');
+                idx += 1;
+            }
+            byte => {
+                ret.push(byte);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8(ret).map_err(|err| format!("Invalid text in '{text}': {err}"))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            headers: Vec::new(),
+            body: body.into().into_bytes(),
+        }
+    }
+
+    /// Error with its message as JSON, e.g. `{"error":"Task 3 not found."}`
+    pub fn error(status: u16, message: &str) -> Self {
+        Self::json(status, serde_json::json!({ "error": message }).to_string())
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            201 => "Created",
+            304 => "Not Modified",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            self.reason(),
+            self.content_type,
+            self.body.len()
+        )?;
+        for (name, value) in self.headers.iter() {
+            write!(writer, "{name}: {value}\r\n")?;
+        }
+        writer.write_all(b"\r\n")?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+/// Page of JSON records selected by the `limit` and `offset` query parameters, with the number
+/// of records on all pages in the `X-Total-Count` header
+pub fn page(request: &Request, records: &[String]) -> Response {
+    let param = |name: &str, default: usize| {
+        request.query(name).map_or(Ok(default), |value| {
+            value
+                .parse::<usize>()
+                .map_err(|err| format!("Invalid {name} '{value}': {err}"))
+        })
+    };
+    let (limit, offset) = match (param("limit", DEFAULT_LIMIT), param("offset", 0)) {
+        (Ok(limit), Ok(offset)) if limit <= MAX_LIMIT => (limit, offset),
+        (Ok(limit), Ok(_)) => {
+            return Response::error(
+                400,
+                &format!("Invalid limit {limit}, at most {MAX_LIMIT} records fit on a page"),
+            )
+        }
+        (Err(err), _) | (_, Err(err)) => return Response::error(400, &err),
+    };
+    let body = records
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut response = Response::json(200, format!("[{body}]"));
+    response
+        .headers
+        .push(("X-Total-Count".to_string(), records.len().to_string()));
+    response
+}
+
+/// Tag of a response body, the FNV-1a hash so it stays the same across builds
+fn etag(body: &[u8]) -> String {
+    format!("\"{:016x}\"", crate::fnv1a(body))
+}
+
+/// Response to a request, checking its token before passing it to `handle`
+///
+/// The OpenAPI document needs no token. Successful `GET` responses get an ETag and are replaced
+/// by `304 Not Modified` if the client has the same one already.
+pub fn respond(
+    request: &Request,
+    tokens: &config::Serve,
+    handle: impl FnOnce(&Request, Access) -> Response,
+) -> Response {
+    let mut response = if request.method == "GET" && request.path == "/openapi.json" {
+        Response::json(200, OPENAPI)
+    } else {
+        match request.access(tokens) {
+            Some(access) => handle(request, access),
+            None => {
+                let mut response = Response::error(401, "Missing or unknown token");
+                response
+                    .headers
+                    .push(("WWW-Authenticate".to_string(), "Bearer".to_string()));
+                return response;
+            }
+        }
+    };
+    if request.method == "GET" && response.status == 200 {
+        let tag = etag(&response.body);
+        let cached = request.header("if-none-match").is_some_and(|tags| {
+            tags.split(',')
+                .any(|candidate| candidate.trim() == tag || candidate.trim() == "*")
+        });
+        if cached {
+            response.status = 304;
+            response.body.clear();
+        }
+        response.headers.push(("ETag".to_string(), tag));
+    }
+    response
+}
+
+/// Value exposed at `/metrics`
+pub struct Metric {
+    pub name: &'static str,
+    pub help: &'static str,
+    /// only ever increases, rather than a gauge going up and down
+    pub counter: bool,
+    pub value: f64,
+}
+
+/// Metrics in the text format scraped by Prometheus
+pub fn prometheus(metrics: &[Metric]) -> Response {
+    let body = metrics
+        .iter()
+        .map(|metric| {
+            format!(
+                "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n",
+                name = metric.name,
+                help = metric.help,
+                kind = if metric.counter { "counter" } else { "gauge" },
+                value = metric.value
+            )
+        })
+        .collect::<String>();
+    Response {
+        status: 200,
+        content_type: "text/plain; version=0.0.4",
+        headers: Vec::new(),
+        body: body.into_bytes(),
+    }
+}
+
+/// Read a line of at most [`MAX_LINE`] bytes
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<(), String> {
+    reader
+        .take(MAX_LINE as u64 + 1)
+        .read_line(line)
+        .map_err(|err| err.to_string())?;
+    if line.len() > MAX_LINE {
+        return Err(format!("Line longer than {MAX_LINE} bytes"));
+    }
+    Ok(())
+}
+
+/// Connection failing reads after a deadline, so slow clients cannot hold the server forever
+struct Deadline {
+    stream: TcpStream,
+    until: Instant,
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let left = self.until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "request took too long",
+            ));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Answer the requests of clients connecting to `listener` one after another, forever
+///
+/// * `handle`: response to a request, called only for requests with a valid token, see
+///   [`respond`]
+pub fn serve(
+    listener: &TcpListener,
+    tokens: &config::Serve,
+    mut handle: impl FnMut(&Request, Access) -> Response,
+) {
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::warn!("could not accept connection: {err}");
+                continue;
+            }
+        };
+        let _ = stream.set_write_timeout(Some(TIMEOUT));
+        let request = match stream.try_clone() {
+            Ok(reader) => Request::read(&mut BufReader::new(Deadline {
+                stream: reader,
+                until: Instant::now() + TIMEOUT,
+            })),
+            Err(err) => Err(format!("Could not read request: {err}")),
+        };
+        let response = match request {
+            Ok(request) => {
+                log::info!("{} {}", request.method, request.path);
+                respond(&request, tokens, &mut handle)
+            }
+            Err(err) => Response::error(400, &err),
+        };
+        if let Err(err) = response.write(&mut stream) {
+            log::warn!("could not send response: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn requests() {
+        let raw = "POST /tasks/3%2F4?token=a+b&all HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\nAuthorization: Bearer secret\r\n\r\nbodyrest";
+        let request = Request::read(&mut raw.as_bytes()).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/tasks/3/4");
+        assert_eq!(request.query("token"), Some("a b"));
+        assert_eq!(request.query("all"), Some(""));
+        assert_eq!(request.header("Host"), Some("localhost"));
+        assert_eq!(request.body, b"body");
+
+        assert!(Request::read(&mut "GET\r\n\r\n".as_bytes()).is_err());
+        assert!(Request::read(&mut "GET /%zz HTTP/1.1\r\n\r\n".as_bytes()).is_err());
+        let huge = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        assert!(Request::read(&mut huge.as_bytes()).is_err());
+
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        assert!(Request::read(&mut long.as_bytes()).is_err());
+        let many = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X-A: b\r\n".repeat(MAX_HEADERS + 1)
+        );
+        assert!(Request::read(&mut many.as_bytes()).is_err());
+        let most = format!("GET / HTTP/1.1\r\n{}\r\n", "X-A: b\r\n".repeat(MAX_HEADERS));
+        assert!(Request::read(&mut most.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let started = Instant::now();
+        let mut reader = BufReader::new(Deadline {
+            stream,
+            until: started + Duration::from_millis(200),
+        });
+        // a client trickling bytes still runs into the deadline
+        let trickle = std::thread::spawn(move || {
+            for _ in 0..10 {
+                if client.write_all(b"G").is_err() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        });
+        assert!(Request::read(&mut reader).is_err());
+        assert!(started.elapsed() < Duration::from_millis(400));
+        drop(reader);
+        trickle.join().unwrap();
+    }
+
+    #[test]
+    fn tokens() {
+        let tokens = config::Serve {
+            read_token: Some("reader".to_string()),
+            write_token: Some("writer".to_string()),
+            ..config::Serve::default()
+        };
+        let request = |auth: &str, query: &str| {
+            let raw = format!("GET /tasks{query} HTTP/1.1\r\n{auth}\r\n");
+            Request::read(&mut raw.as_bytes()).unwrap()
+        };
+        assert_eq!(
+            request("Authorization: Bearer writer\r\n", "").access(&tokens),
+            Some(Access::Write)
+        );
+        assert_eq!(
+            request("", "?token=reader").access(&tokens),
+            Some(Access::Read)
+        );
+        assert_eq!(
+            request("Authorization: Bearer other\r\n", "").access(&tokens),
+            None
+        );
+        assert_eq!(request("", "").access(&tokens), None);
+        let read_only = config::Serve {
+            write_token: None,
+            ..tokens
+        };
+        assert_eq!(request("", "?token=writer").access(&read_only), None);
+        let empty = config::Serve {
+            write_token: Some(String::new()),
+            ..read_only
+        };
+        assert_eq!(request("", "?token=").access(&empty), None);
+        assert_eq!(request("", "?token").access(&empty), None);
+        assert_eq!(
+            request("Authorization: Bearer \r\n", "").access(&empty),
+            None
+        );
+
+        let metrics = prometheus(&[Metric {
+            name: "rem_open_tasks",
+            help: "Tasks not completed yet",
+            counter: false,
+            value: 3.0,
+        }]);
+        assert_eq!(
+            String::from_utf8(metrics.body).unwrap(),
+            "# HELP rem_open_tasks Tasks not completed yet\n# TYPE rem_open_tasks gauge\nrem_open_tasks 3\n"
+        );
+
+        let mut out = Vec::new();
+        Response::error(404, "Task 3 not found.")
+            .write(&mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 404 Not Found\r\n"), "{out}");
+        assert!(
+            out.ends_with("\r\n\r\n{\"error\":\"Task 3 not found.\"}"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn pages() {
+        let records = (0..250).map(|i| i.to_string()).collect::<Vec<_>>();
+        let request = |query: &str| Request {
+            method: "GET".to_string(),
+            query: query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            ..Request::default()
+        };
+        let ids = |response: Response| serde_json::from_slice::<Vec<u64>>(&response.body).unwrap();
+
+        let first = page(&request(""), &records);
+        assert_eq!(
+            first.headers,
+            [("X-Total-Count".to_string(), "250".to_string())]
+        );
+        assert_eq!(ids(first), (0..100).collect::<Vec<_>>());
+        assert_eq!(
+            ids(page(&request("limit=3&offset=248"), &records)),
+            [248, 249]
+        );
+        assert!(ids(page(&request("offset=300"), &records)).is_empty());
+        assert_eq!(page(&request("limit=1001"), &records).status, 400);
+        assert_eq!(page(&request("offset=-1"), &records).status, 400);
+    }
+
+    #[test]
+    fn etags_and_spec() {
+        let tokens = config::Serve {
+            read_token: Some("reader".to_string()),
+            ..config::Serve::default()
+        };
+        let request = |path: &str, headers: &[(&str, &str)]| Request {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            ..Request::default()
+        };
+        let auth = ("authorization", "Bearer reader");
+        let handle = |_: &Request, _: Access| Response::json(200, "[1,2]");
+
+        let first = respond(&request("/tasks", &[auth]), &tokens, handle);
+        let (_, tag) = first
+            .headers
+            .iter()
+            .find(|(name, _)| name == "ETag")
+            .unwrap()
+            .clone();
+        let again = respond(
+            &request("/tasks", &[auth, ("if-none-match", &tag)]),
+            &tokens,
+            handle,
+        );
+        assert_eq!(again.status, 304);
+        assert!(again.body.is_empty());
+        let changed = respond(
+            &request("/tasks", &[auth, ("if-none-match", "\"0\"")]),
+            &tokens,
+            handle,
+        );
+        assert_eq!(changed.status, 200);
+        assert_eq!(
+            respond(&request("/tasks", &[]), &tokens, handle).status,
+            401
+        );
+
+        let spec = respond(&request("/openapi.json", &[]), &tokens, handle);
+        assert_eq!(spec.status, 200);
+        let spec: serde_json::Value = serde_json::from_slice(&spec.body).unwrap();
+        for path in [
+            "/tasks",
+            "/tasks/{id}",
+            "/tasks/{id}/complete",
+            "/reminders",
+            "/metrics",
+        ] {
+            assert!(spec["paths"][path].is_object(), "{path}");
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! Definitions of services running `rem daemon` for the service managers of the platforms:
+//! systemd and cron on Linux, launchd on macOS and the Task Scheduler on Windows
+
+use std::path::Path;
+
+/// Comment marking lines written by `rem install-service`
+pub const MARKER: &str = "# installed by rem install-service";
+
+/// Label of the launchd agent, also the name of its plist
+pub const LAUNCHD_LABEL: &str = "io.github.hawo-hoefer.rem";
+
+/// systemd unit running the daemon
+///
+/// * `exe`: path of the rem binary
+/// * `env`: environment variables to run the daemon with, e.g. the XDG directories rem used
+///   when installing the service
+/// * `user`: user to run a system service as, `None` for user services
+pub fn systemd_unit(
+    exe: &Path,
+    interval: u64,
+    env: &[(&str, String)],
+    user: Option<&str>,
+) -> String {
+    let mut unit = format!(
+        "{MARKER}\n[Unit]\nDescription=rem reminders and time tracking\n\n[Service]\nExecStart=\"{}\" --quiet daemon run --interval {interval}\nRestart=on-failure\n",
+        // systemd expands variables in the command line, but not in the environment
+        systemd_escape(&exe.display().to_string()).replace('$', "$$")
+    );
+    if let Some(user) = user {
+        unit.push_str(&format!("User={user}\n"));
+    }
+    for (var, value) in env.iter() {
+        unit.push_str(&format!(
+            "Environment=\"{var}={}\"\n",
+            systemd_escape(value)
+        ));
+    }
+    let target = if user.is_some() {
+        "multi-user.target"
+    } else {
+        "default.target"
+    };
+    unit.push_str(&format!("\n[Install]\nWantedBy={target}\n"));
+    unit
+}
+
+/// Escape `text` for a double-quoted word of a systemd unit, including `%` specifiers
+fn systemd_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+}
+
+/// Quote `arg` for a POSIX shell, e.g. one running a command given to ssh or cron
+pub fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Replace the entry of rem in a crontab with one refreshing every `interval` minutes
+///
+/// cron has no long-running processes, so the entry refreshes once per run instead.
+pub fn crontab(
+    existing: &str,
+    exe: &Path,
+    interval: u64,
+    env: &[(&str, String)],
+) -> Result<String, String> {
+    if !(1..60).contains(&interval) {
+        return Err(format!(
+            "cron runs rem every {interval} minutes only for intervals from 1 to 59 minutes"
+        ));
+    }
+    let mut ret = existing
+        .lines()
+        .filter(|line| !line.ends_with(MARKER))
+        .map(|line| format!("{line}\n"))
+        .collect::<String>();
+    let env = env
+        .iter()
+        .map(|(var, value)| format!("{var}={} ", shell_quote(value)))
+        .collect::<String>();
+    let command = format!(
+        "{env}{} --quiet daemon run --once < /dev/null",
+        shell_quote(&exe.display().to_string())
+    );
+    // cron ends the command at an unescaped `%`
+    ret.push_str(&format!(
+        "*/{interval} * * * * {} {MARKER}\n",
+        command.replace('%', "\\%")
+    ));
+    Ok(ret)
+}
+
+/// launchd agent running the daemon, kept alive by launchd
+pub fn launchd_plist(exe: &Path, interval: u64, env: &[(&str, String)]) -> String {
+    let escape = |text: &str| {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    let args = [
+        exe.display().to_string(),
+        "--quiet".to_string(),
+        "daemon".to_string(),
+        "run".to_string(),
+        "--interval".to_string(),
+        interval.to_string(),
+    ]
+    .iter()
+    .map(|arg| format!("        <string>{}</string>\n", escape(arg)))
+    .collect::<String>();
+    let env = env
+        .iter()
+        .map(|(var, value)| {
+            format!(
+                "        <key>{var}</key>\n        <string>{}</string>\n",
+                escape(value)
+            )
+        })
+        .collect::<String>();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">
+<!-- {} -->
+<plist version=\"1.0\">
+<dict>
+    <key>Label</key>
+    <string>{LAUNCHD_LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+{args}    </array>
+    <key>EnvironmentVariables</key>
+    <dict>
+{env}    </dict>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+</dict>
+</plist>
+",
+        MARKER.trim_start_matches("# ")
+    )
+}
+
+/// Arguments to `schtasks` creating a scheduled task that refreshes every `interval` minutes
+///
+/// Windows has no unix sockets for the daemon to listen at, so the task refreshes once per run
+/// like the cron entry. Scheduled tasks take no environment, so `cmd` sets `env` first.
+pub fn schtasks_args(
+    exe: &Path,
+    interval: u64,
+    env: &[(&str, String)],
+) -> Result<Vec<String>, String> {
+    if !(1..=1439).contains(&interval) {
+        return Err(format!(
+            "Task Scheduler runs rem every {interval} minutes only for intervals from 1 to 1439 minutes"
+        ));
+    }
+    Ok([
+        "/Create",
+        "/F",
+        "/SC",
+        "MINUTE",
+        "/MO",
+        &interval.to_string(),
+        "/TN",
+        "rem",
+        "/TR",
+        &schtasks_command(exe, env),
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect())
+}
+
+fn schtasks_command(exe: &Path, env: &[(&str, String)]) -> String {
+    let command = format!("\"{}\" --quiet daemon run --once", exe.display());
+    if env.is_empty() {
+        return command;
+    }
+    let env = env
+        .iter()
+        .map(|(var, value)| format!("set \"{var}={value}\" && "))
+        .collect::<String>();
+    format!("cmd /c {env}{command}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn units() {
+        let exe = Path::new("/usr/bin/rem");
+        let env = [("XDG_DATA_HOME", "/data".to_string())];
+        let unit = systemd_unit(exe, 5, &env, None);
+        assert!(unit.contains("ExecStart=\"/usr/bin/rem\" --quiet daemon run --interval 5\n"));
+        assert!(unit.contains("Environment=\"XDG_DATA_HOME=/data\"\n"));
+        assert!(unit.ends_with("WantedBy=default.target\n"));
+        assert!(!unit.contains("User="));
+        let unit = systemd_unit(exe, 5, &[], Some("alice"));
+        assert!(unit.contains("User=alice\n"));
+        assert!(unit.ends_with("WantedBy=multi-user.target\n"));
+
+        let odd = Path::new("/opt/my $rem/100%/rem");
+        let odd_env = [("XDG_DATA_HOME", "/my \"data\" 100%".to_string())];
+        let unit = systemd_unit(odd, 5, &odd_env, None);
+        assert!(unit.contains("ExecStart=\"/opt/my $$rem/100%%/rem\" --quiet"));
+        assert!(unit.contains("Environment=\"XDG_DATA_HOME=/my \\\"data\\\" 100%%\"\n"));
+        let line = crontab("", odd, 5, &odd_env).unwrap();
+        assert!(line.starts_with(
+            "*/5 * * * * XDG_DATA_HOME='/my \"data\" 100\\%' '/opt/my $rem/100\\%/rem' --quiet"
+        ));
+
+        let first = crontab("MAILTO=me\n", exe, 10, &env).unwrap();
+        assert_eq!(
+            first,
+            format!("MAILTO=me\n*/10 * * * * XDG_DATA_HOME='/data' '/usr/bin/rem' --quiet daemon run --once < /dev/null {MARKER}\n")
+        );
+        let second = crontab(&first, exe, 15, &[]).unwrap();
+        assert_eq!(second.lines().count(), 2);
+        assert!(second.contains("*/15 "));
+        assert!(crontab("", exe, 90, &[]).is_err());
+    }
+
+    #[test]
+    fn other_platforms() {
+        let exe = Path::new("/Applications/rem & co/rem");
+        let plist = launchd_plist(exe, 5, &[("XDG_DATA_HOME", "/data".to_string())]);
+        assert!(plist.contains("<string>/Applications/rem &amp; co/rem</string>\n"));
+        assert!(plist.contains("<string>--interval</string>\n        <string>5</string>\n"));
+        assert!(plist.contains("<key>XDG_DATA_HOME</key>\n        <string>/data</string>\n"));
+
+        let exe = Path::new(r"C:\Program Files\rem\rem.exe");
+        let args = schtasks_args(exe, 10, &[]).unwrap();
+        assert_eq!(args[5], "10");
+        assert_eq!(
+            args[9],
+            r#""C:\Program Files\rem\rem.exe" --quiet daemon run --once"#
+        );
+        let env = [("XDG_DATA_HOME", r"D:\rem data".to_string())];
+        let args = schtasks_args(exe, 10, &env).unwrap();
+        assert_eq!(
+            args[9],
+            r#"cmd /c set "XDG_DATA_HOME=D:\rem data" && "C:\Program Files\rem\rem.exe" --quiet daemon run --once"#
+        );
+        assert!(schtasks_args(exe, 0, &[]).is_err());
+    }
+}
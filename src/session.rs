@@ -0,0 +1,123 @@
+//! Work sessions, i.e. time tracked on a task between starting and stopping work on it
+
+use chrono::TimeDelta;
+use rusqlite::Row;
+
+use crate::{import_datetime, Config, LocalDT};
+
+pub struct Session {
+    pub id: u64,
+    pub task_id: u64,
+    pub started: LocalDT,
+    /// unset while the session is running
+    pub stopped: Option<LocalDT>,
+    /// last time rem ran while the session was running
+    pub last_seen: LocalDT,
+}
+
+impl Session {
+    pub fn from_db_row(row: &Row<'_>) -> Result<Self, rusqlite::Error> {
+        Ok(Self {
+            id: row.get("id")?,
+            task_id: row.get("task_id")?,
+            started: import_datetime(row.get("started")?),
+            stopped: row.get::<_, Option<i64>>("stopped")?.map(import_datetime),
+            last_seen: import_datetime(row.get("last_seen")?),
+        })
+    }
+
+    /// Time worked, up to `now` for running sessions
+    pub fn duration(&self, now: LocalDT) -> TimeDelta {
+        self.stopped.unwrap_or(now) - self.started
+    }
+}
+
+/// Elapsed time as hours and minutes for status lines, e.g. `01:05`
+pub fn format_elapsed(elapsed: TimeDelta) -> String {
+    let minutes = elapsed.num_minutes().max(0);
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Time without a work session during today's working hours, if it exceeds the configured
+/// nudge threshold
+///
+/// * `last_stopped`: end of the latest session, `None` if a session is running or there never
+///   was one
+pub fn untracked(
+    now: LocalDT,
+    running: bool,
+    last_stopped: Option<LocalDT>,
+    config: &Config,
+) -> Option<TimeDelta> {
+    let threshold = TimeDelta::minutes(config.tracking_nudge_minutes? as i64);
+    let today = now.date_naive();
+    if running || !config.is_working_day(today) {
+        return None;
+    }
+    let (Some(start), Some(end)) = (config.workday_start, config.workday_end) else {
+        return None;
+    };
+    if now.time() < start || now.time() >= end {
+        return None;
+    }
+
+    let workday_start = config.start_of_due_day(today);
+    let since = last_stopped.map_or(workday_start, |stopped| stopped.max(workday_start));
+    let untracked = now - since;
+    (untracked > threshold).then_some(untracked)
+}
+
+/// What to do with a running session after a long time without activity, e.g. because the
+/// machine was suspended or stopping the session was forgotten
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdleAction {
+    /// count the idle time as work
+    Keep,
+    /// stop the session when activity was last seen
+    Trim,
+    /// stop the session when activity was last seen and start a new one now
+    Split,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nudge() {
+        let config = Config::from_toml(
+            r#"
+            workday_start = "09:00"
+            workday_end = "17:00"
+            tracking_nudge_minutes = 30
+            "#,
+        )
+        .unwrap();
+        // a monday
+        let day = crate::start_of_day(chrono::NaiveDate::from_ymd_opt(2025, 6, 2).unwrap());
+        let at = |h, m| day + TimeDelta::hours(h) + TimeDelta::minutes(m);
+
+        assert_eq!(untracked(at(9, 20), false, None, &config), None);
+        assert_eq!(
+            untracked(at(9, 45), false, None, &config),
+            Some(TimeDelta::minutes(45))
+        );
+        assert_eq!(untracked(at(9, 45), true, None, &config), None);
+        assert_eq!(untracked(at(11, 0), false, Some(at(10, 40)), &config), None);
+        assert_eq!(
+            untracked(at(11, 0), false, Some(at(10, 0)), &config),
+            Some(TimeDelta::hours(1))
+        );
+        assert_eq!(untracked(at(18, 0), false, None, &config), None);
+        // saturday
+        assert_eq!(untracked(at(5 * 24 + 10, 0), false, None, &config), None);
+        assert_eq!(untracked(at(11, 0), false, None, &Config::default()), None);
+    }
+
+    #[test]
+    fn elapsed() {
+        assert_eq!(format_elapsed(TimeDelta::minutes(42)), "00:42");
+        assert_eq!(format_elapsed(TimeDelta::minutes(605)), "10:05");
+        assert_eq!(format_elapsed(TimeDelta::seconds(-5)), "00:00");
+    }
+}
@@ -0,0 +1,330 @@
+//! Printable plan of a day for those who prefer paper: the agenda of the day, the tasks to focus
+//! on and checkboxes for daily habits
+//!
+//! Sheets are written as HTML laid out for A4 pages, or as PDF with the `pdf` feature.
+
+use chrono::NaiveDate;
+
+use crate::Task;
+
+/// What is printed for a day
+pub struct Sheet<'a> {
+    pub date: NaiveDate,
+    /// tasks due on the day, in order of their due time
+    pub agenda: Vec<&'a Task>,
+    /// tasks recommended to work on besides the agenda
+    pub priorities: Vec<&'a Task>,
+    /// titles of reminders recurring at least daily
+    pub habits: Vec<String>,
+    /// show dates as ISO 8601, see [`crate::Config::iso_dates`]
+    pub iso: bool,
+}
+
+impl Sheet<'_> {
+    /// The day with its weekday, e.g. `Monday, 04.03.2024`
+    pub(crate) fn title(&self) -> String {
+        let fmt = if self.iso {
+            "%A, %Y-%m-%d"
+        } else {
+            "%A, %d.%m.%Y"
+        };
+        self.date.format(fmt).to_string()
+    }
+}
+
+/// Time of a task on the agenda, e.g. `09:00`, `09:00-10:30` or `all day`
+pub(crate) fn agenda_time(task: &Task) -> String {
+    match (task.due_start, task.due) {
+        _ if task.due_all_day => "all day".to_string(),
+        (Some(start), Some(due)) => format!("{}-{}", start.format("%H:%M"), due.format("%H:%M")),
+        (None, Some(due)) => due.format("%H:%M").to_string(),
+        (_, None) => String::new(),
+    }
+}
+
+/// Title of a priority with its deadline, if any
+///
+/// * `iso`: show the deadline as `MM-DD` instead of `DD.MM.`
+pub(crate) fn priority_line(task: &Task, iso: bool) -> String {
+    let fmt = if iso { "%m-%d" } else { "%d.%m." };
+    match task.due {
+        Some(due) => format!("{}, due {}", task.title, due.format(fmt)),
+        None => task.title.clone(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const STYLE: &str = "@page { size: A4; margin: 18mm; }
+body { font-family: sans-serif; font-size: 11pt; color: #000; }
+h1 { font-size: 18pt; margin: 0 0 4mm; }
+h2 { font-size: 13pt; border-bottom: 1px solid #000; margin: 6mm 0 2mm; }
+table { width: 100%; border-collapse: collapse; }
+td { padding: 1.5mm 1mm; border-bottom: 1px solid #ccc; vertical-align: top; }
+td.time { width: 25mm; white-space: nowrap; }
+td.box, li::before { width: 6mm; }
+ul { list-style: none; padding: 0; margin: 0; }
+li { padding: 1.5mm 0; border-bottom: 1px solid #ccc; }
+li::before { content: \"☐\"; display: inline-block; }
+.notes div { height: 7mm; border-bottom: 1px solid #ccc; }
+";
+
+/// Render as a standalone HTML page, to be printed from a browser
+pub fn html(sheet: &Sheet) -> String {
+    let mut body = format!("<h1>{}</h1>\n<h2>Agenda</h2>\n", sheet.title());
+    if sheet.agenda.is_empty() {
+        body.push_str("<p>Nothing due.</p>\n");
+    } else {
+        body.push_str("<table>\n");
+        for task in sheet.agenda.iter() {
+            body.push_str(&format!(
+                "<tr><td class=\"box\">☐</td><td class=\"time\">{}</td><td>{}</td></tr>\n",
+                agenda_time(task),
+                escape(&task.title)
+            ));
+        }
+        body.push_str("</table>\n");
+    }
+
+    for (heading, items) in [
+        (
+            "Top priorities",
+            sheet
+                .priorities
+                .iter()
+                .map(|task| priority_line(task, sheet.iso))
+                .collect(),
+        ),
+        ("Habits", sheet.habits.clone()),
+    ] {
+        if items.is_empty() {
+            continue;
+        }
+        body.push_str(&format!("<h2>{heading}</h2>\n<ul>\n"));
+        for item in items.iter() {
+            body.push_str(&format!("<li>{}</li>\n", escape(item)));
+        }
+        body.push_str("</ul>\n");
+    }
+    body.push_str("<h2>Notes</h2>\n<div class=\"notes\">");
+    body.push_str(&"<div></div>".repeat(8));
+    body.push_str("</div>\n");
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        sheet.date.format(if sheet.iso {
+            crate::ISO_DATE_FMT
+        } else {
+            crate::DATE_FMT
+        })
+    )
+}
+
+/// Render as a PDF of A4 pages, using the Helvetica font every PDF viewer ships
+///
+/// Characters outside of Latin-1 are printed as `?`.
+#[cfg(feature = "pdf")]
+pub fn pdf(sheet: &Sheet) -> Vec<u8> {
+    use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+
+    const WIDTH: f32 = 595.0;
+    const HEIGHT: f32 = 842.0;
+    const MARGIN: f32 = 50.0;
+    const LINE: f32 = 18.0;
+
+    enum Line {
+        Title(String),
+        Heading(String),
+        /// text after a checkbox, with the time of agenda entries
+        Item(Option<String>, String),
+        Text(String),
+    }
+
+    let mut lines = vec![
+        Line::Title(sheet.title()),
+        Line::Heading("Agenda".to_string()),
+    ];
+    if sheet.agenda.is_empty() {
+        lines.push(Line::Text("Nothing due.".to_string()));
+    }
+    for task in sheet.agenda.iter() {
+        lines.push(Line::Item(Some(agenda_time(task)), task.title.clone()));
+    }
+    if !sheet.priorities.is_empty() {
+        lines.push(Line::Heading("Top priorities".to_string()));
+        for task in sheet.priorities.iter() {
+            lines.push(Line::Item(None, priority_line(task, sheet.iso)));
+        }
+    }
+    if !sheet.habits.is_empty() {
+        lines.push(Line::Heading("Habits".to_string()));
+        for habit in sheet.habits.iter() {
+            lines.push(Line::Item(None, habit.clone()));
+        }
+    }
+    lines.push(Line::Heading("Notes".to_string()));
+
+    let latin1 = |text: &str| {
+        text.chars()
+            .map(|c| if (c as u32) < 256 { c as u8 } else { b'?' })
+            .collect::<Vec<u8>>()
+    };
+    let font = Name(b"F1");
+    let bold = Name(b"F2");
+    let mut pages = vec![Content::new()];
+    let mut y = HEIGHT - MARGIN;
+    for line in lines.iter() {
+        if y < MARGIN + LINE {
+            pages.push(Content::new());
+            y = HEIGHT - MARGIN;
+        }
+        let content = pages.last_mut().expect("pages are not empty");
+        let mut text = |x: f32, y: f32, font: Name, size: f32, repr: &str| {
+            content
+                .begin_text()
+                .set_font(font, size)
+                .next_line(x, y)
+                .show(Str(&latin1(repr)))
+                .end_text();
+        };
+        match line {
+            Line::Title(title) => {
+                text(MARGIN, y - 6.0, bold, 18.0, title);
+                y -= LINE * 1.5;
+            }
+            Line::Heading(heading) => {
+                y -= LINE * 0.5;
+                text(MARGIN, y, bold, 13.0, heading);
+                content
+                    .move_to(MARGIN, y - 4.0)
+                    .line_to(WIDTH - MARGIN, y - 4.0)
+                    .stroke();
+                y -= LINE;
+            }
+            Line::Item(time, title) => {
+                let mut x = MARGIN + 16.0;
+                if let Some(time) = time {
+                    text(x, y, font, 11.0, time);
+                    x += 80.0;
+                }
+                text(x, y, font, 11.0, title);
+                content.rect(MARGIN, y - 1.0, 9.0, 9.0).stroke();
+                y -= LINE;
+            }
+            Line::Text(repr) => {
+                text(MARGIN, y, font, 11.0, repr);
+                y -= LINE;
+            }
+        }
+    }
+    // ruled lines for notes fill the rest of the last page
+    let content = pages.last_mut().expect("pages are not empty");
+    content.set_stroke_gray(0.8);
+    while y > MARGIN {
+        content
+            .move_to(MARGIN, y)
+            .line_to(WIDTH - MARGIN, y)
+            .stroke();
+        y -= LINE;
+    }
+
+    let mut pdf = Pdf::new();
+    let catalog = Ref::new(1);
+    let tree = Ref::new(2);
+    let font_ref = Ref::new(3);
+    let bold_ref = Ref::new(4);
+    let page_refs = (0..pages.len() as i32)
+        .map(|i| (Ref::new(5 + 2 * i), Ref::new(6 + 2 * i)))
+        .collect::<Vec<_>>();
+    pdf.catalog(catalog).pages(tree);
+    pdf.pages(tree)
+        .kids(page_refs.iter().map(|(page, _)| *page))
+        .count(pages.len() as i32);
+    for (name, id) in [
+        (b"Helvetica".as_slice(), font_ref),
+        (b"Helvetica-Bold", bold_ref),
+    ] {
+        pdf.type1_font(id)
+            .base_font(Name(name))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+    }
+    for ((page_ref, content_ref), content) in page_refs.into_iter().zip(pages) {
+        let mut page = pdf.page(page_ref);
+        page.media_box(Rect::new(0.0, 0.0, WIDTH, HEIGHT))
+            .parent(tree)
+            .contents(content_ref);
+        page.resources()
+            .fonts()
+            .pair(font, font_ref)
+            .pair(bold, bold_ref);
+        page.finish();
+        pdf.stream(content_ref, &content.finish());
+    }
+    pdf.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::start_of_day;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn sheets() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let day = start_of_day(date);
+        let meeting = Task::builder()
+            .title("Standup <team>")
+            .created(day)
+            .due_start(day + TimeDelta::hours(9))
+            .due(day + TimeDelta::minutes(9 * 60 + 15))
+            .build();
+        let rent = Task::builder()
+            .title("pay rent")
+            .created(day)
+            .due(day)
+            .all_day(true)
+            .build();
+        let report = Task::builder()
+            .title("write report")
+            .created(day)
+            .due(day + TimeDelta::days(2))
+            .build();
+        let sheet = Sheet {
+            date,
+            agenda: vec![&rent, &meeting],
+            priorities: vec![&report],
+            habits: vec!["stretch".to_string()],
+            iso: false,
+        };
+
+        let page = html(&sheet);
+        assert!(page.contains("<h1>Monday, 04.03.2024</h1>"));
+        assert!(page.contains("<td class=\"time\">all day</td><td>pay rent</td>"));
+        assert!(page.contains("<td class=\"time\">09:00-09:15</td><td>Standup &lt;team&gt;</td>"));
+        assert!(page.contains("<li>write report, due 06.03.</li>"));
+        assert!(page.contains("<h2>Habits</h2>\n<ul>\n<li>stretch</li>"));
+
+        let empty = html(&Sheet {
+            date,
+            agenda: Vec::new(),
+            priorities: Vec::new(),
+            habits: Vec::new(),
+            iso: false,
+        });
+        assert!(empty.contains("Nothing due."));
+        assert!(!empty.contains("Habits"));
+
+        let iso = html(&Sheet { iso: true, ..sheet });
+        assert!(iso.contains("<h1>Monday, 2024-03-04</h1>"));
+        assert!(iso.contains("<li>write report, due 03-06</li>"));
+
+        #[cfg(feature = "pdf")]
+        assert!(pdf(&sheet).starts_with(b"%PDF"));
+    }
+}
@@ -0,0 +1,99 @@
+//! Status line snippets in the formats read by shell prompts and status bars
+
+use colored::Colorize;
+use serde_json::json;
+
+use crate::theme::State;
+use crate::{Config, LocalDT, Task};
+
+/// How urgent the open tasks are, deciding the color of the status line
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    Ok,
+    /// a task is due soon, see [`State::DueSoon`]
+    DueSoon,
+    Overdue,
+}
+
+impl Level {
+    pub fn of(tasks: &[Task], now: LocalDT, config: &Config) -> Self {
+        tasks
+            .iter()
+            .map(|t| match t.state(now, config) {
+                State::Overdue => Self::Overdue,
+                State::DueSoon => Self::DueSoon,
+                State::Open | State::Started | State::Done => Self::Ok,
+            })
+            .max()
+            .unwrap_or(Self::Ok)
+    }
+
+    /// Name used as css class of waybar modules
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::DueSoon => "due-soon",
+            Self::Overdue => "overdue",
+        }
+    }
+}
+
+/// Text colored with ANSI escapes for a starship custom module with `format = "$output "`
+pub fn starship(text: &str, level: Level) -> String {
+    match level {
+        Level::Ok => text.to_string(),
+        Level::DueSoon => text.yellow().to_string(),
+        Level::Overdue => text.red().to_string(),
+    }
+}
+
+/// Text with tmux style directives, for `status-right "#(rem status --tmux)"`
+pub fn tmux(text: &str, level: Level) -> String {
+    match level {
+        _ if text.is_empty() => String::new(),
+        Level::Ok => text.to_string(),
+        Level::DueSoon => format!("#[fg=yellow]{text}#[default]"),
+        Level::Overdue => format!("#[fg=red]{text}#[default]"),
+    }
+}
+
+/// One line of JSON as read by waybar custom modules with `return-type = "json"`
+pub fn json(text: &str, tooltip: &str, level: Level) -> String {
+    json!({
+        "text": text,
+        "tooltip": tooltip,
+        "class": level.name(),
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        let config = Config::default();
+        let now = chrono::Local::now();
+        let task = |due| Task::builder().title("x").created(now).due(due).build();
+
+        assert_eq!(Level::of(&[], now, &config), Level::Ok);
+        let later = task(now + chrono::TimeDelta::days(3));
+        assert_eq!(Level::of(&[later], now, &config), Level::Ok);
+        let soon = task(now + chrono::TimeDelta::hours(2));
+        let late = task(now - chrono::TimeDelta::hours(2));
+        assert_eq!(Level::of(&[soon], now, &config), Level::DueSoon);
+        let soon = task(now + chrono::TimeDelta::hours(2));
+        assert_eq!(Level::of(&[soon, late], now, &config), Level::Overdue);
+
+        assert_eq!(
+            tmux("1 overdue", Level::Overdue),
+            "#[fg=red]1 overdue#[default]"
+        );
+        assert_eq!(tmux("", Level::Overdue), "");
+        assert_eq!(
+            json("1 overdue", "", Level::Overdue),
+            r#"{"class":"overdue","text":"1 overdue","tooltip":""}"#
+        );
+    }
+}
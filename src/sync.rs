@@ -0,0 +1,335 @@
+//! Merging two rem databases edited independently, e.g. on a laptop and a desktop
+//!
+//! Tasks and reminders are identified across databases by a uuid and carry the time they were
+//! last modified to the millisecond, kept up to date by triggers. Every change moves it forward,
+//! also one within the same millisecond as the merge that copied the row. Merging copies every task and reminder that is
+//! newer on one side to the other, along with its tags, checklist and work bits, so the last
+//! change wins. Deleted tasks and reminders leave a tombstone that deletes them on the other
+//! side as well, unless they were changed there after being deleted.
+//!
+//! Rows with the same external id are the same task or reminder even with different uuids, as
+//! when two machines create the reminders declared in a shared config, and the row copied over
+//! the other passes on its uuid. Of two different rows changed at the same time, the one
+//! comparing greater column by column wins, so both databases settle on the same one.
+//!
+//! Work sessions, templates and relative due dates are not merged.
+
+use rusqlite::Connection;
+
+/// Table of tasks or reminders with the tables holding their details
+struct Entity {
+    table: &'static str,
+    /// tables of details with the column referring to the entity
+    children: &'static [(&'static str, &'static str)],
+    /// columns referring to another entity, with its table
+    refs: &'static [(&'static str, &'static str)],
+}
+
+/// Merged entities, in an order that lets tasks refer to the reminders generating them
+const ENTITIES: [Entity; 2] = [
+    Entity {
+        table: "reminders",
+        children: &[
+            ("reminder_tags", "reminder_id"),
+            ("reminder_checklist_items", "reminder_id"),
+            ("reminder_lead_tasks", "reminder_id"),
+        ],
+        refs: &[("parent", "reminders")],
+    },
+    Entity {
+        table: "tasks",
+        children: &[
+            ("tags", "task_id"),
+            ("checklist_items", "task_id"),
+            ("work_bits", "task_id"),
+        ],
+        refs: &[("parent", "tasks"), ("generated_by", "reminders")],
+    },
+];
+
+/// Give all tasks and reminders a uuid and create the triggers keeping their modification times
+/// and tombstones
+///
+/// Needs the `uuid` and `modified` columns of tasks and reminders.
+pub fn install(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS tombstones (
+           uuid TEXT PRIMARY KEY,
+           deleted INTEGER NOT NULL
+         );",
+    )
+    .map_err(|err| format!("could not create tombstones table: {err}"))?;
+
+    // older versions stamped whole seconds, so changes in the same second as a merge were lost
+    let outdated = conn
+        .prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'trigger' AND sql LIKE '%unixepoch()%';",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|err| format!("could not inspect triggers: {err}"))?;
+    for name in outdated {
+        conn.execute_batch(&format!("DROP TRIGGER \"{name}\";"))
+            .map_err(|err| format!("could not drop trigger {name}: {err}"))?;
+    }
+
+    for Entity {
+        table, children, ..
+    } in ENTITIES.iter()
+    {
+        let mut sql = format!(
+            "UPDATE {table} SET uuid = lower(hex(randomblob(16))) WHERE uuid IS NULL;
+             UPDATE {table} SET modified = created WHERE modified IS NULL;
+             CREATE UNIQUE INDEX IF NOT EXISTS {table}_uuid ON {table}(uuid);
+             CREATE TRIGGER IF NOT EXISTS {table}_inserted AFTER INSERT ON {table}
+               WHEN new.uuid IS NULL OR new.modified IS NULL BEGIN
+               UPDATE {table} SET uuid = coalesce(new.uuid, lower(hex(randomblob(16)))),
+                 modified = coalesce(new.modified, unixepoch('subsec')) WHERE id = new.id;
+             END;
+             CREATE TRIGGER IF NOT EXISTS {table}_revived AFTER INSERT ON {table}
+               WHEN new.uuid IS NOT NULL BEGIN
+               DELETE FROM tombstones WHERE uuid = new.uuid;
+             END;
+             CREATE TRIGGER IF NOT EXISTS {table}_modified AFTER UPDATE ON {table}
+               WHEN old.modified IS new.modified BEGIN
+               UPDATE {table} SET modified = max(unixepoch('subsec'), old.modified + 0.001)
+                 WHERE id = new.id;
+             END;
+             CREATE TRIGGER IF NOT EXISTS {table}_deleted AFTER DELETE ON {table}
+               WHEN old.uuid IS NOT NULL BEGIN
+               INSERT OR REPLACE INTO tombstones (uuid, deleted) VALUES (old.uuid, unixepoch('subsec'));
+             END;"
+        );
+        for (child, key) in children.iter() {
+            for (event, row) in [("INSERT", "new"), ("UPDATE", "new"), ("DELETE", "old")] {
+                sql.push_str(&format!(
+                    "CREATE TRIGGER IF NOT EXISTS {child}_{event}_touch AFTER {event} ON {child} BEGIN
+                       UPDATE {table} SET modified = max(unixepoch('subsec'), modified + 0.001)
+                         WHERE id = {row}.{key};
+                     END;",
+                    event = event.to_lowercase()
+                ));
+            }
+        }
+        conn.execute_batch(&sql)
+            .map_err(|err| format!("could not track changes of {table}: {err}"))?;
+    }
+    Ok(())
+}
+
+/// Tasks and reminders merged in one direction
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    /// created or overwritten
+    pub copied: usize,
+    pub deleted: usize,
+}
+
+/// Merge the database attached as `peer` to `conn` and the main one of `conn`, returning the
+/// changes to the main database and to the peer
+///
+/// Both databases need the layout of this version of rem, see [`install`].
+pub fn merge(conn: &Connection, peer: &str) -> Result<(Changes, Changes), String> {
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|err| format!("Could not start transaction: {err}"))?;
+    // details are deleted and inserted before the rows they refer to are
+    tx.pragma_update(None, "defer_foreign_keys", true)
+        .map_err(|err| format!("Could not defer foreign keys: {err}"))?;
+    let received = pull(&tx, peer, "main")?;
+    let sent = pull(&tx, "main", peer)?;
+    tx.commit()
+        .map_err(|err| format!("Could not commit merge: {err}"))?;
+    Ok((received, sent))
+}
+
+/// Columns of `table` in database `db`, without the one aliasing the rowid and `skip`
+fn columns(conn: &Connection, db: &str, table: &str, skip: &[&str]) -> Result<Vec<String>, String> {
+    conn.prepare(&format!("PRAGMA {db}.table_info({table});"))
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>("name")?,
+                    row.get::<_, String>("type")?,
+                    row.get::<_, i64>("pk")?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()
+        })
+        .map(|columns| {
+            let keys = columns.iter().filter(|(_, _, pk)| *pk > 0).count();
+            columns
+                .into_iter()
+                .filter(|(_, ty, pk)| {
+                    !(keys == 1 && *pk == 1 && ty.eq_ignore_ascii_case("INTEGER"))
+                })
+                .filter(|(name, _, _)| !skip.contains(&name.as_str()))
+                .map(|(name, _, _)| format!("\"{name}\""))
+                .collect()
+        })
+        .map_err(|err| format!("Could not inspect {db}.{table}: {err}"))
+}
+
+/// Ids of the rows selected by `query`
+fn ids<const N: usize>(conn: &Connection, query: &str) -> Result<Vec<[Option<u64>; N]>, String> {
+    conn.prepare(query)
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| {
+                let mut ret = [None; N];
+                for (idx, id) in ret.iter_mut().enumerate() {
+                    *id = row.get(idx)?;
+                }
+                Ok(ret)
+            })?
+            .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|err| format!("Could not compare databases: {err}"))
+}
+
+/// Apply the tombstones and newer tasks and reminders of database `src` to database `dst`
+fn pull(conn: &Connection, src: &str, dst: &str) -> Result<Changes, String> {
+    let mut changes = Changes::default();
+    let exec = |sql: &str, params: &[&dyn rusqlite::ToSql]| {
+        conn.execute(sql, params)
+            .map_err(|err| format!("Could not merge into {dst}: {err}"))
+    };
+
+    for entity in ENTITIES.iter() {
+        let table = entity.table;
+        let doomed = ids::<1>(
+            conn,
+            &format!(
+                "SELECT d.id FROM {dst}.{table} d JOIN {src}.tombstones s ON s.uuid = d.uuid
+                 WHERE s.deleted >= d.modified;"
+            ),
+        )?;
+        for [id] in doomed.iter() {
+            for (child, key) in entity.children.iter() {
+                exec(
+                    &format!("DELETE FROM {dst}.{child} WHERE {key} = ?1;"),
+                    &[id],
+                )?;
+            }
+            for other in ENTITIES.iter() {
+                for (col, _) in other.refs.iter().filter(|(_, t)| *t == table) {
+                    exec(
+                        &format!(
+                            "UPDATE {dst}.{} SET {col} = NULL WHERE {col} = ?1;",
+                            other.table
+                        ),
+                        &[id],
+                    )?;
+                }
+            }
+            exec(&format!("DELETE FROM {dst}.{table} WHERE id = ?1;"), &[id])?;
+            changes.deleted += 1;
+        }
+    }
+    exec(
+        &format!(
+            "INSERT OR REPLACE INTO {dst}.tombstones (uuid, deleted)
+             SELECT s.uuid, s.deleted FROM {src}.tombstones s
+             LEFT JOIN {dst}.tombstones d ON d.uuid = s.uuid
+             WHERE d.uuid IS NULL OR d.deleted < s.deleted;"
+        ),
+        &[],
+    )?;
+
+    for entity in ENTITIES.iter() {
+        let table = entity.table;
+        let refs = entity.refs.iter().map(|(col, _)| *col).collect::<Vec<_>>();
+        let copied_columns = columns(conn, dst, table, &refs)?;
+        let cols = copied_columns.join(", ");
+        let values = |side: &str| {
+            copied_columns
+                .iter()
+                .map(|col| format!("quote({side}.{col})"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let newer = ids::<2>(
+            conn,
+            &format!(
+                "SELECT s.id, d.id FROM {src}.{table} s
+                 LEFT JOIN {dst}.{table} d ON d.id = coalesce(
+                   (SELECT id FROM {dst}.{table} WHERE uuid = s.uuid),
+                   (SELECT id FROM {dst}.{table} WHERE external_id = s.external_id)
+                 )
+                 LEFT JOIN {dst}.tombstones x ON x.uuid = s.uuid
+                 WHERE (d.id IS NULL OR s.modified > d.modified
+                        OR (s.modified = d.modified AND ({}) > ({})))
+                   AND (x.uuid IS NULL OR x.deleted < s.modified);",
+                values("s"),
+                values("d")
+            ),
+        )?;
+
+        let mut copied = Vec::new();
+        for [src_id, dst_id] in newer.iter() {
+            let dst_id = match dst_id {
+                Some(id) => {
+                    exec(
+                        &format!(
+                            "UPDATE {dst}.{table} SET ({cols}) = (SELECT {cols} FROM {src}.{table} WHERE id = ?1) WHERE id = ?2;"
+                        ),
+                        &[src_id, id],
+                    )?;
+                    for (child, key) in entity.children.iter() {
+                        exec(
+                            &format!("DELETE FROM {dst}.{child} WHERE {key} = ?1;"),
+                            &[id],
+                        )?;
+                    }
+                    *id
+                }
+                None => {
+                    exec(
+                        &format!(
+                            "INSERT INTO {dst}.{table} ({cols}) SELECT {cols} FROM {src}.{table} WHERE id = ?1;"
+                        ),
+                        &[src_id],
+                    )?;
+                    conn.last_insert_rowid() as u64
+                }
+            };
+            for (child, key) in entity.children.iter() {
+                let child_cols = columns(conn, dst, child, &[key])?.join(", ");
+                exec(
+                    &format!(
+                        "INSERT INTO {dst}.{child} ({key}, {child_cols}) SELECT ?1, {child_cols} FROM {src}.{child} WHERE {key} = ?2;"
+                    ),
+                    &[&dst_id, src_id],
+                )?;
+            }
+            copied.push((src_id, dst_id));
+        }
+
+        // references are resolved once all rows they may refer to are copied
+        for (src_id, dst_id) in copied.iter() {
+            for (col, other) in entity.refs.iter() {
+                exec(
+                    &format!(
+                        "UPDATE {dst}.{table} SET {col} = (
+                           SELECT d.id FROM {dst}.{other} d JOIN {src}.{other} s
+                             ON s.uuid = d.uuid OR s.external_id = d.external_id
+                           WHERE s.id = (SELECT {col} FROM {src}.{table} WHERE id = ?1)
+                           ORDER BY s.uuid = d.uuid DESC LIMIT 1
+                         ) WHERE id = ?2;"
+                    ),
+                    &[src_id, dst_id],
+                )?;
+            }
+            // copying the details touched the row
+            exec(
+                &format!(
+                    "UPDATE {dst}.{table} SET modified = (SELECT modified FROM {src}.{table} WHERE id = ?1) WHERE id = ?2;"
+                ),
+                &[src_id, dst_id],
+            )?;
+        }
+        changes.copied += copied.len();
+    }
+    Ok(changes)
+}
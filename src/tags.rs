@@ -0,0 +1,81 @@
+//! Helpers for keeping the set of tags tidy
+
+/// Tag without the `#` it may be written with
+pub fn normalize_tag(tag: &str) -> &str {
+    tag.strip_prefix('#').unwrap_or(tag)
+}
+
+/// Check that a tag can be written in quick-add notation
+pub fn validate_tag(tag: &str) -> Result<&str, String> {
+    let tag = normalize_tag(tag);
+    if tag.is_empty() || tag.contains(char::is_whitespace) {
+        return Err(format!(
+            "Invalid tag '{tag}'. Tags cannot be empty or contain spaces."
+        ));
+    }
+    Ok(tag)
+}
+
+/// Pairs of tags that likely mean the same, i.e. differ only in case or by a single typo
+///
+/// Tags shorter than four characters are only compared case-insensitively, as single edits turn
+/// short tags into different words too easily.
+pub fn near_duplicates(tags: &[String]) -> Vec<(String, String)> {
+    let mut ret = Vec::new();
+    for (i, a) in tags.iter().enumerate() {
+        for b in tags.iter().skip(i + 1) {
+            let (la, lb) = (a.to_lowercase(), b.to_lowercase());
+            let similar = la == lb
+                || (la.chars().count() >= 4
+                    && lb.chars().count() >= 4
+                    && edit_distance(&la, &lb) <= 1);
+            if similar {
+                ret.push((a.clone(), b.clone()));
+            }
+        }
+    }
+    ret
+}
+
+/// Levenshtein distance between two strings
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            cur[j + 1] = substitution.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("finance", "finance"), 0);
+        assert_eq!(edit_distance("finance", "fiance"), 1);
+        assert_eq!(edit_distance("finance", "finances"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn duplicates() {
+        let tags = [
+            "Finance", "finance", "fiance", "work", "home", "hom", "car", "cat",
+        ]
+        .map(String::from)
+        .to_vec();
+        let pairs = near_duplicates(&tags);
+        assert!(pairs.contains(&("Finance".to_string(), "finance".to_string())));
+        assert!(pairs.contains(&("finance".to_string(), "fiance".to_string())));
+        assert!(!pairs.iter().any(|(a, _)| a == "car"));
+        assert!(!pairs.iter().any(|(a, _)| a == "home"));
+    }
+}
@@ -1,8 +1,79 @@
+use chrono::TimeDelta;
 use colored::Colorize;
 use rusqlite::fallible_iterator::FallibleIterator;
 use rusqlite::{Connection, Row};
+use serde::{Deserialize, Serialize};
 
-use crate::{import_datetime, LocalDT, DATETIME_FMT};
+use crate::hyperlink::{link_path, linkify};
+use crate::theme::State;
+use crate::{format_timedelta, import_datetime, Config, LocalDT};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low = 1,
+    Medium = 2,
+    High = 3,
+}
+
+impl Priority {
+    pub fn from_db(value: i64) -> Option<Self> {
+        match value {
+            1 => Some(Self::Low),
+            2 => Some(Self::Medium),
+            3 => Some(Self::High),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "low" | "l" => Ok(Self::Low),
+            "medium" | "med" | "m" => Ok(Self::Medium),
+            "high" | "h" => Ok(Self::High),
+            _ => Err(format!(
+                "invalid priority '{s}'. Expected 'low', 'medium' or 'high'."
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Low => f.write_str("low"),
+            Self::Medium => f.write_str("medium"),
+            Self::High => f.write_str("high"),
+        }
+    }
+}
+
+/// Work recorded on a task at some point in time
+pub struct WorkBit {
+    pub datetime: LocalDT,
+    pub description: Option<String>,
+    /// working directory rem was run from
+    pub cwd: Option<String>,
+    /// top level directory of the git repository rem was run in
+    pub git_repo: Option<String>,
+    pub git_branch: Option<String>,
+}
+
+impl WorkBit {
+    pub fn new(datetime: LocalDT, description: Option<String>) -> Self {
+        Self {
+            datetime,
+            description,
+            cwd: None,
+            git_repo: None,
+            git_branch: None,
+        }
+    }
+}
 
 pub struct Task {
     pub id: u64,
@@ -10,89 +81,276 @@ pub struct Task {
     pub description: Option<String>,
 
     pub generated_by: Option<u64>,
+    pub parent: Option<u64>,
+
+    pub priority: Option<Priority>,
+    pub context: Option<String>,
+    pub estimate: Option<TimeDelta>,
+    pub tags: Vec<String>,
+    /// who work on the task is billed to, set on projects
+    pub client: Option<String>,
 
     pub created: LocalDT,
     pub start: Option<LocalDT>,
+    /// earliest acceptable completion; `due` is the end of the window if this is set
+    pub due_start: Option<LocalDT>,
     pub due: Option<LocalDT>,
+    /// the task is due some time on the day of `due` rather than at a specific time
+    pub due_all_day: bool,
     pub completed: Option<LocalDT>,
+    /// closed without being done, `completed` holds the time it was skipped
+    pub skipped: bool,
 
-    pub work_bits: Vec<(LocalDT, Option<String>)>,
+    pub work_bits: Vec<WorkBit>,
+    /// checklist items in order and whether they are done
+    pub checklist: Vec<(String, bool)>,
 }
 
 impl Task {
+    /// Start building a task created now, without any optional attributes
+    pub fn builder() -> TaskBuilder {
+        TaskBuilder {
+            task: Task {
+                id: 0,
+                title: String::new(),
+                description: None,
+                generated_by: None,
+                parent: None,
+                priority: None,
+                context: None,
+                estimate: None,
+                tags: Vec::new(),
+                client: None,
+                created: chrono::Local::now(),
+                start: None,
+                due_start: None,
+                due: None,
+                due_all_day: false,
+                completed: None,
+                skipped: false,
+                work_bits: Vec::new(),
+                checklist: Vec::new(),
+            },
+        }
+    }
+
     pub fn from_db_row(
         row: &Row,
-        conn_if_work_bits: Option<&Connection>,
+        conn_if_details: Option<&Connection>,
     ) -> Result<Self, rusqlite::Error> {
         let id: u64 = row.get("ID")?;
         let title: String = row.get("title")?;
         let description: Option<String> = row.get("description")?;
 
         let generated_by: Option<u64> = row.get("generated_by")?;
+        let parent: Option<u64> = row.get("parent")?;
+        let priority = row
+            .get::<_, Option<i64>>("priority")?
+            .and_then(Priority::from_db);
+        let context: Option<String> = row.get("context")?;
+        let estimate = row
+            .get::<_, Option<i64>>("estimate")?
+            .map(TimeDelta::seconds);
+        let client: Option<String> = row.get("client")?;
 
         let created = import_datetime(row.get("created")?);
         let due = row.get::<_, Option<i64>>("due")?.map(import_datetime);
+        let due_all_day: bool = row.get("due_all_day")?;
         let start = row.get::<_, Option<i64>>("start")?.map(import_datetime);
+        let due_start = row.get::<_, Option<i64>>("due_start")?.map(import_datetime);
         let completed = row.get::<_, Option<i64>>("completed")?.map(import_datetime);
+        let skipped: bool = row.get("skipped")?;
 
-        let work_bits = if let Some(conn) = conn_if_work_bits {
+        let tags = if let Some(conn) = conn_if_details {
+            conn.prepare("SELECT tag FROM tags WHERE task_id = ?1 ORDER BY tag")?
+                .query([id])?
+                .map(|x| x.get::<_, String>("tag"))
+                .collect()?
+        } else {
+            Vec::new()
+        };
+
+        let work_bits = if let Some(conn) = conn_if_details {
             conn.prepare(&format!(
-                "SELECT datetime, description from work_bits WHERE task_id = {id}"
+                "SELECT datetime, description, cwd, git_repo, git_branch from work_bits WHERE task_id = {id}"
             ))?
             .query([])?
             .map(|x| {
-                let datetime = x.get::<_, i64>("datetime").map(import_datetime)?;
-                let description: Option<String> = x.get("description")?;
-                Ok((datetime, description))
+                Ok(WorkBit {
+                    datetime: x.get::<_, i64>("datetime").map(import_datetime)?,
+                    description: x.get("description")?,
+                    cwd: x.get("cwd")?,
+                    git_repo: x.get("git_repo")?,
+                    git_branch: x.get("git_branch")?,
+                })
             })
             .collect()?
         } else {
             Vec::new()
         };
 
+        let checklist = if let Some(conn) = conn_if_details {
+            conn.prepare(
+                "SELECT text, done FROM checklist_items WHERE task_id = ?1 ORDER BY position",
+            )?
+            .query([id])?
+            .map(|x| Ok((x.get::<_, String>("text")?, x.get::<_, bool>("done")?)))
+            .collect()?
+        } else {
+            Vec::new()
+        };
+
         Ok(Task {
             id,
             title,
             description,
             created,
             start,
+            due_start,
             due,
+            due_all_day,
             completed,
+            skipped,
             generated_by,
+            parent,
+            priority,
+            context,
+            estimate,
+            tags,
+            client,
             work_bits,
+            checklist,
         })
     }
 
-    fn fmt(
-        &self,
-        f: &mut std::fmt::Formatter<'_>,
-        all: bool,
-        verbose: bool,
-        now: LocalDT,
-    ) -> std::fmt::Result {
+    /// Point in time after which the task is overdue.
+    ///
+    /// All-day tasks are due until the end of their (work) day.
+    pub fn deadline(&self, config: &Config) -> Option<LocalDT> {
+        let due = self.due?;
+        if self.due_all_day {
+            Some(config.end_of_due_day(due.date_naive()))
+        } else {
+            Some(due)
+        }
+    }
+
+    /// Point in time from which completing the task is acceptable, if restricted.
+    ///
+    /// This is the start of the due window or the start of the (work) day for all-day tasks.
+    pub fn due_window_start(&self, config: &Config) -> Option<LocalDT> {
+        match (self.due_start, self.due) {
+            (Some(due_start), _) => Some(due_start),
+            (None, Some(due)) if self.due_all_day => {
+                Some(config.start_of_due_day(due.date_naive()))
+            }
+            _ => None,
+        }
+    }
+
+    /// State of the task at `now`, deciding its color
+    pub fn state(&self, now: LocalDT, config: &Config) -> State {
+        if self.completed.is_some() {
+            return State::Done;
+        }
+        if let Some(deadline) = self.deadline(config) {
+            let soon = deadline - TimeDelta::hours(config.due_soon_hours as i64);
+            let window_start = self.due_window_start(config).unwrap_or(deadline);
+            return if now >= deadline {
+                State::Overdue
+            } else if now >= soon.min(window_start) {
+                State::DueSoon
+            } else {
+                State::Open
+            };
+        }
+        match self.start {
+            Some(start) if now > start => State::Started,
+            _ => State::Open,
+        }
+    }
+
+    /// Whether the task is open but not to be started yet
+    pub fn starts_later(&self, now: LocalDT) -> bool {
+        self.completed.is_none() && self.start.is_some_and(|start| start > now)
+    }
+
+    pub fn is_overdue(&self, now: LocalDT, config: &Config) -> bool {
+        self.completed.is_none()
+            && self
+                .deadline(config)
+                .is_some_and(|deadline| now >= deadline)
+    }
+
+    /// Priority, context and tags in quick-add notation, e.g. ` !high @home #finance`
+    pub fn annotations(&self) -> String {
+        let mut ret = String::new();
+        if let Some(priority) = self.priority {
+            ret.push_str(&format!(" !{priority}"));
+        }
+        if let Some(ref context) = self.context {
+            ret.push_str(&format!(" @{context}"));
+        }
+        for tag in self.tags.iter() {
+            ret.push_str(&format!(" #{tag}"));
+        }
+        ret
+    }
+
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>, display: &TaskDisplay<'_>) -> std::fmt::Result {
+        let TaskDisplay {
+            all,
+            verbose,
+            now,
+            config,
+            urgency,
+            project,
+            markdown,
+            width,
+            ..
+        } = *display;
+        let links = config.hyperlinks == Some(true);
         if !all && self.completed.is_some() {
             return Ok(());
         }
 
-        let marker = if self.completed.is_some() { "x" } else { " " };
-        let mut heading = format!(
-            "- [{marker}] ({id}) {title}",
-            id = self.id,
-            title = self.title
+        let marker = match (self.completed, self.skipped) {
+            (Some(_), true) => "-",
+            (Some(_), false) => "x",
+            (None, _) => " ",
+        };
+        let state = self.state(now, config);
+        let symbol = match state.symbol() {
+            symbol if config.theme.symbols && !symbol.is_empty() => format!("{symbol} "),
+            _ => String::new(),
+        };
+        let icons = config
+            .icons
+            .of(self, project)
+            .iter()
+            .map(|icon| format!("{icon} "))
+            .collect::<String>();
+        let prefix = format!("- [{marker}] {symbol}({id}) {icons}", id = self.id);
+        let annotations = self.annotations();
+        let title = match width {
+            Some(width) => {
+                let rest = console::measure_text_width(&prefix)
+                    + console::measure_text_width(&annotations);
+                console::truncate_str(&self.title, width.saturating_sub(rest).max(1), "…")
+            }
+            None => self.title.as_str().into(),
+        };
+        let heading = format!(
+            "{prefix}{title}{annotations}",
+            // links in shortened titles would lead elsewhere
+            title = if links && title == self.title {
+                linkify(&title)
+            } else {
+                title.into_owned()
+            },
         )
         .bold();
-
-        if self.completed.is_some() {
-            heading = heading.bright_green();
-        } else if let Some(due) = self.due {
-            if now > due {
-                heading = heading.bright_red();
-            }
-        } else if let Some(start) = self.start {
-            if now > start {
-                heading = heading.yellow();
-            }
-        }
+        let heading = config.theme.paint(state, heading);
         writeln!(f, "{}", heading)?;
 
         if !verbose {
@@ -100,38 +358,108 @@ impl Task {
         }
 
         if let Some(completed) = self.completed {
-            let text = format!("completed: {}", completed.format(DATETIME_FMT));
+            let label = if self.skipped {
+                "skipped:  "
+            } else {
+                "completed:"
+            };
+            let text = format!("{label} {}", completed.format(config.datetime_fmt()));
             writeln!(f, "  {}", text.green())?;
         }
 
-        let created = format!("  created:   {}", self.created.format(DATETIME_FMT));
+        let created = format!(
+            "  created:   {}",
+            self.created.format(config.datetime_fmt())
+        );
         writeln!(f, "{}", created)?;
 
+        if let Some(parent) = self.parent {
+            writeln!(f, "  parent:    {parent}")?;
+        }
+
+        if let Some(ref client) = self.client {
+            writeln!(f, "  client:    {client}")?;
+        }
+
+        if let Some(urgency) = urgency {
+            writeln!(f, "  urgency:   {urgency:.1}")?;
+        }
+
         if let Some(start) = self.start {
-            let start_repr = format!("  start:     {}", start.format(DATETIME_FMT));
+            let start_repr = format!("  start:     {}", start.format(config.datetime_fmt()));
             writeln!(f, "{}", start_repr)?;
         }
 
         if let Some(due) = self.due {
-            let due_repr = format!("  due:       {}", due.format(DATETIME_FMT));
-            if now < due || self.completed.is_some() {
+            let due_fmt = if self.due_all_day {
+                config.date_fmt()
+            } else {
+                config.datetime_fmt()
+            };
+            let due_repr = if let Some(due_start) = self.due_start {
+                format!(
+                    "  due:       {} - {}",
+                    due_start.format(config.datetime_fmt()),
+                    due.format(due_fmt)
+                )
+            } else {
+                format!("  due:       {}", due.format(due_fmt))
+            };
+            if !self.is_overdue(now, config) {
                 writeln!(f, "{}", due_repr)?;
             } else {
                 writeln!(f, "{}", due_repr.bright_red())?;
             }
         }
 
+        if let Some(estimate) = self.estimate {
+            writeln!(f, "  estimate:  {}", format_timedelta(estimate))?;
+        }
+
         if let Some(ref description) = self.description {
-            writeln!(f, "  {}", description)?;
+            if markdown {
+                let color = colored::control::SHOULD_COLORIZE.should_colorize();
+                for line in crate::markdown::render(description, links, color).lines() {
+                    writeln!(f, "  {line}")?;
+                }
+            } else if links {
+                writeln!(f, "  {}", linkify(description))?;
+            } else {
+                writeln!(f, "  {}", description)?;
+            }
         }
 
-        if verbose && self.work_bits.len() > 0 {
+        if !self.checklist.is_empty() {
+            writeln!(f, "  checklist:")?;
+            for (idx, (text, done)) in self.checklist.iter().enumerate() {
+                let marker = if *done { "x" } else { " " };
+                writeln!(f, "  {no}. [{marker}] {text}", no = idx + 1)?;
+            }
+        }
+
+        if verbose && !self.work_bits.is_empty() {
             writeln!(f, "  work bits:")?;
-            for (datetime, desc) in self.work_bits.iter() {
-                write!(f, "  - {}", datetime.format(DATETIME_FMT))?;
-                if let Some(ref desc) = desc {
+            // the directories stored with the work open in the file manager
+            let path = |path: &str| {
+                if links {
+                    link_path(path)
+                } else {
+                    path.to_string()
+                }
+            };
+            for bit in self.work_bits.iter() {
+                write!(f, "  - {}", bit.datetime.format(config.datetime_fmt()))?;
+                if let Some(ref desc) = bit.description {
                     write!(f, ": {}", desc)?;
                 }
+                match (&bit.git_repo, &bit.git_branch) {
+                    (Some(repo), Some(branch)) => write!(f, " [{} on {branch}]", path(repo))?,
+                    (Some(repo), None) => write!(f, " [{}]", path(repo))?,
+                    _ => {}
+                }
+                if let Some(ref cwd) = bit.cwd {
+                    write!(f, " in {}", path(cwd))?;
+                }
                 f.write_str("\n")?;
             }
         }
@@ -139,25 +467,168 @@ impl Task {
         Ok(())
     }
 
-    pub fn display<'a>(&'a self, all: bool, verbose: bool, now: LocalDT) -> TaskDisplay<'a> {
+    pub fn display<'a>(
+        &'a self,
+        all: bool,
+        verbose: bool,
+        now: LocalDT,
+        config: &'a Config,
+    ) -> TaskDisplay<'a> {
         TaskDisplay {
             inner: self,
             all,
             verbose,
             now,
+            config,
+            urgency: None,
+            project: None,
+            markdown: false,
+            width: None,
         }
     }
 }
 
+/// Builder for tasks that are not stored yet, see [`Task::builder`]
+pub struct TaskBuilder {
+    task: Task,
+}
+
+impl TaskBuilder {
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.task.title = title.into();
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.task.description = Some(description.into());
+        self
+    }
+
+    pub fn generated_by(mut self, reminder_id: u64) -> Self {
+        self.task.generated_by = Some(reminder_id);
+        self
+    }
+
+    pub fn parent(mut self, parent: u64) -> Self {
+        self.task.parent = Some(parent);
+        self
+    }
+
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.task.priority = Some(priority);
+        self
+    }
+
+    pub fn context(mut self, context: impl Into<String>) -> Self {
+        self.task.context = Some(context.into());
+        self
+    }
+
+    pub fn estimate(mut self, estimate: TimeDelta) -> Self {
+        self.task.estimate = Some(estimate);
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.task.tags.push(tag.into());
+        self
+    }
+
+    pub fn tags(mut self, tags: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.task.tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn client(mut self, client: impl Into<String>) -> Self {
+        self.task.client = Some(client.into());
+        self
+    }
+
+    pub fn created(mut self, created: LocalDT) -> Self {
+        self.task.created = created;
+        self
+    }
+
+    pub fn start(mut self, start: LocalDT) -> Self {
+        self.task.start = Some(start);
+        self
+    }
+
+    /// Restrict completion to the window from `due_start` to the due date
+    pub fn due_start(mut self, due_start: LocalDT) -> Self {
+        self.task.due_start = Some(due_start);
+        self
+    }
+
+    pub fn due(mut self, due: LocalDT) -> Self {
+        self.task.due = Some(due);
+        self
+    }
+
+    /// The task is due some time on the day of its due date
+    pub fn all_day(mut self, all_day: bool) -> Self {
+        self.task.due_all_day = all_day;
+        self
+    }
+
+    pub fn completed(mut self, completed: LocalDT) -> Self {
+        self.task.completed = Some(completed);
+        self
+    }
+
+    /// Add an unchecked checklist item
+    pub fn checklist_item(mut self, text: impl Into<String>) -> Self {
+        self.task.checklist.push((text.into(), false));
+        self
+    }
+
+    pub fn build(self) -> Task {
+        self.task
+    }
+}
+
 pub struct TaskDisplay<'a> {
     inner: &'a Task,
     all: bool,
     verbose: bool,
     now: LocalDT,
+    config: &'a Config,
+    urgency: Option<f64>,
+    project: Option<u64>,
+    /// render the description as Markdown
+    markdown: bool,
+    /// columns to fit the heading into by shortening the title
+    width: Option<usize>,
+}
+
+impl TaskDisplay<'_> {
+    /// Show the urgency of the task in verbose output
+    pub fn with_urgency(mut self, urgency: f64) -> Self {
+        self.urgency = Some(urgency);
+        self
+    }
+
+    /// Show the icon of the project with the given top-level task
+    pub fn in_project(mut self, project: Option<u64>) -> Self {
+        self.project = project;
+        self
+    }
+
+    /// Render the description with terminal styles instead of Markdown markup
+    pub fn with_markdown(mut self) -> Self {
+        self.markdown = true;
+        self
+    }
+
+    /// Shorten the title with an ellipsis so the heading fits into `width` columns
+    pub fn truncated(mut self, width: Option<usize>) -> Self {
+        self.width = width;
+        self
+    }
 }
 
 impl std::fmt::Display for TaskDisplay<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.inner.fmt(f, self.all, self.verbose, self.now)
+        self.inner.fmt(f, self)
     }
 }
@@ -0,0 +1,658 @@
+//! Templates of tasks and reminders, applied to create them anew and shared as TOML files
+//!
+//! Dates are stored as offsets from the time a template is applied, e.g. `due = "3d"`. All-day
+//! dates are offsets in days from the start of that day.
+//!
+//! Tasks can be given a `key` for other tasks of the template to refer to them. `parent` makes a
+//! task a subtask of another one, `after` counts its offsets from the due date of another one
+//! instead, e.g. to schedule a release a week after its code freeze.
+
+use std::collections::HashMap;
+
+use chrono::{NaiveTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+use crate::parse::parse_duration;
+use crate::reminder::{LeadTask, Season};
+use crate::{format_timedelta, start_of_day, CatchUp, LocalDT, Priority, Reminder, Task};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Template {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tasks: Vec<TaskTemplate>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reminders: Vec<ReminderTemplate>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TaskTemplate {
+    pub title: String,
+    /// name other tasks of the template refer to this one by
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// key of the task this is a subtask of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// key of the task whose due date the offsets of this one count from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "duration")]
+    pub estimate: Option<TimeDelta>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "duration")]
+    pub start: Option<TimeDelta>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "duration")]
+    pub due_start: Option<TimeDelta>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "duration")]
+    pub due: Option<TimeDelta>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub all_day: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub checklist: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReminderTemplate {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "duration")]
+    pub first_due: Option<TimeDelta>,
+    #[serde(with = "duration")]
+    pub period: Option<TimeDelta>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at: Option<NaiveTime>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "duration")]
+    pub until: Option<TimeDelta>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub all_day: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub require_completion: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub checklist: Vec<String>,
+    /// like `prepare agenda:-2d`, see [`LeadTask`]
+    #[serde(skip_serializing_if = "Vec::is_empty", with = "lead_tasks")]
+    pub lead_tasks: Vec<LeadTask>,
+    /// `all` if not given
+    #[serde(skip_serializing_if = "Option::is_none", with = "text")]
+    pub catch_up: Option<CatchUp>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "duration")]
+    pub skip_stale_after: Option<TimeDelta>,
+    /// like `01.04..31.10`, see [`Season`]
+    #[serde(skip_serializing_if = "Option::is_none", with = "text")]
+    pub season: Option<Season>,
+}
+
+impl Template {
+    pub fn from_toml(repr: &str) -> Result<Self, String> {
+        let template: Self = toml::from_str(repr).map_err(|err| err.to_string())?;
+        if template.tasks.is_empty() && template.reminders.is_empty() {
+            return Err("template has neither tasks nor reminders".to_string());
+        }
+        let titles = template.tasks.iter().map(|t| &t.title);
+        if titles
+            .chain(template.reminders.iter().map(|r| &r.title))
+            .any(|title| title.trim().is_empty())
+        {
+            return Err("titles cannot be empty".to_string());
+        }
+        if let Some(reminder) = template
+            .reminders
+            .iter()
+            .find(|r| r.period.is_none_or(|period| period <= TimeDelta::zero()))
+        {
+            return Err(format!(
+                "reminder '{}' needs a positive period",
+                reminder.title
+            ));
+        }
+
+        // tasks may only refer to tasks before them, so dates and parents can be resolved in order
+        let mut keys = HashMap::new();
+        for task in template.tasks.iter() {
+            for key in task.parent.iter().chain(task.after.iter()) {
+                if !keys.contains_key(key.as_str()) {
+                    return Err(format!(
+                        "task '{}' refers to '{key}', which is not the key of a task before it",
+                        task.title
+                    ));
+                }
+            }
+            if let Some(after) = task.after.as_ref().map(|key| keys[key.as_str()]) {
+                let after: &TaskTemplate = after;
+                if after.due.is_none() {
+                    return Err(format!(
+                        "task '{}' is after '{}', which has no due date",
+                        task.title, after.title
+                    ));
+                }
+            }
+            if let Some(ref key) = task.key {
+                if keys.insert(key.as_str(), task).is_some() {
+                    return Err(format!("key '{key}' is used twice"));
+                }
+            }
+        }
+        Ok(template)
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|err| err.to_string())
+    }
+
+    /// Template creating copies of tasks and reminders, with dates relative to their creation
+    ///
+    /// Subtasks of captured tasks stay their subtasks, following their parent as templates may
+    /// only refer to tasks before them.
+    pub fn capture(tasks: &[Task], reminders: &[Reminder]) -> Self {
+        let mut by_id = tasks.iter().collect::<Vec<_>>();
+        by_id.sort_by_key(|t| t.id);
+        let captured = by_id.iter().map(|t| t.id).collect::<Vec<_>>();
+        let is_root = |t: &Task| t.parent.is_none_or(|id| !captured.contains(&id));
+        // depth first from the tasks whose parent is not captured, in order of their ids
+        let mut stack = by_id
+            .iter()
+            .rev()
+            .filter(|t| is_root(t))
+            .copied()
+            .collect::<Vec<_>>();
+        let mut tasks = Vec::new();
+        while let Some(task) = stack.pop() {
+            tasks.push(task);
+            stack.extend(by_id.iter().rev().filter(|t| t.parent == Some(task.id)));
+        }
+
+        let key = |id: u64| format!("task-{id}");
+        let is_parent = |id: u64| tasks.iter().any(|t| t.parent == Some(id));
+        Self {
+            tasks: tasks
+                .iter()
+                .map(|task| TaskTemplate {
+                    key: is_parent(task.id).then(|| key(task.id)),
+                    parent: task.parent.filter(|id| captured.contains(id)).map(key),
+                    ..TaskTemplate::capture(task)
+                })
+                .collect(),
+            reminders: reminders.iter().map(ReminderTemplate::capture).collect(),
+        }
+    }
+
+    /// Tasks and reminders of the template, with dates relative to `at`
+    ///
+    /// Tasks are not linked to their parents, see [`Template::parents`].
+    pub fn instantiate(&self, at: LocalDT) -> (Vec<Task>, Vec<Reminder>) {
+        let mut dues = HashMap::new();
+        let mut tasks = Vec::new();
+        for template in self.tasks.iter() {
+            let base = template
+                .after
+                .as_ref()
+                .and_then(|key| dues.get(key.as_str()).copied())
+                .unwrap_or(at);
+            let task = template.instantiate(base);
+            if let (Some(key), Some(due)) = (&template.key, task.due) {
+                dues.insert(key.as_str(), due);
+            }
+            tasks.push(Task {
+                created: at,
+                ..task
+            });
+        }
+        let reminders = self.reminders.iter().map(|r| r.instantiate(at)).collect();
+        (tasks, reminders)
+    }
+
+    /// Index of the parent of each task among the tasks of the template
+    pub fn parents(&self) -> Vec<Option<usize>> {
+        self.tasks
+            .iter()
+            .map(|task| {
+                let parent = task.parent.as_ref()?;
+                self.tasks
+                    .iter()
+                    .position(|t| t.key.as_ref() == Some(parent))
+            })
+            .collect()
+    }
+}
+
+impl TaskTemplate {
+    pub fn capture(task: &Task) -> Self {
+        Self {
+            title: task.title.clone(),
+            key: None,
+            parent: None,
+            after: None,
+            description: task.description.clone(),
+            priority: task.priority,
+            context: task.context.clone(),
+            tags: task.tags.clone(),
+            client: task.client.clone(),
+            estimate: task.estimate,
+            start: task.start.map(|start| offset(task.created, start, false)),
+            due_start: task
+                .due_start
+                .map(|due_start| offset(task.created, due_start, task.due_all_day)),
+            due: task
+                .due
+                .map(|due| offset(task.created, due, task.due_all_day)),
+            all_day: task.due_all_day,
+            checklist: task
+                .checklist
+                .iter()
+                .map(|(text, _)| text.clone())
+                .collect(),
+        }
+    }
+
+    pub fn instantiate(&self, at: LocalDT) -> Task {
+        let mut task = Task::builder()
+            .title(&self.title)
+            .tags(&self.tags)
+            .created(at)
+            .all_day(self.all_day);
+        if let Some(ref description) = self.description {
+            task = task.description(description);
+        }
+        if let Some(priority) = self.priority {
+            task = task.priority(priority);
+        }
+        if let Some(ref context) = self.context {
+            task = task.context(context);
+        }
+        if let Some(ref client) = self.client {
+            task = task.client(client);
+        }
+        if let Some(estimate) = self.estimate {
+            task = task.estimate(estimate);
+        }
+        if let Some(start) = self.start {
+            task = task.start(apply(at, start, false));
+        }
+        if let Some(due_start) = self.due_start {
+            task = task.due_start(apply(at, due_start, self.all_day));
+        }
+        if let Some(due) = self.due {
+            task = task.due(apply(at, due, self.all_day));
+        }
+        for item in self.checklist.iter() {
+            task = task.checklist_item(item);
+        }
+        task.build()
+    }
+}
+
+impl ReminderTemplate {
+    pub fn capture(reminder: &Reminder) -> Self {
+        Self {
+            title: reminder.title.clone(),
+            description: reminder.description.clone(),
+            priority: reminder.priority,
+            context: reminder.context.clone(),
+            tags: reminder.tags.clone(),
+            first_due: Some(offset(
+                reminder.created,
+                reminder.first_due,
+                reminder.all_day,
+            )),
+            period: Some(reminder.period),
+            at: reminder.at,
+            until: reminder
+                .until
+                .map(|until| offset(reminder.created, until, false)),
+            all_day: reminder.all_day,
+            require_completion: reminder.require_completion,
+            checklist: reminder.checklist.clone(),
+            lead_tasks: reminder.lead_tasks.clone(),
+            catch_up: (reminder.catch_up != CatchUp::All).then_some(reminder.catch_up),
+            skip_stale_after: reminder.skip_stale_after,
+            season: reminder.season,
+        }
+    }
+
+    pub fn instantiate(&self, at: LocalDT) -> Reminder {
+        let mut reminder = Reminder::builder()
+            .title(&self.title)
+            .tags(&self.tags)
+            .created(at)
+            .first_due(apply(at, self.first_due.unwrap_or_default(), self.all_day))
+            .all_day(self.all_day)
+            .period(
+                self.period
+                    .expect("templates are validated to have a period"),
+            )
+            .require_completion(self.require_completion);
+        if let Some(ref description) = self.description {
+            reminder = reminder.description(description);
+        }
+        if let Some(priority) = self.priority {
+            reminder = reminder.priority(priority);
+        }
+        if let Some(ref context) = self.context {
+            reminder = reminder.context(context);
+        }
+        if let Some(time) = self.at {
+            reminder = reminder.at(time);
+        }
+        if let Some(until) = self.until {
+            reminder = reminder.until(apply(at, until, false));
+        }
+        for item in self.checklist.iter() {
+            reminder = reminder.checklist_item(item);
+        }
+        for lead in self.lead_tasks.iter() {
+            reminder = reminder.lead_task(&lead.title, lead.offset);
+        }
+        if let Some(catch_up) = self.catch_up {
+            reminder = reminder.catch_up(catch_up);
+        }
+        if let Some(stale) = self.skip_stale_after {
+            reminder = reminder.skip_stale_after(stale);
+        }
+        if let Some(season) = self.season {
+            reminder = reminder.season(season);
+        }
+        reminder.build()
+    }
+}
+
+/// Offset of `date` from `base`, in whole days for all-day dates. Dates before `base` are
+/// moved to it.
+fn offset(base: LocalDT, date: LocalDT, all_day: bool) -> TimeDelta {
+    let offset = if all_day {
+        TimeDelta::days((date.date_naive() - base.date_naive()).num_days())
+    } else {
+        date - base
+    };
+    offset.max(TimeDelta::zero())
+}
+
+/// Date at `offset` from `base`, see [`offset`]
+fn apply(base: LocalDT, offset: TimeDelta, all_day: bool) -> LocalDT {
+    if all_day {
+        start_of_day(base.date_naive() + TimeDelta::days(offset.num_days()))
+    } else {
+        base + offset
+    }
+}
+
+/// Durations written like `1w 2d` or `1h 30m`, see [`parse_duration`]
+mod duration {
+    use chrono::TimeDelta;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{format_timedelta, parse_duration};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<TimeDelta>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(delta) => serializer.serialize_str(&format_timedelta(*delta)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<TimeDelta>, D::Error> {
+        let repr = String::deserialize(deserializer)?;
+        parse_duration(&repr)
+            .map(Some)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Lead tasks written like `prepare agenda:-2d`, as given to `rem reminder --lead-task`
+mod lead_tasks {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{format_timedelta, LeadTask};
+
+    pub fn serialize<S: Serializer>(value: &[LeadTask], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(value.iter().map(|lead| {
+            let sign = if lead.offset < chrono::TimeDelta::zero() {
+                "-"
+            } else {
+                "+"
+            };
+            format!(
+                "{}:{sign}{}",
+                lead.title,
+                format_timedelta(lead.offset.abs())
+            )
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<LeadTask>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|repr| repr.parse().map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+/// Values written as they are displayed and read back by `FromStr`, e.g. seasons
+mod text {
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T: Display, S: Serializer>(
+        value: &Option<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.collect_str(value),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T: FromStr<Err = String>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<T>, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map(Some)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let now = chrono::Local::now();
+        let task = Task::builder()
+            .title("send contract")
+            .priority(Priority::High)
+            .tag("client")
+            .created(now)
+            .due(now + TimeDelta::days(2))
+            .checklist_item("sign")
+            .build();
+        let reminder = Reminder::builder()
+            .title("check in")
+            .created(now)
+            .first_due(now + TimeDelta::weeks(1))
+            .period(TimeDelta::weeks(2))
+            .build();
+
+        let template = Template::capture(&[task], &[reminder]);
+        let repr = template.to_toml().unwrap();
+        assert!(repr.contains("due = \"2d\""));
+        assert!(repr.contains("priority = \"high\""));
+        assert_eq!(Template::from_toml(&repr).unwrap(), template);
+
+        let later = now + TimeDelta::days(30);
+        let (tasks, reminders) = template.instantiate(later);
+        assert_eq!(tasks[0].due, Some(later + TimeDelta::days(2)));
+        assert_eq!(tasks[0].checklist, [("sign".to_string(), false)]);
+        assert_eq!(reminders[0].first_due, later + TimeDelta::weeks(1));
+        assert_eq!(reminders[0].period, TimeDelta::weeks(2));
+    }
+
+    #[test]
+    fn every_field() {
+        let now = start_of_day(chrono::NaiveDate::from_ymd_opt(2026, 3, 2).unwrap());
+        let task = Task::builder()
+            .title("audit")
+            .description("yearly")
+            .priority(Priority::Low)
+            .context("office")
+            .tag("finance")
+            .client("acme")
+            .estimate(TimeDelta::hours(3))
+            .created(now)
+            .start(now + TimeDelta::days(1))
+            .due_start(now + TimeDelta::days(5))
+            .due(now + TimeDelta::days(7))
+            .all_day(true)
+            .checklist_item("collect receipts")
+            .build();
+        let reminder = Reminder::builder()
+            .title("standup")
+            .description("daily")
+            .priority(Priority::Medium)
+            .context("team")
+            .tag("meeting")
+            .created(now)
+            .first_due(now + TimeDelta::days(1))
+            .period(TimeDelta::days(1))
+            .at(NaiveTime::from_hms_opt(9, 30, 0).unwrap())
+            .until(now + TimeDelta::weeks(10))
+            .require_completion(true)
+            .checklist_item("notes")
+            .lead_task("prepare agenda", -TimeDelta::hours(2))
+            .lead_task("send minutes", TimeDelta::days(1))
+            .catch_up(CatchUp::Collapse)
+            .skip_stale_after(TimeDelta::days(2))
+            .season("01.04..31.10".parse().unwrap())
+            .build();
+
+        let template = Template::capture(&[task], &[reminder]);
+        let repr = template.to_toml().unwrap();
+        assert!(repr.contains("lead_tasks = [\"prepare agenda:-2h\", \"send minutes:+1d\"]"));
+        assert!(repr.contains("season = \"01.04..31.10\""));
+        assert!(repr.contains("catch_up = \"collapse\""));
+        assert_eq!(Template::from_toml(&repr).unwrap(), template);
+
+        let (tasks, reminders) = template.instantiate(now);
+        let task = &tasks[0];
+        assert_eq!(task.client.as_deref(), Some("acme"));
+        assert_eq!(task.due_start, Some(now + TimeDelta::days(5)));
+        assert_eq!(task.start, Some(now + TimeDelta::days(1)));
+        let reminder = &reminders[0];
+        assert_eq!(reminder.at, NaiveTime::from_hms_opt(9, 30, 0));
+        assert_eq!(reminder.lead_tasks.len(), 2);
+        assert_eq!(reminder.lead_tasks[0].offset, -TimeDelta::hours(2));
+        assert_eq!(reminder.catch_up, CatchUp::Collapse);
+        assert_eq!(reminder.skip_stale_after, Some(TimeDelta::days(2)));
+        assert_eq!(
+            reminder.season.map(|s| s.to_string()).as_deref(),
+            Some("01.04..31.10")
+        );
+    }
+
+    #[test]
+    fn capture_order() {
+        let task = |id: u64, parent: Option<u64>| {
+            let mut builder = Task::builder().title(format!("task {id}"));
+            if let Some(parent) = parent {
+                builder = builder.parent(parent);
+            }
+            Task {
+                id,
+                ..builder.build()
+            }
+        };
+        // 1 was moved below 3 after both were created
+        let tasks = [
+            task(1, Some(3)),
+            task(2, None),
+            task(3, None),
+            task(4, Some(1)),
+        ];
+        let template = Template::capture(&tasks, &[]);
+        let titles = template
+            .tasks
+            .iter()
+            .map(|t| t.title.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, ["task 2", "task 3", "task 1", "task 4"]);
+        assert_eq!(template.parents(), [None, None, Some(1), Some(2)]);
+        let repr = template.to_toml().unwrap();
+        assert_eq!(Template::from_toml(&repr).unwrap(), template);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Template::from_toml("").is_err());
+        assert!(Template::from_toml("[[tasks]]\ntitle = \"\"").is_err());
+        assert!(Template::from_toml("[[reminders]]\ntitle = \"x\"").is_err());
+        assert!(Template::from_toml("[[tasks]]\ntitle = \"x\"\ndue = \"soon\"").is_err());
+        assert!(Template::from_toml("[[tasks]]\ntitle = \"x\"\ndue = \"3d\"").is_ok());
+        let dangling = "[[tasks]]\ntitle = \"x\"\nafter = \"y\"";
+        assert!(Template::from_toml(dangling).is_err());
+        let undated =
+            "[[tasks]]\ntitle = \"x\"\nkey = \"x\"\n[[tasks]]\ntitle = \"y\"\nafter = \"x\"";
+        assert!(Template::from_toml(undated).is_err());
+    }
+
+    #[test]
+    fn blueprint() {
+        let template = Template::from_toml(
+            r#"
+            [[tasks]]
+            title = "release"
+            key = "release"
+            due = "3w"
+            all_day = true
+
+            [[tasks]]
+            title = "code freeze"
+            key = "freeze"
+            parent = "release"
+            due = "1w"
+            all_day = true
+
+            [[tasks]]
+            title = "announce"
+            parent = "release"
+            after = "freeze"
+            due = "2d"
+            "#,
+        )
+        .unwrap();
+        let start = crate::parse::parse_date_time("01.06.2026", false).unwrap();
+        let (tasks, _) = template.instantiate(start);
+        let date = |task: &Task| task.due.unwrap().date_naive();
+        assert_eq!(date(&tasks[0]).to_string(), "2026-06-22");
+        assert_eq!(date(&tasks[1]).to_string(), "2026-06-08");
+        assert_eq!(date(&tasks[2]).to_string(), "2026-06-10");
+        assert_eq!(template.parents(), [None, Some(0), Some(0)]);
+    }
+}
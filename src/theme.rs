@@ -0,0 +1,202 @@
+//! Colors of tasks and reminders depending on their state, configured in the `[theme]` section
+
+use std::collections::BTreeMap;
+
+use colored::{Color, ColoredString, Colorize};
+use serde::{Deserialize, Deserializer};
+
+use crate::Task;
+
+/// State of a task deciding how it is shown
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum State {
+    Open,
+    /// the start of the task has passed
+    Started,
+    /// within the due window of the task or the configured `due_soon_hours` before its deadline
+    DueSoon,
+    Overdue,
+    /// completed or skipped
+    Done,
+}
+
+impl State {
+    /// Prefix conveying the state without relying on color
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Open => "",
+            Self::Started => ">",
+            Self::DueSoon => "!",
+            Self::Overdue => "!!",
+            Self::Done => "✓",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    #[serde(deserialize_with = "color")]
+    pub open: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    pub started: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    pub due_soon: Option<Color>,
+    #[serde(deserialize_with = "color")]
+    pub overdue: Option<Color>,
+    /// also used for reminders that no longer generate tasks
+    #[serde(deserialize_with = "color")]
+    pub done: Option<Color>,
+    /// prefix tasks with a symbol of their state, for colorblind users and monochrome terminals
+    pub symbols: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            open: None,
+            started: Some(Color::Yellow),
+            due_soon: Some(Color::Yellow),
+            overdue: Some(Color::BrightRed),
+            done: Some(Color::BrightGreen),
+            symbols: false,
+        }
+    }
+}
+
+impl Theme {
+    pub fn color(&self, state: State) -> Option<Color> {
+        match state {
+            State::Open => self.open,
+            State::Started => self.started,
+            State::DueSoon => self.due_soon,
+            State::Overdue => self.overdue,
+            State::Done => self.done,
+        }
+    }
+
+    /// Color `text` as configured for `state`, keeping its style
+    pub fn paint(&self, state: State, text: ColoredString) -> ColoredString {
+        match self.color(state) {
+            Some(color) => text.color(color),
+            None => text,
+        }
+    }
+}
+
+/// Icons shown before the titles of tasks with a tag or in a project
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Icons {
+    pub tags: BTreeMap<String, String>,
+    /// by id of the top-level task of the project
+    pub projects: BTreeMap<u64, String>,
+}
+
+impl Icons {
+    /// Icons of a task, the one of its project first
+    ///
+    /// * `project`: id of the top-level task of the task, if it is part of a project
+    pub fn of(&self, task: &Task, project: Option<u64>) -> Vec<&str> {
+        let mut ret = Vec::new();
+        let project = project.or(self.projects.contains_key(&task.id).then_some(task.id));
+        ret.extend(
+            project
+                .and_then(|id| self.projects.get(&id))
+                .map(String::as_str),
+        );
+        for icon in task.tags.iter().filter_map(|tag| self.tags.get(tag)) {
+            if !ret.contains(&icon.as_str()) {
+                ret.push(icon);
+            }
+        }
+        ret
+    }
+}
+
+/// Read a color name as understood by [`colored`], e.g. `bright red`, or `none` for no color
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Color>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    if name == "none" {
+        return Ok(None);
+    }
+    name.parse().map(Some).map_err(|_| {
+        serde::de::Error::custom(format!(
+            "invalid color '{name}'. Expected one of black, red, green, yellow, blue, magenta, \
+             cyan, white, their bright variants like 'bright red', or 'none'"
+        ))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let theme: Theme = toml::from_str("overdue = \"magenta\"\ndone = \"none\"").unwrap();
+        assert_eq!(theme.color(State::Overdue), Some(Color::Magenta));
+        assert_eq!(theme.color(State::Done), None);
+        assert_eq!(theme.color(State::DueSoon), Some(Color::Yellow));
+        assert!(toml::from_str::<Theme>("overdue = \"reddish\"").is_err());
+    }
+
+    #[test]
+    fn icons() {
+        let icons: Icons = toml::from_str(
+            "tags = { finance = \"$\", home = \"H\", rent = \"$\" }\nprojects = { 12 = \"P\" }",
+        )
+        .unwrap();
+        let now = chrono::Local::now();
+        let mut project = Task::builder().title("flat").created(now).build();
+        project.id = 12;
+        let task = Task::builder()
+            .title("pay rent")
+            .tags(["rent", "finance", "home"])
+            .created(now)
+            .build();
+
+        assert_eq!(icons.of(&project, None), ["P"]);
+        assert_eq!(icons.of(&task, Some(12)), ["P", "$", "H"]);
+        assert_eq!(icons.of(&task, None), ["$", "H"]);
+    }
+
+    #[test]
+    fn states() {
+        let config = crate::Config::default();
+        let now = chrono::Local::now();
+        let task = || crate::Task::builder().title("x").created(now);
+        let days = chrono::TimeDelta::days;
+
+        assert_eq!(task().build().state(now, &config), State::Open);
+        let started = task().start(now - days(1)).build();
+        assert_eq!(started.state(now, &config), State::Started);
+        let due = task().start(now - days(1)).due(now + days(3)).build();
+        assert_eq!(due.state(now, &config), State::Open);
+        let window = task().due_start(now - days(1)).due(now + days(3)).build();
+        assert_eq!(window.state(now, &config), State::DueSoon);
+        let soon = task().due(now + chrono::TimeDelta::hours(3)).build();
+        assert_eq!(soon.state(now, &config), State::DueSoon);
+        let late = task().due(now - days(1)).build();
+        assert_eq!(late.state(now, &config), State::Overdue);
+        let done = task().due(now - days(1)).completed(now).build();
+        assert_eq!(done.state(now, &config), State::Done);
+    }
+
+    #[test]
+    fn symbols() {
+        let mut config = crate::Config::default();
+        let now = chrono::Local::now();
+        let late = crate::Task::builder()
+            .title("late")
+            .created(now)
+            .due(now - chrono::TimeDelta::days(1))
+            .build();
+
+        let shown = late.display(false, false, now, &config).to_string();
+        assert!(!shown.contains("!!"));
+        config.theme.symbols = true;
+        let shown = late.display(false, false, now, &config).to_string();
+        assert!(shown.contains("[ ] !! (0) late"));
+    }
+}
@@ -0,0 +1,198 @@
+//! TODO comments of a source tree, kept as tasks by `rem scan-todos`
+//!
+//! Every comment is keyed by its file and a hash of its text, so moving it around the file keeps
+//! its task while editing the text replaces it.
+
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+
+use crate::quickadd::{self, QuickAdd};
+
+/// Start of the external ids of tasks created from TODO comments
+pub const PREFIX: &str = "todo:";
+
+/// Directories that hold dependencies or build output rather than code of the project
+const SKIPPED: [&str; 2] = ["target", "node_modules"];
+
+/// Starts of comments a marker has to follow to count, so strings like `"TODO"` do not
+const COMMENTS: [&str; 8] = ["//", "//!", "#", "/*", "*", "--", ";", "%"];
+
+/// Comment found in a file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Todo {
+    /// external id of its task
+    pub key: String,
+    pub path: PathBuf,
+    /// 1-based
+    pub line: usize,
+    /// title and annotations like `TODO(due=friday)`
+    pub quick: QuickAdd,
+    /// why the annotations could not be read, in which case `quick` holds the title only
+    pub invalid: Option<String>,
+}
+
+/// Prefix of the keys of all comments below `dir`
+pub fn dir_key(dir: &Path) -> String {
+    format!("{PREFIX}{}/", dir.display())
+}
+
+/// Find the TODO, FIXME and XXX comments in the files below `dir`
+///
+/// Hidden files and directories, `target` and `node_modules` are skipped, as are files that are
+/// not UTF-8 and comments referring to a task already with `rem:#ID`. Keys start with
+/// [`dir_key`] of `dir`, which should be canonical. Comments with annotations that cannot be read
+/// keep the key of their title and note why in [`Todo::invalid`].
+///
+/// * `today`: date that due dates like `due=friday` refer to
+/// * `lenient`: read due dates in all forms of [`crate::parse::parse_date`]
+pub fn scan(dir: &Path, today: NaiveDate, lenient: bool) -> Result<Vec<Todo>, String> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        let entries = std::fs::read_dir(&current)
+            .map_err(|err| format!("Could not read {}: {err}", current.display()))?;
+        for entry in entries {
+            let entry =
+                entry.map_err(|err| format!("Could not read {}: {err}", current.display()))?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') {
+                continue;
+            }
+            let file_type = entry
+                .file_type()
+                .map_err(|err| format!("Could not read {}: {err}", entry.path().display()))?;
+            if file_type.is_dir() && !SKIPPED.contains(&name.as_ref()) {
+                dirs.push(entry.path());
+            } else if file_type.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+
+    let mut todos = Vec::new();
+    for path in files {
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let mut keys = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let Some((parsed, invalid)) = comment(line, today, lenient) else {
+                continue;
+            };
+            let base = format!(
+                "{}{}:{:016x}",
+                dir_key(dir),
+                path.strip_prefix(dir).unwrap_or(&path).display(),
+                crate::fnv1a(parsed.quick.title.as_bytes())
+            );
+            // the same comment repeated in a file
+            let repeated = keys.iter().filter(|key| **key == base).count();
+            keys.push(base.clone());
+            let key = match repeated {
+                0 => base,
+                n => format!("{base}.{}", n + 1),
+            };
+            todos.push(Todo {
+                key,
+                path: path.clone(),
+                line: idx + 1,
+                quick: parsed.quick,
+                invalid,
+            });
+        }
+    }
+    Ok(todos)
+}
+
+/// The comment of a line, if it starts with a marker and does not refer to a task yet, and the
+/// error of its annotations if they are left out
+fn comment(
+    line: &str,
+    today: NaiveDate,
+    lenient: bool,
+) -> Option<(quickadd::Line, Option<String>)> {
+    let (parsed, invalid) = match quickadd::parse_line(line, today, lenient) {
+        Ok(parsed) => (parsed, None),
+        Err(err) => (
+            quickadd::parse_line(&without_annotations(line)?, today, lenient).ok()?,
+            Some(err),
+        ),
+    };
+    let before = quickadd::MARKERS
+        .iter()
+        .find_map(|marker| parsed.head.strip_suffix(marker))?
+        .trim_end();
+    let commented = COMMENTS.iter().any(|start| before.ends_with(start));
+    (commented && parsed.task.is_none()).then_some((parsed, invalid))
+}
+
+/// `line` without the parentheses after its first marker, e.g. `// TODO: x` of
+/// `// TODO(owner=bob): x`
+fn without_annotations(line: &str) -> Option<String> {
+    let open = quickadd::MARKERS
+        .iter()
+        .filter_map(|marker| {
+            line.find(&format!("{marker}("))
+                .map(|idx| idx + marker.len())
+        })
+        .min()?;
+    let close = open + line[open..].find(')')?;
+    Some(format!("{}{}", &line[..open], &line[close + 1..]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn comments() {
+        let dir = std::env::temp_dir().join(format!("rem-todos-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::write(
+            dir.join("src/main.rs"),
+            "fn main() {\n    // TODO(due=1.7, tag=cli): parse args\n    let markers = [\"TODO\"];\n    \
+             // FIXME: leaks\n    // FIXME: leaks\n    // TODO(rem:#3): tracked\n    \
+             // TODO(owner=bob): parse args\n}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("notes.py"), "# XXX cache this\n").unwrap();
+        std::fs::write(dir.join("target/out.rs"), "// TODO: generated\n").unwrap();
+        std::fs::write(dir.join(".hidden.rs"), "// TODO: hidden\n").unwrap();
+
+        let today = NaiveDate::from_ymd_opt(2025, 6, 15).unwrap();
+        let todos = scan(&dir, today, false).unwrap();
+        let found = todos
+            .iter()
+            .map(|todo| (todo.quick.title.as_str(), todo.line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                ("cache this", 1),
+                ("parse args", 2),
+                ("leaks", 4),
+                ("leaks", 5),
+                ("parse args", 7)
+            ]
+        );
+        assert!(todos[4].invalid.as_ref().unwrap().contains("owner"));
+        assert_eq!(todos[4].key, format!("{}.2", todos[1].key));
+        assert_eq!(todos[1].quick.tags, ["cli"]);
+        assert_eq!(todos[1].quick.due, NaiveDate::from_ymd_opt(2025, 7, 1));
+        assert!(todos[1]
+            .key
+            .starts_with(&format!("{}src/main.rs:", dir_key(&dir))));
+        assert_eq!(todos[3].key, format!("{}.2", todos[2].key));
+
+        // moving a comment keeps its key
+        std::fs::write(dir.join("notes.py"), "\n\n# XXX cache this\n").unwrap();
+        let moved = scan(&dir, today, false).unwrap();
+        assert_eq!((moved[0].line, &moved[0].key), (3, &todos[0].key));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
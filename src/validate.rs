@@ -0,0 +1,199 @@
+//! Rules checked whenever tasks and reminders are created or changed
+//!
+//! Input that cannot be stored is an error. Input that is possible but likely a mistake results
+//! in warnings, which are shown to the user.
+
+use chrono::{NaiveTime, TimeDelta};
+
+use crate::{format_timedelta, Config, LocalDT};
+
+/// Check the dates of a task, returning warnings
+pub fn task(
+    due_start: Option<LocalDT>,
+    due: Option<LocalDT>,
+    due_all_day: bool,
+    now: LocalDT,
+    config: &Config,
+) -> Result<Vec<String>, String> {
+    let mut warnings = Vec::new();
+
+    if let Some(due_start) = due_start {
+        let Some(due) = due else {
+            return Err("A due window needs an end. Specify a due date.".to_string());
+        };
+        if due_start > due {
+            return Err(format!(
+                "Due window starts at {start} which is after its end at {end}",
+                start = due_start.format(config.datetime_fmt()),
+                end = due.format(config.datetime_fmt())
+            ));
+        }
+    }
+
+    if let Some(due) = due {
+        let deadline = if due_all_day {
+            config.end_of_due_day(due.date_naive())
+        } else {
+            due
+        };
+        if deadline <= now {
+            warnings.push(format!(
+                "The task is due at {} which has already passed.",
+                due.format(config.datetime_fmt())
+            ));
+        }
+    }
+
+    Ok(warnings)
+}
+
+/// Periods shorter than this flood the task list, e.g. of a cron expression firing every minute
+const SHORT_PERIOD: TimeDelta = TimeDelta::hours(1);
+
+/// Check the schedule of a reminder, returning warnings
+pub fn reminder(
+    first_due: LocalDT,
+    period: TimeDelta,
+    at: Option<NaiveTime>,
+    until: Option<LocalDT>,
+    now: LocalDT,
+    config: &Config,
+) -> Result<Vec<String>, String> {
+    let mut warnings = Vec::new();
+
+    if period <= TimeDelta::zero() {
+        return Err(format!(
+            "The period needs to be positive, got {}.",
+            format_timedelta(period)
+        ));
+    }
+    if at.is_some() && period.num_seconds() % TimeDelta::days(1).num_seconds() != 0 {
+        return Err(format!(
+            "A time of day needs a period of whole days, got {}.",
+            format_timedelta(period)
+        ));
+    }
+    if period < SHORT_PERIOD {
+        warnings.push(format!(
+            "The reminder repeats every {}, which creates a lot of tasks.",
+            format_timedelta(period)
+        ));
+    }
+
+    if let Some(until) = until {
+        if until > first_due && first_due + period >= until {
+            warnings.push(format!(
+                "The period of {} is longer than the time until the reminder ends. It will only occur once.",
+                format_timedelta(period)
+            ));
+        }
+        if until <= first_due {
+            warnings.push(format!(
+                "The reminder ends at {until} before its first occurrence at {first}. It will never generate a task.",
+                until = until.format(config.datetime_fmt()),
+                first = first_due.format(config.datetime_fmt())
+            ));
+        } else if until <= now {
+            warnings.push(format!(
+                "The reminder ended at {} already. It will not generate any tasks.",
+                until.format(config.datetime_fmt())
+            ));
+        }
+    }
+
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn now() -> LocalDT {
+        crate::start_of_day(chrono::NaiveDate::from_ymd_opt(2025, 6, 4).unwrap())
+            + TimeDelta::hours(12)
+    }
+
+    #[test]
+    fn task_due() {
+        let config = Config::default();
+        let hour = TimeDelta::hours(1);
+        assert_eq!(
+            task(None, Some(now() + hour), false, now(), &config),
+            Ok(vec![])
+        );
+        assert_eq!(
+            task(None, Some(now() - hour), false, now(), &config)
+                .unwrap()
+                .len(),
+            1
+        );
+        // all-day tasks due today are due until the end of the day
+        let today = crate::start_of_day(now().date_naive());
+        assert_eq!(task(None, Some(today), true, now(), &config), Ok(vec![]));
+
+        assert!(task(Some(now()), None, false, now(), &config).is_err());
+        assert!(task(Some(now() + hour), Some(now()), false, now(), &config).is_err());
+    }
+
+    #[test]
+    fn reminder_until() {
+        let config = Config::default();
+        let day = TimeDelta::days(1);
+        let hour = TimeDelta::hours(1);
+        assert_eq!(reminder(now(), day, None, None, now(), &config), Ok(vec![]));
+        assert_eq!(
+            reminder(now(), hour, None, Some(now() + day), now(), &config),
+            Ok(vec![])
+        );
+        assert_eq!(
+            reminder(now(), day, None, Some(now()), now(), &config)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            reminder(
+                now() - day * 2,
+                hour,
+                None,
+                Some(now() - day),
+                now(),
+                &config
+            )
+            .unwrap()
+            .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn reminder_period() {
+        let config = Config::default();
+        let day = TimeDelta::days(1);
+        assert!(reminder(now(), TimeDelta::zero(), None, None, now(), &config).is_err());
+        assert!(reminder(now(), -day, None, None, now(), &config).is_err());
+        assert_eq!(
+            reminder(now(), TimeDelta::minutes(5), None, None, now(), &config)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            reminder(now(), TimeDelta::hours(1), None, None, now(), &config),
+            Ok(vec![])
+        );
+        let at = NaiveTime::from_hms_opt(7, 30, 0);
+        assert_eq!(
+            reminder(now(), day * 7, at, None, now(), &config),
+            Ok(vec![])
+        );
+        assert!(reminder(now(), TimeDelta::hours(12), at, None, now(), &config).is_err());
+        // longer than the lifetime of the reminder
+        assert_eq!(
+            reminder(now(), day * 7, None, Some(now() + day), now(), &config)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}